@@ -0,0 +1,105 @@
+-- wireshark dissector for captures written by PcapSink (link type USER0)
+--
+-- install: copy into your wireshark plugins folder, or run
+--     wireshark -X lua_script:ringbuffer.lua capture.pcap
+--
+-- packet layout: [flags][header fields in flag order][payload][crc8]. flag 0x80 puts an
+-- extension flags byte after the kind field, followed by its own fields
+
+local proto = Proto("ringbuffer", "Ring buffer frame")
+
+local levels = { [1] = "ERROR", [2] = "WARN", [3] = "INFO", [4] = "DEBUG", [5] = "TRACE" }
+local kinds = {
+    [0] = "Log", [1] = "Telemetry", [2] = "Command", [3] = "Blob", [4] = "Interned",
+    [5] = "KeyValue", [6] = "Cbor", [7] = "Protobuf", [8] = "Defmt",
+}
+
+local f = proto.fields
+f.flags = ProtoField.uint8("ringbuffer.flags", "Flags", base.HEX)
+f.timestamp = ProtoField.uint64("ringbuffer.timestamp", "Timestamp")
+f.seq = ProtoField.uint16("ringbuffer.seq", "Sequence")
+f.dropped_messages = ProtoField.uint32("ringbuffer.dropped.messages", "Dropped messages")
+f.dropped_bytes = ProtoField.uint32("ringbuffer.dropped.bytes", "Dropped bytes")
+f.level = ProtoField.uint8("ringbuffer.level", "Level", base.DEC, levels)
+f.tag = ProtoField.uint16("ringbuffer.tag", "Tag")
+f.channel = ProtoField.uint8("ringbuffer.channel", "Channel")
+f.kind = ProtoField.uint8("ringbuffer.kind", "Kind", base.DEC, kinds)
+f.ext = ProtoField.uint8("ringbuffer.ext", "Extension flags", base.HEX)
+f.ttl = ProtoField.uint32("ringbuffer.ttl", "TTL")
+f.payload = ProtoField.bytes("ringbuffer.payload", "Payload")
+f.text = ProtoField.string("ringbuffer.text", "Text")
+f.crc = ProtoField.uint8("ringbuffer.crc", "CRC-8", base.HEX)
+
+local crc_bad = ProtoExpert.new("ringbuffer.crc.bad", "CRC-8 mismatch", expert.group.CHECKSUM, expert.severity.ERROR)
+proto.experts = { crc_bad }
+
+-- flag bit, field, size in bytes, little endian
+local header = {
+    { 0x01, f.timestamp, 8 },
+    { 0x02, f.seq, 2 },
+    { 0x04, nil, 8 },
+    { 0x08, f.level, 1 },
+    { 0x10, f.tag, 2 },
+    { 0x20, f.channel, 1 },
+    { 0x40, f.kind, 1 },
+}
+
+function proto.dissector(buffer, pinfo, tree)
+    local len = buffer:len()
+    if len < 2 then return 0 end
+    pinfo.cols.protocol = "RINGBUF"
+    local subtree = tree:add(proto, buffer(), "Ring buffer frame")
+
+    local flags = buffer(0, 1):uint()
+    subtree:add(f.flags, buffer(0, 1))
+    local offset = 1
+    local info = {}
+    for _, field in ipairs(header) do
+        local bit, pf, size = field[1], field[2], field[3]
+        if bit.band(flags, bit) ~= 0 and offset + size < len then
+            if bit == 0x04 then
+                subtree:add_le(f.dropped_messages, buffer(offset, 4))
+                subtree:add_le(f.dropped_bytes, buffer(offset + 4, 4))
+                table.insert(info, "dropped " .. buffer(offset, 4):le_uint() .. " messages")
+            else
+                subtree:add_le(pf, buffer(offset, size))
+                if bit == 0x08 then
+                    table.insert(info, "[" .. (levels[buffer(offset, 1):uint()] or "?") .. "]")
+                end
+            end
+            offset = offset + size
+        end
+    end
+    if bit.band(flags, 0x80) ~= 0 and offset + 1 < len then
+        local ext = buffer(offset, 1):uint()
+        subtree:add(f.ext, buffer(offset, 1))
+        offset = offset + 1
+        if bit.band(ext, 0x01) ~= 0 and offset + 4 < len then
+            subtree:add_le(f.ttl, buffer(offset, 4))
+            offset = offset + 4
+        end
+    end
+
+    local payload_len = len - 1 - offset
+    if payload_len > 0 then
+        local payload = buffer(offset, payload_len)
+        subtree:add(f.payload, payload)
+        subtree:add(f.text, payload)
+        table.insert(info, payload:string())
+    end
+
+    -- xor of everything before the crc byte
+    local crc = 0
+    for i = 0, len - 2 do
+        crc = bit.bxor(crc, buffer(i, 1):uint())
+    end
+    local crc_item = subtree:add(f.crc, buffer(len - 1, 1))
+    if crc ~= buffer(len - 1, 1):uint() then
+        crc_item:add_proto_expert_info(crc_bad)
+    end
+
+    pinfo.cols.info = table.concat(info, " ")
+    return len
+end
+
+DissectorTable.get("wtap_encap"):add(wtap.USER0, proto)
@@ -0,0 +1,48 @@
+#ifndef RINGBUFFER_H
+#define RINGBUFFER_H
+
+/* generated by cbindgen from src/ffi.rs, don't edit by hand */
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#define RB_OK 0
+
+#define RB_ERR_INVALID -1
+
+#define RB_ERR_FULL -2
+
+#define RB_ERR_EMPTY -3
+
+#define RB_ERR_CRC -4
+
+#define RB_ERR_TOO_SMALL -5
+
+typedef struct RingBuffer RingBuffer;
+
+RingBuffer *rb_new(size_t size);
+
+void rb_free(RingBuffer *ring);
+
+int32_t rb_push_frame(RingBuffer *ring, const uint8_t *data, size_t len);
+
+int32_t rb_push_frame_with_level(RingBuffer *ring, uint8_t level, const uint8_t *data, size_t len);
+
+int32_t rb_flush_frame(RingBuffer *ring, uint8_t *out, size_t capacity, size_t *out_len);
+
+int32_t rb_dma_flush(RingBuffer *ring, uint8_t *out, size_t capacity, size_t *out_len);
+
+void rb_set_max_flush_size(RingBuffer *ring, size_t max_flush_size);
+
+int32_t rb_set_max_level(RingBuffer *ring, uint8_t level);
+
+void rb_enable_sequence_numbers(RingBuffer *ring);
+
+size_t rb_len(const RingBuffer *ring);
+
+size_t rb_free_space(const RingBuffer *ring);
+
+bool rb_is_empty(const RingBuffer *ring);
+
+#endif  /* RINGBUFFER_H */
@@ -0,0 +1,150 @@
+// compile-time side of the interned message pipeline, see `ringbuffer_rs::interned`
+
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{parse_macro_input, Expr, LitStr, Token};
+
+struct RingLog {
+    ring: Option<Expr>,
+    format: LitStr,
+    args: Punctuated<Expr, Token![,]>,
+}
+
+impl Parse for RingLog {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        // `ring_log!("fmt", args..)` goes to the global ring, `ring_log!(ring, "fmt", args..)`
+        // to an explicit one
+        let ring = if input.peek(LitStr) {
+            None
+        } else {
+            let ring = input.parse()?;
+            input.parse::<Token![,]>()?;
+            Some(ring)
+        };
+        let format = input.parse()?;
+        let args = if input.is_empty() {
+            Punctuated::new()
+        } else {
+            input.parse::<Token![,]>()?;
+            Punctuated::parse_terminated(input)?
+        };
+        Ok(RingLog { ring, format, args })
+    }
+}
+
+// 16-bit fnv-1a, has to match `ringbuffer_rs::interned::message_id`. each call site
+// expands on its own, so two format strings with the same id only show up when the host
+// loads the table
+fn message_id(format: &str) -> u16 {
+    let mut hash: u32 = 0x811c_9dc5;
+    for byte in format.bytes() {
+        hash ^= byte as u32;
+        hash = hash.wrapping_mul(0x0100_0193);
+    }
+    ((hash >> 16) ^ (hash & 0xffff)) as u16
+}
+
+// number of `{}` placeholders, `{{` and `}}` are escapes
+fn placeholders(format: &str) -> Option<usize> {
+    let mut count = 0;
+    let mut chars = format.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('{', Some('{')) | ('}', Some('}')) => {
+                chars.next();
+            }
+            ('{', Some('}')) => {
+                chars.next();
+                count += 1;
+            }
+            ('{', _) | ('}', _) => return None,
+            _ => {}
+        }
+    }
+    Some(count)
+}
+
+// log an interned message: the format string is stored in the `ring_log` link section
+// and only its id plus the packed args end up in the frame
+//
+//     ring_log!("motor speed {}", rpm);
+//     ring_log!(ring, "motor speed {}", rpm);
+#[proc_macro]
+pub fn ring_log(input: TokenStream) -> TokenStream {
+    let RingLog { ring, format, args } = parse_macro_input!(input as RingLog);
+    let text = format.value();
+
+    match placeholders(&text) {
+        Some(count) if count == args.len() => {}
+        Some(count) => {
+            let message = format!(
+                "format string has {} placeholders but {} arguments were given",
+                count,
+                args.len()
+            );
+            return syn::Error::new(format.span(), message)
+                .to_compile_error()
+                .into();
+        }
+        None => {
+            let message = "only `{}` placeholders are supported";
+            return syn::Error::new(format.span(), message)
+                .to_compile_error()
+                .into();
+        }
+    }
+    if text.len() > u16::MAX as usize {
+        return syn::Error::new(format.span(), "format string is too long")
+            .to_compile_error()
+            .into();
+    }
+
+    // table record: [id u16][len u16][format bytes]
+    let id = message_id(&text);
+    let mut record = Vec::with_capacity(text.len() + 4);
+    record.extend_from_slice(&id.to_le_bytes());
+    record.extend_from_slice(&(text.len() as u16).to_le_bytes());
+    record.extend_from_slice(text.as_bytes());
+    let record_len = record.len();
+
+    let args = args.iter();
+    let args = quote! { &[#(::ringbuffer_rs::Arg::from(#args)),*] };
+    let log = match ring {
+        Some(ring) => quote! { (#ring).log_interned(#id, #args) },
+        None => quote! { ::ringbuffer_rs::global::log_interned(#id, #args) },
+    };
+
+    quote! {
+        {
+            #[used]
+            #[cfg_attr(target_os = "macos", link_section = "__DATA,__ring_log")]
+            #[cfg_attr(not(target_os = "macos"), link_section = "ring_log")]
+            static RING_LOG_ENTRY: [u8; #record_len] = [#(#record),*];
+            #log
+        }
+    }
+    .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ids_match_the_host_side_hash() {
+        // pinned, `ringbuffer_rs::interned` checks the same values
+        assert_eq!(message_id("motor speed {}"), 0xb3d0);
+        assert_eq!(message_id(""), 0x1cd9);
+    }
+
+    #[test]
+    fn placeholders_are_counted_past_escapes() {
+        assert_eq!(placeholders("no args"), Some(0));
+        assert_eq!(placeholders("{} and {}"), Some(2));
+        assert_eq!(placeholders("{{literal}} {}"), Some(1));
+        assert_eq!(placeholders("{:x}"), None);
+        assert_eq!(placeholders("unbalanced }"), None);
+    }
+}
@@ -0,0 +1,351 @@
+// decode a raw memory dump of a ring buffer's storage (pulled over jtag, out of a core
+// file, ...) and print every frame in it with its crc status
+//
+//     ringbuf-decode dump.bin [--tail N --head N] [--strings FILE] [--section FILE]
+//                             [--json] [--valid-only]
+//
+// without --tail/--head the dump is scanned: it's read circularly starting right after
+// the first terminator, so a frame wrapping around the end of the storage comes out
+// whole. stale bytes between head and tail show up as corrupt frames
+
+use std::process::ExitCode;
+use std::{env, fs};
+
+use ringbuffer_rs::frame::TERMINATOR;
+use ringbuffer_rs::{
+    json, Frame, FrameKind, FrameResult, RehydrateResult, StringTable, TableResult,
+};
+
+struct Options {
+    dump: String,
+    tail: Option<usize>,
+    head: Option<usize>,
+    table: StringTable,
+    json: bool,
+    valid_only: bool,
+}
+
+const USAGE: &str = "usage: ringbuf-decode DUMP [--tail N --head N] [--strings FILE] \
+                     [--section FILE] [--json] [--valid-only]";
+
+fn parse_args() -> Result<Options, String> {
+    let mut args = env::args().skip(1);
+    let mut options = Options {
+        dump: String::new(),
+        tail: None,
+        head: None,
+        table: StringTable::new(),
+        json: false,
+        valid_only: false,
+    };
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--tail" => options.tail = Some(parse_offset(&value()?)?),
+            "--head" => options.head = Some(parse_offset(&value()?)?),
+            // `<id> <format>` lines, e.g. generated at build time
+            "--strings" => {
+                let path = value()?;
+                let text = fs::read_to_string(&path).map_err(|e| format!("{}: {}", path, e))?;
+                merge(&mut options.table, StringTable::parse(&text))?;
+            }
+            // the raw `ring_log` section, e.g. `objcopy -O binary --only-section=ring_log`
+            "--section" => {
+                let path = value()?;
+                let bytes = fs::read(&path).map_err(|e| format!("{}: {}", path, e))?;
+                merge(&mut options.table, StringTable::from_section(&bytes))?;
+            }
+            "--json" => options.json = true,
+            "--valid-only" => options.valid_only = true,
+            "-h" | "--help" => return Err(USAGE.to_string()),
+            _ if options.dump.is_empty() && !arg.starts_with('-') => options.dump = arg,
+            _ => return Err(format!("unexpected argument {}\n{}", arg, USAGE)),
+        }
+    }
+    if options.dump.is_empty() {
+        return Err(USAGE.to_string());
+    }
+    if options.tail.is_some() != options.head.is_some() {
+        return Err("--tail and --head go together".to_string());
+    }
+    Ok(options)
+}
+
+// decimal or 0x-prefixed hex
+fn parse_offset(text: &str) -> Result<usize, String> {
+    let parsed = match text.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => text.parse(),
+    };
+    parsed.map_err(|_| format!("bad offset {}", text))
+}
+
+// a message id the tables disagree on is an error, it would rehydrate the wrong text
+fn merge(table: &mut StringTable, other: TableResult) -> Result<(), String> {
+    let other = match other {
+        TableResult::Ok(other) => other,
+        TableResult::Err(e) => return Err(e),
+    };
+    for id in 0..=u16::MAX {
+        if let Some(format) = other.get(id) {
+            if !table.insert(id, format) {
+                return Err(format!(
+                    "message id {:#06x} is {:?} in one table and {:?} in another",
+                    id,
+                    table.get(id).unwrap_or_default(),
+                    format
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+// the stored bytes in order, each with its offset in the dump
+fn linearize(dump: &[u8], tail: Option<usize>, head: Option<usize>) -> Vec<(usize, u8)> {
+    let len = dump.len();
+    let (start, count) = match (tail, head) {
+        (Some(tail), Some(head)) => (tail % len, (head + len - tail % len) % len),
+        _ => match dump.iter().position(|&b| b == TERMINATOR) {
+            Some(first) => (first + 1, len),
+            None => (0, len),
+        },
+    };
+    (0..count)
+        .map(|i| {
+            let offset = (start + i) % len;
+            (offset, dump[offset])
+        })
+        .collect()
+}
+
+// escaped frame bytes between terminators, with the offset each one starts at
+fn split_frames(bytes: &[(usize, u8)]) -> Vec<(usize, Vec<u8>)> {
+    let mut frames = Vec::new();
+    let mut current: Option<(usize, Vec<u8>)> = None;
+    for &(offset, byte) in bytes {
+        if byte == TERMINATOR {
+            // runs of terminators are unused (zeroed) storage, not empty frames
+            if let Some(frame) = current.take() {
+                frames.push(frame);
+            }
+        } else {
+            current
+                .get_or_insert_with(|| (offset, Vec::new()))
+                .1
+                .push(byte);
+        }
+    }
+    // a frame still being written when the dump was taken has no terminator yet
+    frames.extend(current);
+    frames
+}
+
+fn text(frame: &Frame, table: &StringTable) -> String {
+    if let Some(dropped) = frame.dropped {
+        return format!("<{}>", dropped);
+    }
+    match frame.kind {
+        Some(FrameKind::Interned) => match table.rehydrate(frame) {
+            RehydrateResult::Ok(text) => text,
+            RehydrateResult::Err(e) => format!("<{}: {:02x?}>", e, frame.payload),
+        },
+        Some(FrameKind::KeyValue) => match frame.pairs() {
+            Some(pairs) => pairs
+                .iter()
+                .map(|(key, value)| format!("{}={}", key, value))
+                .collect::<Vec<_>>()
+                .join(" "),
+            None => format!("<malformed pairs: {:02x?}>", frame.payload),
+        },
+        _ => match std::str::from_utf8(&frame.payload) {
+            Ok(text) => text.to_string(),
+            Err(_) => format!("{:02x?}", frame.payload),
+        },
+    }
+}
+
+fn print_text(offset: usize, frame: &Frame, table: &StringTable) {
+    let mut line = format!("{:#06x} ok ", offset);
+    if let Some(seq) = frame.seq {
+        line += &format!("#{} ", seq);
+    }
+    if let Some(timestamp) = frame.timestamp {
+        line += &format!("@{} ", timestamp);
+    }
+    if let Some(level) = frame.level {
+        line += &format!("[{:<5}] ", level);
+    }
+    if let Some(channel) = frame.channel {
+        line += &format!("ch{} ", channel);
+    }
+    if let Some(tag) = frame.tag {
+        line += &format!("tag{} ", tag);
+    }
+    if let Some(kind) = frame.kind {
+        line += &format!("{}: ", json::kind_name(kind));
+    }
+    println!("{}{}", line, text(frame, table));
+}
+
+fn print_json(offset: usize, frame: &Frame, table: &StringTable) {
+    let mut out = format!("{{\"offset\":{},\"crc\":\"ok\",", offset);
+    json::write_frame_fields(&mut out, frame);
+    if matches!(
+        frame.kind,
+        Some(FrameKind::Interned) | Some(FrameKind::KeyValue)
+    ) {
+        out.push_str(",\"text\":");
+        json::write_str(&mut out, &text(frame, table));
+    }
+    out.push('}');
+    println!("{}", out);
+}
+
+fn print_corrupt(offset: usize, bytes: &[u8], error: &str, as_json: bool) {
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    if as_json {
+        let mut out = format!("{{\"offset\":{},\"crc\":\"bad\",\"error\":", offset);
+        json::write_str(&mut out, error);
+        out.push_str(&format!(",\"raw_hex\":\"{}\"}}", hex));
+        println!("{}", out);
+    } else {
+        println!(
+            "{:#06x} BAD {} ({} bytes: {})",
+            offset,
+            error,
+            bytes.len(),
+            hex
+        );
+    }
+}
+
+fn main() -> ExitCode {
+    let options = match parse_args() {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::from(2);
+        }
+    };
+    let dump = match fs::read(&options.dump) {
+        Ok(dump) if !dump.is_empty() => dump,
+        Ok(_) => {
+            eprintln!("{}: empty dump", options.dump);
+            return ExitCode::FAILURE;
+        }
+        Err(e) => {
+            eprintln!("{}: {}", options.dump, e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let (mut valid, mut corrupt) = (0, 0);
+    let bytes = linearize(&dump, options.tail, options.head);
+    for (offset, frame_bytes) in split_frames(&bytes) {
+        match Frame::decode(&frame_bytes) {
+            FrameResult::Ok(frame) => {
+                valid += 1;
+                if options.json {
+                    print_json(offset, &frame, &options.table);
+                } else {
+                    print_text(offset, &frame, &options.table);
+                }
+            }
+            FrameResult::Err(e) => {
+                corrupt += 1;
+                if !options.valid_only {
+                    print_corrupt(offset, &frame_bytes, &e, options.json);
+                }
+            }
+        }
+    }
+    eprintln!("{} frames, {} corrupt", valid, corrupt);
+    ExitCode::SUCCESS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // ring storage of `size` zeroed bytes with `frames` written from `at` on, wrapping
+    fn storage(size: usize, at: usize, frames: &[Frame]) -> Vec<u8> {
+        let mut dump = vec![0; size];
+        let bytes: Vec<u8> = frames.iter().flat_map(Frame::encode).collect();
+        for (i, byte) in bytes.into_iter().enumerate() {
+            dump[(at + i) % size] = byte;
+        }
+        dump
+    }
+
+    fn decoded(bytes: &[(usize, u8)]) -> Vec<(usize, Result<Vec<u8>, String>)> {
+        split_frames(bytes)
+            .into_iter()
+            .map(|(offset, bytes)| match Frame::decode(&bytes) {
+                FrameResult::Ok(frame) => (offset, Ok(frame.payload)),
+                FrameResult::Err(e) => (offset, Err(e)),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn offsets_are_decimal_or_hex() {
+        assert_eq!(parse_offset("64"), Ok(64));
+        assert_eq!(parse_offset("0x40"), Ok(64));
+        assert!(parse_offset("0xzz").is_err());
+    }
+
+    #[test]
+    fn a_scan_brings_a_wrapped_frame_back_whole() {
+        let dump = storage(32, 24, &[Frame::new(b"wrapped around")]);
+        assert_eq!(
+            decoded(&linearize(&dump, None, None)),
+            [(24, Ok(b"wrapped around".to_vec()))]
+        );
+    }
+
+    #[test]
+    fn head_and_tail_limit_the_dump_to_the_stored_bytes() {
+        let live = Frame::new(b"live");
+        let mut dump = storage(64, 0, &[Frame::new(b"stale"), live.clone()]);
+        // what's left of an older frame after the head
+        dump[40..44].copy_from_slice(b"junk");
+        let tail = Frame::new(b"stale").encode().len();
+        let head = tail + live.encode().len();
+
+        assert_eq!(
+            decoded(&linearize(&dump, Some(tail), Some(head))),
+            [(tail, Ok(b"live".to_vec()))]
+        );
+        // a scan starts after the first terminator and shows the stale bytes as well
+        let scanned = decoded(&linearize(&dump, None, None));
+        assert_eq!(scanned.len(), 3);
+        assert_eq!(scanned[0], (tail, Ok(b"live".to_vec())));
+        assert!(matches!(scanned[1], (40, Err(_))));
+        assert_eq!(scanned[2], (0, Ok(b"stale".to_vec())));
+    }
+
+    #[test]
+    fn an_unterminated_frame_still_comes_out() {
+        let mut bytes = Frame::new(b"done").encode();
+        let done = bytes.len();
+        let partial = Frame::new(b"half").encode();
+        bytes.extend_from_slice(&partial[..partial.len() - 1]);
+        let offsets: Vec<_> = bytes.iter().copied().enumerate().collect();
+
+        let frames = split_frames(&offsets);
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[1].0, done);
+        assert!(matches!(
+            Frame::decode(&frames[1].1),
+            FrameResult::Ok(frame) if frame.payload == b"half"
+        ));
+    }
+
+    #[test]
+    fn key_value_frames_print_as_pairs() {
+        let frame = Frame::default().kv("rpm", 1200).kv("state", "ok");
+        assert_eq!(text(&frame, &StringTable::new()), "rpm=1200 state=ok");
+        assert_eq!(text(&Frame::new(&[0xff]), &StringTable::new()), "[ff]");
+    }
+}
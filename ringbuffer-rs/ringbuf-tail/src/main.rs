@@ -0,0 +1,308 @@
+// live pretty-printer for a frame stream: reads from a serial port, a tcp socket or a
+// file, runs it through `FrameDecoder` and prints one line per frame, coloured by level,
+// with a warning wherever sequence numbers show frames went missing
+//
+//     ringbuf-tail serial:/dev/ttyUSB0[@115200] | tcp:HOST:PORT | [file:]PATH
+//                  [--follow] [--strings FILE] [--section FILE] [--no-color]
+//
+// --follow keeps reading a file as it grows, like `tail -f`
+
+use std::fs::{self, File};
+use std::io::{self, IsTerminal, Read};
+use std::net::TcpStream;
+use std::process::ExitCode;
+use std::thread;
+use std::time::Duration;
+
+use ringbuffer_rs::{
+    json, Frame, FrameDecoder, FrameKind, Level, RehydrateResult, SequenceTracker, StringTable,
+    TableResult,
+};
+
+const USAGE: &str = "usage: ringbuf-tail serial:PATH[@BAUD] | tcp:HOST:PORT | [file:]PATH \
+                     [--follow] [--strings FILE] [--section FILE] [--no-color]";
+
+enum Source {
+    Serial(String, u32),
+    Tcp(String),
+    File(String),
+}
+
+struct Options {
+    source: Source,
+    follow: bool,
+    table: StringTable,
+    color: bool,
+}
+
+fn parse_source(text: &str) -> Result<Source, String> {
+    if let Some(serial) = text.strip_prefix("serial:") {
+        return match serial.split_once('@') {
+            Some((path, baud)) => {
+                let baud = baud
+                    .parse()
+                    .map_err(|_| format!("bad baud rate {}", baud))?;
+                Ok(Source::Serial(path.to_string(), baud))
+            }
+            None => Ok(Source::Serial(serial.to_string(), 115_200)),
+        };
+    }
+    if let Some(addr) = text.strip_prefix("tcp:") {
+        return Ok(Source::Tcp(addr.to_string()));
+    }
+    let path = text.strip_prefix("file:").unwrap_or(text);
+    Ok(Source::File(path.to_string()))
+}
+
+fn parse_args() -> Result<Options, String> {
+    let mut args = std::env::args().skip(1);
+    let mut source = None;
+    let mut options = Options {
+        source: Source::File(String::new()),
+        follow: false,
+        table: StringTable::new(),
+        color: io::stdout().is_terminal(),
+    };
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--follow" | "-f" => options.follow = true,
+            "--strings" => {
+                let path = value()?;
+                let text = fs::read_to_string(&path).map_err(|e| format!("{}: {}", path, e))?;
+                merge(&mut options.table, StringTable::parse(&text))?;
+            }
+            "--section" => {
+                let path = value()?;
+                let bytes = fs::read(&path).map_err(|e| format!("{}: {}", path, e))?;
+                merge(&mut options.table, StringTable::from_section(&bytes))?;
+            }
+            "--no-color" => options.color = false,
+            "-h" | "--help" => return Err(USAGE.to_string()),
+            _ if source.is_none() && !arg.starts_with('-') => source = Some(parse_source(&arg)?),
+            _ => return Err(format!("unexpected argument {}\n{}", arg, USAGE)),
+        }
+    }
+    options.source = source.ok_or(USAGE.to_string())?;
+    Ok(options)
+}
+
+// a message id the tables disagree on is an error, it would rehydrate the wrong text
+fn merge(table: &mut StringTable, other: TableResult) -> Result<(), String> {
+    let other = match other {
+        TableResult::Ok(other) => other,
+        TableResult::Err(e) => return Err(e),
+    };
+    for id in 0..=u16::MAX {
+        if let Some(format) = other.get(id) {
+            if !table.insert(id, format) {
+                return Err(format!(
+                    "message id {:#06x} is {:?} in one table and {:?} in another",
+                    id,
+                    table.get(id).unwrap_or_default(),
+                    format
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn open(source: &Source) -> io::Result<Box<dyn Read>> {
+    Ok(match source {
+        Source::Serial(path, baud) => Box::new(
+            serialport::new(path, *baud)
+                .timeout(Duration::from_millis(100))
+                .open()?,
+        ),
+        Source::Tcp(addr) => Box::new(TcpStream::connect(addr)?),
+        Source::File(path) => Box::new(File::open(path)?),
+    })
+}
+
+fn color(level: Level) -> &'static str {
+    match level {
+        Level::Error => "\x1b[31m",
+        Level::Warn => "\x1b[33m",
+        Level::Info => "\x1b[32m",
+        Level::Debug => "\x1b[34m",
+        Level::Trace => "\x1b[2m",
+    }
+}
+
+fn text(frame: &Frame, table: &StringTable) -> String {
+    if let Some(dropped) = frame.dropped {
+        return format!("<{}>", dropped);
+    }
+    match frame.kind {
+        Some(FrameKind::Interned) => match table.rehydrate(frame) {
+            RehydrateResult::Ok(text) => text,
+            RehydrateResult::Err(e) => format!("<{}: {:02x?}>", e, frame.payload),
+        },
+        Some(FrameKind::KeyValue) => match frame.pairs() {
+            Some(pairs) => pairs
+                .iter()
+                .map(|(key, value)| format!("{}={}", key, value))
+                .collect::<Vec<_>>()
+                .join(" "),
+            None => format!("<malformed pairs: {:02x?}>", frame.payload),
+        },
+        _ => String::from_utf8_lossy(&frame.payload).into_owned(),
+    }
+}
+
+fn line(frame: &Frame, options: &Options) -> String {
+    let mut line = String::new();
+    if let Some(timestamp) = frame.timestamp {
+        line += &format!("{:>12} ", timestamp);
+    }
+    if let Some(seq) = frame.seq {
+        line += &format!("#{:<5} ", seq);
+    }
+    if let Some(level) = frame.level {
+        match options.color {
+            true => line += &format!("{}{:<5}\x1b[0m ", color(level), level),
+            false => line += &format!("{:<5} ", level),
+        }
+    }
+    if let Some(channel) = frame.channel {
+        line += &format!("ch{} ", channel);
+    }
+    if let Some(kind) = frame.kind.filter(|&kind| kind != FrameKind::Log) {
+        line += &format!("{}: ", json::kind_name(kind));
+    }
+    line + &text(frame, &options.table)
+}
+
+fn warn(message: &str, options: &Options) {
+    match options.color {
+        true => println!("\x1b[1;33m!! {}\x1b[0m", message),
+        false => println!("!! {}", message),
+    }
+}
+
+fn run(options: &Options) -> io::Result<()> {
+    let mut reader = open(&options.source)?;
+    let mut decoder = FrameDecoder::new();
+    let mut tracker = SequenceTracker::new();
+    let mut corrupt = 0;
+    let mut buf = [0u8; 4096];
+    loop {
+        let count = match reader.read(&mut buf) {
+            Ok(0) if options.follow && matches!(options.source, Source::File(_)) => {
+                thread::sleep(Duration::from_millis(200));
+                continue;
+            }
+            Ok(0) => return Ok(()),
+            Ok(count) => count,
+            // serial reads time out whenever the line is idle
+            Err(e) if e.kind() == io::ErrorKind::TimedOut => continue,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+
+        for frame in decoder.feed(&buf[..count]) {
+            if let Some(gap) = frame.seq.and_then(|seq| tracker.check(seq)) {
+                warn(&gap.to_string(), options);
+            }
+            println!("{}", line(&frame, options));
+        }
+        if decoder.corrupt() > corrupt {
+            warn(
+                &format!("{} corrupt frames dropped", decoder.corrupt() - corrupt),
+                options,
+            );
+            corrupt = decoder.corrupt();
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let options = match parse_args() {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::from(2);
+        }
+    };
+    match run(&options) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(color: bool) -> Options {
+        Options {
+            source: Source::File(String::new()),
+            follow: false,
+            table: StringTable::new(),
+            color,
+        }
+    }
+
+    #[test]
+    fn sources_go_by_prefix() {
+        assert!(matches!(
+            parse_source("serial:/dev/ttyUSB0"),
+            Ok(Source::Serial(path, 115_200)) if path == "/dev/ttyUSB0"
+        ));
+        assert!(matches!(
+            parse_source("serial:COM3@921600"),
+            Ok(Source::Serial(path, 921_600)) if path == "COM3"
+        ));
+        assert!(parse_source("serial:COM3@fast").is_err());
+        assert!(matches!(
+            parse_source("tcp:device.local:7000"),
+            Ok(Source::Tcp(addr)) if addr == "device.local:7000"
+        ));
+        assert!(matches!(
+            parse_source("file:tcp:odd"),
+            Ok(Source::File(path)) if path == "tcp:odd"
+        ));
+        assert!(
+            matches!(parse_source("capture.bin"), Ok(Source::File(path)) if path == "capture.bin")
+        );
+    }
+
+    #[test]
+    fn lines_are_coloured_by_level_when_asked() {
+        let frame = Frame::new(b"stalled")
+            .with_timestamp(1500)
+            .with_seq(7)
+            .with_level(Level::Error)
+            .with_channel(2);
+        assert_eq!(
+            line(&frame, &options(false)),
+            "        1500 #7     ERROR ch2 stalled"
+        );
+        assert_eq!(
+            line(&frame, &options(true)),
+            "        1500 #7     \x1b[31mERROR\x1b[0m ch2 stalled"
+        );
+    }
+
+    #[test]
+    fn kinds_other_than_log_are_named() {
+        let kv = Frame::default().kv("rpm", 1200);
+        assert_eq!(line(&kv, &options(false)), "kv: rpm=1200");
+        let log = Frame::new(b"plain").with_kind(FrameKind::Log);
+        assert_eq!(line(&log, &options(false)), "plain");
+    }
+
+    #[test]
+    fn tables_that_disagree_dont_merge() {
+        let mut table = StringTable::new();
+        merge(&mut table, StringTable::parse("1 motor {}\n2 idle")).unwrap();
+        merge(&mut table, StringTable::parse("2 idle")).unwrap();
+        assert!(merge(&mut table, StringTable::parse("1 pump {}"))
+            .unwrap_err()
+            .starts_with("message id 0x0001"));
+    }
+}
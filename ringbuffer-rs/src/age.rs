@@ -0,0 +1,151 @@
+// bounds how long a frame can sit in the ring when traffic is too light to ever reach a
+// watermark or fill a flush budget. call it from a periodic timer:
+//
+//     // nothing older than 500 ticks of the ring's clock stays behind
+//     ring.flush_older_than(&mut sink, 500);
+//
+// frames are in logging order, so this flushes from the tail up to the first frame
+// that's still young enough. frames without a timestamp (logged before the clock was
+// set) have no age and go out with the old ones rather than holding them back
+
+use alloc::string::ToString;
+
+use crate::frame::{Frame, FrameResult};
+use crate::sink::{FlushSink, FlushStats, FlushToResult, SinkResult};
+use crate::RingBuffer;
+
+impl RingBuffer {
+    // hand every frame logged more than `max_age` clock ticks ago to `sink`
+    pub fn flush_older_than(
+        &mut self,
+        sink: &mut (impl FlushSink + ?Sized),
+        max_age: u64,
+    ) -> FlushToResult {
+        let Some(now) = self.clock.as_ref().map(|clock| clock.now()) else {
+            return FlushToResult::Err("No clock".to_string());
+        };
+        self.find_newest_latest();
+        let mut stats = FlushStats::default();
+        while self.oldest_is_older(now, max_age) {
+            match self.flush_next_to(sink, &mut stats) {
+                Some(SinkResult::Ok) => {}
+                Some(SinkResult::Err(e)) => {
+                    self.pay_flush(stats.bytes);
+                    return FlushToResult::Err(e);
+                }
+                None => break,
+            }
+        }
+        self.pay_flush(stats.bytes);
+
+        if let SinkResult::Err(e) = sink.flush() {
+            return FlushToResult::Err(e);
+        }
+        if let Some(hooks) = &mut self.hooks {
+            hooks.on_flush(&stats);
+        }
+        FlushToResult::Ok(stats)
+    }
+
+    // whether the frame at the tail (the first unsent one with acks on) is due. a corrupt
+    // one is, flushing drops it
+    fn oldest_is_older(&self, now: u64, max_age: u64) -> bool {
+        let Some(bytes) = self.frame_bytes_at(self.unsent_start()) else {
+            return false;
+        };
+        match Frame::decode(&bytes) {
+            FrameResult::Ok(Frame {
+                timestamp: Some(logged),
+                ..
+            }) => now.saturating_sub(logged) > max_age,
+            _ => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::sink::MemorySink;
+
+    fn clocked(now: &Arc<AtomicU64>) -> RingBuffer {
+        let mut ring = RingBuffer::new(128);
+        let now = now.clone();
+        ring.set_clock(move || now.load(Ordering::SeqCst));
+        ring
+    }
+
+    fn payloads(sink: &MemorySink) -> Vec<Vec<u8>> {
+        sink.frames()
+            .iter()
+            .map(|frame| frame.payload.clone())
+            .collect()
+    }
+
+    #[test]
+    fn only_frames_past_the_age_go_out() {
+        let now = Arc::new(AtomicU64::new(100));
+        let mut ring = clocked(&now);
+        let _ = ring.log(Frame::new(b"old"));
+        now.store(150, Ordering::SeqCst);
+        let _ = ring.log(Frame::new(b"young"));
+        now.store(200, Ordering::SeqCst);
+
+        let mut sink = MemorySink::new();
+        let FlushToResult::Ok(stats) = ring.flush_older_than(&mut sink, 60) else {
+            panic!("flush failed");
+        };
+        assert_eq!(stats.frames, 1);
+        assert_eq!(payloads(&sink), [b"old".to_vec()]);
+
+        // exactly max_age old isn't older than it
+        now.store(210, Ordering::SeqCst);
+        let _ = ring.flush_older_than(&mut sink, 60);
+        assert_eq!(sink.frames().len(), 1);
+        now.store(211, Ordering::SeqCst);
+        let _ = ring.flush_older_than(&mut sink, 60);
+        assert_eq!(payloads(&sink), [b"old".to_vec(), b"young".to_vec()]);
+        assert!(ring.is_empty());
+    }
+
+    #[test]
+    fn a_young_frame_holds_back_the_ones_behind_it() {
+        let now = Arc::new(AtomicU64::new(100));
+        let mut ring = clocked(&now);
+        let _ = ring.log(Frame::new(b"young"));
+        // stamped by hand as older, but logged later
+        ring.push_slice(&Frame::new(b"stamped").with_timestamp(0).encode());
+
+        let mut sink = MemorySink::new();
+        let _ = ring.flush_older_than(&mut sink, 50);
+        assert!(sink.frames().is_empty());
+    }
+
+    #[test]
+    fn frames_without_a_timestamp_go_out_with_the_old_ones() {
+        let now = Arc::new(AtomicU64::new(100));
+        let mut ring = RingBuffer::new(128);
+        let _ = ring.log(Frame::new(b"unstamped"));
+        let clock = now.clone();
+        ring.set_clock(move || clock.load(Ordering::SeqCst));
+        let _ = ring.log(Frame::new(b"young"));
+
+        let mut sink = MemorySink::new();
+        let _ = ring.flush_older_than(&mut sink, 50);
+        assert_eq!(payloads(&sink), [b"unstamped".to_vec()]);
+    }
+
+    #[test]
+    fn without_a_clock_nothing_has_an_age() {
+        let mut ring = RingBuffer::new(128);
+        let _ = ring.log(Frame::new(b"kept"));
+        assert!(matches!(
+            ring.flush_older_than(&mut MemorySink::new(), 0),
+            FlushToResult::Err(e) if e == "No clock"
+        ));
+        assert!(!ring.is_empty());
+    }
+}
@@ -0,0 +1,743 @@
+// broadcast mode: any number of readers go through the same stored frames, each with its
+// own cursor and filter, without taking anything out. a uart console tails warnings while
+// a telemetry uploader only sees telemetry:
+//
+//     let console = ring.subscribe(Filter::new().at_least(Level::Warn));
+//     let uploader = ring.subscribe(Filter::new().kinds([FrameKind::Telemetry]));
+//     ...
+//     while let Some(frame) = ring.read(console) {
+//         uart.write_frame(&frame);
+//     }
+//
+// filters are checked as frames are read, changing one applies to everything the reader
+// hasn't read yet. frames still leave the ring the usual ways (flush, pop, overwrite), a
+// reader they left behind carries on at the oldest stored frame or lapses, as its
+// `SlowReader` policy says. corrupt frames are skipped
+//
+// with retention on the readers are what consumes: a frame leaves once every reader has
+// read or skipped it. when a frame doesn't fit, each reader's `SlowReader` policy says
+// what happens to the frames it still has ahead of it, the drop policy doesn't apply.
+// readers come and go with `subscribe` and `unsubscribe`, `lag` tells how far behind one
+// is:
+//
+//     ring.set_reader_retention(true);
+//     let uploader = ring.subscribe(Filter::new());
+//     let console = ring.subscribe(Filter::new().at_least(Level::Warn));
+//     ring.set_slow_reader(console, SlowReader::Skip);
+//     ...
+//     if let Some(gap) = ring.take_gap(console) {
+//         uart.write_all(format!("... {} frames lost\n", gap.frames).as_bytes());
+//     }
+//
+// flushing and popping still take frames out without asking the readers
+//
+// `follow` is `tail -f`: the reader skips what's stored and only sees frames logged from
+// then on. `SharedRing::follow` waits for them, blocking or async
+//
+// `catch_up` is for a reader that just subscribed or lapsed and wants the history too:
+// the stored frames it hasn't read are copied out for it in one go and it follows live
+// from the next frame on, so it's free to take its time with the copy without holding
+// anything back
+
+use alloc::collections::{BTreeSet, VecDeque};
+use alloc::vec::Vec;
+
+use crate::frame::{self, Dropped, Frame, FrameResult};
+use crate::kind::FrameKind;
+use crate::level::Level;
+use crate::RingBuffer;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ReaderId(u32);
+
+// which frames a reader gets, everything by default
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+    level: Option<Level>,
+    tags: Option<BTreeSet<u16>>,
+    kinds: Option<Vec<FrameKind>>,
+}
+
+impl Filter {
+    pub fn new() -> Self {
+        Filter::default()
+    }
+
+    // frames at `level` or more severe, frames without a level count as info
+    pub fn at_least(mut self, level: Level) -> Self {
+        self.level = Some(level);
+        self
+    }
+
+    // frames with one of `tags`, untagged frames don't pass
+    pub fn tags(mut self, tags: impl IntoIterator<Item = u16>) -> Self {
+        self.tags = Some(tags.into_iter().collect());
+        self
+    }
+
+    // frames of one of `kinds`, frames without a kind count as log frames
+    pub fn kinds(mut self, kinds: impl IntoIterator<Item = FrameKind>) -> Self {
+        self.kinds = Some(kinds.into_iter().collect());
+        self
+    }
+
+    pub fn matches(&self, frame: &Frame) -> bool {
+        if let Some(level) = self.level {
+            if frame.level.unwrap_or(Level::Info) > level {
+                return false;
+            }
+        }
+        if let Some(tags) = &self.tags {
+            if !frame.tag.is_some_and(|tag| tags.contains(&tag)) {
+                return false;
+            }
+        }
+        if let Some(kinds) = &self.kinds {
+            if !kinds.contains(&frame.kind.unwrap_or(FrameKind::Log)) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+// what happens to a reader that's in the way of a frame that doesn't fit, and to one
+// frames left before it read them
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SlowReader {
+    // the producer waits for it: with retention on, the new frame is turned away. without
+    // it nothing waits for readers, it's skipped like `Skip`
+    #[default]
+    Block,
+    // it lapses and reads nothing until `resync`, which picks up at the oldest frame still
+    // stored then. a lapsed reader holds nothing back
+    Drop,
+    // it moves on to the oldest frame left, what it lost adds up in `take_gap`
+    Skip,
+}
+
+// how much a reader has left to read, or lost, in complete frames and their encoded bytes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Lag {
+    pub frames: usize,
+    pub bytes: usize,
+}
+
+pub(crate) struct Reader {
+    // where the next frame to read starts, counted in bytes since the ring was created
+    // like `consumed`
+    position: u64,
+    filter: Filter,
+    slow: SlowReader,
+    lapsed: bool,
+    gap: Lag,
+    // copied out by `catch_up`, read before anything stored
+    backlog: VecDeque<Frame>,
+}
+
+impl RingBuffer {
+    // a reader starting at the oldest stored frame
+    pub fn subscribe(&mut self, filter: Filter) -> ReaderId {
+        let position = self.consumed;
+        self.add_reader(position, filter)
+    }
+
+    // a reader starting after the last complete frame, history is skipped
+    pub fn follow(&mut self, filter: Filter) -> ReaderId {
+        let position = self.consumed + self.complete_len() as u64;
+        self.add_reader(position, filter)
+    }
+
+    // move `reader` back to the `count`th newest complete frame, so it reads the last
+    // `count` frames again (or all of them when fewer are stored)
+    pub fn rewind(&mut self, reader: ReaderId, count: usize) -> bool {
+        let frames = self.stored_frames();
+        let first = frames.len().saturating_sub(count);
+        let start = frames
+            .get(first)
+            .map_or(self.complete_len(), |stored| stored.start);
+        let position = self.consumed + start as u64;
+        match self.readers.get_mut(&reader.0) {
+            Some(subscribed) => {
+                subscribed.position = position;
+                subscribed.backlog.clear();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub(crate) fn add_reader(&mut self, position: u64, filter: Filter) -> ReaderId {
+        let id = self.next_reader;
+        self.next_reader += 1;
+        self.readers.insert(
+            id,
+            Reader {
+                position,
+                filter,
+                slow: SlowReader::default(),
+                lapsed: false,
+                gap: Lag::default(),
+                backlog: VecDeque::new(),
+            },
+        );
+        ReaderId(id)
+    }
+
+    // false for a reader that isn't subscribed
+    pub fn unsubscribe(&mut self, reader: ReaderId) -> bool {
+        let removed = self.readers.remove(&reader.0).is_some();
+        self.reclaim_read();
+        removed
+    }
+
+    pub fn set_slow_reader(&mut self, reader: ReaderId, slow: SlowReader) -> bool {
+        match self.readers.get_mut(&reader.0) {
+            Some(subscribed) => {
+                subscribed.slow = slow;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn is_lapsed(&self, reader: ReaderId) -> bool {
+        self.readers
+            .get(&reader.0)
+            .is_some_and(|reader| reader.lapsed)
+    }
+
+    // let a lapsed reader read again, from the oldest stored frame. false when it wasn't
+    // lapsed
+    pub fn resync(&mut self, reader: ReaderId) -> bool {
+        match self.readers.get_mut(&reader.0) {
+            Some(subscribed) if subscribed.lapsed => {
+                subscribed.lapsed = false;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // what `reader` lost to frames leaving before it read them since the last call, None
+    // when it didn't lose anything
+    pub fn take_gap(&mut self, reader: ReaderId) -> Option<Lag> {
+        let subscribed = self.readers.get_mut(&reader.0)?;
+        let gap = core::mem::take(&mut subscribed.gap);
+        (gap != Lag::default()).then_some(gap)
+    }
+
+    pub fn set_reader_retention(&mut self, enabled: bool) {
+        self.reader_retention = enabled;
+        self.reclaim_read();
+    }
+
+    // whether readers hold on to what they haven't read
+    pub(crate) fn retaining(&self) -> bool {
+        self.reader_retention && !self.readers.is_empty()
+    }
+
+    // None for a reader that isn't subscribed. frames its filter skips count too
+    pub fn lag(&self, reader: ReaderId) -> Option<Lag> {
+        let offset = self.reader_offset(reader)?;
+        let end = self.complete_len();
+        let frames = (offset..end)
+            .filter(|&i| self.stored_byte(i) == frame::TERMINATOR)
+            .count();
+        Some(Lag {
+            frames,
+            bytes: end - offset,
+        })
+    }
+
+    pub fn set_filter(&mut self, reader: ReaderId, filter: Filter) -> bool {
+        match self.readers.get_mut(&reader.0) {
+            Some(subscribed) => {
+                subscribed.filter = filter;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn readers(&self) -> usize {
+        self.readers.len()
+    }
+
+    // the next frame `reader` hasn't seen that passes its filter, None once it's caught up,
+    // while it's lapsed or when it isn't subscribed
+    pub fn read(&mut self, reader: ReaderId) -> Option<Frame> {
+        if self.is_lapsed(reader) {
+            return None;
+        }
+        let subscribed = self.readers.get_mut(&reader.0)?;
+        while let Some(frame) = subscribed.backlog.pop_front() {
+            if subscribed.filter.matches(&frame) {
+                return Some(frame);
+            }
+        }
+        loop {
+            let offset = self.reader_offset(reader)?;
+            let bytes = self.frame_bytes_at(offset)?;
+            let subscribed = self.readers.get_mut(&reader.0)?;
+            subscribed.position = self.consumed + (offset + bytes.len() + 1) as u64;
+            let passes = match Frame::decode(&bytes) {
+                FrameResult::Ok(frame) => subscribed.filter.matches(&frame).then_some(frame),
+                FrameResult::Err(_) => None,
+            };
+            self.reclaim_read();
+            if passes.is_some() {
+                return passes;
+            }
+        }
+    }
+
+    // copy the stored frames `reader` hasn't read out for it and move it past them, a
+    // lapsed reader reads again. returns how many frames it has to catch up on, None when
+    // it isn't subscribed. frames it read already aren't copied again, what's left of an
+    // earlier copy stays in front
+    pub fn catch_up(&mut self, reader: ReaderId) -> Option<usize> {
+        let mut frames = VecDeque::new();
+        let mut offset = self.reader_offset(reader)?;
+        while let Some(bytes) = self.frame_bytes_at(offset) {
+            offset += bytes.len() + 1;
+            if let FrameResult::Ok(frame) = Frame::decode(&bytes) {
+                frames.push_back(frame);
+            }
+        }
+        let position = self.consumed + offset as u64;
+        let subscribed = self.readers.get_mut(&reader.0)?;
+        subscribed.position = position;
+        subscribed.lapsed = false;
+        subscribed.backlog.extend(frames);
+        let count = subscribed.backlog.len();
+        self.reclaim_read();
+        Some(count)
+    }
+
+    // frames `reader` still has to read from its `catch_up` copy
+    pub fn backlog(&self, reader: ReaderId) -> Option<usize> {
+        Some(self.readers.get(&reader.0)?.backlog.len())
+    }
+
+    // with retention on, take out the frames every reader is past
+    fn reclaim_read(&mut self) {
+        if !self.retaining() {
+            return;
+        }
+        let Some(oldest) = self
+            .readers
+            .values()
+            .filter(|reader| !reader.lapsed)
+            .map(|reader| reader.position)
+            .min()
+        else {
+            return;
+        };
+        let read = oldest.saturating_sub(self.consumed) as usize;
+        if read > 0 {
+            self.skip(read.min(self.complete_len()));
+            #[cfg(feature = "std")]
+            self.refill();
+        }
+    }
+
+    // where `reader` is from the tail, the tail when frames left before it read them
+    pub(crate) fn reader_offset(&self, reader: ReaderId) -> Option<usize> {
+        let position = self.readers.get(&reader.0)?.position;
+        let offset = position.saturating_sub(self.consumed) as usize;
+        Some(offset.min(self.complete_len()))
+    }
+
+    // the escaped bytes of the complete frame starting `offset` bytes from the tail
+    pub(crate) fn frame_bytes_at(&self, offset: usize) -> Option<Vec<u8>> {
+        let end =
+            (offset..self.complete_len()).find(|&i| self.stored_byte(i) == frame::TERMINATOR)?;
+        Some((offset..end).map(|i| self.stored_byte(i)).collect())
+    }
+
+    // with retention on, make `len` bytes of room for a frame out of the oldest frames no
+    // blocking reader has ahead of it. whether it fits now
+    pub(crate) fn make_room_for_readers(&mut self, len: usize) -> bool {
+        let blocking = self
+            .readers
+            .values()
+            .filter(|reader| reader.slow == SlowReader::Block && !reader.lapsed)
+            .map(|reader| reader.position)
+            .min();
+        let free_to_go = blocking.map_or(self.complete_len(), |position| {
+            position.saturating_sub(self.consumed) as usize
+        });
+        if self.free() + free_to_go < len {
+            return false;
+        }
+        self.make_room(len, &mut Dropped::default()).is_some()
+    }
+
+    // the tail is about to move `count` bytes on, readers that haven't read them lose them
+    pub(crate) fn pass_readers(&mut self, count: usize) {
+        let end = self.consumed + count as u64;
+        let behind: Vec<(u32, usize)> = self
+            .readers
+            .iter()
+            .filter(|(_, reader)| reader.position < end)
+            .map(|(&id, reader)| (id, reader.position.saturating_sub(self.consumed) as usize))
+            .collect();
+        for (id, offset) in behind {
+            let frames = (offset..count)
+                .filter(|&i| self.stored_byte(i) == frame::TERMINATOR)
+                .count();
+            let reader = self.readers.get_mut(&id).unwrap();
+            reader.gap.frames += frames;
+            reader.gap.bytes += count - offset;
+            reader.position = end;
+            if reader.slow == SlowReader::Drop {
+                reader.lapsed = true;
+            }
+        }
+    }
+
+    // `len` bytes at `offset` are about to be taken out of the middle, the older ones move
+    // up and readers among those with them
+    pub(crate) fn readers_removed(&mut self, offset: usize, len: usize) {
+        let start = self.consumed + offset as u64;
+        for reader in self.readers.values_mut() {
+            if reader.position <= start {
+                reader.position += len as u64;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PushResult;
+
+    // payload ids of everything `reader` has left to read
+    fn read_all(ring: &mut RingBuffer, reader: ReaderId) -> Vec<u8> {
+        core::iter::from_fn(|| ring.read(reader))
+            .map(|frame| frame.payload[0])
+            .collect()
+    }
+
+    #[test]
+    fn readers_go_through_the_frames_on_their_own_without_taking_them_out() {
+        let mut ring = RingBuffer::new(256);
+        let _ = ring.log(Frame::new(&[1]));
+        let first = ring.subscribe(Filter::new());
+        let _ = ring.log(Frame::new(&[2]));
+        let second = ring.subscribe(Filter::new());
+        assert_eq!(ring.readers(), 2);
+
+        assert_eq!(read_all(&mut ring, first), [1, 2]);
+        let _ = ring.log(Frame::new(&[3]));
+        assert_eq!(read_all(&mut ring, first), [3]);
+        assert_eq!(read_all(&mut ring, second), [1, 2, 3]);
+        assert!(ring.read(first).is_none());
+        assert!(matches!(ring.flush_frame(), FrameResult::Ok(_)));
+    }
+
+    #[test]
+    fn filters_pick_by_level_tag_and_kind() {
+        let frames = [
+            Frame::new(&[1]).with_level(Level::Error),
+            Frame::new(&[2]).with_level(Level::Debug).with_tag(7),
+            Frame::new(&[3]).with_tag(7).with_kind(FrameKind::Telemetry),
+            Frame::new(&[4]),
+        ];
+        let mut ring = RingBuffer::new(256);
+        for frame in frames {
+            let _ = ring.log(frame);
+        }
+        let warnings = ring.subscribe(Filter::new().at_least(Level::Warn));
+        let tagged = ring.subscribe(Filter::new().tags([7]));
+        let telemetry = ring.subscribe(Filter::new().kinds([FrameKind::Telemetry]));
+        let logs = ring.subscribe(Filter::new().kinds([FrameKind::Log]).at_least(Level::Info));
+        assert_eq!(read_all(&mut ring, warnings), [1]);
+        assert_eq!(read_all(&mut ring, tagged), [2, 3]);
+        assert_eq!(read_all(&mut ring, telemetry), [3]);
+        assert_eq!(read_all(&mut ring, logs), [1, 4]);
+    }
+
+    #[test]
+    fn a_new_filter_applies_to_what_isnt_read_yet() {
+        let mut ring = RingBuffer::new(256);
+        let _ = ring.log(Frame::new(&[1]).with_tag(1));
+        let _ = ring.log(Frame::new(&[2]).with_tag(2));
+        let _ = ring.log(Frame::new(&[3]).with_tag(1));
+        let reader = ring.subscribe(Filter::new());
+        assert_eq!(ring.read(reader).map(|frame| frame.payload), Some(vec![1]));
+        assert!(ring.set_filter(reader, Filter::new().tags([1])));
+        assert_eq!(read_all(&mut ring, reader), [3]);
+    }
+
+    #[test]
+    fn corrupt_frames_are_skipped() {
+        let mut ring = RingBuffer::new(256);
+        let mut corrupt = Frame::new(b"bad").encode();
+        corrupt[1] ^= 0xff;
+        ring.push_slice(&corrupt);
+        let _ = ring.log(Frame::new(&[1]));
+        let reader = ring.subscribe(Filter::new());
+        assert_eq!(read_all(&mut ring, reader), [1]);
+    }
+
+    #[test]
+    fn a_reader_left_behind_carries_on_at_the_oldest_frame() {
+        let mut ring = RingBuffer::new(256);
+        let reader = ring.subscribe(Filter::new());
+        for i in 1..=3 {
+            let _ = ring.log(Frame::new(&[i]));
+        }
+        let _ = ring.flush_frame();
+        let _ = ring.flush_frame();
+        assert_eq!(read_all(&mut ring, reader), [3]);
+    }
+
+    #[test]
+    fn unsubscribed_readers_read_nothing() {
+        let mut ring = RingBuffer::new(256);
+        let _ = ring.log(Frame::new(&[1]));
+        let reader = ring.subscribe(Filter::new());
+        assert!(ring.unsubscribe(reader));
+        assert!(!ring.unsubscribe(reader));
+        assert!(!ring.set_filter(reader, Filter::new()));
+        assert!(ring.read(reader).is_none());
+        assert_eq!(ring.readers(), 0);
+    }
+
+    #[test]
+    fn a_follower_only_sees_frames_logged_after_it_started() {
+        let mut ring = RingBuffer::new(256);
+        let _ = ring.log(Frame::new(&[1]));
+        let _ = ring.log(Frame::new(&[2]));
+        let follower = ring.follow(Filter::new());
+        assert!(ring.read(follower).is_none());
+        let _ = ring.log(Frame::new(&[3]));
+        assert_eq!(read_all(&mut ring, follower), [3]);
+    }
+
+    #[test]
+    fn rewinding_reads_the_last_frames_again() {
+        let mut ring = RingBuffer::new(256);
+        for i in 1..=4 {
+            let _ = ring.log(Frame::new(&[i]));
+        }
+        let reader = ring.subscribe(Filter::new());
+        assert_eq!(read_all(&mut ring, reader), [1, 2, 3, 4]);
+        assert!(ring.rewind(reader, 2));
+        assert_eq!(read_all(&mut ring, reader), [3, 4]);
+        assert!(ring.rewind(reader, 10));
+        assert_eq!(read_all(&mut ring, reader), [1, 2, 3, 4]);
+        assert!(ring.rewind(reader, 0));
+        assert!(ring.read(reader).is_none());
+
+        assert!(ring.unsubscribe(reader));
+        assert!(!ring.rewind(reader, 1));
+    }
+
+    #[test]
+    fn with_retention_frames_leave_once_every_reader_read_them() {
+        let mut ring = RingBuffer::new(256);
+        ring.set_reader_retention(true);
+        let all = ring.subscribe(Filter::new());
+        let errors = ring.subscribe(Filter::new().at_least(Level::Error));
+        let _ = ring.log(Frame::new(&[1]));
+        let _ = ring.log(Frame::new(&[2]).with_level(Level::Error));
+        let two = ring.len();
+        assert_eq!(
+            ring.lag(all),
+            Some(Lag {
+                frames: 2,
+                bytes: two
+            })
+        );
+
+        assert_eq!(read_all(&mut ring, all), [1, 2]);
+        assert_eq!(ring.len(), two);
+        // skipping a frame its filter turns down counts as reading it
+        assert_eq!(ring.read(errors).map(|frame| frame.payload), Some(vec![2]));
+        assert!(ring.is_empty());
+        assert_eq!(ring.lag(errors), Some(Lag::default()));
+    }
+
+    #[test]
+    fn unsubscribing_the_last_reader_behind_lets_its_frames_go() {
+        let mut ring = RingBuffer::new(256);
+        ring.set_reader_retention(true);
+        let fast = ring.subscribe(Filter::new());
+        let slow = ring.subscribe(Filter::new());
+        let _ = ring.log(Frame::new(&[1]));
+        let _ = read_all(&mut ring, fast);
+        assert!(!ring.is_empty());
+        assert!(ring.unsubscribe(slow));
+        assert!(ring.is_empty());
+        assert_eq!(ring.lag(slow), None);
+    }
+
+    #[test]
+    fn a_full_ring_waits_for_a_blocking_reader() {
+        let len = Frame::new(&[1; 16]).encode().len();
+        let mut ring = RingBuffer::new(3 * len + 1);
+        ring.set_reader_retention(true);
+        let reader = ring.subscribe(Filter::new());
+        for i in 1..=3 {
+            let _ = ring.log(Frame::new(&[i; 16]));
+        }
+        assert!(matches!(ring.log(Frame::new(&[4; 16])), PushResult::Err(_)));
+
+        // once it read some there's room again, for the dropped marker too
+        let _ = ring.read(reader);
+        let _ = ring.read(reader);
+        assert!(matches!(ring.log(Frame::new(&[4; 16])), PushResult::Ok));
+        let next: Vec<Frame> = core::iter::from_fn(|| ring.read(reader)).collect();
+        assert_eq!(next.len(), 3);
+        assert_eq!(next[1].dropped.map(|dropped| dropped.messages), Some(1));
+        assert_eq!(next[2].payload, [4; 16]);
+    }
+
+    #[test]
+    fn without_readers_retention_holds_nothing_back() {
+        let mut ring = RingBuffer::new(256);
+        ring.set_reader_retention(true);
+        let _ = ring.log(Frame::new(&[1]));
+        assert!(!ring.is_empty());
+        // flushing still takes frames out without asking
+        let reader = ring.subscribe(Filter::new());
+        let _ = ring.flush_frame();
+        assert!(ring.is_empty());
+        assert!(ring.read(reader).is_none());
+    }
+
+    // retention on, room for three frames of 16 byte payloads, all of them stored, and
+    // one reader with `slow` that hasn't read any
+    fn overrun(slow: SlowReader) -> (RingBuffer, ReaderId, usize) {
+        let len = Frame::new(&[1; 16]).encode().len();
+        let mut ring = RingBuffer::new(3 * len + 1);
+        ring.set_reader_retention(true);
+        let reader = ring.subscribe(Filter::new());
+        assert!(ring.set_slow_reader(reader, slow));
+        for i in 1..=3 {
+            let _ = ring.log(Frame::new(&[i; 16]));
+        }
+        (ring, reader, len)
+    }
+
+    #[test]
+    fn a_skipping_reader_moves_on_and_reports_the_gap() {
+        let (mut ring, reader, len) = overrun(SlowReader::Skip);
+        assert!(matches!(ring.log(Frame::new(&[4; 16])), PushResult::Ok));
+        assert_eq!(
+            ring.take_gap(reader),
+            Some(Lag {
+                frames: 1,
+                bytes: len
+            })
+        );
+        assert_eq!(ring.take_gap(reader), None);
+        assert_eq!(read_all(&mut ring, reader), [2, 3, 4]);
+    }
+
+    #[test]
+    fn a_dropping_reader_lapses_until_resynced() {
+        let (mut ring, reader, _) = overrun(SlowReader::Drop);
+        assert!(!ring.resync(reader));
+        assert!(matches!(ring.log(Frame::new(&[4; 16])), PushResult::Ok));
+        assert!(ring.is_lapsed(reader));
+        assert!(ring.read(reader).is_none());
+
+        // a lapsed reader holds nothing back
+        assert!(matches!(ring.log(Frame::new(&[5; 16])), PushResult::Ok));
+        assert!(ring.resync(reader));
+        assert!(!ring.is_lapsed(reader));
+        assert_eq!(read_all(&mut ring, reader), [3, 4, 5]);
+    }
+
+    #[test]
+    fn blocking_readers_hold_the_new_frame_back_but_others_dont() {
+        let (mut ring, blocking, _) = overrun(SlowReader::Block);
+        let skipping = ring.subscribe(Filter::new());
+        ring.set_slow_reader(skipping, SlowReader::Skip);
+        assert!(matches!(ring.log(Frame::new(&[4; 16])), PushResult::Err(_)));
+        assert_eq!(ring.take_gap(blocking), None);
+
+        // without retention nothing waits, a blocking reader is skipped over instead
+        ring.set_reader_retention(false);
+        let _ = ring.flush_frame();
+        assert_eq!(ring.take_gap(blocking).map(|gap| gap.frames), Some(1));
+        assert!(!ring.is_lapsed(blocking));
+    }
+
+    #[test]
+    fn policies_only_apply_to_subscribed_readers() {
+        let mut ring = RingBuffer::new(64);
+        let reader = ring.subscribe(Filter::new());
+        ring.unsubscribe(reader);
+        assert!(!ring.set_slow_reader(reader, SlowReader::Drop));
+        assert!(!ring.is_lapsed(reader));
+        assert_eq!(ring.take_gap(reader), None);
+    }
+
+    #[test]
+    fn catching_up_copies_the_history_out_and_follows_live() {
+        let mut ring = RingBuffer::new(256);
+        ring.set_reader_retention(true);
+        let reader = ring.follow(Filter::new().at_least(Level::Warn));
+        let _ = ring.log(Frame::new(&[1]).with_level(Level::Error));
+        let _ = ring.log(Frame::new(&[2]).with_level(Level::Debug));
+        assert_eq!(ring.catch_up(reader), Some(2));
+        assert_eq!(ring.backlog(reader), Some(2));
+        // the copy holds nothing back in the ring
+        assert!(ring.is_empty());
+
+        let _ = ring.log(Frame::new(&[3]).with_level(Level::Warn));
+        // the filter still applies to the copy
+        assert_eq!(read_all(&mut ring, reader), [1, 3]);
+        assert_eq!(ring.backlog(reader), Some(0));
+    }
+
+    #[test]
+    fn a_lapsed_reader_catches_up_and_reads_again() {
+        let (mut ring, reader, _) = overrun(SlowReader::Drop);
+        let _ = ring.read(reader);
+        let _ = ring.log(Frame::new(&[4; 16]));
+        let _ = ring.log(Frame::new(&[5; 16]));
+        assert!(ring.is_lapsed(reader));
+        assert_eq!(ring.catch_up(reader), Some(3));
+        assert!(!ring.is_lapsed(reader));
+        assert_eq!(read_all(&mut ring, reader), [3, 4, 5]);
+    }
+
+    #[test]
+    fn rewinding_drops_whats_left_of_the_copy() {
+        let mut ring = RingBuffer::new(256);
+        for i in 1..=3 {
+            let _ = ring.log(Frame::new(&[i]));
+        }
+        let reader = ring.subscribe(Filter::new());
+        let _ = ring.catch_up(reader);
+        assert!(ring.rewind(reader, 1));
+        assert_eq!(ring.backlog(reader), Some(0));
+        assert_eq!(read_all(&mut ring, reader), [3]);
+
+        ring.unsubscribe(reader);
+        assert_eq!(ring.catch_up(reader), None);
+        assert_eq!(ring.backlog(reader), None);
+    }
+
+    #[test]
+    fn catching_up_doesnt_copy_what_was_read() {
+        let mut ring = RingBuffer::new(256);
+        let reader = ring.subscribe(Filter::new());
+        for i in 1..=3 {
+            let _ = ring.log(Frame::new(&[i]));
+        }
+        assert_eq!(ring.read(reader).map(|frame| frame.payload), Some(vec![1]));
+        assert_eq!(ring.catch_up(reader), Some(2));
+        let _ = ring.log(Frame::new(&[4]));
+        // a second copy goes behind what's left of the first
+        assert_eq!(ring.catch_up(reader), Some(3));
+        assert_eq!(read_all(&mut ring, reader), [2, 3, 4]);
+    }
+}
@@ -0,0 +1,97 @@
+// self-describing structured payloads: any serde type encoded as cbor, flagged with
+// `FrameKind::Cbor` so the host can decode it generically without knowing the type
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use ciborium::Value;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::frame::{Frame, FrameResult};
+use crate::kind::FrameKind;
+use crate::{PushResult, RingBuffer};
+
+impl Frame {
+    // a cbor frame carrying `value`
+    pub fn cbor<T: Serialize>(value: &T) -> FrameResult {
+        let mut payload = Vec::new();
+        match ciborium::into_writer(value, &mut payload) {
+            Ok(()) => FrameResult::Ok(Frame::new(&payload).with_kind(FrameKind::Cbor)),
+            Err(e) => FrameResult::Err(e.to_string()),
+        }
+    }
+
+    // the payload of a cbor frame as `T`, None for other kinds or if it doesn't match
+    pub fn cbor_as<T: DeserializeOwned>(&self) -> Option<T> {
+        if self.kind != Some(FrameKind::Cbor) {
+            return None;
+        }
+        ciborium::from_reader(self.payload.as_slice()).ok()
+    }
+
+    // the payload of a cbor frame as a generic value tree, for hosts that don't have the type
+    pub fn cbor_value(&self) -> Option<Value> {
+        self.cbor_as()
+    }
+}
+
+impl RingBuffer {
+    pub fn log_cbor<T: Serialize>(&mut self, value: &T) -> PushResult {
+        match Frame::cbor(value) {
+            FrameResult::Ok(frame) => self.log(frame),
+            FrameResult::Err(e) => PushResult::Err(String::from("Error encoding cbor: ") + &e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::collections::BTreeMap;
+
+    use super::*;
+
+    fn flushed(ring: &mut RingBuffer) -> Frame {
+        match ring.flush_frame() {
+            FrameResult::Ok(frame) => frame,
+            FrameResult::Err(e) => panic!("{e}"),
+        }
+    }
+
+    #[test]
+    fn values_come_back_as_their_type() {
+        let mut ring = RingBuffer::new(128);
+        let _ = ring.log_cbor(&(1200u16, String::from("running")));
+        let frame = flushed(&mut ring);
+        assert_eq!(frame.kind, Some(FrameKind::Cbor));
+        assert_eq!(
+            frame.cbor_as::<(u16, String)>(),
+            Some((1200, String::from("running")))
+        );
+        assert_eq!(frame.cbor_as::<String>(), None);
+    }
+
+    #[test]
+    fn hosts_without_the_type_get_a_value_tree() {
+        let mut record = BTreeMap::new();
+        record.insert("rpm", 1200);
+        let mut ring = RingBuffer::new(128);
+        let _ = ring.log_cbor(&record);
+
+        let value = flushed(&mut ring).cbor_value().unwrap();
+        let entries = value.as_map().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].0, Value::Text("rpm".to_string()));
+        assert_eq!(entries[0].1, Value::Integer(1200.into()));
+    }
+
+    #[test]
+    fn other_kinds_arent_read_as_cbor() {
+        let FrameResult::Ok(frame) = Frame::cbor(&7u8) else {
+            panic!("encoding failed");
+        };
+        // same bytes, no cbor flag
+        assert_eq!(Frame::new(&frame.payload).cbor_as::<u8>(), None);
+        assert_eq!(frame.cbor_as::<u8>(), Some(7));
+    }
+}
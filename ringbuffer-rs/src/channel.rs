@@ -0,0 +1,126 @@
+// multiplexing several logical streams (stdout, telemetry, crash data, ...) through one buffer
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+use crate::frame::{Frame, FrameResult, Handler};
+use crate::{PushResult, RingBuffer};
+
+// producer handle that stamps every frame with its channel id
+pub struct Channel<'a> {
+    ring: &'a mut RingBuffer,
+    id: u8,
+}
+
+impl<'a> Channel<'a> {
+    pub(crate) fn new(ring: &'a mut RingBuffer, id: u8) -> Self {
+        Channel { ring, id }
+    }
+
+    pub fn id(&self) -> u8 {
+        self.id
+    }
+
+    pub fn log(&mut self, message: &[u8]) -> PushResult {
+        self.log_frame(Frame::new(message))
+    }
+
+    pub fn log_frame(&mut self, frame: Frame) -> PushResult {
+        self.ring.log(frame.with_channel(self.id))
+    }
+}
+
+// consumer side: drains the buffer and hands each frame to the sink for its channel
+#[derive(Default)]
+pub struct Demux<'a> {
+    routes: Vec<(u8, Handler<'a>)>,
+    fallback: Option<Handler<'a>>,
+}
+
+impl<'a> Demux<'a> {
+    pub fn new() -> Self {
+        Demux {
+            routes: Vec::new(),
+            fallback: None,
+        }
+    }
+
+    pub fn route(mut self, channel: u8, sink: impl FnMut(Frame) + 'a) -> Self {
+        self.routes.retain(|(id, _)| *id != channel);
+        self.routes.push((channel, Box::new(sink)));
+        self
+    }
+
+    // gets frames without a channel or on a channel nobody routed, otherwise they're dropped
+    pub fn fallback(mut self, sink: impl FnMut(Frame) + 'a) -> Self {
+        self.fallback = Some(Box::new(sink));
+        self
+    }
+
+    // flush every complete frame, returns how many were delivered. corrupt frames are skipped
+    pub fn dispatch(&mut self, ring: &mut RingBuffer) -> usize {
+        let mut delivered = 0;
+        while let Some(bytes) = ring.pop_frame_bytes() {
+            let frame = match ring.decode_flushed(&bytes) {
+                FrameResult::Ok(frame) => frame,
+                FrameResult::Err(_) => continue,
+            };
+            let route = frame.channel.and_then(|channel| {
+                self.routes
+                    .iter_mut()
+                    .find(|(id, _)| *id == channel)
+                    .map(|(_, sink)| sink)
+            });
+            if let Some(sink) = route.or(self.fallback.as_mut()) {
+                sink(frame);
+                delivered += 1;
+            }
+        }
+        delivered
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_come_out_on_their_channel_in_order() {
+        let mut ring = RingBuffer::new(256);
+        let _ = ring.channel(1).log(b"out 1");
+        let _ = ring.channel(2).log(b"telemetry");
+        let _ = ring.channel(1).log(b"out 2");
+        let _ = ring.log(Frame::new(b"unchannelled"));
+        let _ = ring.channel(9).log(b"unrouted");
+
+        let mut stdout = Vec::new();
+        let mut telemetry = Vec::new();
+        let mut rest = Vec::new();
+        let delivered = Demux::new()
+            .route(1, |frame| stdout.push(frame.payload))
+            .route(2, |frame| telemetry.push(frame.payload))
+            .fallback(|frame| rest.push(frame.payload))
+            .dispatch(&mut ring);
+        assert_eq!(delivered, 5);
+        assert_eq!(stdout, [b"out 1", b"out 2"]);
+        assert_eq!(telemetry, [b"telemetry"]);
+        assert_eq!(rest, [&b"unchannelled"[..], b"unrouted"]);
+    }
+
+    #[test]
+    fn without_a_fallback_unrouted_frames_are_dropped() {
+        let mut ring = RingBuffer::new(64);
+        let _ = ring.channel(3).log(b"nobody listens");
+        let mut seen = 0;
+        // a later route replaces an earlier one for the same channel
+        let delivered = Demux::new()
+            .route(3, |_| panic!("replaced"))
+            .route(3, |_| seen += 1)
+            .dispatch(&mut ring);
+        assert_eq!((delivered, seen), (1, 1));
+
+        let _ = ring.channel(4).log(b"nobody listens");
+        assert_eq!(Demux::new().dispatch(&mut ring), 0);
+        assert!(ring.is_empty());
+    }
+}
@@ -0,0 +1,212 @@
+// chrome trace event json of timestamped frames, opens in chrome://tracing and perfetto.
+// span open/close frames (see `RingBufferLayer::with_spans`) become begin/end pairs,
+// everything else an instant event. frames without a timestamp have no place on the
+// timeline and are left out
+
+use std::fmt::Write as _;
+use std::io::{self, Write};
+use std::time::Duration;
+
+use crate::frame::{Frame, FrameResult};
+use crate::json;
+use crate::kv::Value;
+use crate::RingBuffer;
+
+pub struct ChromeTrace {
+    tick: Duration,
+}
+
+impl Default for ChromeTrace {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ChromeTrace {
+    // timestamps are read as milliseconds, what `SystemClock::new` produces
+    pub fn new() -> Self {
+        ChromeTrace {
+            tick: Duration::from_millis(1),
+        }
+    }
+
+    // length of one timestamp tick, match the resolution of the ring's clock
+    pub fn with_tick(mut self, tick: Duration) -> Self {
+        self.tick = tick;
+        self
+    }
+
+    // drain every complete frame into one trace document, returns how many events
+    // were written
+    pub fn export(&self, ring: &mut RingBuffer, out: &mut impl Write) -> io::Result<usize> {
+        let mut events = Vec::new();
+        while let Some(bytes) = ring.peek_frame_bytes() {
+            let decoded = Frame::decode(&bytes);
+            if let FrameResult::Ok(frame) = &decoded {
+                events.extend(self.event(frame));
+            }
+            ring.consume_frame(bytes.len());
+            ring.count_flushed(&bytes, &decoded);
+        }
+        self.write(out, &events)
+    }
+
+    // same as export but leaves the buffer untouched
+    pub fn dump(&self, ring: &RingBuffer, out: &mut impl Write) -> io::Result<usize> {
+        let events: Vec<_> = ring
+            .frame_bytes()
+            .iter()
+            .filter_map(|bytes| match Frame::decode(bytes) {
+                FrameResult::Ok(frame) => self.event(&frame),
+                FrameResult::Err(_) => None,
+            })
+            .collect();
+        self.write(out, &events)
+    }
+
+    fn write(&self, out: &mut impl Write, events: &[String]) -> io::Result<usize> {
+        writeln!(out, "{{\"traceEvents\":[")?;
+        for (i, event) in events.iter().enumerate() {
+            let sep = if i + 1 < events.len() { "," } else { "" };
+            writeln!(out, "{}{}", event, sep)?;
+        }
+        writeln!(out, "],\"displayTimeUnit\":\"ms\"}}")?;
+        Ok(events.len())
+    }
+
+    fn event(&self, frame: &Frame) -> Option<String> {
+        let ticks = frame.timestamp?;
+        let micros = ticks as f64 * self.tick.as_nanos() as f64 / 1000.0;
+        let pairs = frame.pairs().unwrap_or_default();
+        let pair = |key: &str| {
+            pairs.iter().find_map(|(k, v)| match v {
+                Value::Str(v) if k == key => Some(v.to_string()),
+                _ => None,
+            })
+        };
+
+        let (phase, name) = match (pair("span"), pair("event").as_deref()) {
+            (Some(span), Some("open")) => ("B", span),
+            (Some(span), Some("close")) => ("E", span),
+            _ => {
+                let name = match (frame.dropped, pair("message")) {
+                    (Some(dropped), _) => dropped.to_string(),
+                    (None, Some(message)) => message,
+                    // a plain key-value record, its pairs end up in args
+                    (None, None) if !pairs.is_empty() => "kv".to_string(),
+                    (None, None) => String::from_utf8_lossy(&frame.payload).into_owned(),
+                };
+                ("i", name)
+            }
+        };
+
+        let mut event = String::from("{\"name\":");
+        json::write_str(&mut event, &name);
+        let category = frame.kind.map(json::kind_name).unwrap_or("log");
+        let _ = write!(
+            event,
+            ",\"cat\":\"{}\",\"ph\":\"{}\",\"ts\":{},\"pid\":1,\"tid\":{}",
+            category,
+            phase,
+            micros,
+            frame.channel.unwrap_or(0)
+        );
+        if phase == "i" {
+            event.push_str(",\"s\":\"t\"");
+        }
+
+        event.push_str(",\"args\":{");
+        let mut sep = "";
+        if let Some(level) = frame.level {
+            let _ = write!(event, "\"level\":\"{}\"", level);
+            sep = ",";
+        }
+        if let Some(seq) = frame.seq {
+            let _ = write!(event, "{}\"seq\":{}", sep, seq);
+            sep = ",";
+        }
+        if let Some(tag) = frame.tag {
+            let _ = write!(event, "{}\"tag\":{}", sep, tag);
+            sep = ",";
+        }
+        for (key, value) in &pairs {
+            if matches!(key.as_str(), "span" | "event" | "message") {
+                continue;
+            }
+            event.push_str(sep);
+            json::write_str(&mut event, key);
+            event.push(':');
+            match value {
+                Value::Str(v) => json::write_str(&mut event, v),
+                Value::Float(v) if !v.is_finite() => event.push_str("null"),
+                v => {
+                    let _ = write!(event, "{}", v);
+                }
+            }
+            sep = ",";
+        }
+        event.push_str("}}");
+        Some(event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level::Level;
+
+    fn events(trace: &ChromeTrace, ring: &mut RingBuffer) -> Vec<String> {
+        let mut out = Vec::new();
+        let count = trace.export(ring, &mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<_> = text.lines().map(str::to_string).collect();
+        assert_eq!(lines.first().unwrap(), "{\"traceEvents\":[");
+        assert_eq!(lines.last().unwrap(), "],\"displayTimeUnit\":\"ms\"}");
+        assert_eq!(lines.len(), count + 2);
+        lines[1..lines.len() - 1].to_vec()
+    }
+
+    #[test]
+    fn spans_become_begin_end_pairs() {
+        let mut ring = RingBuffer::new(256);
+        let span = |event| Frame::default().kv("span", "spin_up").kv("event", event);
+        let _ = ring.log(span("open").with_timestamp(2).with_channel(1));
+        let _ = ring.log(
+            Frame::default()
+                .kv("message", "stalled")
+                .kv("rpm", 1200)
+                .with_timestamp(3)
+                .with_level(Level::Warn),
+        );
+        let _ = ring.log(span("close").with_timestamp(5).with_channel(1));
+
+        let trace = ChromeTrace::new().with_tick(Duration::from_micros(500));
+        assert_eq!(
+            events(&trace, &mut ring),
+            [
+                r#"{"name":"spin_up","cat":"kv","ph":"B","ts":1000,"pid":1,"tid":1,"args":{}},"#,
+                r#"{"name":"stalled","cat":"kv","ph":"i","ts":1500,"pid":1,"tid":0,"s":"t","args":{"level":"WARN","rpm":1200}},"#,
+                r#"{"name":"spin_up","cat":"kv","ph":"E","ts":2500,"pid":1,"tid":1,"args":{}}"#,
+            ]
+        );
+        assert!(ring.is_empty());
+    }
+
+    #[test]
+    fn frames_without_a_timestamp_are_left_out() {
+        let mut ring = RingBuffer::new(256);
+        let _ = ring.log(Frame::new(b"untimed"));
+        let _ = ring.log(Frame::new(b"boot").with_timestamp(1).with_seq(4));
+
+        let trace = ChromeTrace::new();
+        let mut dumped = Vec::new();
+        assert_eq!(trace.dump(&ring, &mut dumped).unwrap(), 1);
+        assert_eq!(
+            events(&trace, &mut ring),
+            [
+                r#"{"name":"boot","cat":"log","ph":"i","ts":1000,"pid":1,"tid":0,"s":"t","args":{"seq":4}}"#
+            ]
+        );
+        assert!(ring.is_empty());
+    }
+}
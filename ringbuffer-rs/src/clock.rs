@@ -0,0 +1,135 @@
+// timestamp sources for frames
+
+#[cfg(feature = "std")]
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// anything that can hand out a monotonic-ish tick count. the unit is up to the
+// implementation (cycles, ms since boot, ...), the ring just stores the number
+pub trait Clock {
+    fn now(&self) -> u64;
+}
+
+// lets a plain closure over a hardware tick counter act as a clock
+impl<F: Fn() -> u64> Clock for F {
+    fn now(&self) -> u64 {
+        self()
+    }
+}
+
+// wall-clock time for host targets: ticks of `resolution` elapsed since `epoch`
+#[cfg(feature = "std")]
+pub struct SystemClock {
+    epoch: SystemTime,
+    resolution: Duration,
+}
+
+#[cfg(feature = "std")]
+impl SystemClock {
+    // milliseconds since the unix epoch
+    pub fn new() -> Self {
+        SystemClock {
+            epoch: UNIX_EPOCH,
+            resolution: Duration::from_millis(1),
+        }
+    }
+
+    pub fn with_epoch(mut self, epoch: SystemTime) -> Self {
+        self.epoch = epoch;
+        self
+    }
+
+    pub fn with_resolution(mut self, resolution: Duration) -> Self {
+        // a zero resolution would divide by zero in now()
+        self.resolution = resolution.max(Duration::from_nanos(1));
+        self
+    }
+
+    // turn a frame timestamp back into a wall-clock time on the consumer side. None when
+    // it's past what SystemTime can hold, e.g. a garbled timestamp
+    pub fn to_system_time(&self, ticks: u64) -> Option<SystemTime> {
+        let nanos = ticks as u128 * self.resolution.as_nanos();
+        let secs = u64::try_from(nanos / 1_000_000_000).ok()?;
+        let elapsed = Duration::new(secs, (nanos % 1_000_000_000) as u32);
+        self.epoch.checked_add(elapsed)
+    }
+}
+
+#[cfg(feature = "std")]
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "std")]
+impl Clock for SystemClock {
+    fn now(&self) -> u64 {
+        // a clock set before the epoch reads as zero rather than panicking
+        let elapsed = SystemTime::now()
+            .duration_since(self.epoch)
+            .unwrap_or_default();
+        (elapsed.as_nanos() / self.resolution.as_nanos()) as u64
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::frame::{Frame, FrameResult};
+    use crate::RingBuffer;
+
+    #[test]
+    fn ticks_count_from_the_epoch_in_the_resolution() {
+        let clock = SystemClock::new()
+            .with_epoch(SystemTime::now() - Duration::from_secs(5))
+            .with_resolution(Duration::from_secs(1));
+        assert!((5..=6).contains(&clock.now()));
+
+        // before the epoch reads as zero
+        let clock = SystemClock::new().with_epoch(SystemTime::now() + Duration::from_secs(60));
+        assert_eq!(clock.now(), 0);
+    }
+
+    #[test]
+    fn timestamps_turn_back_into_wall_clock_times() {
+        let epoch = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let clock = SystemClock::new()
+            .with_epoch(epoch)
+            .with_resolution(Duration::from_millis(10));
+        assert_eq!(
+            clock.to_system_time(150),
+            Some(epoch + Duration::from_millis(1500))
+        );
+        // garbled, way past what SystemTime holds
+        let clock = clock.with_resolution(Duration::from_secs(1));
+        assert_eq!(clock.to_system_time(u64::MAX), None);
+    }
+
+    #[test]
+    fn a_zero_resolution_doesnt_panic() {
+        let clock = SystemClock::new().with_resolution(Duration::ZERO);
+        assert!(clock.now() > 0);
+    }
+
+    #[test]
+    fn logged_frames_get_the_system_time() {
+        let mut ring = RingBuffer::new(64);
+        let _ = ring.log(Frame::new(b"unstamped"));
+        ring.set_clock(SystemClock::new().with_resolution(Duration::from_secs(1)));
+        let _ = ring.log(Frame::new(b"stamped"));
+
+        let FrameResult::Ok(unstamped) = ring.flush_frame() else {
+            panic!("corrupt frame");
+        };
+        assert_eq!(unstamped.timestamp, None);
+        let FrameResult::Ok(stamped) = ring.flush_frame() else {
+            panic!("corrupt frame");
+        };
+        let logged = SystemClock::new()
+            .with_resolution(Duration::from_secs(1))
+            .to_system_time(stamped.timestamp.unwrap())
+            .unwrap();
+        let since = SystemTime::now().duration_since(logged).unwrap();
+        assert!(since < Duration::from_secs(2));
+    }
+}
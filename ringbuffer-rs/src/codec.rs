@@ -0,0 +1,170 @@
+// tokio-util codec for the frame format, so the bytes a ring flushes can be read back
+// with `Framed` on the other end of a socket or serial port
+
+use std::io;
+
+use bytes::{BufMut, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+use crate::frame::{self, Frame, FrameResult};
+
+#[derive(Debug, Clone, Copy)]
+pub struct FrameCodec {
+    max_frame_len: usize,
+    // dropping bytes until the next terminator after an oversized frame
+    discarding: bool,
+    oversized: usize,
+}
+
+impl Default for FrameCodec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FrameCodec {
+    pub fn new() -> Self {
+        FrameCodec {
+            max_frame_len: 64 * 1024,
+            discarding: false,
+            oversized: 0,
+        }
+    }
+
+    // same limit as `FrameDecoder::with_max_frame_len`: a frame longer than this (escaped,
+    // without terminator) is thrown away, which bounds the read buffer when the stream is
+    // garbage or never sends a terminator
+    pub fn with_max_frame_len(mut self, max_frame_len: usize) -> Self {
+        self.max_frame_len = max_frame_len;
+        self
+    }
+
+    // frames dropped for going over the max frame length
+    pub fn oversized(&self) -> usize {
+        self.oversized
+    }
+
+    // drop bytes up to and including the next terminator, true once it was found
+    fn discard(&mut self, src: &mut BytesMut) -> bool {
+        match src.iter().position(|&b| b == frame::TERMINATOR) {
+            Some(end) => {
+                let _ = src.split_to(end + 1);
+                self.discarding = false;
+                true
+            }
+            None => {
+                src.clear();
+                false
+            }
+        }
+    }
+}
+
+// a corrupt frame comes out as FrameResult::Err rather than an io error, so one bad
+// frame doesn't end the stream. an oversized one comes out as an Err once, then its
+// bytes are dropped up to the next terminator
+impl Decoder for FrameCodec {
+    type Item = FrameResult;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> io::Result<Option<FrameResult>> {
+        if self.discarding && !self.discard(src) {
+            return Ok(None);
+        }
+
+        let searched = src.len().min(self.max_frame_len + 1);
+        match src[..searched].iter().position(|&b| b == frame::TERMINATOR) {
+            Some(end) => {
+                let bytes = src.split_to(end + 1);
+                Ok(Some(Frame::decode(&bytes[..end])))
+            }
+            None if src.len() > self.max_frame_len => {
+                self.discarding = true;
+                self.oversized += 1;
+                self.discard(src);
+                Ok(Some(FrameResult::Err("Frame too long".to_string())))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+// same bytes `log_message_with_crc` puts into the ring
+impl Encoder<Vec<u8>> for FrameCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, item: Vec<u8>, dst: &mut BytesMut) -> io::Result<()> {
+        dst.put_slice(&Frame::new(&item).encode());
+        Ok(())
+    }
+}
+
+impl Encoder<Frame> for FrameCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, item: Frame, dst: &mut BytesMut) -> io::Result<()> {
+        dst.put_slice(&item.encode());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode_all(codec: &mut FrameCodec, src: &mut BytesMut) -> Vec<FrameResult> {
+        let mut results = Vec::new();
+        while let Some(result) = codec.decode(src).unwrap() {
+            results.push(result);
+        }
+        results
+    }
+
+    #[test]
+    fn an_oversized_frame_is_dropped_up_to_its_terminator() {
+        let mut codec = FrameCodec::new().with_max_frame_len(16);
+        let mut src = BytesMut::new();
+        src.put_slice(&Frame::new(b"before").encode());
+        src.put_slice(&Frame::new(&[b'x'; 64]).encode());
+        src.put_slice(&Frame::new(b"after").encode());
+
+        let results = decode_all(&mut codec, &mut src);
+        assert_eq!(results.len(), 3);
+        assert!(matches!(&results[0], FrameResult::Ok(frame) if frame.payload == b"before"));
+        assert!(matches!(&results[1], FrameResult::Err(_)));
+        assert!(matches!(&results[2], FrameResult::Ok(frame) if frame.payload == b"after"));
+        assert_eq!(codec.oversized(), 1);
+    }
+
+    #[test]
+    fn a_stream_without_terminators_doesnt_grow_the_buffer() {
+        let mut codec = FrameCodec::new().with_max_frame_len(16);
+        let mut src = BytesMut::new();
+        for _ in 0..10 {
+            src.put_slice(&[0x55; 10]);
+            decode_all(&mut codec, &mut src);
+            assert!(src.len() <= 16);
+        }
+        assert_eq!(codec.oversized(), 1);
+
+        // the frame after the garbage ends comes through
+        src.put_slice(&[frame::TERMINATOR]);
+        src.put_slice(&Frame::new(b"back").encode());
+        let results = decode_all(&mut codec, &mut src);
+        assert_eq!(results.len(), 1);
+        assert!(matches!(&results[0], FrameResult::Ok(frame) if frame.payload == b"back"));
+    }
+
+    #[test]
+    fn a_frame_at_the_limit_is_kept_across_reads() {
+        let encoded = Frame::new(b"exactly").encode();
+        let mut codec = FrameCodec::new().with_max_frame_len(encoded.len() - 1);
+        let mut src = BytesMut::new();
+        src.put_slice(&encoded[..3]);
+        assert!(codec.decode(&mut src).unwrap().is_none());
+        src.put_slice(&encoded[3..]);
+        let results = decode_all(&mut codec, &mut src);
+        assert!(matches!(&results[..], [FrameResult::Ok(frame)] if frame.payload == b"exactly"));
+        assert_eq!(codec.oversized(), 0);
+    }
+}
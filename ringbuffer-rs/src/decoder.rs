@@ -0,0 +1,303 @@
+// sans-io decoder for the frame format: feed it bytes as they arrive from any transport
+// (socket reads, serial chunks, a file dump) and it hands back complete frames, carrying
+// partial ones over to the next call
+
+use alloc::vec::Vec;
+
+use crate::frame::{self, Frame, FrameResult};
+
+#[derive(Debug, Clone)]
+pub struct FrameDecoder {
+    partial: Vec<u8>,
+    max_frame_len: usize,
+    // dropping bytes until the next terminator after an oversized frame
+    discarding: bool,
+    corrupt: usize,
+    oversized: usize,
+    heartbeats: usize,
+    last_heartbeat: Option<u64>,
+}
+
+impl Default for FrameDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FrameDecoder {
+    pub fn new() -> Self {
+        FrameDecoder {
+            partial: Vec::new(),
+            max_frame_len: 64 * 1024,
+            discarding: false,
+            corrupt: 0,
+            oversized: 0,
+            heartbeats: 0,
+            last_heartbeat: None,
+        }
+    }
+
+    // frames longer than this (escaped, without terminator) are thrown away, which also
+    // bounds memory when the stream is garbage
+    pub fn with_max_frame_len(mut self, max_frame_len: usize) -> Self {
+        self.max_frame_len = max_frame_len;
+        self
+    }
+
+    // decode every frame completed by `bytes`. frames that fail their crc are counted
+    // and skipped, the decoder resyncs on the next terminator. heartbeats are counted
+    // and not handed back with the data frames
+    pub fn feed(&mut self, bytes: &[u8]) -> Vec<Frame> {
+        let mut frames = Vec::new();
+        for &byte in bytes {
+            if byte != frame::TERMINATOR {
+                if self.discarding {
+                    continue;
+                }
+                if self.partial.len() == self.max_frame_len {
+                    self.partial.clear();
+                    self.discarding = true;
+                    self.oversized += 1;
+                    continue;
+                }
+                self.partial.push(byte);
+                continue;
+            }
+
+            if self.discarding {
+                self.discarding = false;
+                continue;
+            }
+            match Frame::decode(&self.partial) {
+                FrameResult::Ok(frame) if frame.is_heartbeat() => {
+                    self.heartbeats += 1;
+                    self.last_heartbeat = frame.timestamp.or(self.last_heartbeat);
+                }
+                FrameResult::Ok(frame) => frames.push(frame),
+                FrameResult::Err(_) => self.corrupt += 1,
+            }
+            self.partial.clear();
+        }
+        frames
+    }
+
+    // bytes of an unfinished frame waiting for more input
+    pub fn pending(&self) -> usize {
+        self.partial.len()
+    }
+
+    // frames dropped for a bad crc or header
+    pub fn corrupt(&self) -> usize {
+        self.corrupt
+    }
+
+    // frames dropped for going over the max frame length
+    pub fn oversized(&self) -> usize {
+        self.oversized
+    }
+
+    // heartbeats seen, the link was up when this goes up even if no data frames came
+    pub fn heartbeats(&self) -> usize {
+        self.heartbeats
+    }
+
+    // timestamp of the newest heartbeat that had one
+    pub fn last_heartbeat(&self) -> Option<u64> {
+        self.last_heartbeat
+    }
+
+    // forget any partial frame, e.g. after reconnecting to the transport
+    pub fn reset(&mut self) {
+        self.partial.clear();
+        self.discarding = false;
+    }
+}
+
+// host side of `ItmSink`: a swo capture is a stream of itm packets, not raw bytes. this
+// pulls the payload of one stimulus port back out so it can be fed to a `FrameDecoder`:
+//
+//     let frames = decoder.feed(&unpacker.unpack(&swo_bytes));
+//
+// every software source packet is a header byte (port << 3 | size) followed by 1, 2 or 4
+// payload bytes. sync, overflow, timestamp and hardware packets are skipped
+#[derive(Debug, Clone)]
+pub struct ItmUnpacker {
+    port: u8,
+    // payload bytes still to come for the current packet, and whether they're ours
+    remaining: u8,
+    keep: bool,
+    // inside a protocol packet that continues while bit 7 is set
+    continuation: bool,
+}
+
+impl ItmUnpacker {
+    pub fn new(port: u8) -> Self {
+        ItmUnpacker {
+            port,
+            remaining: 0,
+            keep: false,
+            continuation: false,
+        }
+    }
+
+    pub fn unpack(&mut self, bytes: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        for &byte in bytes {
+            if self.remaining > 0 {
+                self.remaining -= 1;
+                if self.keep {
+                    out.push(byte);
+                }
+                continue;
+            }
+            if self.continuation {
+                self.continuation = byte & 0x80 != 0;
+                continue;
+            }
+
+            match byte & 0x03 {
+                // sync (zeros then 0x80), overflow (0x70), timestamps and extensions
+                0 => self.continuation = byte & 0x80 != 0 && byte != 0x80,
+                size => {
+                    self.remaining = [1, 2, 4][size as usize - 1];
+                    // bit 2 set means a hardware (dwt) source
+                    self.keep = byte & 0x04 == 0 && byte >> 3 == self.port;
+                }
+            }
+        }
+        out
+    }
+
+    pub fn reset(&mut self) {
+        self.remaining = 0;
+        self.continuation = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stream() -> Vec<u8> {
+        [
+            Frame::new(b"first").with_seq(1).encode(),
+            // escaped bytes inside the frame
+            Frame::new(&[0x00, 0xdb, 0xdc, 0x00]).encode(),
+            Frame::new(b"last").with_timestamp(7).encode(),
+        ]
+        .concat()
+    }
+
+    fn payloads(frames: &[Frame]) -> Vec<&[u8]> {
+        frames.iter().map(|frame| &frame.payload[..]).collect()
+    }
+
+    #[test]
+    fn frames_come_out_whatever_the_chunking() {
+        let stream = stream();
+        let mut whole = FrameDecoder::new();
+        let expected = whole.feed(&stream);
+        assert_eq!(payloads(&expected)[0], b"first");
+        assert_eq!(expected.len(), 3);
+
+        for chunk in 1..stream.len() {
+            let mut decoder = FrameDecoder::new();
+            let mut frames = Vec::new();
+            for piece in stream.chunks(chunk) {
+                frames.extend(decoder.feed(piece));
+            }
+            assert_eq!(frames, expected, "chunks of {}", chunk);
+            assert_eq!(decoder.pending(), 0);
+        }
+    }
+
+    #[test]
+    fn a_corrupt_frame_is_counted_and_skipped() {
+        let mut bad = Frame::new(b"bad").encode();
+        bad[1] ^= 0x55;
+        let stream = [bad, stream()].concat();
+        let mut decoder = FrameDecoder::new();
+        let frames = decoder.feed(&stream);
+        assert_eq!(
+            payloads(&frames),
+            [&b"first"[..], &[0x00, 0xdb, 0xdc, 0x00], b"last"]
+        );
+        assert_eq!(decoder.corrupt(), 1);
+    }
+
+    #[test]
+    fn an_oversized_frame_is_dropped_up_to_its_terminator() {
+        let stream = [Frame::new(&[b'x'; 100]).encode(), stream()].concat();
+        let mut decoder = FrameDecoder::new().with_max_frame_len(32);
+        let frames = decoder.feed(&stream);
+        assert_eq!(frames.len(), 3);
+        assert_eq!(decoder.oversized(), 1);
+        assert_eq!(decoder.corrupt(), 0);
+    }
+
+    #[test]
+    fn reset_forgets_the_partial_frame() {
+        let mut decoder = FrameDecoder::new();
+        assert!(decoder.feed(b"half a fr").is_empty());
+        assert_eq!(decoder.pending(), 9);
+        decoder.reset();
+        assert_eq!(decoder.feed(&stream()).len(), 3);
+        assert_eq!(decoder.corrupt(), 0);
+    }
+
+    // software source packets the way `itm::write_all` sends them: words, then a half
+    // word and a byte for the tail
+    fn itm_packets(port: u8, bytes: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut rest = bytes;
+        while !rest.is_empty() {
+            let (size, header) = match rest.len() {
+                4.. => (4, 0x03),
+                2..=3 => (2, 0x02),
+                _ => (1, 0x01),
+            };
+            out.push(port << 3 | header);
+            out.extend_from_slice(&rest[..size]);
+            rest = &rest[size..];
+        }
+        out
+    }
+
+    #[test]
+    fn itm_packets_of_other_sources_are_skipped() {
+        let stream = stream();
+        let (head, tail) = stream.split_at(9);
+        let capture = [
+            // sync
+            &[0x00, 0x00, 0x00, 0x00, 0x00, 0x80][..],
+            &itm_packets(1, head),
+            // overflow, then a local timestamp with two continuation bytes
+            &[0x70, 0xc0, 0x85, 0x01],
+            // the same bytes on port 2 and from a hardware source on port 1
+            &itm_packets(2, head),
+            &[1 << 3 | 0x04 | 0x02, 0xaa, 0xbb],
+            &itm_packets(1, tail),
+        ]
+        .concat();
+
+        let mut unpacker = ItmUnpacker::new(1);
+        assert_eq!(unpacker.unpack(&capture), stream);
+        // and byte by byte
+        let mut unpacker = ItmUnpacker::new(1);
+        let unpacked: Vec<u8> = capture
+            .iter()
+            .flat_map(|byte| unpacker.unpack(&[*byte]))
+            .collect();
+        assert_eq!(FrameDecoder::new().feed(&unpacked).len(), 3);
+    }
+
+    #[test]
+    fn itm_reset_drops_a_cut_packet() {
+        let packets = itm_packets(1, &stream());
+        let mut unpacker = ItmUnpacker::new(1);
+        // the capture stops two bytes into a word packet
+        unpacker.unpack(&packets[..3]);
+        unpacker.reset();
+        assert_eq!(unpacker.unpack(&packets), stream());
+    }
+}
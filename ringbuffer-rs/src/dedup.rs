@@ -0,0 +1,225 @@
+// collapses runs of the same frame, for firmware stuck in a loop logging the same error
+// over and over. a frame identical to the one logged right before it (same payload,
+// level, tag, channel, kind and ttl, whatever its timestamp and sequence number) isn't
+// stored, it's counted. once a different frame comes along a `FrameKind::Repeated` frame
+// goes in front of it: the repeated frame's level, tag and channel, the timestamp of the
+// last repeat, and in its dropped field how many repeats there were and the bytes they
+// would have taken
+//
+//     ring.enable_dedup();
+//
+// call `log_repeats` from a timer to also report a run that's still going. repeats
+// don't take a sequence number, the numbers stay gapless
+
+use alloc::vec::Vec;
+
+use crate::frame::{Dropped, Frame};
+use crate::kind::FrameKind;
+use crate::{PushResult, RingBuffer};
+
+#[derive(Default)]
+pub(crate) struct Dedup {
+    // hash of the last frame that went in, and the repeats of it since
+    last: Option<u64>,
+    repeats: Dropped,
+    // the header fields the repeated frame takes over, no payload
+    template: Frame,
+}
+
+// fnv-1a over everything that makes two frames the same message
+fn identity(frame: &Frame) -> u64 {
+    let mut hash = 0xcbf2_9ce4_8422_2325u64;
+    let header = [
+        frame.level.map_or(0, |level| level as u8),
+        u8::from(frame.channel.is_some()),
+        frame.channel.unwrap_or(0),
+        frame.kind.map_or(0xff, |kind| kind as u8),
+    ];
+    let tag = frame.tag.map_or(u32::MAX, u32::from).to_le_bytes();
+    let ttl = frame.ttl.map_or(u64::MAX, u64::from).to_le_bytes();
+    for &byte in header.iter().chain(&tag).chain(&ttl).chain(&frame.payload) {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+fn repeated_marker(template: &Frame, repeats: Dropped) -> Frame {
+    Frame {
+        timestamp: template.timestamp,
+        level: template.level,
+        tag: template.tag,
+        channel: template.channel,
+        kind: Some(FrameKind::Repeated),
+        ..Frame::dropped_marker(repeats)
+    }
+}
+
+impl RingBuffer {
+    pub fn enable_dedup(&mut self) {
+        self.dedup.get_or_insert_with(Dedup::default);
+    }
+
+    // a run still being counted is lost
+    pub fn disable_dedup(&mut self) {
+        self.dedup = None;
+    }
+
+    // log the repeated frame for the current run now, if it has any repeats. the run
+    // goes on, repeats after this are counted from zero
+    pub fn log_repeats(&mut self) -> PushResult {
+        let Some(dedup) = &self.dedup else {
+            return PushResult::Ok;
+        };
+        if dedup.repeats.messages == 0 {
+            return PushResult::Ok;
+        }
+        let marker = repeated_marker(&dedup.template, dedup.repeats);
+        let result = self.log_frame(&marker);
+        if let (PushResult::Ok, Some(dedup)) = (&result, &mut self.dedup) {
+            dedup.repeats = Dropped::default();
+        }
+        result
+    }
+
+    pub(crate) fn repeats_last(&self, frame: &Frame) -> bool {
+        match &self.dedup {
+            Some(dedup) => {
+                !frame.kind.is_some_and(FrameKind::is_control)
+                    && dedup.last == Some(identity(frame))
+            }
+            None => false,
+        }
+    }
+
+    // whether `frame` (`len` bytes encoded) repeats the last frame, counts it if so
+    pub(crate) fn is_repeat(&mut self, frame: &Frame, len: usize) -> bool {
+        if !self.repeats_last(frame) {
+            return false;
+        }
+        let Some(dedup) = &mut self.dedup else {
+            return false;
+        };
+        dedup.repeats.messages = dedup.repeats.messages.saturating_add(1);
+        dedup.repeats.bytes = dedup.repeats.bytes.saturating_add(len as u32);
+        dedup.template.timestamp = frame.timestamp;
+        true
+    }
+
+    // the repeated frame to log in front of `frame`, the next different one. control
+    // frames don't end a run
+    pub(crate) fn pending_repeats(&self, frame: &Frame) -> Option<Frame> {
+        let dedup = self.dedup.as_ref()?;
+        (dedup.repeats.messages > 0 && !frame.kind.is_some_and(FrameKind::is_control))
+            .then(|| repeated_marker(&dedup.template, dedup.repeats))
+    }
+
+    // `frame` went in, a new run starts with it
+    pub(crate) fn note_logged(&mut self, frame: &Frame) {
+        let Some(dedup) = &mut self.dedup else {
+            return;
+        };
+        if frame.kind.is_some_and(FrameKind::is_control) {
+            return;
+        }
+        dedup.last = Some(identity(frame));
+        dedup.repeats = Dropped::default();
+        dedup.template = Frame {
+            payload: Vec::new(),
+            ..frame.clone()
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level::Level;
+    use crate::sink::MemorySink;
+
+    fn flushed(ring: &mut RingBuffer) -> Vec<Frame> {
+        let mut sink = MemorySink::new();
+        let _ = ring.flush_all_to(&mut sink);
+        sink.take()
+    }
+
+    fn stuck(at: u64) -> Frame {
+        Frame::new(b"stuck")
+            .with_level(Level::Error)
+            .with_tag(4)
+            .with_timestamp(at)
+    }
+
+    #[test]
+    fn a_run_collapses_into_a_repeated_frame() {
+        let mut ring = RingBuffer::new(256);
+        ring.enable_dedup();
+        ring.enable_sequence_numbers();
+        for at in 1..=4 {
+            assert!(matches!(ring.log(stuck(at)), PushResult::Ok));
+        }
+        let _ = ring.log(Frame::new(b"next"));
+
+        let frames = flushed(&mut ring);
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[0].payload, b"stuck");
+        let repeated = &frames[1];
+        assert_eq!(repeated.kind, Some(FrameKind::Repeated));
+        assert_eq!(repeated.level, Some(Level::Error));
+        assert_eq!(repeated.tag, Some(4));
+        assert_eq!(repeated.timestamp, Some(4));
+        let bytes = (2..=4).map(|at| stuck(at).encode().len() as u32).sum();
+        assert_eq!(repeated.dropped, Some(Dropped { messages: 3, bytes }));
+        // repeats didn't use up sequence numbers
+        assert_eq!(frames[0].seq, Some(0));
+        assert_eq!(frames[2].seq, Some(1));
+    }
+
+    #[test]
+    fn any_difference_but_the_time_ends_a_run() {
+        let mut ring = RingBuffer::new(256);
+        ring.enable_dedup();
+        let _ = ring.log(stuck(1));
+        let _ = ring.log(stuck(1).with_tag(5));
+        let _ = ring.log(stuck(1).with_level(Level::Warn));
+        let _ = ring.log(Frame::new(b"stuck!").with_level(Level::Warn).with_tag(5));
+        assert_eq!(flushed(&mut ring).len(), 4);
+    }
+
+    #[test]
+    fn log_repeats_reports_a_run_thats_still_going() {
+        let mut ring = RingBuffer::new(256);
+        ring.enable_dedup();
+        // nothing to report yet
+        assert!(matches!(ring.log_repeats(), PushResult::Ok));
+        for at in 1..=3 {
+            let _ = ring.log(stuck(at));
+        }
+        assert!(matches!(ring.log_repeats(), PushResult::Ok));
+        assert!(matches!(ring.log_repeats(), PushResult::Ok));
+        let _ = ring.log(stuck(4));
+
+        let frames = flushed(&mut ring);
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[1].kind, Some(FrameKind::Repeated));
+        assert_eq!(frames[1].dropped.map(|dropped| dropped.messages), Some(2));
+        // the run goes on, the later repeat is counted from zero
+        let _ = ring.log(Frame::new(b"next"));
+        let frames = flushed(&mut ring);
+        assert_eq!(frames[0].dropped.map(|dropped| dropped.messages), Some(1));
+        assert_eq!(frames[1].payload, b"next");
+    }
+
+    #[test]
+    fn disabling_loses_the_run() {
+        let mut ring = RingBuffer::new(256);
+        ring.enable_dedup();
+        let _ = ring.log(stuck(1));
+        let _ = ring.log(stuck(2));
+        ring.disable_dedup();
+        let _ = ring.log(stuck(3));
+        let frames = flushed(&mut ring);
+        assert_eq!(frames.len(), 2);
+        assert!(frames.iter().all(|frame| frame.kind.is_none()));
+    }
+}
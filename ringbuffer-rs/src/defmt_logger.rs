@@ -0,0 +1,160 @@
+// defmt global logger backed by the ring: each defmt log call becomes one `Defmt` frame
+// holding the encoded defmt bytes, so it can go out over any transport instead of rtt.
+// the host pulls the payloads back out and hands them to defmt's decoder with the elf
+
+use alloc::vec::Vec;
+use core::cell::RefCell;
+
+use critical_section::{CriticalSection, Mutex, RestoreState};
+
+use crate::frame::Frame;
+use crate::kind::FrameKind;
+use crate::RingBuffer;
+
+static RING: Mutex<RefCell<Option<RingBuffer>>> = Mutex::new(RefCell::new(None));
+
+// the log call in progress, only touched between acquire and release
+struct State {
+    taken: bool,
+    restore: RestoreState,
+    encoder: defmt::Encoder,
+    pending: Vec<u8>,
+}
+
+static STATE: Mutex<RefCell<State>> = Mutex::new(RefCell::new(State {
+    taken: false,
+    restore: RestoreState::invalid(),
+    encoder: defmt::Encoder::new(),
+    pending: Vec::new(),
+}));
+
+// install the ring defmt logs into, returns the one it replaced
+pub fn init(ring: RingBuffer) -> Option<RingBuffer> {
+    critical_section::with(|cs| RING.borrow_ref_mut(cs).replace(ring))
+}
+
+// run `f` against the defmt ring, e.g. to flush it, None if `init` wasn't called
+pub fn with_ring<R>(f: impl FnOnce(&mut RingBuffer) -> R) -> Option<R> {
+    critical_section::with(|cs| RING.borrow_ref_mut(cs).as_mut().map(f))
+}
+
+#[defmt::global_logger]
+struct RingLogger;
+
+unsafe impl defmt::Logger for RingLogger {
+    fn acquire() {
+        // held until release, defmt guarantees the two are paired
+        let restore = unsafe { critical_section::acquire() };
+        let cs = unsafe { CriticalSection::new() };
+        let mut state = STATE.borrow_ref_mut(cs);
+        if state.taken {
+            panic!("defmt logger taken reentrantly");
+        }
+        state.taken = true;
+        state.restore = restore;
+        let State {
+            encoder, pending, ..
+        } = &mut *state;
+        encoder.start_frame(|bytes| pending.extend_from_slice(bytes));
+    }
+
+    unsafe fn flush() {}
+
+    unsafe fn release() {
+        let cs = unsafe { CriticalSection::new() };
+        let mut state = STATE.borrow_ref_mut(cs);
+        let State {
+            encoder, pending, ..
+        } = &mut *state;
+        encoder.end_frame(|bytes| pending.extend_from_slice(bytes));
+        if let Some(ring) = RING.borrow_ref_mut(cs).as_mut() {
+            // a full ring counts the message as dropped, defmt has nobody to report to
+            let _ = ring.log(Frame::new(pending).with_kind(FrameKind::Defmt));
+        }
+        pending.clear();
+        state.taken = false;
+        let restore = state.restore;
+        drop(state);
+        unsafe { critical_section::release(restore) };
+    }
+
+    unsafe fn write(bytes: &[u8]) {
+        let cs = unsafe { CriticalSection::new() };
+        let mut state = STATE.borrow_ref_mut(cs);
+        let State {
+            encoder, pending, ..
+        } = &mut *state;
+        encoder.write(bytes, |bytes| pending.extend_from_slice(bytes));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex as TestLock;
+
+    use defmt::Logger;
+
+    use super::*;
+    use crate::frame::FrameResult;
+
+    // the logger is global, one test at a time
+    static SERIAL: TestLock<()> = TestLock::new(());
+
+    // one defmt log call writing `chunks`
+    fn log_call(chunks: &[&[u8]]) {
+        RingLogger::acquire();
+        for chunk in chunks {
+            unsafe { RingLogger::write(chunk) };
+        }
+        unsafe { RingLogger::release() };
+    }
+
+    // what defmt's encoder makes of one call. the encoder marks the start of its very
+    // first frame, so both sides get a frame in before the one compared
+    fn encoded(chunks: &[&[u8]]) -> Vec<u8> {
+        let mut encoder = defmt::Encoder::new();
+        encoder.start_frame(|_| {});
+        encoder.end_frame(|_| {});
+        let mut out = Vec::new();
+        encoder.start_frame(|bytes| out.extend_from_slice(bytes));
+        for chunk in chunks {
+            encoder.write(chunk, |bytes| out.extend_from_slice(bytes));
+        }
+        encoder.end_frame(|bytes| out.extend_from_slice(bytes));
+        out
+    }
+
+    #[test]
+    fn each_log_call_is_one_defmt_frame() {
+        let _serial = SERIAL.lock().unwrap();
+        log_call(&[]);
+        init(RingBuffer::new(128));
+        log_call(&[&[1, 0], &[2, 3]]);
+        log_call(&[&[4]]);
+
+        let frames = with_ring(|ring| {
+            let mut frames = Vec::new();
+            while let FrameResult::Ok(frame) = ring.flush_frame() {
+                frames.push(frame);
+            }
+            frames
+        })
+        .unwrap();
+        assert_eq!(frames.len(), 2);
+        assert!(frames
+            .iter()
+            .all(|frame| frame.kind == Some(FrameKind::Defmt)));
+        assert_eq!(frames[0].payload, encoded(&[&[1, 0], &[2, 3]]));
+        assert_eq!(frames[1].payload, encoded(&[&[4]]));
+    }
+
+    #[test]
+    fn init_hands_back_the_previous_ring() {
+        let _serial = SERIAL.lock().unwrap();
+        init(RingBuffer::new(64));
+        log_call(&[&[9]]);
+        let previous = init(RingBuffer::new(64)).unwrap();
+        assert!(!previous.is_empty());
+        assert_eq!(with_ring(|ring| ring.is_empty()), Some(true));
+    }
+}
@@ -0,0 +1,531 @@
+// zero-copy flush for a real dma engine: hand the contiguous run of frame bytes at the
+// tail straight to the peripheral and only advance tail once the transfer completed
+//
+//     let grant = unsafe { ring.dma_read_grant() };
+//     let len = grant.len();
+//     uart_tx_dma.start(grant);
+//     ...
+//     // transfer-complete interrupt
+//     ring.dma_read_done(len);
+//
+// frames go out in wire format without a crc check, the receiving decoder drops corrupt
+// ones. a complete frame that wraps around the end of the storage goes out in two
+// transfers
+
+use alloc::boxed::Box;
+
+use embedded_dma::ReadBuffer;
+
+use crate::frame::{self, Frame};
+use crate::{PushResult, RingBuffer};
+
+// data cache maintenance for cores where the dma engine doesn't see the cache (cortex-m7,
+// a-class). every grant api cleans the region it hands out, so the engine reads what the
+// cpu wrote and not stale ram:
+//
+//     ring.set_cache_ops(|addr, len| unsafe { (*SCB::PTR).clean_dcache_by_address(addr, len) });
+pub trait CacheOps {
+    // write back dirty cache lines covering [addr, addr + len)
+    fn clean(&self, addr: usize, len: usize);
+}
+
+impl<F: Fn(usize, usize)> CacheOps for F {
+    fn clean(&self, addr: usize, len: usize) {
+        self(addr, len)
+    }
+}
+
+// a detached view of stored bytes, it doesn't borrow the ring so it can be moved into a
+// hal's dma transfer
+pub struct DmaReadGrant {
+    ptr: *const u8,
+    len: usize,
+}
+
+impl DmaReadGrant {
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+unsafe impl ReadBuffer for DmaReadGrant {
+    type Word = u8;
+
+    unsafe fn read_buffer(&self) -> (*const u8, usize) {
+        (self.ptr, self.len)
+    }
+}
+
+// the peripheral reads the memory, the grant never hands out a reference to it
+unsafe impl Send for DmaReadGrant {}
+
+impl RingBuffer {
+    pub fn set_cache_ops(&mut self, cache: impl CacheOps + Send + 'static) {
+        self.cache = Some(Box::new(cache));
+    }
+
+    fn clean_dcache(&self, ptr: *const u8, len: usize) {
+        if let (Some(cache), true) = (&self.cache, len > 0) {
+            cache.clean(ptr as usize, len);
+        }
+    }
+
+    // grant over the complete frames at the tail, up to max_flush_size bytes of them (at
+    // least the first frame, however long) and never past the end of the storage.
+    // empty when there's no complete frame
+    //
+    // safety: the ring must outlive the transfer, and nothing else may consume from it
+    // (flush, pop, read view, ...) until `dma_read_done`. producers can keep logging
+    #[allow(clippy::missing_safety_doc)]
+    pub unsafe fn dma_read_grant(&self) -> DmaReadGrant {
+        self.dma_grant_within(0, usize::MAX)
+    }
+
+    // same as dma_read_grant for the stored bytes after the first `skip`, which are
+    // already out in earlier grants, and at most `limit` bytes long for engines with a
+    // transfer size limit. a frame longer than the limit goes out in pieces
+    pub(crate) fn dma_grant_within(&self, skip: usize, limit: usize) -> DmaReadGrant {
+        let skip = skip.min(self.len());
+        // bytes already granted haven't been paid for yet
+        let limit = limit.min(self.flush_allowance().saturating_sub(skip));
+        let start = (self.tail + skip) % self.size;
+        let contiguous = if self.head >= start {
+            self.head - start
+        } else {
+            self.size - start
+        };
+        let mut len = 0;
+        for i in 0..contiguous.min(limit) {
+            if self.byte_at(start + i) == frame::TERMINATOR {
+                len = i + 1;
+                if len >= self.max_flush_size {
+                    break;
+                }
+            }
+        }
+
+        // no terminator in reach: the frame runs past the end of the storage or past the
+        // limit, send the first piece of it if the rest has been written
+        if len == 0 && self.complete_len() > skip {
+            len = contiguous.min(limit);
+        }
+        let ptr = self.buffer[start..].as_ptr();
+        self.clean_dcache(ptr, len);
+        DmaReadGrant { ptr, len }
+    }
+
+    // release the first `count` bytes of the last grant once the transfer is done
+    pub fn dma_read_done(&mut self, count: usize) {
+        let count = count.min(self.len());
+        let frames = (0..count)
+            .filter(|&i| self.stored_byte(i) == frame::TERMINATOR)
+            .count();
+        self.counters.flushed_frames += frames as u64;
+        self.counters.flushed_bytes += count as u64;
+        self.pay_flush(count);
+        self.advance_tail(count);
+    }
+}
+
+impl RingBuffer {
+    // every complete frame as at most two (address, length) descriptors, the second one
+    // non-empty only when the frames wrap around the end of the storage. for dma engines
+    // with linked descriptors, so a full flush needs no bounce buffer. release the bytes
+    // with `dma_read_done` once the chain completed, the same rules as dma_read_grant
+    // apply in between
+    pub fn scatter_gather(&self) -> [(*const u8, usize); 2] {
+        let len = self.complete_len();
+        let first = len.min(self.size - self.tail);
+        let descriptors = [
+            (self.buffer[self.tail..].as_ptr(), first),
+            (self.buffer.as_ptr(), len - first),
+        ];
+        for (ptr, len) in descriptors {
+            self.clean_dcache(ptr, len);
+        }
+        descriptors
+    }
+}
+
+// a region handed to the dma engine, holding the ring for as long as the transfer runs
+// so nothing can consume (and then overwrite) the bytes in flight. frames can still be
+// logged through the guard, they only ever land in free space
+//
+//     let transfer = ring.dma_transfer();
+//     uart_tx_dma.start(transfer.bytes());
+//     ... wait for transfer complete
+//     transfer.commit();
+//
+// dropping the guard without commit (error, timeout, dma stopped) aborts: tail stays
+// put and the bytes go out again with the next transfer
+pub struct DmaTransfer<'a> {
+    ring: &'a mut RingBuffer,
+    grant: DmaReadGrant,
+}
+
+impl RingBuffer {
+    // same region as dma_read_grant, possibly empty
+    pub fn dma_transfer(&mut self) -> DmaTransfer<'_> {
+        let grant = self.dma_grant_within(0, usize::MAX);
+        DmaTransfer { ring: self, grant }
+    }
+}
+
+impl DmaTransfer<'_> {
+    pub fn bytes(&self) -> &[u8] {
+        &self.ring.buffer[self.ring.tail..self.ring.tail + self.grant.len]
+    }
+
+    pub fn len(&self) -> usize {
+        self.grant.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.grant.len == 0
+    }
+
+    pub fn ring(&self) -> &RingBuffer {
+        self.ring
+    }
+
+    // log while the transfer is in flight
+    pub fn log(&mut self, frame: Frame) -> PushResult {
+        self.ring.log(frame)
+    }
+
+    // the whole region went out, release it
+    pub fn commit(self) {
+        self.ring.dma_read_done(self.grant.len);
+    }
+
+    // only the first `count` bytes went out, e.g. the dma was stopped half way. the rest
+    // is sent again
+    pub fn commit_partial(self, count: usize) {
+        self.ring.dma_read_done(count.min(self.grant.len));
+    }
+}
+
+unsafe impl ReadBuffer for DmaTransfer<'_> {
+    type Word = u8;
+
+    unsafe fn read_buffer(&self) -> (*const u8, usize) {
+        self.grant.read_buffer()
+    }
+}
+
+// double buffering for dma engines that can queue a second transfer (or for keeping the
+// gap between transfers short): up to two grants are out at once, one being sent and the
+// next one right behind it. from the transfer-complete interrupt:
+//
+//     pump.transfer_complete(&mut ring);
+//     if let Some(grant) = unsafe { pump.next_grant(&ring) } {
+//         uart_tx_dma.queue(grant);
+//     }
+//
+// the same safety rules as dma_read_grant apply to every grant handed out
+#[derive(Debug, Default)]
+pub struct DmaDoubleBuffer {
+    // lengths of the outstanding grants, oldest first
+    in_flight: [usize; 2],
+    count: usize,
+}
+
+impl DmaDoubleBuffer {
+    pub fn new() -> Self {
+        DmaDoubleBuffer {
+            in_flight: [0; 2],
+            count: 0,
+        }
+    }
+
+    // the region after everything already granted, None when two grants are out or
+    // there's no complete frame left to send
+    #[allow(clippy::missing_safety_doc)]
+    pub unsafe fn next_grant(&mut self, ring: &RingBuffer) -> Option<DmaReadGrant> {
+        if self.count == self.in_flight.len() {
+            return None;
+        }
+        let grant = ring.dma_grant_within(self.in_flight[..self.count].iter().sum(), usize::MAX);
+        if grant.is_empty() {
+            return None;
+        }
+        self.in_flight[self.count] = grant.len();
+        self.count += 1;
+        Some(grant)
+    }
+
+    // the oldest transfer finished, release its bytes. returns whether another grant is
+    // still out
+    pub fn transfer_complete(&mut self, ring: &mut RingBuffer) -> bool {
+        if self.count == 0 {
+            return false;
+        }
+        ring.dma_read_done(self.in_flight[0]);
+        self.in_flight = [self.in_flight[1], 0];
+        self.count -= 1;
+        self.count > 0
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.count
+    }
+
+    // forget the outstanding grants after the dma was stopped, their bytes stay queued
+    // and go out again with the next grant
+    pub fn abort(&mut self) {
+        self.count = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    // what the engine would read
+    fn bytes(grant: &impl ReadBuffer<Word = u8>) -> Vec<u8> {
+        unsafe {
+            let (ptr, len) = grant.read_buffer();
+            core::slice::from_raw_parts(ptr, len).to_vec()
+        }
+    }
+
+    fn ring(payloads: &[&[u8]]) -> RingBuffer {
+        let mut ring = RingBuffer::new(64);
+        for payload in payloads {
+            let _ = ring.log(Frame::new(payload));
+        }
+        ring
+    }
+
+    // a ring whose head and tail sit `from_end` bytes before the end of the storage
+    fn near_the_end(from_end: usize) -> RingBuffer {
+        let mut ring = RingBuffer::new(32);
+        ring.push_slice(&[0; 32][..32 - from_end]);
+        ring.read_view().consume(32 - from_end);
+        ring
+    }
+
+    #[test]
+    fn a_grant_covers_the_complete_frames_in_wire_format() {
+        let mut ring = ring(&[b"one", b"two"]);
+        ring.push_slice(b"half a frame");
+        let grant = unsafe { ring.dma_read_grant() };
+        assert_eq!(
+            bytes(&grant),
+            [Frame::new(b"one").encode(), Frame::new(b"two").encode()].concat()
+        );
+
+        ring.dma_read_done(grant.len());
+        assert_eq!(ring.len(), b"half a frame".len());
+        assert_eq!(ring.stats().flushed_frames, 2);
+        assert!(unsafe { ring.dma_read_grant() }.is_empty());
+    }
+
+    #[test]
+    fn a_grant_stops_at_the_flush_budget() {
+        let mut ring = ring(&[b"one", b"two"]);
+        ring.set_max_flush_size(Frame::new(b"one").encode().len());
+        let grant = unsafe { ring.dma_read_grant() };
+        assert_eq!(bytes(&grant), Frame::new(b"one").encode());
+
+        // only what went out is released, the next grant picks up the rest
+        ring.dma_read_done(2);
+        let grant = unsafe { ring.dma_read_grant() };
+        assert!(bytes(&grant).starts_with(&Frame::new(b"one").encode()[2..]));
+        assert_eq!(ring.stats().flushed_frames, 0);
+    }
+
+    #[test]
+    fn a_frame_across_the_end_goes_out_in_two_grants() {
+        let mut ring = near_the_end(4);
+        let _ = ring.log(Frame::new(b"wrapped"));
+        let first = unsafe { ring.dma_read_grant() };
+        assert_eq!(first.len(), 4);
+        let mut sent = bytes(&first);
+        ring.dma_read_done(first.len());
+
+        let second = unsafe { ring.dma_read_grant() };
+        sent.extend(bytes(&second));
+        ring.dma_read_done(second.len());
+        assert_eq!(sent, Frame::new(b"wrapped").encode());
+        assert!(ring.is_empty());
+        assert_eq!(ring.stats().flushed_frames, 1);
+    }
+
+    #[test]
+    fn two_grants_are_out_at_once_back_to_back() {
+        let mut ring = ring(&[b"one", b"two", b"three"]);
+        ring.set_max_flush_size(1);
+        let mut pump = DmaDoubleBuffer::new();
+        let first = unsafe { pump.next_grant(&ring) }.unwrap();
+        let second = unsafe { pump.next_grant(&ring) }.unwrap();
+        assert!(unsafe { pump.next_grant(&ring) }.is_none());
+        assert_eq!(bytes(&first), Frame::new(b"one").encode());
+        assert_eq!(bytes(&second), Frame::new(b"two").encode());
+
+        assert!(pump.transfer_complete(&mut ring));
+        assert_eq!(ring.stats().flushed_frames, 1);
+        let third = unsafe { pump.next_grant(&ring) }.unwrap();
+        assert_eq!(bytes(&third), Frame::new(b"three").encode());
+
+        assert!(pump.transfer_complete(&mut ring));
+        assert!(!pump.transfer_complete(&mut ring));
+        assert!(!pump.transfer_complete(&mut ring));
+        assert!(ring.is_empty());
+        assert!(unsafe { pump.next_grant(&ring) }.is_none());
+    }
+
+    #[test]
+    fn aborted_grants_go_out_again() {
+        let mut ring = ring(&[b"one", b"two"]);
+        ring.set_max_flush_size(1);
+        let mut pump = DmaDoubleBuffer::new();
+        let _ = unsafe { pump.next_grant(&ring) };
+        let _ = unsafe { pump.next_grant(&ring) };
+        pump.abort();
+        assert_eq!(pump.in_flight(), 0);
+        assert!(!pump.transfer_complete(&mut ring));
+
+        let again = unsafe { pump.next_grant(&ring) }.unwrap();
+        assert_eq!(bytes(&again), Frame::new(b"one").encode());
+        assert_eq!(ring.stats().flushed_frames, 0);
+    }
+
+    #[test]
+    fn the_second_grant_picks_up_after_the_wrap() {
+        let mut ring = near_the_end(4);
+        let _ = ring.log(Frame::new(b"wrapped"));
+        let mut pump = DmaDoubleBuffer::new();
+        let first = unsafe { pump.next_grant(&ring) }.unwrap();
+        let second = unsafe { pump.next_grant(&ring) }.unwrap();
+        assert_eq!(
+            [bytes(&first), bytes(&second)].concat(),
+            Frame::new(b"wrapped").encode()
+        );
+        pump.transfer_complete(&mut ring);
+        pump.transfer_complete(&mut ring);
+        assert!(ring.is_empty());
+    }
+
+    #[test]
+    fn a_committed_transfer_releases_its_region() {
+        let mut ring = ring(&[b"sent"]);
+        let mut transfer = ring.dma_transfer();
+        assert_eq!(transfer.bytes(), Frame::new(b"sent").encode());
+        assert_eq!(bytes(&transfer), transfer.bytes());
+        // logged in flight, not part of this transfer
+        let _ = transfer.log(Frame::new(b"later"));
+        assert_eq!(transfer.len(), Frame::new(b"sent").encode().len());
+        transfer.commit();
+
+        assert_eq!(ring.dma_transfer().bytes(), Frame::new(b"later").encode());
+        assert_eq!(ring.stats().flushed_frames, 1);
+    }
+
+    #[test]
+    fn dropping_a_transfer_keeps_its_bytes() {
+        let mut ring = ring(&[b"retried"]);
+        {
+            let transfer = ring.dma_transfer();
+            assert!(!transfer.is_empty());
+        }
+        assert_eq!(ring.dma_transfer().bytes(), Frame::new(b"retried").encode());
+        assert_eq!(ring.stats().flushed_frames, 0);
+    }
+
+    #[test]
+    fn a_partial_commit_sends_the_rest_again() {
+        let mut ring = ring(&[b"halfway"]);
+        let encoded = Frame::new(b"halfway").encode();
+        let transfer = ring.dma_transfer();
+        transfer.commit_partial(3);
+        assert_eq!(ring.dma_transfer().bytes(), &encoded[3..]);
+
+        // never more than the region
+        let mut transfer = ring.dma_transfer();
+        let _ = transfer.log(Frame::new(b"next"));
+        transfer.commit_partial(usize::MAX);
+        assert_eq!(ring.dma_transfer().bytes(), Frame::new(b"next").encode());
+    }
+
+    fn gathered(descriptors: [(*const u8, usize); 2]) -> Vec<u8> {
+        descriptors
+            .iter()
+            .flat_map(|&(ptr, len)| unsafe { core::slice::from_raw_parts(ptr, len) })
+            .copied()
+            .collect()
+    }
+
+    #[test]
+    fn scatter_gather_covers_the_complete_frames_across_the_wrap() {
+        let mut ring = near_the_end(6);
+        let _ = ring.log(Frame::new(b"first"));
+        let _ = ring.log(Frame::new(b"second"));
+        ring.push_slice(b"cut");
+        let descriptors = ring.scatter_gather();
+        assert_eq!(descriptors[0].1, 6);
+        assert_eq!(
+            gathered(descriptors),
+            [
+                Frame::new(b"first").encode(),
+                Frame::new(b"second").encode()
+            ]
+            .concat()
+        );
+
+        ring.dma_read_done(descriptors[0].1 + descriptors[1].1);
+        assert_eq!(ring.len(), 3);
+        assert_eq!(ring.stats().flushed_frames, 2);
+    }
+
+    #[test]
+    fn frames_that_dont_wrap_need_one_descriptor() {
+        let ring = ring(&[b"one"]);
+        let descriptors = ring.scatter_gather();
+        assert_eq!(descriptors[1].1, 0);
+        assert_eq!(gathered(descriptors), Frame::new(b"one").encode());
+        assert!(gathered(RingBuffer::new(8).scatter_gather()).is_empty());
+    }
+
+    // every region cleaned, as (offset into the storage, len)
+    fn cleaning(ring: &mut RingBuffer) -> Arc<Mutex<Vec<(usize, usize)>>> {
+        let cleaned = Arc::new(Mutex::new(Vec::new()));
+        let start = ring.buffer.as_ptr() as usize;
+        let log = cleaned.clone();
+        ring.set_cache_ops(move |addr: usize, len| log.lock().unwrap().push((addr - start, len)));
+        cleaned
+    }
+
+    #[test]
+    fn handed_out_regions_are_cleaned_first() {
+        let mut ring = near_the_end(6);
+        let _ = ring.log(Frame::new(b"first"));
+        let len = Frame::new(b"first").encode().len();
+        let cleaned = cleaning(&mut ring);
+
+        let _ = unsafe { ring.dma_read_grant() };
+        assert_eq!(*cleaned.lock().unwrap(), [(26, 6)]);
+        ring.scatter_gather();
+        assert_eq!(cleaned.lock().unwrap()[1..], [(26, 6), (0, len - 6)]);
+        let transfer = ring.dma_transfer();
+        transfer.commit();
+        assert_eq!(cleaned.lock().unwrap().len(), 4);
+    }
+
+    #[test]
+    fn empty_regions_arent_cleaned() {
+        let mut ring = RingBuffer::new(32);
+        ring.push_slice(b"no terminator");
+        let cleaned = cleaning(&mut ring);
+        assert!(unsafe { ring.dma_read_grant() }.is_empty());
+        ring.scatter_gather();
+        assert!(cleaned.lock().unwrap().is_empty());
+    }
+}
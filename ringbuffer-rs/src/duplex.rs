@@ -0,0 +1,240 @@
+// both directions of a link with the same framing: the log ring carries logs and
+// telemetry from the device to the host, the command ring commands from the host to the
+// device. on the device:
+//
+//     let mut link = Duplex::new(4096, 256);
+//     let mut commands = CommandDispatcher::new()
+//         .on(SET_RATE, |frame| { ...; None })
+//         .on(GET_VERSION, |_| Some(Frame::new(VERSION)));
+//
+//     // uart rx interrupt
+//     link.commands_mut().push_slice(&received);
+//     // main loop
+//     link.dispatch(&mut commands);
+//     link.flush_to(&mut uart);
+//
+// the host side is the mirror image, it queues commands with `send_command`, writes out
+// the command ring and feeds what comes back into the log ring. a handler's reply goes
+// into the log ring, as a command frame with the tag of the command it answers
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+use crate::frame::{Frame, FrameResult, Handler};
+use crate::kind::FrameKind;
+use crate::sink::{FlushSink, FlushToResult};
+use crate::{PushResult, RingBuffer};
+
+pub struct Duplex {
+    logs: RingBuffer,
+    commands: RingBuffer,
+}
+
+impl Duplex {
+    pub fn new(log_size: usize, command_size: usize) -> Self {
+        Self::from_rings(RingBuffer::new(log_size), RingBuffer::new(command_size))
+    }
+
+    pub fn from_rings(logs: RingBuffer, commands: RingBuffer) -> Self {
+        Duplex { logs, commands }
+    }
+
+    // device to host
+    pub fn logs(&self) -> &RingBuffer {
+        &self.logs
+    }
+
+    pub fn logs_mut(&mut self) -> &mut RingBuffer {
+        &mut self.logs
+    }
+
+    // host to device
+    pub fn commands(&self) -> &RingBuffer {
+        &self.commands
+    }
+
+    pub fn commands_mut(&mut self) -> &mut RingBuffer {
+        &mut self.commands
+    }
+
+    pub fn log(&mut self, frame: Frame) -> PushResult {
+        self.logs.log(frame)
+    }
+
+    // queue command `tag` for the device
+    pub fn send_command(&mut self, tag: u16, payload: &[u8]) -> PushResult {
+        self.commands.log(
+            Frame::new(payload)
+                .with_kind(FrameKind::Command)
+                .with_tag(tag),
+        )
+    }
+
+    pub fn flush_to(&mut self, sink: &mut (impl FlushSink + ?Sized)) -> FlushToResult {
+        self.logs.flush_to(sink)
+    }
+
+    // run every queued command through `dispatcher`, returns how many had a handler
+    pub fn dispatch(&mut self, dispatcher: &mut CommandDispatcher) -> usize {
+        dispatcher.dispatch(&mut self.commands, &mut self.logs)
+    }
+
+    pub fn into_rings(self) -> (RingBuffer, RingBuffer) {
+        (self.logs, self.commands)
+    }
+}
+
+// handles a command, returns the reply if there is one
+type CommandHandler<'a> = Box<dyn FnMut(Frame) -> Option<Frame> + 'a>;
+
+// receive side: drains the command ring and hands each command frame to the handler for
+// its tag
+#[derive(Default)]
+pub struct CommandDispatcher<'a> {
+    handlers: Vec<(u16, CommandHandler<'a>)>,
+    fallback: Option<Handler<'a>>,
+}
+
+impl<'a> CommandDispatcher<'a> {
+    pub fn new() -> Self {
+        CommandDispatcher {
+            handlers: Vec::new(),
+            fallback: None,
+        }
+    }
+
+    pub fn on(mut self, tag: u16, handler: impl FnMut(Frame) -> Option<Frame> + 'a) -> Self {
+        self.handlers.retain(|(t, _)| *t != tag);
+        self.handlers.push((tag, Box::new(handler)));
+        self
+    }
+
+    // gets frames that aren't commands and commands nobody handles, otherwise they're
+    // dropped
+    pub fn fallback(mut self, handler: impl FnMut(Frame) + 'a) -> Self {
+        self.fallback = Some(Box::new(handler));
+        self
+    }
+
+    // flush every complete frame out of `commands`, replies go into `replies`. returns
+    // how many commands had a handler, corrupt frames are skipped
+    pub fn dispatch(&mut self, commands: &mut RingBuffer, replies: &mut RingBuffer) -> usize {
+        let mut handled = 0;
+        while let Some(bytes) = commands.pop_frame_bytes() {
+            let frame = match commands.decode_flushed(&bytes) {
+                FrameResult::Ok(frame) => frame,
+                FrameResult::Err(_) => continue,
+            };
+            let route = match (frame.kind, frame.tag) {
+                (Some(FrameKind::Command), Some(tag)) => self
+                    .handlers
+                    .iter_mut()
+                    .find(|(t, _)| *t == tag)
+                    .map(|(_, handler)| (tag, handler)),
+                _ => None,
+            };
+            match route {
+                Some((tag, handler)) => {
+                    if let Some(reply) = handler(frame) {
+                        // a full log ring loses the reply like any other frame
+                        let _ = replies.log(reply.with_kind(FrameKind::Command).with_tag(tag));
+                    }
+                    handled += 1;
+                }
+                None => {
+                    if let Some(fallback) = &mut self.fallback {
+                        fallback(frame);
+                    }
+                }
+            }
+        }
+        handled
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sink::MemorySink;
+
+    const SET_RATE: u16 = 1;
+    const GET_VERSION: u16 = 2;
+
+    // what the host wrote out of its command ring arrives at the device
+    fn carry(host: &mut Duplex, device: &mut Duplex) {
+        let mut view = host.commands_mut().read_view();
+        let bytes = view.readable().to_vec();
+        view.consume(bytes.len());
+        device.commands_mut().push_slice(&bytes);
+    }
+
+    #[test]
+    fn commands_reach_their_handlers_and_replies_go_back_in_the_logs() {
+        let mut host = Duplex::new(256, 256);
+        let mut device = Duplex::new(256, 256);
+        let _ = host.send_command(SET_RATE, b"100");
+        let _ = host.send_command(GET_VERSION, b"");
+        carry(&mut host, &mut device);
+
+        let mut rate = Vec::new();
+        let mut commands = CommandDispatcher::new()
+            .on(SET_RATE, |frame| {
+                rate = frame.payload;
+                None
+            })
+            .on(GET_VERSION, |_| Some(Frame::new(b"1.2")));
+        assert_eq!(device.dispatch(&mut commands), 2);
+        drop(commands);
+        assert_eq!(rate, b"100");
+        assert!(device.commands().is_empty());
+
+        let mut sink = MemorySink::new();
+        let _ = device.flush_to(&mut sink);
+        let replies = sink.take();
+        assert_eq!(replies.len(), 1);
+        assert_eq!(replies[0].payload, b"1.2");
+        assert_eq!(replies[0].kind, Some(FrameKind::Command));
+        assert_eq!(replies[0].tag, Some(GET_VERSION));
+    }
+
+    #[test]
+    fn everything_unhandled_goes_to_the_fallback() {
+        let mut device = Duplex::new(256, 256);
+        let commands = device.commands_mut();
+        let _ = commands.log(Frame::new(b"not a command").with_tag(SET_RATE));
+        let _ = commands.log(
+            Frame::new(b"unknown")
+                .with_kind(FrameKind::Command)
+                .with_tag(9),
+        );
+        let mut corrupt = Frame::new(b"bad").encode();
+        corrupt[1] ^= 0xff;
+        commands.push_slice(&corrupt);
+
+        let mut unhandled = Vec::new();
+        let mut dispatcher = CommandDispatcher::new()
+            .on(SET_RATE, |_| None)
+            .fallback(|frame| unhandled.push(frame.payload));
+        assert_eq!(device.dispatch(&mut dispatcher), 0);
+        drop(dispatcher);
+        assert_eq!(unhandled, [b"not a command".to_vec(), b"unknown".to_vec()]);
+        assert!(device.logs().is_empty());
+    }
+
+    #[test]
+    fn a_later_handler_for_a_tag_replaces_the_earlier_one() {
+        let mut device = Duplex::new(256, 256);
+        let _ = device.send_command(SET_RATE, b"");
+        let mut dispatcher = CommandDispatcher::new()
+            .on(SET_RATE, |_| Some(Frame::new(b"first")))
+            .on(SET_RATE, |_| Some(Frame::new(b"second")));
+        assert_eq!(device.dispatch(&mut dispatcher), 1);
+
+        let (mut logs, commands) = device.into_rings();
+        assert!(commands.is_empty());
+        match logs.flush_frame() {
+            FrameResult::Ok(frame) => assert_eq!(frame.payload, b"second"),
+            FrameResult::Err(e) => panic!("{e}"),
+        }
+    }
+}
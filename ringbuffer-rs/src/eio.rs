@@ -0,0 +1,97 @@
+// embedded-io plumbing, the no_std counterpart of io.rs: raw bytes in through `Write`,
+// raw bytes out through `Read`, no framing added or checked.
+//
+// the embedded-io traits are blocking by contract and a ring has nobody to wait for, so
+// check `WriteReady` / `ReadReady` first. a write into a full buffer fails with
+// `BufferFull` (a 0-byte write isn't allowed), a read from an empty one returns Ok(0)
+
+use core::fmt;
+
+use embedded_io::{ErrorKind, ErrorType, Read, ReadReady, Write, WriteReady};
+
+use crate::RingBuffer;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BufferFull;
+
+impl fmt::Display for BufferFull {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Buffer is full")
+    }
+}
+
+impl core::error::Error for BufferFull {}
+
+impl embedded_io::Error for BufferFull {
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::WriteZero
+    }
+}
+
+impl ErrorType for RingBuffer {
+    type Error = BufferFull;
+}
+
+impl Write for RingBuffer {
+    // short writes once the buffer fills up
+    fn write(&mut self, buf: &[u8]) -> Result<usize, BufferFull> {
+        match self.push_slice(buf) {
+            0 if !buf.is_empty() => Err(BufferFull),
+            count => Ok(count),
+        }
+    }
+
+    fn flush(&mut self) -> Result<(), BufferFull> {
+        Ok(())
+    }
+}
+
+impl WriteReady for RingBuffer {
+    fn write_ready(&mut self) -> Result<bool, BufferFull> {
+        Ok(!self.is_full())
+    }
+}
+
+impl Read for RingBuffer {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, BufferFull> {
+        Ok(self.pop_slice(buf))
+    }
+}
+
+impl ReadReady for RingBuffer {
+    fn read_ready(&mut self) -> Result<bool, BufferFull> {
+        Ok(!self.is_empty())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_come_up_short_then_fail_when_full() {
+        let mut ring = RingBuffer::new(8);
+        assert_eq!(WriteReady::write_ready(&mut ring), Ok(true));
+        assert_eq!(Write::write(&mut ring, b"0123456789"), Ok(7));
+        assert_eq!(WriteReady::write_ready(&mut ring), Ok(false));
+        assert_eq!(Write::write(&mut ring, b"8"), Err(BufferFull));
+        assert_eq!(Write::write(&mut ring, b""), Ok(0));
+        assert_eq!(Write::write_all(&mut ring, b"x"), Err(BufferFull));
+        assert_eq!(embedded_io::Error::kind(&BufferFull), ErrorKind::WriteZero);
+    }
+
+    #[test]
+    fn reads_drain_in_order_across_the_wrap() {
+        let mut ring = RingBuffer::new(8);
+        let mut buf = [0; 8];
+        Write::write_all(&mut ring, b"abcde").unwrap();
+        assert_eq!(Read::read(&mut ring, &mut buf[..4]), Ok(4));
+        Write::write_all(&mut ring, b"fghij").unwrap();
+
+        assert_eq!(ReadReady::read_ready(&mut ring), Ok(true));
+        Read::read_exact(&mut ring, &mut buf[..6]).unwrap();
+        assert_eq!(&buf[..6], b"efghij");
+        assert_eq!(ReadReady::read_ready(&mut ring), Ok(false));
+        assert_eq!(Read::read(&mut ring, &mut buf), Ok(0));
+    }
+}
@@ -0,0 +1,331 @@
+// text exports of the buffer contents for analysis scripts, one record per frame.
+// corrupt frames are kept in the output with their crc status instead of being dropped
+
+use std::io::{self, Write};
+
+use crate::frame::{Frame, FrameResult};
+use crate::json;
+use crate::RingBuffer;
+
+impl RingBuffer {
+    // drain every complete frame as json lines, returns how many lines were written.
+    // a frame only leaves the ring once its line was written
+    pub fn export_jsonl(&mut self, out: &mut impl Write) -> io::Result<usize> {
+        let mut lines = 0;
+        while let Some(bytes) = self.peek_frame_bytes() {
+            let decoded = Frame::decode(&bytes);
+            writeln!(out, "{}", jsonl_line(&bytes, &decoded))?;
+            self.consume_frame(bytes.len());
+            self.count_flushed(&bytes, &decoded);
+            lines += 1;
+        }
+        Ok(lines)
+    }
+
+    // same as export_jsonl but leaves the buffer untouched
+    pub fn dump_jsonl(&self, out: &mut impl Write) -> io::Result<usize> {
+        let frames = self.frame_bytes();
+        for bytes in &frames {
+            writeln!(out, "{}", jsonl_line(bytes, &Frame::decode(bytes)))?;
+        }
+        Ok(frames.len())
+    }
+}
+
+// `{"crc":"ok",...frame fields}` for good frames, `{"crc":"bad","error":..,"raw_hex":..}`
+// with the escaped bytes for the rest
+fn jsonl_line(bytes: &[u8], decoded: &FrameResult) -> String {
+    let mut out = String::from("{");
+    match decoded {
+        FrameResult::Ok(frame) => {
+            out.push_str("\"crc\":\"ok\",");
+            json::write_frame_fields(&mut out, frame);
+        }
+        FrameResult::Err(e) => {
+            out.push_str("\"crc\":\"bad\",\"error\":");
+            json::write_str(&mut out, e);
+            out.push_str(",\"raw_hex\":\"");
+            for byte in bytes {
+                out.push_str(&format!("{:02x}", byte));
+            }
+            out.push('"');
+        }
+    }
+    out.push('}');
+    out
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CsvColumn {
+    Seq,
+    Timestamp,
+    Level,
+    Tag,
+    Channel,
+    Kind,
+    // "ok" or "bad"
+    Crc,
+    // payload as (lossy) utf-8 text
+    Payload,
+    PayloadHex,
+}
+
+impl CsvColumn {
+    fn name(self) -> &'static str {
+        match self {
+            CsvColumn::Seq => "seq",
+            CsvColumn::Timestamp => "timestamp",
+            CsvColumn::Level => "level",
+            CsvColumn::Tag => "tag",
+            CsvColumn::Channel => "channel",
+            CsvColumn::Kind => "kind",
+            CsvColumn::Crc => "crc",
+            CsvColumn::Payload => "payload",
+            CsvColumn::PayloadHex => "payload_hex",
+        }
+    }
+}
+
+// rfc 4180 csv, one row per frame. fields a frame doesn't carry are left empty, as is
+// everything but the crc column for corrupt frames
+pub struct CsvExporter {
+    columns: Vec<CsvColumn>,
+    header: bool,
+}
+
+impl Default for CsvExporter {
+    fn default() -> Self {
+        Self::new(&[
+            CsvColumn::Seq,
+            CsvColumn::Timestamp,
+            CsvColumn::Level,
+            CsvColumn::Tag,
+            CsvColumn::Crc,
+            CsvColumn::Payload,
+        ])
+    }
+}
+
+impl CsvExporter {
+    pub fn new(columns: &[CsvColumn]) -> Self {
+        CsvExporter {
+            columns: columns.to_vec(),
+            header: true,
+        }
+    }
+
+    // write a header row with the column names first, on by default
+    pub fn with_header(mut self, header: bool) -> Self {
+        self.header = header;
+        self
+    }
+
+    // drain every complete frame, returns the number of rows written (header not included)
+    pub fn export(&self, ring: &mut RingBuffer, out: &mut impl Write) -> io::Result<usize> {
+        self.write_header(out)?;
+        let mut rows = 0;
+        while let Some(bytes) = ring.peek_frame_bytes() {
+            let decoded = Frame::decode(&bytes);
+            self.write_row(out, &decoded)?;
+            ring.consume_frame(bytes.len());
+            ring.count_flushed(&bytes, &decoded);
+            rows += 1;
+        }
+        Ok(rows)
+    }
+
+    // same as export but leaves the buffer untouched
+    pub fn dump(&self, ring: &RingBuffer, out: &mut impl Write) -> io::Result<usize> {
+        self.write_header(out)?;
+        let frames = ring.frame_bytes();
+        for bytes in &frames {
+            self.write_row(out, &Frame::decode(bytes))?;
+        }
+        Ok(frames.len())
+    }
+
+    fn write_header(&self, out: &mut impl Write) -> io::Result<()> {
+        if !self.header {
+            return Ok(());
+        }
+        let names: Vec<_> = self.columns.iter().map(|column| column.name()).collect();
+        write!(out, "{}\r\n", names.join(","))
+    }
+
+    fn write_row(&self, out: &mut impl Write, decoded: &FrameResult) -> io::Result<()> {
+        let frame = match decoded {
+            FrameResult::Ok(frame) => Some(frame),
+            FrameResult::Err(_) => None,
+        };
+        let cells: Vec<_> = self
+            .columns
+            .iter()
+            .map(|column| match (frame, column) {
+                (None, CsvColumn::Crc) => "bad".to_string(),
+                (None, _) => String::new(),
+                (Some(frame), column) => cell(frame, *column),
+            })
+            .collect();
+        write!(out, "{}\r\n", cells.join(","))
+    }
+}
+
+fn cell(frame: &Frame, column: CsvColumn) -> String {
+    match column {
+        CsvColumn::Seq => frame.seq.map(|seq| seq.to_string()).unwrap_or_default(),
+        CsvColumn::Timestamp => frame.timestamp.map(|ts| ts.to_string()).unwrap_or_default(),
+        CsvColumn::Level => frame
+            .level
+            .map(|level| level.to_string())
+            .unwrap_or_default(),
+        CsvColumn::Tag => frame.tag.map(|tag| tag.to_string()).unwrap_or_default(),
+        CsvColumn::Channel => frame.channel.map(|ch| ch.to_string()).unwrap_or_default(),
+        CsvColumn::Kind => frame
+            .kind
+            .map(json::kind_name)
+            .unwrap_or_default()
+            .to_string(),
+        CsvColumn::Crc => "ok".to_string(),
+        CsvColumn::Payload => {
+            let text = match frame.dropped {
+                Some(dropped) => dropped.to_string(),
+                None => String::from_utf8_lossy(&frame.payload).into_owned(),
+            };
+            quote(&text)
+        }
+        CsvColumn::PayloadHex => frame.payload.iter().map(|b| format!("{:02x}", b)).collect(),
+    }
+}
+
+// quote a field when it holds a separator, quote or line break, doubling inner quotes
+fn quote(text: &str) -> String {
+    if text.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level::Level;
+
+    fn lines(out: &[u8]) -> Vec<&str> {
+        core::str::from_utf8(out).unwrap().lines().collect()
+    }
+
+    #[test]
+    fn jsonl_keeps_corrupt_frames_with_their_bytes() {
+        let mut ring = RingBuffer::new(128);
+        let _ = ring.log(Frame::new(b"spin up").with_seq(1).with_level(Level::Info));
+        let mut bad = Frame::new(b"bad").encode();
+        bad[1] ^= 0x01;
+        ring.push_slice(&bad);
+
+        let mut out = Vec::new();
+        assert_eq!(ring.export_jsonl(&mut out).unwrap(), 2);
+        assert!(ring.is_empty());
+        let hex: String = bad[..bad.len() - 1]
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        assert_eq!(
+            lines(&out),
+            [
+                r#"{"crc":"ok","seq":1,"level":"INFO","payload":"spin up"}"#.to_string(),
+                format!(
+                    r#"{{"crc":"bad","error":"CRC-8 checksum failed","raw_hex":"{}"}}"#,
+                    hex
+                ),
+            ]
+        );
+        assert_eq!(ring.stats().crc_errors, 1);
+    }
+
+    #[test]
+    fn dump_jsonl_leaves_the_frames_in_place() {
+        let mut ring = RingBuffer::new(128);
+        let _ = ring.log(Frame::new(b"one"));
+        let _ = ring.log(Frame::new(b"two"));
+        let len = ring.len();
+
+        let mut dumped = Vec::new();
+        assert_eq!(ring.dump_jsonl(&mut dumped).unwrap(), 2);
+        assert_eq!(ring.len(), len);
+        let mut exported = Vec::new();
+        ring.export_jsonl(&mut exported).unwrap();
+        assert_eq!(dumped, exported);
+    }
+
+    // a writer that fails once it took `room` bytes
+    struct Full {
+        room: usize,
+    }
+
+    impl Write for Full {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.room < buf.len() {
+                return Err(io::ErrorKind::WriteZero.into());
+            }
+            self.room -= buf.len();
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn a_frame_stays_when_its_line_couldnt_be_written() {
+        let mut ring = RingBuffer::new(128);
+        let _ = ring.log(Frame::new(b"one"));
+        let _ = ring.log(Frame::new(b"two"));
+        let first = r#"{"crc":"ok","payload":"one"}"#.len() + 1;
+
+        assert!(ring.export_jsonl(&mut Full { room: first }).is_err());
+        let mut rest = Vec::new();
+        assert_eq!(ring.export_jsonl(&mut rest).unwrap(), 1);
+        assert_eq!(lines(&rest), [r#"{"crc":"ok","payload":"two"}"#]);
+    }
+
+    #[test]
+    fn csv_rows_follow_the_columns() {
+        let mut ring = RingBuffer::new(128);
+        let _ = ring.log(Frame::new(b"say \"hi\", twice").with_tag(3));
+        let _ = ring.log(Frame::new(&[0xff, 0x01]).with_seq(9));
+        let mut bad = Frame::new(b"bad").encode();
+        bad[1] ^= 0x01;
+        ring.push_slice(&bad);
+
+        let exporter = CsvExporter::new(&[
+            CsvColumn::Seq,
+            CsvColumn::Tag,
+            CsvColumn::Crc,
+            CsvColumn::Payload,
+            CsvColumn::PayloadHex,
+        ]);
+        let mut out = Vec::new();
+        assert_eq!(exporter.export(&mut ring, &mut out).unwrap(), 3);
+        assert!(ring.is_empty());
+        assert_eq!(
+            core::str::from_utf8(&out).unwrap(),
+            "seq,tag,crc,payload,payload_hex\r\n\
+             ,3,ok,\"say \"\"hi\"\", twice\",73617920226869222c207477696365\r\n\
+             9,,ok,\u{fffd}\u{1},ff01\r\n\
+             ,,bad,,\r\n"
+        );
+    }
+
+    #[test]
+    fn csv_dump_without_a_header() {
+        let mut ring = RingBuffer::new(128);
+        let _ = ring.log(Frame::new(b"kept").with_level(Level::Warn));
+        let mut out = Vec::new();
+        let exporter = CsvExporter::default().with_header(false);
+        assert_eq!(exporter.dump(&ring, &mut out).unwrap(), 1);
+        assert_eq!(out, b",,WARN,,ok,kept\r\n");
+        assert!(!ring.is_empty());
+    }
+}
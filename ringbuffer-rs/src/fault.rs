@@ -0,0 +1,181 @@
+// for hardfault and other exception handlers: write down what went wrong and then keep
+// the ring exactly as it is until the reset, so the recovery path (retained ram, a debug
+// probe reading ram) finds the last frames and the fault next to each other:
+//
+//     #[exception]
+//     unsafe fn HardFault(frame: &ExceptionFrame) -> ! {
+//         let scb = &*SCB::PTR;
+//         let ring = &mut *addr_of_mut!(RING);
+//         ring.record_fault(&FaultInfo {
+//             pc: frame.pc(),
+//             lr: frame.lr(),
+//             xpsr: frame.xpsr(),
+//             cfsr: scb.cfsr.read(),
+//             hfsr: scb.hfsr.read(),
+//             mmfar: scb.mmfar.read(),
+//             bfar: scb.bfar.read(),
+//         });
+//         SCB::sys_reset()
+//     }
+//
+// the fault goes out as an error level key-value frame, so every decoder already shows
+// the registers
+
+use alloc::string::ToString;
+
+use crate::frame::Frame;
+use crate::level::Level;
+use crate::{PushResult, RingBuffer};
+
+// registers at the time of the fault, as read by the handler. cortex-m names, leave
+// what doesn't apply at 0
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FaultInfo {
+    pub pc: u32,
+    pub lr: u32,
+    pub xpsr: u32,
+    pub cfsr: u32,
+    pub hfsr: u32,
+    pub mmfar: u32,
+    pub bfar: u32,
+}
+
+impl FaultInfo {
+    pub fn to_frame(&self) -> Frame {
+        Frame::default()
+            .with_level(Level::Error)
+            .kv("exception", self.xpsr & 0x1ff)
+            .kv("pc", self.pc)
+            .kv("lr", self.lr)
+            .kv("xpsr", self.xpsr)
+            .kv("cfsr", self.cfsr)
+            .kv("hfsr", self.hfsr)
+            .kv("mmfar", self.mmfar)
+            .kv("bfar", self.bfar)
+    }
+}
+
+impl RingBuffer {
+    // reject every frame logged from now on. consumers can still flush
+    pub fn freeze(&mut self) {
+        self.frozen = true;
+    }
+
+    pub fn unfreeze(&mut self) {
+        self.frozen = false;
+    }
+
+    pub fn is_frozen(&self) -> bool {
+        self.frozen
+    }
+
+    // log `info`, evicting the oldest frames if that's the only way to fit it, then
+    // freeze. a fault while already frozen (a fault in the handler) records nothing
+    pub fn record_fault(&mut self, info: &FaultInfo) -> PushResult {
+        if self.frozen {
+            return PushResult::Err("Buffer is frozen".to_string());
+        }
+        let mut frame = info.to_frame();
+        if let PushResult::Err(e) = self.stamp(&mut frame) {
+            return PushResult::Err(e);
+        }
+        let result = match self.evict_for(&frame) {
+            Some(_) => self.log_frame(&frame),
+            None => PushResult::Err("Fault frame larger than the buffer".to_string()),
+        };
+        self.freeze();
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::*;
+    use crate::frame::FrameResult;
+    use crate::kv::Value;
+
+    const HARD_FAULT: FaultInfo = FaultInfo {
+        pc: 0x0800_1234,
+        lr: 0xffff_fff9,
+        xpsr: 0x6100_0003,
+        cfsr: 0x0000_8200,
+        hfsr: 0x4000_0000,
+        mmfar: 0,
+        bfar: 0x2000_0000,
+    };
+
+    #[test]
+    fn the_fault_frame_carries_every_register() {
+        let frame = HARD_FAULT.to_frame();
+        assert_eq!(frame.level, Some(Level::Error));
+        let pairs = frame.pairs().unwrap();
+        let keys = pairs
+            .iter()
+            .map(|(key, _)| key.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            keys,
+            [
+                "exception",
+                "pc",
+                "lr",
+                "xpsr",
+                "cfsr",
+                "hfsr",
+                "mmfar",
+                "bfar"
+            ]
+        );
+        // the exception number out of ipsr
+        assert_eq!(pairs[0].1, Value::Int(3));
+        assert_eq!(pairs[1].1, Value::Int(0x0800_1234));
+    }
+
+    #[test]
+    fn recording_a_fault_freezes_the_ring() {
+        let mut ring = RingBuffer::new(128);
+        let _ = ring.log(Frame::new(b"last words"));
+        assert!(matches!(ring.record_fault(&HARD_FAULT), PushResult::Ok));
+        assert!(ring.is_frozen());
+        assert!(
+            matches!(ring.log(Frame::new(b"after")), PushResult::Err(e) if e == "Buffer is frozen")
+        );
+        // a second fault in the handler leaves the first one alone
+        assert!(matches!(
+            ring.record_fault(&FaultInfo::default()),
+            PushResult::Err(_)
+        ));
+
+        // consumers still get everything
+        let FrameResult::Ok(first) = ring.flush_frame() else {
+            panic!("no frame");
+        };
+        assert_eq!(first.payload, b"last words");
+        let FrameResult::Ok(fault) = ring.flush_frame() else {
+            panic!("no fault frame");
+        };
+        assert_eq!(fault.pairs(), HARD_FAULT.to_frame().pairs());
+        assert!(ring.is_empty());
+
+        ring.unfreeze();
+        assert!(matches!(ring.log(Frame::new(b"after")), PushResult::Ok));
+    }
+
+    #[test]
+    fn a_full_ring_gives_up_its_oldest_frames_for_the_fault() {
+        let mut ring = RingBuffer::new(256);
+        while let PushResult::Ok = ring.log(Frame::new(b"filler")) {}
+        assert!(matches!(ring.record_fault(&HARD_FAULT), PushResult::Ok));
+        let mut last = None;
+        while let FrameResult::Ok(frame) = ring.flush_frame() {
+            last = Some(frame);
+        }
+        assert_eq!(last.unwrap().pairs(), HARD_FAULT.to_frame().pairs());
+
+        let mut tiny = RingBuffer::new(16);
+        assert!(matches!(tiny.record_fault(&HARD_FAULT), PushResult::Err(_)));
+        assert!(tiny.is_frozen());
+    }
+}
@@ -0,0 +1,314 @@
+// c interface for mixed c/rust firmware. the ring is an opaque pointer owned by the c
+// side between `rb_new` and `rb_free`. the header in include/ringbuffer.h is generated
+// from this file:
+//
+//     cbindgen --config cbindgen.toml --output include/ringbuffer.h
+//
+// and the library built with `cargo rustc --lib --release --features ffi --crate-type staticlib`.
+// every pointer argument must be null or valid for the length passed alongside it, and a
+// ring must only be used from one thread at a time
+#![allow(clippy::missing_safety_doc)]
+
+use alloc::boxed::Box;
+
+use crate::frame::{self, Frame, FrameResult};
+use crate::level::Level;
+use crate::{FlushResult, PushResult, RingBuffer};
+
+pub const RB_OK: i32 = 0;
+// a null pointer or an out of range argument
+pub const RB_ERR_INVALID: i32 = -1;
+// the frame didn't fit, or was filtered by level
+pub const RB_ERR_FULL: i32 = -2;
+// no complete frame to flush
+pub const RB_ERR_EMPTY: i32 = -3;
+// the flushed frame failed its crc and was dropped
+pub const RB_ERR_CRC: i32 = -4;
+// the output buffer is too small, `out_len` holds the size needed. nothing was flushed
+pub const RB_ERR_TOO_SMALL: i32 = -5;
+
+// a ring of `size` bytes, null for a size of 0. release it with `rb_free`
+#[no_mangle]
+pub extern "C" fn rb_new(size: usize) -> *mut RingBuffer {
+    if size == 0 {
+        return core::ptr::null_mut();
+    }
+    Box::into_raw(Box::new(RingBuffer::new(size)))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rb_free(ring: *mut RingBuffer) {
+    if !ring.is_null() {
+        drop(Box::from_raw(ring));
+    }
+}
+
+unsafe fn bytes<'a>(data: *const u8, len: usize) -> Option<&'a [u8]> {
+    match data.is_null() {
+        true if len == 0 => Some(&[]),
+        true => None,
+        false => Some(core::slice::from_raw_parts(data, len)),
+    }
+}
+
+fn push_code(result: PushResult) -> i32 {
+    match result {
+        PushResult::Ok => RB_OK,
+        PushResult::Err(_) => RB_ERR_FULL,
+    }
+}
+
+// log `len` bytes as one frame
+#[no_mangle]
+pub unsafe extern "C" fn rb_push_frame(ring: *mut RingBuffer, data: *const u8, len: usize) -> i32 {
+    match (ring.as_mut(), bytes(data, len)) {
+        (Some(ring), Some(message)) => push_code(ring.log_message_with_crc(message)),
+        _ => RB_ERR_INVALID,
+    }
+}
+
+// same as rb_push_frame with a level, 1 (error) to 5 (trace)
+#[no_mangle]
+pub unsafe extern "C" fn rb_push_frame_with_level(
+    ring: *mut RingBuffer,
+    level: u8,
+    data: *const u8,
+    len: usize,
+) -> i32 {
+    match (ring.as_mut(), Level::from_u8(level), bytes(data, len)) {
+        (Some(ring), Some(level), Some(message)) => push_code(ring.log_with_level(level, message)),
+        _ => RB_ERR_INVALID,
+    }
+}
+
+// copy the payload of the next frame into `out` and remove it from the ring. `out_len`
+// gets the payload length
+#[no_mangle]
+pub unsafe extern "C" fn rb_flush_frame(
+    ring: *mut RingBuffer,
+    out: *mut u8,
+    capacity: usize,
+    out_len: *mut usize,
+) -> i32 {
+    let (Some(ring), Some(out_len)) = (ring.as_mut(), out_len.as_mut()) else {
+        return RB_ERR_INVALID;
+    };
+    if out.is_null() && capacity > 0 {
+        return RB_ERR_INVALID;
+    }
+    *out_len = 0;
+    let Some(bytes) = ring.peek_frame_bytes() else {
+        return RB_ERR_EMPTY;
+    };
+
+    // leave the frame queued when the caller can't take it yet
+    if let FrameResult::Ok(frame) = Frame::decode(&bytes) {
+        if frame.payload.len() > capacity {
+            *out_len = frame.payload.len();
+            return RB_ERR_TOO_SMALL;
+        }
+    }
+    match ring.flush_frame() {
+        FrameResult::Ok(frame) => {
+            let len = frame.payload.len();
+            if len > 0 {
+                core::ptr::copy_nonoverlapping(frame.payload.as_ptr(), out, len);
+            }
+            *out_len = len;
+            RB_OK
+        }
+        FrameResult::Err(_) => RB_ERR_CRC,
+    }
+}
+
+// copy the payloads of complete, crc checked frames, up to max_flush_size bytes' worth,
+// into `out` back to back, the same as dma_flush_with_crc_check
+#[no_mangle]
+pub unsafe extern "C" fn rb_dma_flush(
+    ring: *mut RingBuffer,
+    out: *mut u8,
+    capacity: usize,
+    out_len: *mut usize,
+) -> i32 {
+    let (Some(ring), Some(out_len)) = (ring.as_mut(), out_len.as_mut()) else {
+        return RB_ERR_INVALID;
+    };
+    *out_len = 0;
+    let needed = ring.dma_flush_bound();
+    if out.is_null() && needed > 0 || capacity < needed {
+        *out_len = needed;
+        return RB_ERR_TOO_SMALL;
+    }
+    match ring.dma_flush_with_crc_check() {
+        FlushResult::Ok(bytes) => {
+            if !bytes.is_empty() {
+                core::ptr::copy_nonoverlapping(bytes.as_ptr(), out, bytes.len());
+            }
+            *out_len = bytes.len();
+            RB_OK
+        }
+        FlushResult::Err(_) => RB_ERR_CRC,
+    }
+}
+
+impl RingBuffer {
+    // escaped bytes of the frames the next dma flush would take, an upper bound for the
+    // payload bytes it returns. the last frame can run past the budget
+    fn dma_flush_bound(&self) -> usize {
+        let (mut taken, mut current) = (0, 0);
+        for i in 0..self.len() {
+            if taken >= self.max_flush_size {
+                break;
+            }
+            current += 1;
+            if self.stored_byte(i) == frame::TERMINATOR {
+                taken += current;
+                current = 0;
+            }
+        }
+        taken
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rb_set_max_flush_size(ring: *mut RingBuffer, max_flush_size: usize) {
+    if let Some(ring) = ring.as_mut() {
+        ring.set_max_flush_size(max_flush_size);
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rb_set_max_level(ring: *mut RingBuffer, level: u8) -> i32 {
+    match (ring.as_mut(), Level::from_u8(level)) {
+        (Some(ring), Some(level)) => {
+            ring.set_max_level(level);
+            RB_OK
+        }
+        _ => RB_ERR_INVALID,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rb_enable_sequence_numbers(ring: *mut RingBuffer) {
+    if let Some(ring) = ring.as_mut() {
+        ring.enable_sequence_numbers();
+    }
+}
+
+// bytes queued, partial frames included. 0 for a null ring
+#[no_mangle]
+pub unsafe extern "C" fn rb_len(ring: *const RingBuffer) -> usize {
+    ring.as_ref().map_or(0, RingBuffer::len)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rb_free_space(ring: *const RingBuffer) -> usize {
+    ring.as_ref().map_or(0, RingBuffer::free)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rb_is_empty(ring: *const RingBuffer) -> bool {
+    ring.as_ref().is_none_or(RingBuffer::is_empty)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::String;
+    use alloc::vec::Vec;
+    use core::ptr::{null, null_mut};
+
+    use super::*;
+
+    #[test]
+    fn frames_go_through_the_c_calls() {
+        unsafe {
+            let ring = rb_new(64);
+            assert!(rb_is_empty(ring));
+            assert_eq!(rb_push_frame(ring, b"first".as_ptr(), 5), RB_OK);
+            assert_eq!(
+                rb_push_frame_with_level(ring, 2, b"warn".as_ptr(), 4),
+                RB_OK
+            );
+            assert_eq!(rb_len(ring) + rb_free_space(ring), 63);
+
+            let mut out = [0u8; 16];
+            let mut len = 0;
+            assert_eq!(rb_flush_frame(ring, out.as_mut_ptr(), 16, &mut len), RB_OK);
+            assert_eq!(&out[..len], b"first");
+            assert_eq!(rb_flush_frame(ring, out.as_mut_ptr(), 16, &mut len), RB_OK);
+            assert_eq!(&out[..len], b"warn");
+            assert_eq!(
+                rb_flush_frame(ring, out.as_mut_ptr(), 16, &mut len),
+                RB_ERR_EMPTY
+            );
+            rb_free(ring);
+        }
+    }
+
+    #[test]
+    fn a_small_buffer_leaves_the_frame_queued() {
+        unsafe {
+            let ring = rb_new(64);
+            rb_push_frame(ring, b"twelve bytes".as_ptr(), 12);
+            let mut out = [0u8; 4];
+            let mut len = 0;
+            assert_eq!(
+                rb_flush_frame(ring, out.as_mut_ptr(), 4, &mut len),
+                RB_ERR_TOO_SMALL
+            );
+            assert_eq!(len, 12);
+            assert!(!rb_is_empty(ring));
+
+            let mut out = [0u8; 32];
+            assert_eq!(
+                rb_dma_flush(ring, out.as_mut_ptr(), 2, &mut len),
+                RB_ERR_TOO_SMALL
+            );
+            assert!(len > 12);
+            assert_eq!(rb_dma_flush(ring, out.as_mut_ptr(), len, &mut len), RB_OK);
+            assert_eq!(&out[..len], b"twelve bytes");
+            assert!(rb_is_empty(ring));
+            rb_free(ring);
+        }
+    }
+
+    #[test]
+    fn bad_arguments_are_refused() {
+        unsafe {
+            assert!(rb_new(0).is_null());
+            assert_eq!(rb_push_frame(null_mut(), b"x".as_ptr(), 1), RB_ERR_INVALID);
+            let ring = rb_new(16);
+            assert_eq!(rb_push_frame(ring, null(), 3), RB_ERR_INVALID);
+            assert_eq!(rb_push_frame(ring, null(), 0), RB_OK);
+            assert_eq!(
+                rb_push_frame_with_level(ring, 9, b"x".as_ptr(), 1),
+                RB_ERR_INVALID
+            );
+            assert_eq!(rb_set_max_level(ring, 0), RB_ERR_INVALID);
+            assert_eq!(rb_push_frame(ring, [7u8; 32].as_ptr(), 32), RB_ERR_FULL);
+            let mut len = 0;
+            assert_eq!(
+                rb_flush_frame(ring, null_mut(), 4, &mut len),
+                RB_ERR_INVALID
+            );
+            assert_eq!(rb_len(null()), 0);
+            assert!(rb_is_empty(null()));
+            rb_free(ring);
+            rb_free(null_mut());
+        }
+    }
+
+    #[test]
+    fn the_header_declares_every_function() {
+        let header = include_str!("../include/ringbuffer.h");
+        let exported: Vec<String> = include_str!("ffi.rs")
+            .lines()
+            .filter_map(|line| line.split_once("extern \"C\" fn ")?.1.split_once('('))
+            .map(|(name, _)| String::from(name))
+            .collect();
+        assert_eq!(exported.len(), 12);
+        for name in exported {
+            assert!(header.contains(&alloc::format!("{}(", name)), "{}", name);
+        }
+    }
+}
@@ -0,0 +1,231 @@
+// flight recorder: records all the time and never turns a producer away, the oldest
+// frames make room for new ones. nothing is flushed in normal operation, the recent
+// history is read out on demand when something interesting happens:
+//
+//     let mut recorder = FlightRecorder::new(16 * 1024);
+//     recorder.ring_mut().set_clock(SystemClock::new());
+//     recorder.record_message(b"...");
+//     ...
+//     // the last five seconds, oldest first (the clock counts milliseconds)
+//     for frame in recorder.snapshot(Recent::Ticks(5000)) { ... }
+//
+// eviction always takes whole frames, so what's stored starts at a frame boundary and
+// reads back in order. reading out doesn't consume, the recorder keeps going
+
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+use crate::frame::{Frame, FrameResult};
+use crate::sink::{FlushSink, SinkResult};
+use crate::{PushResult, RingBuffer};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Recent {
+    // everything still stored
+    All,
+    // the newest n frames
+    Frames(usize),
+    // frames stamped at most this many clock ticks before now (the newest frame's
+    // timestamp without a clock). frames without a timestamp are left out
+    Ticks(u64),
+}
+
+pub struct FlightRecorder {
+    ring: RingBuffer,
+    evicted: u64,
+}
+
+impl FlightRecorder {
+    pub fn new(size: usize) -> Self {
+        Self::from_ring(RingBuffer::new(size))
+    }
+
+    // record into an existing ring, e.g. one in retained ram
+    pub fn from_ring(ring: RingBuffer) -> Self {
+        FlightRecorder { ring, evicted: 0 }
+    }
+
+    pub fn ring(&self) -> &RingBuffer {
+        &self.ring
+    }
+
+    // for setup (clock, sequence numbers, level filter). consuming frames through it
+    // takes them out of the recording
+    pub fn ring_mut(&mut self) -> &mut RingBuffer {
+        &mut self.ring
+    }
+
+    pub fn into_ring(self) -> RingBuffer {
+        self.ring
+    }
+
+    // frames thrown out to make room since the recorder was created
+    pub fn evicted(&self) -> u64 {
+        self.evicted
+    }
+
+    // stamp and store `frame`, evicting the oldest frames as needed. only fails for a
+    // frame filtered by level or too big for the whole ring (or, with `DropPolicy::Evict`,
+    // for the room left by more severe frames)
+    pub fn record(&mut self, mut frame: Frame) -> PushResult {
+        if let PushResult::Err(e) = self.ring.stamp(&mut frame) {
+            return PushResult::Err(e);
+        }
+        match self.ring.evict_for(&frame) {
+            Some(evicted) => self.evicted += evicted,
+            None => return PushResult::Err("No room for the frame in the recorder".to_string()),
+        }
+        self.ring.log_frame(&frame)
+    }
+
+    pub fn record_message(&mut self, message: &[u8]) -> PushResult {
+        self.record(Frame::new(message))
+    }
+
+    // the frames in `recent`, oldest first, without consuming them. corrupt frames are
+    // skipped
+    pub fn snapshot(&self, recent: Recent) -> Vec<Frame> {
+        let mut frames: Vec<Frame> = self
+            .ring
+            .frame_bytes()
+            .iter()
+            .filter_map(|bytes| match Frame::decode(bytes) {
+                FrameResult::Ok(frame) => Some(frame),
+                FrameResult::Err(_) => None,
+            })
+            .collect();
+        match recent {
+            Recent::All => {}
+            Recent::Frames(count) => {
+                frames.drain(..frames.len().saturating_sub(count));
+            }
+            Recent::Ticks(ticks) => {
+                let now = match &self.ring.clock {
+                    Some(clock) => Some(clock.now()),
+                    None => frames.iter().filter_map(|frame| frame.timestamp).max(),
+                };
+                let since = now.unwrap_or(0).saturating_sub(ticks);
+                frames.retain(|frame| frame.timestamp.is_some_and(|t| t >= since));
+            }
+        }
+        frames
+    }
+
+    // hand the frames in `recent` to `sink`, oldest first, without consuming them
+    pub fn dump_to(&self, recent: Recent, sink: &mut impl FlushSink) -> SinkResult {
+        for frame in self.snapshot(recent) {
+            if let SinkResult::Err(e) = sink.write_frame(&frame) {
+                return SinkResult::Err(e);
+            }
+        }
+        sink.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::sync::Arc;
+    use core::sync::atomic::{AtomicU64, Ordering};
+
+    use super::*;
+
+    fn payloads(frames: &[Frame]) -> Vec<&[u8]> {
+        frames.iter().map(|frame| &frame.payload[..]).collect()
+    }
+
+    #[derive(Default)]
+    struct Collect(Vec<Frame>);
+
+    impl FlushSink for Collect {
+        fn write_frame(&mut self, frame: &Frame) -> SinkResult {
+            self.0.push(frame.clone());
+            SinkResult::Ok
+        }
+    }
+
+    #[test]
+    fn the_oldest_frames_make_room_whole() {
+        let mut recorder = FlightRecorder::new(64);
+        for i in 0..20u8 {
+            assert!(matches!(
+                recorder.record_message(&[b'a' + i; 5]),
+                PushResult::Ok
+            ));
+        }
+        let frames = recorder.snapshot(Recent::All);
+        assert_eq!(frames.len() as u64 + recorder.evicted(), 20);
+        assert_eq!(frames.last().unwrap().payload, [b'a' + 19; 5]);
+        // whole frames in order, nothing half evicted
+        assert!(frames
+            .windows(2)
+            .all(|pair| pair[0].payload[0] + 1 == pair[1].payload[0]));
+        assert_eq!(recorder.ring().stats().crc_errors, 0);
+    }
+
+    #[test]
+    fn reading_out_doesnt_consume() {
+        let mut recorder = FlightRecorder::new(128);
+        for payload in [b"one", b"two", b"six"] {
+            let _ = recorder.record_message(payload);
+        }
+        assert_eq!(
+            payloads(&recorder.snapshot(Recent::Frames(2))),
+            [b"two", b"six"]
+        );
+        assert_eq!(
+            payloads(&recorder.snapshot(Recent::Frames(9))),
+            [b"one", b"two", b"six"]
+        );
+        let mut sink = Collect::default();
+        assert!(matches!(
+            recorder.dump_to(Recent::Frames(1), &mut sink),
+            SinkResult::Ok
+        ));
+        assert_eq!(payloads(&sink.0), [b"six"]);
+        assert_eq!(recorder.snapshot(Recent::All).len(), 3);
+    }
+
+    #[test]
+    fn recent_ticks_count_back_from_the_clock() {
+        let now = Arc::new(AtomicU64::new(100));
+        let mut recorder = FlightRecorder::new(128);
+        let clock = now.clone();
+        recorder
+            .ring_mut()
+            .set_clock(move || clock.load(Ordering::Relaxed));
+        for (at, payload) in [(100, b"old"), (150, b"mid"), (200, b"new")] {
+            now.store(at, Ordering::Relaxed);
+            let _ = recorder.record_message(payload);
+        }
+        now.store(260, Ordering::Relaxed);
+        assert_eq!(
+            payloads(&recorder.snapshot(Recent::Ticks(110))),
+            [b"mid", b"new"]
+        );
+        assert!(recorder.snapshot(Recent::Ticks(10)).is_empty());
+    }
+
+    #[test]
+    fn without_a_clock_ticks_count_back_from_the_newest_frame() {
+        let mut recorder = FlightRecorder::new(128);
+        let _ = recorder.record(Frame::new(b"untimed"));
+        let _ = recorder.record(Frame::new(b"old").with_timestamp(10));
+        let _ = recorder.record(Frame::new(b"new").with_timestamp(30));
+        assert_eq!(
+            payloads(&recorder.snapshot(Recent::Ticks(20))),
+            [b"old", b"new"]
+        );
+        assert_eq!(payloads(&recorder.snapshot(Recent::Ticks(5))), [b"new"]);
+    }
+
+    #[test]
+    fn a_frame_bigger_than_the_ring_is_turned_away() {
+        let mut recorder = FlightRecorder::new(16);
+        let _ = recorder.record_message(b"kept");
+        assert!(matches!(
+            recorder.record_message(&[1; 32]),
+            PushResult::Err(_)
+        ));
+        assert_eq!(payloads(&recorder.snapshot(Recent::All)), [b"kept"]);
+    }
+}
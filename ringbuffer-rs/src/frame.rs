@@ -0,0 +1,444 @@
+// on-wire frame format
+//
+//   [flags][header fields...][payload...][crc8] 0x00
+//
+// everything before the terminator is escaped so header fields (and the crc) can
+// contain zero bytes without ending the frame early. plain text payloads never
+// contain the escape bytes, so the payload itself goes out byte-for-byte unchanged
+// (the flags byte and the crc still get escaped when they are zero or an escape byte)
+
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::kind::FrameKind;
+use crate::level::Level;
+
+// header field flags, fields follow the flags byte in bit order. the last bit says an
+// extension flags byte follows the kind field, with the fields for its bits after it
+pub const FLAG_TIMESTAMP: u8 = 1 << 0;
+pub const FLAG_SEQ: u8 = 1 << 1;
+pub const FLAG_DROPPED: u8 = 1 << 2;
+pub const FLAG_LEVEL: u8 = 1 << 3;
+pub const FLAG_TAG: u8 = 1 << 4;
+pub const FLAG_CHANNEL: u8 = 1 << 5;
+pub const FLAG_KIND: u8 = 1 << 6;
+pub const FLAG_EXT: u8 = 1 << 7;
+
+// extension flags, a frame with bits set nobody knows yet doesn't decode
+pub const EXT_TTL: u8 = 1 << 0;
+const KNOWN_EXT: u8 = EXT_TTL;
+
+pub const TERMINATOR: u8 = b'\0';
+const ESC: u8 = 0xdb;
+const ESC_NUL: u8 = 0xdc;
+const ESC_ESC: u8 = 0xdd;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Frame {
+    pub timestamp: Option<u64>,
+    pub seq: Option<u16>,
+    pub dropped: Option<Dropped>,
+    pub level: Option<Level>,
+    // subsystem id, lets several producers share one buffer
+    pub tag: Option<u16>,
+    pub channel: Option<u8>,
+    pub kind: Option<FrameKind>,
+    // clock ticks after its timestamp the frame is worth keeping, see `ttl`
+    pub ttl: Option<u32>,
+    pub payload: Vec<u8>,
+}
+
+// marker for messages the producer had to throw away, written where the loss happened
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Dropped {
+    pub messages: u32,
+    pub bytes: u32,
+}
+
+impl Dropped {
+    // fold another loss into this one
+    pub(crate) fn add(&mut self, other: Dropped) {
+        self.messages = self.messages.saturating_add(other.messages);
+        self.bytes = self.bytes.saturating_add(other.bytes);
+    }
+}
+
+impl fmt::Display for Dropped {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "dropped {} messages, {} bytes",
+            self.messages, self.bytes
+        )
+    }
+}
+
+// consumer-side callback that frames get routed to
+pub(crate) type Handler<'a> = Box<dyn FnMut(Frame) + 'a>;
+
+pub enum FrameResult {
+    Ok(Frame),
+    Err(String),
+}
+
+// add in CRC-8 checksum
+pub fn crc8(slice: &[u8]) -> u8 {
+    let mut crc = 0;
+    for byte in slice {
+        crc ^= byte;
+    }
+    crc
+}
+
+// the sequence number in the header of a frame that failed to decode, if its flags say
+// it has one. the header may be part of the damage, so it's only a hint
+pub(crate) fn claimed_seq(bytes: &[u8]) -> Option<u16> {
+    let mut raw = Vec::new();
+    let mut iter = bytes.iter();
+    // flags, timestamp and seq are at most 11 bytes
+    while let (true, Some(&byte)) = (raw.len() < 11, iter.next()) {
+        match byte {
+            ESC => match *iter.next()? {
+                ESC_NUL => raw.push(TERMINATOR),
+                ESC_ESC => raw.push(ESC),
+                _ => return None,
+            },
+            byte => raw.push(byte),
+        }
+    }
+    let flags = *raw.first()?;
+    if flags & FLAG_SEQ == 0 {
+        return None;
+    }
+    let at = if flags & FLAG_TIMESTAMP != 0 { 9 } else { 1 };
+    let seq = raw.get(at..at + 2)?;
+    Some(u16::from_le_bytes([seq[0], seq[1]]))
+}
+
+impl Frame {
+    pub fn new(payload: &[u8]) -> Self {
+        Frame {
+            payload: payload.to_vec(),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_timestamp(mut self, timestamp: u64) -> Self {
+        self.timestamp = Some(timestamp);
+        self
+    }
+
+    pub fn with_seq(mut self, seq: u16) -> Self {
+        self.seq = Some(seq);
+        self
+    }
+
+    pub fn with_level(mut self, level: Level) -> Self {
+        self.level = Some(level);
+        self
+    }
+
+    pub fn with_tag(mut self, tag: u16) -> Self {
+        self.tag = Some(tag);
+        self
+    }
+
+    pub fn with_channel(mut self, channel: u8) -> Self {
+        self.channel = Some(channel);
+        self
+    }
+
+    pub fn with_kind(mut self, kind: FrameKind) -> Self {
+        self.kind = Some(kind);
+        self
+    }
+
+    pub fn with_ttl(mut self, ttl: u32) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    // an empty frame that only reports lost messages
+    pub fn dropped_marker(dropped: Dropped) -> Self {
+        Frame {
+            dropped: Some(dropped),
+            ..Default::default()
+        }
+    }
+
+    fn flags(&self) -> u8 {
+        let mut flags = 0;
+        if self.timestamp.is_some() {
+            flags |= FLAG_TIMESTAMP;
+        }
+        if self.seq.is_some() {
+            flags |= FLAG_SEQ;
+        }
+        if self.dropped.is_some() {
+            flags |= FLAG_DROPPED;
+        }
+        if self.level.is_some() {
+            flags |= FLAG_LEVEL;
+        }
+        if self.tag.is_some() {
+            flags |= FLAG_TAG;
+        }
+        if self.channel.is_some() {
+            flags |= FLAG_CHANNEL;
+        }
+        if self.kind.is_some() {
+            flags |= FLAG_KIND;
+        }
+        if self.ext_flags() != 0 {
+            flags |= FLAG_EXT;
+        }
+        flags
+    }
+
+    fn ext_flags(&self) -> u8 {
+        let mut ext = 0;
+        if self.ttl.is_some() {
+            ext |= EXT_TTL;
+        }
+        ext
+    }
+
+    // raw header + payload + crc, before escaping
+    pub(crate) fn to_raw(&self) -> Vec<u8> {
+        let mut raw = Vec::with_capacity(self.payload.len() + 10);
+        raw.push(self.flags());
+        if let Some(timestamp) = self.timestamp {
+            raw.extend_from_slice(&timestamp.to_le_bytes());
+        }
+        if let Some(seq) = self.seq {
+            raw.extend_from_slice(&seq.to_le_bytes());
+        }
+        if let Some(dropped) = self.dropped {
+            raw.extend_from_slice(&dropped.messages.to_le_bytes());
+            raw.extend_from_slice(&dropped.bytes.to_le_bytes());
+        }
+        if let Some(level) = self.level {
+            raw.push(level as u8);
+        }
+        if let Some(tag) = self.tag {
+            raw.extend_from_slice(&tag.to_le_bytes());
+        }
+        if let Some(channel) = self.channel {
+            raw.push(channel);
+        }
+        if let Some(kind) = self.kind {
+            raw.push(kind as u8);
+        }
+        let ext = self.ext_flags();
+        if ext != 0 {
+            raw.push(ext);
+        }
+        if let Some(ttl) = self.ttl {
+            raw.extend_from_slice(&ttl.to_le_bytes());
+        }
+        raw.extend_from_slice(&self.payload);
+        raw.push(crc8(&raw));
+        raw
+    }
+
+    // encode into the escaped, terminated form that goes into the ring
+    pub fn encode(&self) -> Vec<u8> {
+        let raw = self.to_raw();
+        let mut out = Vec::with_capacity(raw.len() + 1);
+        for byte in raw {
+            match byte {
+                TERMINATOR => out.extend_from_slice(&[ESC, ESC_NUL]),
+                ESC => out.extend_from_slice(&[ESC, ESC_ESC]),
+                _ => out.push(byte),
+            }
+        }
+        out.push(TERMINATOR);
+        out
+    }
+
+    // decode one frame from its escaped bytes, terminator already stripped
+    pub fn decode(bytes: &[u8]) -> FrameResult {
+        let mut raw = Vec::with_capacity(bytes.len());
+        let mut iter = bytes.iter();
+        while let Some(&byte) = iter.next() {
+            if byte != ESC {
+                raw.push(byte);
+                continue;
+            }
+            match iter.next() {
+                Some(&ESC_NUL) => raw.push(TERMINATOR),
+                Some(&ESC_ESC) => raw.push(ESC),
+                _ => return FrameResult::Err("Invalid escape sequence".to_string()),
+            }
+        }
+
+        // check CRC-8 checksum
+        let crc_read = match raw.pop() {
+            Some(crc) => crc,
+            None => return FrameResult::Err("Frame is empty".to_string()),
+        };
+        if crc8(&raw) != crc_read {
+            return FrameResult::Err("CRC-8 checksum failed".to_string());
+        }
+
+        let Some((&flags, mut rest)) = raw.split_first() else {
+            return FrameResult::Err("Frame header is truncated".to_string());
+        };
+        let mut frame = Frame::default();
+        if flags & FLAG_TIMESTAMP != 0 {
+            let Some((field, tail)) = rest.split_first_chunk::<8>() else {
+                return FrameResult::Err("Frame header is truncated".to_string());
+            };
+            frame.timestamp = Some(u64::from_le_bytes(*field));
+            rest = tail;
+        }
+        if flags & FLAG_SEQ != 0 {
+            let Some((field, tail)) = rest.split_first_chunk::<2>() else {
+                return FrameResult::Err("Frame header is truncated".to_string());
+            };
+            frame.seq = Some(u16::from_le_bytes(*field));
+            rest = tail;
+        }
+        if flags & FLAG_DROPPED != 0 {
+            let Some((field, tail)) = rest.split_first_chunk::<8>() else {
+                return FrameResult::Err("Frame header is truncated".to_string());
+            };
+            let (messages, bytes) = field.split_at(4);
+            frame.dropped = Some(Dropped {
+                messages: u32::from_le_bytes(messages.try_into().unwrap()),
+                bytes: u32::from_le_bytes(bytes.try_into().unwrap()),
+            });
+            rest = tail;
+        }
+        if flags & FLAG_LEVEL != 0 {
+            let Some((&level, tail)) = rest.split_first() else {
+                return FrameResult::Err("Frame header is truncated".to_string());
+            };
+            frame.level = match Level::from_u8(level) {
+                Some(level) => Some(level),
+                None => return FrameResult::Err("Invalid log level".to_string()),
+            };
+            rest = tail;
+        }
+        if flags & FLAG_TAG != 0 {
+            let Some((field, tail)) = rest.split_first_chunk::<2>() else {
+                return FrameResult::Err("Frame header is truncated".to_string());
+            };
+            frame.tag = Some(u16::from_le_bytes(*field));
+            rest = tail;
+        }
+        if flags & FLAG_CHANNEL != 0 {
+            let Some((&channel, tail)) = rest.split_first() else {
+                return FrameResult::Err("Frame header is truncated".to_string());
+            };
+            frame.channel = Some(channel);
+            rest = tail;
+        }
+        if flags & FLAG_KIND != 0 {
+            let Some((&kind, tail)) = rest.split_first() else {
+                return FrameResult::Err("Frame header is truncated".to_string());
+            };
+            frame.kind = match FrameKind::from_u8(kind) {
+                Some(kind) => Some(kind),
+                None => return FrameResult::Err("Invalid frame kind".to_string()),
+            };
+            rest = tail;
+        }
+        let mut ext = 0;
+        if flags & FLAG_EXT != 0 {
+            let Some((&field, tail)) = rest.split_first() else {
+                return FrameResult::Err("Frame header is truncated".to_string());
+            };
+            if field & !KNOWN_EXT != 0 {
+                return FrameResult::Err("Unknown frame header flags".to_string());
+            }
+            ext = field;
+            rest = tail;
+        }
+        if ext & EXT_TTL != 0 {
+            let Some((field, tail)) = rest.split_first_chunk::<4>() else {
+                return FrameResult::Err("Frame header is truncated".to_string());
+            };
+            frame.ttl = Some(u32::from_le_bytes(*field));
+            rest = tail;
+        }
+        frame.payload = rest.to_vec();
+        FrameResult::Ok(frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn escape(raw: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        for &byte in raw {
+            match byte {
+                TERMINATOR => out.extend_from_slice(&[ESC, ESC_NUL]),
+                ESC => out.extend_from_slice(&[ESC, ESC_ESC]),
+                _ => out.push(byte),
+            }
+        }
+        out
+    }
+
+    fn decode(frame: &Frame) -> Frame {
+        let encoded = frame.encode();
+        match Frame::decode(&encoded[..encoded.len() - 1]) {
+            FrameResult::Ok(frame) => frame,
+            FrameResult::Err(e) => panic!("{e}"),
+        }
+    }
+
+    #[test]
+    fn every_header_field_round_trips() {
+        let frame = Frame::new(b"hello")
+            .with_timestamp(0x0102_0304_0506_0708)
+            .with_seq(7)
+            .with_level(Level::Warn)
+            .with_tag(0xdb00)
+            .with_channel(3)
+            .with_kind(FrameKind::Telemetry)
+            .with_ttl(500);
+        assert_eq!(decode(&frame), frame);
+        assert_eq!(claimed_seq(&frame.encode()), Some(7));
+    }
+
+    #[test]
+    fn ttl_goes_in_the_extension_byte() {
+        let raw = Frame::new(b"x")
+            .with_kind(FrameKind::Log)
+            .with_ttl(9)
+            .to_raw();
+        assert_eq!(raw[0], FLAG_KIND | FLAG_EXT);
+        assert_eq!(raw[2], EXT_TTL);
+        assert_eq!(raw[3..7], 9u32.to_le_bytes());
+
+        // no extension byte without extension fields
+        let raw = Frame::new(b"x").with_kind(FrameKind::Log).to_raw();
+        assert_eq!(raw[0], FLAG_KIND);
+        assert_eq!(raw.len(), 4);
+    }
+
+    #[test]
+    fn unknown_extension_flags_are_rejected() {
+        let mut raw = Frame::new(b"x").with_ttl(9).to_raw();
+        raw.pop();
+        raw[1] |= 1 << 1;
+        raw.push(crc8(&raw));
+        assert!(matches!(
+            Frame::decode(&escape(&raw)),
+            FrameResult::Err(e) if e == "Unknown frame header flags"
+        ));
+    }
+
+    #[test]
+    fn truncated_extension_is_rejected() {
+        let mut raw = vec![FLAG_EXT];
+        raw.push(crc8(&raw));
+        assert!(matches!(Frame::decode(&escape(&raw)), FrameResult::Err(_)));
+    }
+}
@@ -0,0 +1,41 @@
+// process-wide ring for call sites that don't have one at hand, e.g. `ring_log!("...")`
+
+use std::sync::{Mutex, MutexGuard, TryLockError};
+
+use crate::interned::Arg;
+use crate::{PushResult, RingBuffer};
+
+static GLOBAL: Mutex<Option<RingBuffer>> = Mutex::new(None);
+
+fn lock() -> MutexGuard<'static, Option<RingBuffer>> {
+    // a panic while holding the lock doesn't leave the ring itself in a bad state
+    GLOBAL.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+// None while someone else holds the lock, for callers that can't afford to wait
+pub(crate) fn try_lock() -> Option<MutexGuard<'static, Option<RingBuffer>>> {
+    match GLOBAL.try_lock() {
+        Ok(guard) => Some(guard),
+        Err(TryLockError::Poisoned(e)) => Some(e.into_inner()),
+        Err(TryLockError::WouldBlock) => None,
+    }
+}
+
+// install the global ring, returns the one it replaced
+pub fn set_global(ring: RingBuffer) -> Option<RingBuffer> {
+    lock().replace(ring)
+}
+
+pub fn take_global() -> Option<RingBuffer> {
+    lock().take()
+}
+
+// run `f` against the global ring, None if there isn't one
+pub fn with_global<R>(f: impl FnOnce(&mut RingBuffer) -> R) -> Option<R> {
+    lock().as_mut().map(f)
+}
+
+pub fn log_interned(id: u16, args: &[Arg]) -> PushResult {
+    with_global(|ring| ring.log_interned(id, args))
+        .unwrap_or_else(|| PushResult::Err("No global ring buffer".to_string()))
+}
@@ -0,0 +1,184 @@
+// borrowed views over the raw storage for zero-copy access: a consumer view over the
+// stored bytes and a producer grant over the free space. both only ever expose one
+// contiguous run, after consuming/committing it the next call returns the wrapped part
+
+use core::sync::atomic::Ordering;
+
+use crate::RingBuffer;
+
+pub struct ReadView<'a> {
+    ring: &'a mut RingBuffer,
+}
+
+pub struct WriteGrant<'a> {
+    ring: &'a mut RingBuffer,
+}
+
+impl RingBuffer {
+    pub fn read_view(&mut self) -> ReadView<'_> {
+        ReadView { ring: self }
+    }
+
+    pub fn write_grant(&mut self) -> WriteGrant<'_> {
+        WriteGrant { ring: self }
+    }
+
+    // end of the contiguous stored bytes starting at tail
+    pub(crate) fn readable_end(&self) -> usize {
+        if self.head >= self.tail {
+            self.head
+        } else {
+            self.size
+        }
+    }
+
+    // end of the contiguous free space starting at head, keeping the one open slot
+    fn writable_end(&self) -> usize {
+        if self.head >= self.tail {
+            if self.tail == 0 {
+                self.size - 1
+            } else {
+                self.size
+            }
+        } else {
+            self.tail - 1
+        }
+    }
+}
+
+impl ReadView<'_> {
+    // total stored bytes, possibly more than `readable()` returns
+    pub fn remaining(&self) -> usize {
+        self.ring.len()
+    }
+
+    pub fn readable(&self) -> &[u8] {
+        &self.ring.buffer[self.ring.tail..self.ring.readable_end()]
+    }
+
+    // drop `count` bytes from the front, clamped to what's stored
+    pub fn consume(&mut self, count: usize) {
+        let count = count.min(self.ring.len());
+        self.ring.advance_tail(count);
+    }
+}
+
+impl WriteGrant<'_> {
+    // total free bytes, possibly more than `writable()` returns
+    pub fn remaining(&self) -> usize {
+        self.ring.free()
+    }
+
+    // make room for `len` bytes as the drop policy says, returns `remaining()` after
+    pub fn reserve(&mut self, len: usize) -> usize {
+        self.ring.free_bytes_by_policy(len)
+    }
+
+    pub fn writable(&mut self) -> &mut [u8] {
+        let end = self.ring.writable_end();
+        &mut self.ring.buffer[self.ring.head..end]
+    }
+
+    // publish the first `count` bytes written through `writable()`, clamped to its length.
+    // the rest of the free space after a wrap isn't written yet, commit it after the next
+    // `writable()`
+    pub fn commit(&mut self, count: usize) {
+        let count = count.min(self.ring.writable_end() - self.ring.head);
+        // in volatile mode the bytes may have come from a dma engine or another core
+        self.ring.fence(Ordering::Acquire);
+        self.ring.advance_head(count);
+        self.ring.note_occupancy();
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl bytes::Buf for ReadView<'_> {
+    fn remaining(&self) -> usize {
+        ReadView::remaining(self)
+    }
+
+    fn chunk(&self) -> &[u8] {
+        self.readable()
+    }
+
+    fn advance(&mut self, cnt: usize) {
+        self.consume(cnt);
+    }
+}
+
+// SAFETY: the chunk is always initialized memory inside the ring's storage, and
+// advance_mut never moves head past the free space
+#[cfg(feature = "bytes")]
+unsafe impl bytes::BufMut for WriteGrant<'_> {
+    fn remaining_mut(&self) -> usize {
+        self.remaining()
+    }
+
+    unsafe fn advance_mut(&mut self, cnt: usize) {
+        self.commit(cnt);
+    }
+
+    fn chunk_mut(&mut self) -> &mut bytes::buf::UninitSlice {
+        bytes::buf::UninitSlice::new(self.writable())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "bytes")]
+    use bytes::{Buf, BufMut};
+
+    use super::*;
+
+    #[test]
+    fn commit_stops_at_the_wrap() {
+        let mut ring = RingBuffer::new(16);
+        ring.push_slice(&[0; 10]);
+        ring.read_view().consume(10);
+
+        let mut grant = ring.write_grant();
+        assert_eq!(grant.remaining(), 15);
+        let writable = grant.writable();
+        assert_eq!(writable.len(), 6);
+        writable.copy_from_slice(b"ending");
+        // the wrapped part wasn't written through this `writable()`
+        grant.commit(15);
+        assert_eq!(ring.len(), 6);
+
+        let mut grant = ring.write_grant();
+        grant.writable()[..3].copy_from_slice(b"new");
+        grant.commit(3);
+        let mut out = [0; 9];
+        assert_eq!(ring.pop_slice(&mut out), 9);
+        assert_eq!(&out, b"endingnew");
+    }
+
+    #[test]
+    #[cfg(feature = "bytes")]
+    fn buf_mut_and_buf_carry_on_across_the_wrap() {
+        let mut ring = RingBuffer::new(16);
+        ring.push_slice(&[0; 10]);
+        ring.read_view().advance(10);
+
+        let mut grant = ring.write_grant();
+        assert_eq!(grant.remaining_mut(), 15);
+        // 6 bytes fit before the end of the storage, the rest wraps
+        grant.put_u32_le(0xdead_beef);
+        grant.put_slice(b"wrapped");
+        assert_eq!(ring.len(), 11);
+
+        let mut view = ring.read_view();
+        assert_eq!(view.remaining(), 11);
+        assert_eq!(view.get_u32_le(), 0xdead_beef);
+        assert_eq!(&view.copy_to_bytes(7)[..], b"wrapped");
+        assert!(!view.has_remaining());
+    }
+
+    #[test]
+    #[cfg(feature = "bytes")]
+    #[should_panic]
+    fn buf_mut_doesnt_write_past_the_free_space() {
+        let mut ring = RingBuffer::new(8);
+        ring.write_grant().put_slice(&[1; 8]);
+    }
+}
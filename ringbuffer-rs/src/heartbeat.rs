@@ -0,0 +1,170 @@
+// heartbeats: empty `FrameKind::Heartbeat` frames that tell the far end the link is up
+// while nothing is being logged. without them a quiet device and a dead link look the
+// same from the host:
+//
+//     // a heartbeat whenever nothing went in for 1000 ticks of the ring's clock
+//     ring.set_heartbeat_interval(1000);
+//     ...
+//     ring.flush_to(&mut sink);
+//
+// the flush_to family puts a due heartbeat in before flushing, for other ways of
+// draining call `heartbeat_if_due` on the same schedule. `heartbeat` logs one right
+// away. on the host `FrameDecoder` counts heartbeats apart from the data frames
+
+use crate::frame::Frame;
+use crate::kind::FrameKind;
+use crate::{PushResult, RingBuffer};
+
+pub(crate) struct Heartbeat {
+    interval: u64,
+    // when the last frame went in, none before the first
+    last: Option<u64>,
+}
+
+impl Frame {
+    pub fn heartbeat() -> Self {
+        Frame::default().with_kind(FrameKind::Heartbeat)
+    }
+
+    pub fn is_heartbeat(&self) -> bool {
+        self.kind == Some(FrameKind::Heartbeat)
+    }
+}
+
+impl RingBuffer {
+    // needs a clock on the ring to ever be due
+    pub fn set_heartbeat_interval(&mut self, interval: u64) {
+        self.heartbeat = Some(Heartbeat {
+            interval,
+            last: None,
+        });
+    }
+
+    pub fn clear_heartbeat_interval(&mut self) {
+        self.heartbeat = None;
+    }
+
+    // log a heartbeat now, stamped like any other frame
+    pub fn heartbeat(&mut self) -> PushResult {
+        self.log(Frame::heartbeat())
+    }
+
+    // log a heartbeat if nothing went in for the interval, returns whether one did
+    pub fn heartbeat_if_due(&mut self) -> bool {
+        let (Some(heartbeat), Some(clock)) = (&mut self.heartbeat, &self.clock) else {
+            return false;
+        };
+        let now = clock.now();
+        let last = *heartbeat.last.get_or_insert(now);
+        if now.saturating_sub(last) < heartbeat.interval {
+            return false;
+        }
+        matches!(self.heartbeat(), PushResult::Ok)
+    }
+
+    // a frame stamped `timestamp` went in, the interval starts over
+    pub(crate) fn note_activity(&mut self, timestamp: Option<u64>) {
+        if let Some(heartbeat) = &mut self.heartbeat {
+            let now = timestamp.or_else(|| self.clock.as_ref().map(|clock| clock.now()));
+            if now.is_some() {
+                heartbeat.last = now;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::decoder::FrameDecoder;
+    use crate::sink::MemorySink;
+
+    fn clocked(now: &Arc<AtomicU64>) -> RingBuffer {
+        let mut ring = RingBuffer::new(256);
+        let now = now.clone();
+        ring.set_clock(move || now.load(Ordering::SeqCst));
+        ring.set_heartbeat_interval(100);
+        ring
+    }
+
+    #[test]
+    fn a_heartbeat_is_due_after_an_idle_interval() {
+        let now = Arc::new(AtomicU64::new(10));
+        let mut ring = clocked(&now);
+        // the first look starts the interval
+        assert!(!ring.heartbeat_if_due());
+        now.store(109, Ordering::SeqCst);
+        assert!(!ring.heartbeat_if_due());
+        now.store(110, Ordering::SeqCst);
+        assert!(ring.heartbeat_if_due());
+        // and the heartbeat itself starts it over
+        assert!(!ring.heartbeat_if_due());
+
+        let frames = {
+            let mut sink = MemorySink::new();
+            let _ = ring.flush_all_to(&mut sink);
+            sink.take()
+        };
+        assert_eq!(frames.len(), 1);
+        assert!(frames[0].is_heartbeat());
+        assert!(frames[0].payload.is_empty());
+        assert_eq!(frames[0].timestamp, Some(110));
+    }
+
+    #[test]
+    fn logging_keeps_the_heartbeat_away() {
+        let now = Arc::new(AtomicU64::new(0));
+        let mut ring = clocked(&now);
+        for at in [50, 120, 190] {
+            now.store(at, Ordering::SeqCst);
+            let _ = ring.log(Frame::new(b"busy"));
+            assert!(!ring.heartbeat_if_due());
+        }
+        now.store(290, Ordering::SeqCst);
+        assert!(ring.heartbeat_if_due());
+    }
+
+    #[test]
+    fn flushing_puts_a_due_heartbeat_in_first() {
+        let now = Arc::new(AtomicU64::new(0));
+        let mut ring = clocked(&now);
+        let mut sink = MemorySink::new();
+        let _ = ring.flush_all_to(&mut sink);
+        now.store(100, Ordering::SeqCst);
+        let _ = ring.flush_all_to(&mut sink);
+        let frames = sink.take();
+        assert_eq!(frames.len(), 1);
+        assert!(frames[0].is_heartbeat());
+    }
+
+    #[test]
+    fn without_a_clock_or_interval_it_never_comes_due() {
+        let mut ring = RingBuffer::new(256);
+        ring.set_heartbeat_interval(0);
+        assert!(!ring.heartbeat_if_due());
+
+        let now = Arc::new(AtomicU64::new(0));
+        let mut ring = clocked(&now);
+        ring.clear_heartbeat_interval();
+        now.store(1000, Ordering::SeqCst);
+        assert!(!ring.heartbeat_if_due());
+        assert!(ring.is_empty());
+    }
+
+    #[test]
+    fn the_decoder_counts_heartbeats_apart_from_data() {
+        let mut decoder = FrameDecoder::new();
+        let mut bytes = Frame::heartbeat().with_timestamp(5).encode();
+        bytes.extend(Frame::new(b"data").encode());
+        bytes.extend(Frame::heartbeat().encode());
+        let frames = decoder.feed(&bytes);
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].payload, b"data");
+        assert_eq!(decoder.heartbeats(), 2);
+        // the unstamped one doesn't forget the time of the last
+        assert_eq!(decoder.last_heartbeat(), Some(5));
+    }
+}
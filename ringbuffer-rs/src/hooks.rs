@@ -0,0 +1,162 @@
+// callbacks for notable events, so an application can bump its own metrics, light an
+// led or escalate right when something happens instead of polling `stats`:
+//
+//     struct Alarm;
+//
+//     impl Hooks for Alarm {
+//         fn on_overflow(&mut self, _bytes: usize, dropped: Dropped) {
+//             if dropped.messages > 100 {
+//                 red_led_on();
+//             }
+//         }
+//     }
+//
+//     ring.set_hooks(Alarm);
+//
+// every method does nothing by default. they run inline, from whatever context logged or
+// flushed (an interrupt handler included), so keep them short
+
+use alloc::boxed::Box;
+
+use crate::frame::Dropped;
+use crate::sink::FlushStats;
+use crate::RingBuffer;
+
+pub trait Hooks {
+    // a frame of `bytes` encoded bytes didn't fit and was dropped. `dropped` counts
+    // everything lost since the last frame that got in, this one included
+    fn on_overflow(&mut self, _bytes: usize, _dropped: Dropped) {}
+
+    // a frame failed to decode on its way out. `seq` is the sequence number its header
+    // claims, if it has one (the header may be part of the damage)
+    fn on_crc_error(&mut self, _seq: Option<u16>, _error: &str) {}
+
+    // a flush_to / flush_all_to call delivered `stats`
+    fn on_flush(&mut self, _stats: &FlushStats) {}
+
+    // `occupancy` bytes stored reached the high watermark, see `set_watermarks`
+    fn on_high_watermark(&mut self, _occupancy: usize) {}
+}
+
+impl RingBuffer {
+    pub fn set_hooks(&mut self, hooks: impl Hooks + Send + 'static) {
+        self.hooks = Some(Box::new(hooks));
+    }
+
+    pub fn clear_hooks(&mut self) {
+        self.hooks = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::frame::{Frame, FrameResult};
+    use crate::sink::{FlushToResult, MemorySink};
+
+    #[derive(Debug, PartialEq)]
+    enum Event {
+        Overflow(usize, Dropped),
+        CrcError(Option<u16>),
+        Flush(FlushStats),
+    }
+
+    #[derive(Clone, Default)]
+    struct Recorder(Arc<Mutex<Vec<Event>>>);
+
+    impl Recorder {
+        fn take(&self) -> Vec<Event> {
+            core::mem::take(&mut self.0.lock().unwrap())
+        }
+    }
+
+    impl Hooks for Recorder {
+        fn on_overflow(&mut self, bytes: usize, dropped: Dropped) {
+            self.0.lock().unwrap().push(Event::Overflow(bytes, dropped));
+        }
+
+        fn on_crc_error(&mut self, seq: Option<u16>, _error: &str) {
+            self.0.lock().unwrap().push(Event::CrcError(seq));
+        }
+
+        fn on_flush(&mut self, stats: &FlushStats) {
+            self.0.lock().unwrap().push(Event::Flush(*stats));
+        }
+    }
+
+    #[test]
+    fn overflows_are_reported_with_the_running_drop_count() {
+        let recorder = Recorder::default();
+        let mut ring = RingBuffer::new(16);
+        ring.set_hooks(recorder.clone());
+
+        let frame = Frame::new(b"too long for this ring");
+        let len = frame.encode().len();
+        let _ = ring.log(frame.clone());
+        let _ = ring.log(frame);
+        assert_eq!(
+            recorder.take(),
+            [
+                Event::Overflow(
+                    len,
+                    Dropped {
+                        messages: 1,
+                        bytes: len as u32
+                    }
+                ),
+                Event::Overflow(
+                    len,
+                    Dropped {
+                        messages: 2,
+                        bytes: 2 * len as u32
+                    }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn crc_errors_report_the_claimed_sequence_number() {
+        let recorder = Recorder::default();
+        let mut ring = RingBuffer::new(64);
+        ring.set_hooks(recorder.clone());
+
+        let mut corrupt = Frame::new(b"bad").with_seq(7).encode();
+        let at = corrupt.iter().position(|&b| b == b'b').unwrap();
+        corrupt[at] = b'B';
+        ring.push_slice(&corrupt);
+        assert!(matches!(ring.flush_frame(), FrameResult::Err(_)));
+        assert_eq!(recorder.take(), [Event::CrcError(Some(7))]);
+    }
+
+    #[test]
+    fn completed_flushes_report_what_they_delivered() {
+        let recorder = Recorder::default();
+        let mut ring = RingBuffer::new(64);
+        ring.set_hooks(recorder.clone());
+        let frame = Frame::new(b"out");
+        let _ = ring.log(frame.clone());
+        let _ = ring.log(frame.clone());
+
+        let mut sink = MemorySink::new();
+        let FlushToResult::Ok(stats) = ring.flush_all_to(&mut sink) else {
+            panic!("flush failed");
+        };
+        assert_eq!(stats.frames, 2);
+        assert_eq!(stats.bytes, 2 * frame.encode().len());
+        assert_eq!(recorder.take(), [Event::Flush(stats)]);
+    }
+
+    #[test]
+    fn cleared_hooks_hear_nothing() {
+        let recorder = Recorder::default();
+        let mut ring = RingBuffer::new(16);
+        ring.set_hooks(recorder.clone());
+        ring.clear_hooks();
+        let _ = ring.log(Frame::new(b"too long for this ring"));
+        let _ = ring.flush_all_to(&mut MemorySink::new());
+        assert!(recorder.take().is_empty());
+    }
+}
@@ -0,0 +1,350 @@
+// influxdb line protocol for telemetry frames, and a sink that posts them in batches to
+// the http write endpoint
+//
+//     <measurement>[,level=..][,channel=..] <field>=<value>[,...] [timestamp ns]
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::{Duration, UNIX_EPOCH};
+
+use crate::clock::SystemClock;
+use crate::frame::Frame;
+use crate::kind::FrameKind;
+use crate::kv::Value;
+use crate::sink::{FlushSink, SinkResult};
+
+pub struct LineFormatter {
+    measurements: BTreeMap<u16, String>,
+    default_measurement: String,
+    clock: Option<SystemClock>,
+}
+
+impl Default for LineFormatter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LineFormatter {
+    // frames without a tag, or with one that has no name, go to `ringbuffer`
+    pub fn new() -> Self {
+        LineFormatter {
+            measurements: BTreeMap::new(),
+            default_measurement: "ringbuffer".to_string(),
+            clock: None,
+        }
+    }
+
+    // measurement name for frames with `tag`
+    pub fn with_measurement(mut self, tag: u16, name: &str) -> Self {
+        self.measurements.insert(tag, name.to_string());
+        self
+    }
+
+    pub fn with_default_measurement(mut self, name: &str) -> Self {
+        self.default_measurement = name.to_string();
+        self
+    }
+
+    // how to turn frame timestamps into wall-clock time, without it the server stamps
+    // points on arrival
+    pub fn with_clock(mut self, clock: SystemClock) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+
+    // one line for the frame. key-value frames become fields, anything else a single
+    // `message` string field. None for frames with nothing to write (dropped markers,
+    // malformed or empty pairs)
+    pub fn format(&self, frame: &Frame) -> Option<String> {
+        if frame.dropped.is_some() {
+            return None;
+        }
+        let pairs = match frame.pairs() {
+            Some(pairs) => pairs,
+            None if frame.kind == Some(FrameKind::KeyValue) => return None,
+            None => {
+                let text = String::from_utf8_lossy(&frame.payload).into_owned();
+                vec![("message".to_string(), Value::from(text))]
+            }
+        };
+
+        let mut fields = String::new();
+        for (key, value) in pairs {
+            let value = match value {
+                Value::Str(v) => format!("\"{}\"", v.replace('\\', "\\\\").replace('"', "\\\"")),
+                Value::Int(v) => format!("{}i", v),
+                Value::Float(v) if v.is_finite() => v.to_string(),
+                // influx has no nan or infinity
+                Value::Float(_) => continue,
+                Value::Bool(v) => v.to_string(),
+            };
+            let sep = if fields.is_empty() { "" } else { "," };
+            let _ = write!(fields, "{}{}={}", sep, escape(&key, ",= "), value);
+        }
+        if fields.is_empty() {
+            return None;
+        }
+
+        let measurement = frame
+            .tag
+            .and_then(|tag| self.measurements.get(&tag))
+            .unwrap_or(&self.default_measurement);
+        let mut line = escape(measurement, ", ");
+        if let Some(level) = frame.level {
+            let _ = write!(line, ",level={}", level.as_str());
+        }
+        if let Some(channel) = frame.channel {
+            let _ = write!(line, ",channel={}", channel);
+        }
+        let _ = write!(line, " {}", fields);
+        if let (Some(ticks), Some(clock)) = (frame.timestamp, &self.clock) {
+            if let Some(Ok(since_epoch)) = clock
+                .to_system_time(ticks)
+                .map(|time| time.duration_since(UNIX_EPOCH))
+            {
+                let _ = write!(line, " {}", since_epoch.as_nanos());
+            }
+        }
+        Some(line)
+    }
+}
+
+// backslash-escape the characters special in this position of a line
+fn escape(text: &str, special: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        if special.contains(c) {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+// posts batches of lines to an influxdb write endpoint over plain http
+pub struct InfluxSink {
+    host: String,
+    path: String,
+    token: Option<String>,
+    formatter: LineFormatter,
+    batch_size: usize,
+    timeout: Duration,
+    pending: Vec<String>,
+}
+
+impl InfluxSink {
+    // `host` is `address:port`, `path` the write endpoint with its query, e.g.
+    // `/api/v2/write?org=acme&bucket=devices&precision=ns`
+    pub fn new(host: &str, path: &str, formatter: LineFormatter) -> Self {
+        InfluxSink {
+            host: host.to_string(),
+            path: path.to_string(),
+            token: None,
+            formatter,
+            batch_size: 500,
+            timeout: Duration::from_secs(5),
+            pending: Vec::new(),
+        }
+    }
+
+    // sent as `Authorization: Token <token>`
+    pub fn with_token(mut self, token: &str) -> Self {
+        self.token = Some(token.to_string());
+        self
+    }
+
+    // lines collected before a post, whatever is left goes out at the end of the flush
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    // lines waiting for the next post, e.g. after the server was unreachable
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    fn post(&mut self) -> io::Result<()> {
+        let body = self.pending.join("\n");
+        let addr = self
+            .host
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no address"))?;
+        let mut stream = TcpStream::connect_timeout(&addr, self.timeout)?;
+        stream.set_read_timeout(Some(self.timeout))?;
+        stream.set_write_timeout(Some(self.timeout))?;
+
+        let mut request = format!(
+            "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n",
+            self.path,
+            self.host,
+            body.len()
+        );
+        if let Some(token) = &self.token {
+            let _ = write!(request, "Authorization: Token {}\r\n", token);
+        }
+        request.push_str("\r\n");
+        stream.write_all(request.as_bytes())?;
+        stream.write_all(body.as_bytes())?;
+
+        let mut status = String::new();
+        BufReader::new(stream).read_line(&mut status)?;
+        match status.split_whitespace().nth(1) {
+            Some(code) if code.starts_with('2') => {
+                self.pending.clear();
+                Ok(())
+            }
+            _ => Err(io::Error::other(format!(
+                "InfluxDB write failed: {}",
+                status.trim()
+            ))),
+        }
+    }
+}
+
+impl FlushSink for InfluxSink {
+    fn write_frame(&mut self, frame: &Frame) -> SinkResult {
+        let Some(line) = self.formatter.format(frame) else {
+            return SinkResult::Ok;
+        };
+        self.pending.push(line);
+        if self.pending.len() < self.batch_size {
+            return SinkResult::Ok;
+        }
+        match self.post() {
+            Ok(()) => SinkResult::Ok,
+            Err(e) => {
+                // this frame stays in the ring, the lines before it wait for the next post
+                self.pending.pop();
+                SinkResult::Err(e.to_string())
+            }
+        }
+    }
+
+    fn flush(&mut self) -> SinkResult {
+        if self.pending.is_empty() {
+            return SinkResult::Ok;
+        }
+        match self.post() {
+            Ok(()) => SinkResult::Ok,
+            Err(e) => SinkResult::Err(e.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+    use std::net::TcpListener;
+    use std::thread::{self, JoinHandle};
+
+    use super::*;
+    use crate::level::Level;
+
+    #[test]
+    fn pairs_become_typed_fields() {
+        let formatter = LineFormatter::new()
+            .with_measurement(3, "motor speed")
+            .with_clock(SystemClock::new());
+        let frame = Frame::default()
+            .kv("rpm", 1200)
+            .kv("load", 0.5)
+            .kv("state", "say \"hi\"")
+            .kv("ok", true)
+            .kv("bad", f64::NAN)
+            .kv("a b", 1)
+            .with_tag(3)
+            .with_level(Level::Warn)
+            .with_timestamp(1500);
+        assert_eq!(
+            formatter.format(&frame).unwrap(),
+            "motor\\ speed,level=WARN rpm=1200i,load=0.5,state=\"say \\\"hi\\\"\",ok=true,a\\ b=1i 1500000000"
+        );
+    }
+
+    #[test]
+    fn other_frames_become_a_message_field() {
+        let formatter = LineFormatter::new().with_default_measurement("device");
+        assert_eq!(
+            formatter
+                .format(&Frame::new(b"booted").with_tag(9).with_channel(2))
+                .unwrap(),
+            "device,channel=2 message=\"booted\""
+        );
+        assert_eq!(
+            formatter.format(&Frame::default().kv("nan", f64::NAN)),
+            None
+        );
+    }
+
+    // answers `requests` posts with `status`, handing back each request's body
+    fn server(status: &'static str, requests: usize) -> (String, JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let host = listener.local_addr().unwrap().to_string();
+        let handle = thread::spawn(move || {
+            (0..requests)
+                .map(|_| {
+                    let (stream, _) = listener.accept().unwrap();
+                    let mut reader = BufReader::new(stream);
+                    let mut len = 0;
+                    let mut line = String::new();
+                    while reader.read_line(&mut line).unwrap() > 2 {
+                        if let Some(value) = line.strip_prefix("Content-Length: ") {
+                            len = value.trim().parse().unwrap();
+                        }
+                        line.clear();
+                    }
+                    let mut body = vec![0; len];
+                    reader.read_exact(&mut body).unwrap();
+                    write!(reader.get_mut(), "HTTP/1.1 {}\r\n\r\n", status).unwrap();
+                    String::from_utf8(body).unwrap()
+                })
+                .collect()
+        });
+        (host, handle)
+    }
+
+    #[test]
+    fn lines_are_posted_in_batches() {
+        let (host, server) = server("204 No Content", 2);
+        let mut sink = InfluxSink::new(&host, "/write", LineFormatter::new()).with_batch_size(2);
+        for text in [&b"a"[..], b"b", b"c"] {
+            assert!(matches!(
+                sink.write_frame(&Frame::new(text)),
+                SinkResult::Ok
+            ));
+        }
+        assert_eq!(sink.pending(), 1);
+        assert!(matches!(sink.flush(), SinkResult::Ok));
+        assert_eq!(
+            server.join().unwrap(),
+            [
+                "ringbuffer message=\"a\"\nringbuffer message=\"b\"",
+                "ringbuffer message=\"c\"",
+            ]
+        );
+    }
+
+    #[test]
+    fn a_failed_post_keeps_the_lines() {
+        let (host, server) = server("500 Internal Server Error", 1);
+        let mut sink = InfluxSink::new(&host, "/write", LineFormatter::new()).with_batch_size(2);
+        let _ = sink.write_frame(&Frame::new(b"a"));
+        assert!(matches!(
+            sink.write_frame(&Frame::new(b"b")),
+            SinkResult::Err(e) if e == "InfluxDB write failed: HTTP/1.1 500 Internal Server Error"
+        ));
+        // the frame that triggered the post stays in the ring instead
+        assert_eq!(sink.pending(), 1);
+        server.join().unwrap();
+    }
+}
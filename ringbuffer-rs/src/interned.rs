@@ -0,0 +1,465 @@
+// defmt-style interned messages: the format string stays on the host, the frame only
+// carries a 16-bit id and the packed arguments
+//
+// payload: [id u16][arg]... with each arg as [type][value], little endian
+
+use alloc::borrow::Cow;
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+use core::fmt::Write;
+
+use crate::frame::Frame;
+use crate::kind::FrameKind;
+
+const ARG_U8: u8 = 0;
+const ARG_U16: u8 = 1;
+const ARG_U32: u8 = 2;
+const ARG_U64: u8 = 3;
+const ARG_I32: u8 = 4;
+const ARG_I64: u8 = 5;
+const ARG_F32: u8 = 6;
+const ARG_F64: u8 = 7;
+const ARG_BOOL: u8 = 8;
+const ARG_STR: u8 = 9;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Arg<'a> {
+    U8(u8),
+    U16(u16),
+    U32(u32),
+    U64(u64),
+    I32(i32),
+    I64(i64),
+    F32(f32),
+    F64(f64),
+    Bool(bool),
+    Str(Cow<'a, str>),
+}
+
+impl fmt::Display for Arg<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Arg::U8(v) => v.fmt(f),
+            Arg::U16(v) => v.fmt(f),
+            Arg::U32(v) => v.fmt(f),
+            Arg::U64(v) => v.fmt(f),
+            Arg::I32(v) => v.fmt(f),
+            Arg::I64(v) => v.fmt(f),
+            Arg::F32(v) => v.fmt(f),
+            Arg::F64(v) => v.fmt(f),
+            Arg::Bool(v) => v.fmt(f),
+            Arg::Str(v) => v.fmt(f),
+        }
+    }
+}
+
+macro_rules! arg_from {
+    ($($ty:ty => $variant:ident),*) => {
+        $(
+            impl From<$ty> for Arg<'_> {
+                fn from(value: $ty) -> Self {
+                    Arg::$variant(value.into())
+                }
+            }
+        )*
+    };
+}
+
+arg_from!(
+    u8 => U8, u16 => U16, u32 => U32, u64 => U64,
+    i8 => I32, i16 => I32, i32 => I32, i64 => I64,
+    f32 => F32, f64 => F64, bool => Bool
+);
+
+impl From<usize> for Arg<'_> {
+    fn from(value: usize) -> Self {
+        Arg::U64(value as u64)
+    }
+}
+
+impl From<isize> for Arg<'_> {
+    fn from(value: isize) -> Self {
+        Arg::I64(value as i64)
+    }
+}
+
+impl<'a> From<&'a str> for Arg<'a> {
+    fn from(value: &'a str) -> Self {
+        Arg::Str(Cow::Borrowed(value))
+    }
+}
+
+impl<'a> From<&'a String> for Arg<'a> {
+    fn from(value: &'a String) -> Self {
+        Arg::Str(Cow::Borrowed(value))
+    }
+}
+
+pub enum RehydrateResult {
+    Ok(String),
+    Err(String),
+}
+
+pub enum TableResult {
+    Ok(StringTable),
+    Err(String),
+}
+
+// id for a format string, 16-bit fnv-1a. the `ring_log!` macro computes the same thing
+// at compile time. two format strings can end up with the same id, loading the table
+// catches that rather than rehydrating one message with the other's text
+pub const fn message_id(format: &str) -> u16 {
+    let bytes = format.as_bytes();
+    let mut hash: u32 = 0x811c_9dc5;
+    let mut i = 0;
+    while i < bytes.len() {
+        hash ^= bytes[i] as u32;
+        hash = hash.wrapping_mul(0x0100_0193);
+        i += 1;
+    }
+    ((hash >> 16) ^ (hash & 0xffff)) as u16
+}
+
+// build the payload for an interned message
+pub fn encode(id: u16, args: &[Arg]) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend_from_slice(&id.to_le_bytes());
+    for arg in args {
+        match arg {
+            Arg::U8(v) => out.extend_from_slice(&[ARG_U8, *v]),
+            Arg::U16(v) => {
+                out.push(ARG_U16);
+                out.extend_from_slice(&v.to_le_bytes());
+            }
+            Arg::U32(v) => {
+                out.push(ARG_U32);
+                out.extend_from_slice(&v.to_le_bytes());
+            }
+            Arg::U64(v) => {
+                out.push(ARG_U64);
+                out.extend_from_slice(&v.to_le_bytes());
+            }
+            Arg::I32(v) => {
+                out.push(ARG_I32);
+                out.extend_from_slice(&v.to_le_bytes());
+            }
+            Arg::I64(v) => {
+                out.push(ARG_I64);
+                out.extend_from_slice(&v.to_le_bytes());
+            }
+            Arg::F32(v) => {
+                out.push(ARG_F32);
+                out.extend_from_slice(&v.to_le_bytes());
+            }
+            Arg::F64(v) => {
+                out.push(ARG_F64);
+                out.extend_from_slice(&v.to_le_bytes());
+            }
+            Arg::Bool(v) => out.extend_from_slice(&[ARG_BOOL, *v as u8]),
+            Arg::Str(v) => {
+                // strings longer than u16::MAX are cut off
+                let bytes = &v.as_bytes()[..v.len().min(u16::MAX as usize)];
+                out.push(ARG_STR);
+                out.extend_from_slice(&(bytes.len() as u16).to_le_bytes());
+                out.extend_from_slice(bytes);
+            }
+        }
+    }
+    out
+}
+
+// split an interned payload back into its id and arguments
+pub fn decode(payload: &[u8]) -> Option<(u16, Vec<Arg<'static>>)> {
+    let (id, mut rest) = payload.split_first_chunk::<2>()?;
+    let mut args = Vec::new();
+    while let Some((&ty, tail)) = rest.split_first() {
+        rest = tail;
+        let arg = match ty {
+            ARG_U8 | ARG_BOOL => {
+                let (&v, tail) = rest.split_first()?;
+                rest = tail;
+                if ty == ARG_U8 {
+                    Arg::U8(v)
+                } else {
+                    Arg::Bool(v != 0)
+                }
+            }
+            ARG_U16 => {
+                let (v, tail) = rest.split_first_chunk::<2>()?;
+                rest = tail;
+                Arg::U16(u16::from_le_bytes(*v))
+            }
+            ARG_U32 | ARG_I32 | ARG_F32 => {
+                let (v, tail) = rest.split_first_chunk::<4>()?;
+                rest = tail;
+                match ty {
+                    ARG_U32 => Arg::U32(u32::from_le_bytes(*v)),
+                    ARG_I32 => Arg::I32(i32::from_le_bytes(*v)),
+                    _ => Arg::F32(f32::from_le_bytes(*v)),
+                }
+            }
+            ARG_U64 | ARG_I64 | ARG_F64 => {
+                let (v, tail) = rest.split_first_chunk::<8>()?;
+                rest = tail;
+                match ty {
+                    ARG_U64 => Arg::U64(u64::from_le_bytes(*v)),
+                    ARG_I64 => Arg::I64(i64::from_le_bytes(*v)),
+                    _ => Arg::F64(f64::from_le_bytes(*v)),
+                }
+            }
+            ARG_STR => {
+                let (len, tail) = rest.split_first_chunk::<2>()?;
+                let len = u16::from_le_bytes(*len) as usize;
+                if tail.len() < len {
+                    return None;
+                }
+                let (v, tail) = tail.split_at(len);
+                rest = tail;
+                Arg::Str(Cow::Owned(String::from_utf8_lossy(v).into_owned()))
+            }
+            _ => return None,
+        };
+        args.push(arg);
+    }
+    Some((u16::from_le_bytes(*id), args))
+}
+
+// host-side id -> format string table, usually generated at build time
+#[derive(Debug, Clone, Default)]
+pub struct StringTable {
+    entries: BTreeMap<u16, String>,
+}
+
+impl StringTable {
+    pub fn new() -> Self {
+        StringTable {
+            entries: BTreeMap::new(),
+        }
+    }
+
+    // one `<id> <format string>` entry per line, blank lines and `#` comments are skipped.
+    // a line without a valid u16 id is an error, not skipped, so a broken table shows up
+    // here instead of as unknown ids when decoding
+    pub fn parse(text: &str) -> TableResult {
+        let mut table = StringTable::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim_start();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (id, format) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            let Ok(id) = id.parse() else {
+                return TableResult::Err(format!(
+                    "Line {} has no valid message id: {:?}",
+                    number + 1,
+                    line
+                ));
+            };
+            if !table.insert(id, format) {
+                return table.collision(id, format);
+            }
+        }
+        TableResult::Ok(table)
+    }
+
+    // parse the records `ring_log!` leaves in the `ring_log` link section,
+    // [id u16][len u16][format bytes] back to back. the same call site can be in there
+    // more than once (inlined, monomorphized), the same id with another format is an error
+    pub fn from_section(mut bytes: &[u8]) -> TableResult {
+        let mut table = StringTable::new();
+        while let Some((id, rest)) = bytes.split_first_chunk::<2>() {
+            let Some((len, rest)) = rest.split_first_chunk::<2>() else {
+                break;
+            };
+            let len = u16::from_le_bytes(*len) as usize;
+            if rest.len() < len {
+                break;
+            }
+            let (format, rest) = rest.split_at(len);
+            let (id, format) = (u16::from_le_bytes(*id), String::from_utf8_lossy(format));
+            if !table.insert(id, &format) {
+                return table.collision(id, &format);
+            }
+            bytes = rest;
+        }
+        TableResult::Ok(table)
+    }
+
+    // table of every `ring_log!` call site linked into the running program
+    #[cfg(all(feature = "macros", target_os = "linux"))]
+    pub fn from_linked() -> TableResult {
+        // the linker defines these around any section whose name is a valid identifier
+        extern "C" {
+            static __start_ring_log: u8;
+            static __stop_ring_log: u8;
+        }
+        // keeps the section (and the symbols above) around even without any call sites
+        #[used]
+        #[link_section = "ring_log"]
+        static EMPTY: [u8; 0] = [];
+
+        unsafe {
+            let start = core::ptr::addr_of!(__start_ring_log);
+            let stop = core::ptr::addr_of!(__stop_ring_log);
+            let len = stop as usize - start as usize;
+            Self::from_section(core::slice::from_raw_parts(start, len))
+        }
+    }
+
+    // false when `id` already stands for another format string, that one stays
+    pub fn insert(&mut self, id: u16, format: &str) -> bool {
+        match self.entries.get(&id) {
+            Some(known) => known == format,
+            None => {
+                self.entries.insert(id, format.to_string());
+                true
+            }
+        }
+    }
+
+    fn collision(&self, id: u16, format: &str) -> TableResult {
+        TableResult::Err(format!(
+            "Message id {:#06x} is taken by both {:?} and {:?}, reword one of them",
+            id,
+            self.get(id).unwrap_or_default(),
+            format
+        ))
+    }
+
+    pub fn get(&self, id: u16) -> Option<&str> {
+        self.entries.get(&id).map(String::as_str)
+    }
+
+    // turn an interned frame back into text, `{}` placeholders are filled in order
+    pub fn rehydrate(&self, frame: &Frame) -> RehydrateResult {
+        if frame.kind != Some(FrameKind::Interned) {
+            return RehydrateResult::Err("Not an interned frame".to_string());
+        }
+        let Some((id, args)) = decode(&frame.payload) else {
+            return RehydrateResult::Err("Malformed interned arguments".to_string());
+        };
+        let Some(format) = self.get(id) else {
+            return RehydrateResult::Err("Unknown message id".to_string());
+        };
+
+        let mut out = String::new();
+        let mut args = args.iter();
+        let mut chars = format.chars().peekable();
+        while let Some(c) = chars.next() {
+            match (c, chars.peek()) {
+                ('{', Some('{')) | ('}', Some('}')) => {
+                    out.push(c);
+                    chars.next();
+                }
+                ('{', Some('}')) => {
+                    chars.next();
+                    match args.next() {
+                        Some(arg) => {
+                            let _ = write!(out, "{}", arg);
+                        }
+                        None => out.push_str("{?}"),
+                    }
+                }
+                _ => out.push(c),
+            }
+        }
+        RehydrateResult::Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(id: u16, format: &str) -> Vec<u8> {
+        let mut out = id.to_le_bytes().to_vec();
+        out.extend_from_slice(&(format.len() as u16).to_le_bytes());
+        out.extend_from_slice(format.as_bytes());
+        out
+    }
+
+    #[test]
+    fn ids_match_the_macro() {
+        // pinned, `ring_log!` checks the same values
+        assert_eq!(message_id("motor speed {}"), 0xb3d0);
+        assert_eq!(message_id(""), 0x1cd9);
+    }
+
+    #[test]
+    fn args_round_trip() {
+        let args = [
+            Arg::from(7u8),
+            Arg::from(-3i32),
+            Arg::from(2.5f64),
+            Arg::from(true),
+            Arg::from("rpm"),
+        ];
+        let payload = encode(message_id("{} {} {} {} {}"), &args);
+        let (id, decoded) = decode(&payload).unwrap();
+        assert_eq!(id, message_id("{} {} {} {} {}"));
+        assert_eq!(decoded, args);
+        assert!(decode(&payload[..payload.len() - 1]).is_none());
+    }
+
+    #[test]
+    fn rehydrate_fills_placeholders() {
+        let mut table = StringTable::new();
+        let id = message_id("motor {} at {} rpm, {{ok}}");
+        assert!(table.insert(id, "motor {} at {} rpm, {{ok}}"));
+        let frame = Frame::new(&encode(id, &[Arg::from(2u8), Arg::from(1500u16)]))
+            .with_kind(FrameKind::Interned);
+        match table.rehydrate(&frame) {
+            RehydrateResult::Ok(text) => assert_eq!(text, "motor 2 at 1500 rpm, {ok}"),
+            RehydrateResult::Err(e) => panic!("{e}"),
+        }
+    }
+
+    #[test]
+    fn repeated_call_sites_load() {
+        let mut section = record(1, "a {}");
+        section.extend(record(2, "b"));
+        section.extend(record(1, "a {}"));
+        match StringTable::from_section(&section) {
+            TableResult::Ok(table) => assert_eq!(table.get(1), Some("a {}")),
+            TableResult::Err(e) => panic!("{e}"),
+        }
+    }
+
+    #[test]
+    fn lines_without_a_valid_id_are_rejected() {
+        match StringTable::parse("# generated\n1 a {}\n\n7x b {}\n") {
+            TableResult::Ok(_) => panic!("parsed"),
+            TableResult::Err(e) => assert!(e.starts_with("Line 4 "), "{e}"),
+        }
+        assert!(matches!(
+            StringTable::parse("70000 too big\n"),
+            TableResult::Err(_)
+        ));
+        match StringTable::parse("1 a {}\n  2 b\n") {
+            TableResult::Ok(table) => assert_eq!(table.get(2), Some("b")),
+            TableResult::Err(e) => panic!("{e}"),
+        }
+    }
+
+    #[test]
+    fn colliding_ids_are_rejected() {
+        let mut section = record(1, "a {}");
+        section.extend(record(1, "b {}"));
+        assert!(matches!(
+            StringTable::from_section(&section),
+            TableResult::Err(_)
+        ));
+        assert!(matches!(
+            StringTable::parse("1 a {}\n1 b {}\n"),
+            TableResult::Err(_)
+        ));
+
+        let mut table = StringTable::new();
+        assert!(table.insert(1, "a"));
+        assert!(!table.insert(1, "b"));
+        assert_eq!(table.get(1), Some("a"));
+    }
+}
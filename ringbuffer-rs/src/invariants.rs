@@ -0,0 +1,127 @@
+// consistency checks after every index update, for debug builds with the
+// `debug-assert-invariants` feature. meant for catching logic errors while working on a
+// new storage or concurrency backend, a violation panics with a dump of the ring
+
+use alloc::format;
+use alloc::string::String;
+use core::fmt::Write;
+
+use crate::RingBuffer;
+
+// stored bytes shown in the dump, from the tail on
+const DUMP_BYTES: usize = 64;
+
+impl RingBuffer {
+    pub(crate) fn assert_invariants(&mut self) {
+        if let Some(violation) = self.violation() {
+            panic!(
+                "ring buffer invariant violated: {violation}\n{}",
+                self.dump()
+            );
+        }
+    }
+
+    fn violation(&mut self) -> Option<String> {
+        if self.buffer.len() != self.size {
+            return Some(format!(
+                "storage is {} bytes, size says {}",
+                self.buffer.len(),
+                self.size
+            ));
+        }
+        if self.head >= self.size || self.tail >= self.size {
+            return Some("index outside the storage".into());
+        }
+        if self.len() >= self.size {
+            return Some("more stored than the storage holds".into());
+        }
+        if self.counters.high_water >= self.size {
+            return Some("high water mark beyond the storage".into());
+        }
+        // retained storage has to hold exactly what's in memory
+        if let Some((head, tail, crc_ok)) = self.buffer.load_indices() {
+            if (head, tail) != (self.head, self.tail) || !crc_ok {
+                return Some(format!(
+                    "retained indices head {head} tail {tail} (crc ok: {crc_ok}) out of step"
+                ));
+            }
+        }
+        None
+    }
+
+    fn dump(&self) -> String {
+        let size = self.buffer.len().max(1);
+        // the indices may be anywhere, don't go through len()
+        let len = self.head.wrapping_sub(self.tail).wrapping_add(size) % size;
+        let mut dump = format!(
+            "size {} head {} tail {} len {len} high water {} dropped {:?} frozen {}\nstored:",
+            self.size, self.head, self.tail, self.counters.high_water, self.dropped, self.frozen,
+        );
+        for i in 0..len.min(DUMP_BYTES) {
+            let _ = write!(
+                dump,
+                " {:02x}",
+                self.buffer[self.tail.wrapping_add(i) % size]
+            );
+        }
+        if len > DUMP_BYTES {
+            dump.push_str(" ...");
+        }
+        dump
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::boxed::Box;
+    use alloc::vec;
+
+    use super::*;
+    use crate::frame::{Frame, FrameResult};
+
+    #[test]
+    fn everyday_traffic_keeps_the_invariants() {
+        let mut ring = RingBuffer::new(32);
+        for i in 0..50u8 {
+            let _ = ring.log(Frame::new(&[i; 5]));
+            if i % 3 == 0 {
+                let _ = ring.flush_frame();
+            }
+            ring.push_slice(&[i]);
+            let _ = ring.pop();
+        }
+        while let FrameResult::Ok(_) = ring.flush_frame() {}
+        assert!(ring.violation().is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "index outside the storage")]
+    fn an_index_outside_the_storage_panics() {
+        let mut ring = RingBuffer::new(32);
+        ring.tail = 40;
+        let _ = ring.push(1);
+    }
+
+    #[test]
+    fn retained_indices_out_of_step_are_caught() {
+        let ptr: *mut u8 = Box::leak(vec![0u32; 32].into_boxed_slice())
+            .as_mut_ptr()
+            .cast();
+        let mut ring = unsafe { RingBuffer::retained(ptr, 128) };
+        let _ = ring.log(Frame::new(b"kept"));
+        assert!(ring.violation().is_none());
+        // the copy in the header lags behind
+        ring.head = 0;
+        assert!(ring.violation().unwrap().starts_with("retained indices"));
+    }
+
+    #[test]
+    fn the_dump_shows_the_indices_and_stored_bytes() {
+        let mut ring = RingBuffer::new(128);
+        ring.push_slice(&[0xab; 70]);
+        ring.pop();
+        let dump = ring.dump();
+        assert!(dump.starts_with("size 128 head 70 tail 1 len 69 high water 70"));
+        assert!(dump.ends_with(&format!("{} ...", " ab".repeat(64))));
+    }
+}
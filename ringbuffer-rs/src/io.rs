@@ -0,0 +1,123 @@
+// std::io plumbing: raw bytes in through `Write`, raw bytes out through `Read`.
+// no framing is added or checked here, pair with the frame API if you need it.
+// `BufRead` is the exception, it only hands out bytes of complete frames
+
+use std::io;
+
+use crate::RingBuffer;
+
+impl io::Write for RingBuffer {
+    // short writes once the buffer fills up, and Ok(0) when it's full so `write_all`
+    // reports WriteZero instead of spinning
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        Ok(self.push_slice(buf))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl io::Read for RingBuffer {
+    // Ok(0) on an empty buffer, which readers treat as end of stream
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        Ok(self.pop_slice(buf))
+    }
+}
+
+impl io::BufRead for RingBuffer {
+    // borrow the contiguous run of bytes at the tail, cut off after the last complete
+    // frame so a parser never sees one that's still being written. a frame that wraps
+    // around the end of the storage comes back in two pieces
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        let contiguous = if self.head >= self.tail {
+            self.head - self.tail
+        } else {
+            self.size - self.tail
+        };
+        let available = contiguous.min(self.complete_len());
+        Ok(&self.buffer[self.tail..self.tail + available])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        let amt = amt.min(self.len());
+        self.advance_tail(amt);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, Read, Write};
+
+    use super::*;
+    use crate::frame::Frame;
+
+    #[test]
+    fn bytes_written_come_back_out_in_order() {
+        let mut ring = RingBuffer::new(16);
+        assert_eq!(Write::write(&mut ring, b"hello ").unwrap(), 6);
+        ring.write_all(b"world").unwrap();
+        let mut out = [0u8; 4];
+        assert_eq!(Read::read(&mut ring, &mut out).unwrap(), 4);
+        assert_eq!(&out, b"hell");
+        let mut rest = Vec::new();
+        ring.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, b"o world");
+        assert_eq!(Read::read(&mut ring, &mut out).unwrap(), 0);
+    }
+
+    #[test]
+    fn a_full_ring_writes_short_then_zero() {
+        let mut ring = RingBuffer::new(8);
+        let written = Write::write(&mut ring, b"0123456789").unwrap();
+        assert!(written < 10);
+        assert_eq!(Write::write(&mut ring, b"x").unwrap(), 0);
+        let err = ring.write_all(b"x").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::WriteZero);
+
+        // wraps around once there's room again
+        let mut out = [0u8; 4];
+        ring.read_exact(&mut out).unwrap();
+        ring.write_all(b"abcd").unwrap();
+        let mut rest = Vec::new();
+        ring.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, [&b"0123456789"[4..written], b"abcd"].concat());
+    }
+
+    #[test]
+    fn fill_buf_stops_at_the_last_complete_frame() {
+        let mut ring = RingBuffer::new(64);
+        let first = Frame::new(b"first").encode();
+        let second = Frame::new(b"second").encode();
+        ring.push_slice(&first);
+        ring.push_slice(&second[..3]);
+        assert_eq!(ring.fill_buf().unwrap(), &first[..]);
+
+        ring.consume(first.len());
+        assert!(ring.fill_buf().unwrap().is_empty());
+        ring.push_slice(&second[3..]);
+        assert_eq!(ring.fill_buf().unwrap(), &second[..]);
+    }
+
+    #[test]
+    fn a_frame_across_the_end_comes_in_two_pieces() {
+        let mut ring = RingBuffer::new(16);
+        ring.push_slice(&[0xaa; 12]);
+        let mut skip = [0u8; 12];
+        Read::read_exact(&mut ring, &mut skip).unwrap();
+        let frame = Frame::new(b"wrapped").encode();
+        ring.push_slice(&frame);
+
+        let mut read = Vec::new();
+        loop {
+            let piece = ring.fill_buf().unwrap().to_vec();
+            if piece.is_empty() {
+                break;
+            }
+            ring.consume(piece.len());
+            read.push(piece);
+        }
+        assert_eq!(read.len(), 2);
+        assert_eq!(read.concat(), frame);
+    }
+}
@@ -0,0 +1,28 @@
+// drains frames through an itm stimulus port, out the swo trace pin on cortex-m parts.
+// the probe sees itm packets rather than raw bytes, `ItmUnpacker` in the decoder strips
+// them again on the host
+
+use cortex_m::itm;
+use cortex_m::peripheral::itm::Stim;
+
+use crate::frame::Frame;
+use crate::sink::{FlushSink, SinkResult};
+
+pub struct ItmSink<'a> {
+    stim: &'a mut Stim,
+}
+
+impl<'a> ItmSink<'a> {
+    // e.g. `ItmSink::new(&mut cp.ITM.stim[1])`, the port has to be enabled in ITM_TER
+    pub fn new(stim: &'a mut Stim) -> Self {
+        ItmSink { stim }
+    }
+}
+
+impl FlushSink for ItmSink<'_> {
+    fn write_frame(&mut self, frame: &Frame) -> SinkResult {
+        // spins on the stimulus fifo, swo drains at a fixed baud so this can't stall forever
+        itm::write_all(self.stim, &frame.encode());
+        SinkResult::Ok
+    }
+}
@@ -0,0 +1,192 @@
+// forwards frames to systemd-journald over its native protocol, one datagram per frame
+//
+// fields are `KEY=value\n`, or `KEY\n[len u64 LE][value]\n` when the value has a newline
+
+use std::io;
+use std::os::unix::net::UnixDatagram;
+
+use crate::frame::Frame;
+use crate::sink::{FlushSink, SinkResult};
+use crate::syslog;
+
+const JOURNAL_SOCKET: &str = "/run/systemd/journal/socket";
+
+pub struct JournaldSink {
+    socket: UnixDatagram,
+    identifier: Option<String>,
+}
+
+impl JournaldSink {
+    pub fn new() -> io::Result<Self> {
+        Self::with_socket(JOURNAL_SOCKET)
+    }
+
+    // talk to a journal socket somewhere other than the default path
+    pub fn with_socket(path: &str) -> io::Result<Self> {
+        let socket = UnixDatagram::unbound()?;
+        socket.connect(path)?;
+        Ok(JournaldSink {
+            socket,
+            identifier: None,
+        })
+    }
+
+    // SYSLOG_IDENTIFIER, what `journalctl -t` filters on
+    pub fn with_identifier(mut self, identifier: &str) -> Self {
+        self.identifier = Some(identifier.to_string());
+        self
+    }
+
+    fn entry(&self, frame: &Frame) -> Vec<u8> {
+        let mut out = Vec::new();
+        let pairs = frame.pairs();
+        let message = match (&frame.dropped, &pairs) {
+            (Some(dropped), _) => dropped.to_string(),
+            (None, Some(pairs)) => pairs
+                .iter()
+                .map(|(key, value)| format!("{}={}", key, value))
+                .collect::<Vec<_>>()
+                .join(" "),
+            (None, None) => String::from_utf8_lossy(&frame.payload).into_owned(),
+        };
+        push_field(&mut out, "MESSAGE", message.as_bytes());
+        let priority = syslog::severity(frame.level).to_string();
+        push_field(&mut out, "PRIORITY", priority.as_bytes());
+        if let Some(identifier) = &self.identifier {
+            push_field(&mut out, "SYSLOG_IDENTIFIER", identifier.as_bytes());
+        }
+        if let Some(timestamp) = frame.timestamp {
+            push_field(&mut out, "RING_TIMESTAMP", timestamp.to_string().as_bytes());
+        }
+        if let Some(seq) = frame.seq {
+            push_field(&mut out, "RING_SEQ", seq.to_string().as_bytes());
+        }
+        if let Some(tag) = frame.tag {
+            push_field(&mut out, "RING_TAG", tag.to_string().as_bytes());
+        }
+        if let Some(channel) = frame.channel {
+            push_field(&mut out, "RING_CHANNEL", channel.to_string().as_bytes());
+        }
+        for (key, value) in pairs.iter().flatten() {
+            if let Some(name) = field_name(key) {
+                push_field(&mut out, &name, value.to_string().as_bytes());
+            }
+        }
+        out
+    }
+}
+
+// journal field names are uppercase letters, digits and underscores, can't start with a
+// digit and a leading underscore is reserved for trusted fields
+fn field_name(key: &str) -> Option<String> {
+    let name: String = key
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' => c.to_ascii_uppercase(),
+            _ => '_',
+        })
+        .skip_while(|c| *c == '_' || c.is_ascii_digit())
+        .take(64)
+        .collect();
+    if name.is_empty() {
+        None
+    } else {
+        Some(name)
+    }
+}
+
+fn push_field(out: &mut Vec<u8>, name: &str, value: &[u8]) {
+    out.extend_from_slice(name.as_bytes());
+    if value.contains(&b'\n') {
+        out.push(b'\n');
+        out.extend_from_slice(&(value.len() as u64).to_le_bytes());
+    } else {
+        out.push(b'=');
+    }
+    out.extend_from_slice(value);
+    out.push(b'\n');
+}
+
+impl FlushSink for JournaldSink {
+    fn write_frame(&mut self, frame: &Frame) -> SinkResult {
+        match self.socket.send(&self.entry(frame)) {
+            Ok(_) => SinkResult::Ok,
+            Err(e) => SinkResult::Err(e.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level::Level;
+
+    // a journal socket of our own, the sink's datagrams land here
+    fn journal(name: &str) -> (UnixDatagram, String) {
+        let path = std::env::temp_dir()
+            .join(format!(
+                "ringbuffer-journald-{}-{}",
+                std::process::id(),
+                name
+            ))
+            .to_string_lossy()
+            .into_owned();
+        let _ = std::fs::remove_file(&path);
+        (UnixDatagram::bind(&path).unwrap(), path)
+    }
+
+    fn receive(journal: &UnixDatagram) -> Vec<u8> {
+        let mut buf = [0u8; 1024];
+        let len = journal.recv(&mut buf).unwrap();
+        buf[..len].to_vec()
+    }
+
+    #[test]
+    fn one_datagram_per_frame_with_its_fields() {
+        let (journal, path) = journal("fields");
+        let mut sink = JournaldSink::with_socket(&path)
+            .unwrap()
+            .with_identifier("motor");
+        let frame = Frame::new(b"stalled")
+            .with_level(Level::Error)
+            .with_seq(4)
+            .with_tag(2);
+        assert!(matches!(sink.write_frame(&frame), SinkResult::Ok));
+        let _ = std::fs::remove_file(&path);
+
+        let entry = String::from_utf8(receive(&journal)).unwrap();
+        assert_eq!(
+            entry,
+            format!(
+                "MESSAGE=stalled\nPRIORITY={}\nSYSLOG_IDENTIFIER=motor\nRING_SEQ=4\nRING_TAG=2\n",
+                syslog::severity(Some(Level::Error))
+            )
+        );
+    }
+
+    #[test]
+    fn values_with_newlines_are_length_prefixed() {
+        let (journal, path) = journal("newlines");
+        let mut sink = JournaldSink::with_socket(&path).unwrap();
+        assert!(matches!(
+            sink.write_frame(&Frame::new(b"two\nlines")),
+            SinkResult::Ok
+        ));
+        let _ = std::fs::remove_file(&path);
+
+        let entry = receive(&journal);
+        let mut expected = b"MESSAGE\n".to_vec();
+        expected.extend_from_slice(&9u64.to_le_bytes());
+        expected.extend_from_slice(b"two\nlines\n");
+        assert!(entry.starts_with(&expected));
+    }
+
+    #[test]
+    fn keys_become_journal_field_names() {
+        assert_eq!(field_name("motor.rpm"), Some("MOTOR_RPM".to_string()));
+        assert_eq!(field_name("_trusted"), Some("TRUSTED".to_string()));
+        assert_eq!(field_name("2fast"), Some("FAST".to_string()));
+        assert_eq!(field_name("__"), None);
+        assert_eq!(field_name(&"k".repeat(100)).unwrap().len(), 64);
+    }
+}
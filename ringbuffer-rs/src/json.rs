@@ -0,0 +1,107 @@
+// minimal json rendering of frames for text-based consumers, no serde needed.
+// payloads that are valid utf-8 come out as a string, anything else as hex
+
+use alloc::string::String;
+use core::fmt::Write;
+
+use crate::frame::Frame;
+use crate::kind::FrameKind;
+
+// append `value` as a quoted, escaped json string
+pub fn write_str(out: &mut String, value: &str) {
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+pub fn kind_name(kind: FrameKind) -> &'static str {
+    match kind {
+        FrameKind::Log => "log",
+        FrameKind::Telemetry => "telemetry",
+        FrameKind::Command => "command",
+        FrameKind::Blob => "blob",
+        FrameKind::Interned => "interned",
+        FrameKind::KeyValue => "kv",
+        FrameKind::Cbor => "cbor",
+        FrameKind::Protobuf => "protobuf",
+        FrameKind::Defmt => "defmt",
+        FrameKind::Suppressed => "suppressed",
+        FrameKind::Repeated => "repeated",
+        FrameKind::Heartbeat => "heartbeat",
+    }
+}
+
+// the frame's header fields followed by `"payload"` (utf-8 text) or `"payload_hex"`,
+// without the surrounding braces so callers can add their own fields
+pub fn write_frame_fields(out: &mut String, frame: &Frame) {
+    let mut sep = "";
+    if let Some(timestamp) = frame.timestamp {
+        let _ = write!(out, "{}\"timestamp\":{}", sep, timestamp);
+        sep = ",";
+    }
+    if let Some(seq) = frame.seq {
+        let _ = write!(out, "{}\"seq\":{}", sep, seq);
+        sep = ",";
+    }
+    if let Some(level) = frame.level {
+        let _ = write!(out, "{}\"level\":\"{}\"", sep, level);
+        sep = ",";
+    }
+    if let Some(tag) = frame.tag {
+        let _ = write!(out, "{}\"tag\":{}", sep, tag);
+        sep = ",";
+    }
+    if let Some(channel) = frame.channel {
+        let _ = write!(out, "{}\"channel\":{}", sep, channel);
+        sep = ",";
+    }
+    if let Some(kind) = frame.kind {
+        let _ = write!(out, "{}\"kind\":\"{}\"", sep, kind_name(kind));
+        sep = ",";
+    }
+    if let Some(ttl) = frame.ttl {
+        let _ = write!(out, "{}\"ttl\":{}", sep, ttl);
+        sep = ",";
+    }
+    if let Some(dropped) = frame.dropped {
+        let _ = write!(
+            out,
+            "{}\"dropped\":{{\"messages\":{},\"bytes\":{}}}",
+            sep, dropped.messages, dropped.bytes
+        );
+        sep = ",";
+    }
+    match core::str::from_utf8(&frame.payload) {
+        Ok(text) => {
+            let _ = write!(out, "{}\"payload\":", sep);
+            write_str(out, text);
+        }
+        Err(_) => {
+            let _ = write!(out, "{}\"payload_hex\":\"", sep);
+            for byte in &frame.payload {
+                let _ = write!(out, "{:02x}", byte);
+            }
+            out.push('"');
+        }
+    }
+}
+
+// one frame as a json object
+pub fn frame_to_json(frame: &Frame) -> String {
+    let mut out = String::from("{");
+    write_frame_fields(&mut out, frame);
+    out.push('}');
+    out
+}
@@ -0,0 +1,137 @@
+// what a frame carries, so mixed-purpose buffers can be split up without sniffing payloads
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+use crate::frame::{Frame, FrameResult, Handler};
+use crate::RingBuffer;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum FrameKind {
+    Log = 0,
+    Telemetry = 1,
+    Command = 2,
+    Blob = 3,
+    // payload is an interned message id plus packed args, see `interned`
+    Interned = 4,
+    // payload is typed key-value pairs, see `kv`
+    KeyValue = 5,
+    // payload is a self-describing cbor item, see `cbor`
+    Cbor = 6,
+    // payload is a protobuf message behind a varint naming its type, see `protobuf`
+    Protobuf = 7,
+    // payload is one encoded defmt log call, see `defmt_logger`
+    Defmt = 8,
+    // reports frames a tag had rate limited in its dropped field, see `ratelimit`
+    Suppressed = 9,
+    // stands for repeats of the frame before it, see `dedup`
+    Repeated = 10,
+    // no payload, says the link is alive while nothing is logged, see `heartbeat`
+    Heartbeat = 11,
+}
+
+impl FrameKind {
+    pub fn from_u8(value: u8) -> Option<FrameKind> {
+        match value {
+            0 => Some(FrameKind::Log),
+            1 => Some(FrameKind::Telemetry),
+            2 => Some(FrameKind::Command),
+            3 => Some(FrameKind::Blob),
+            4 => Some(FrameKind::Interned),
+            5 => Some(FrameKind::KeyValue),
+            6 => Some(FrameKind::Cbor),
+            7 => Some(FrameKind::Protobuf),
+            8 => Some(FrameKind::Defmt),
+            9 => Some(FrameKind::Suppressed),
+            10 => Some(FrameKind::Repeated),
+            11 => Some(FrameKind::Heartbeat),
+            _ => None,
+        }
+    }
+
+    // frames the ring itself writes about the stream rather than data, rate limits,
+    // sampling and dedup leave them alone
+    pub fn is_control(self) -> bool {
+        matches!(
+            self,
+            FrameKind::Suppressed | FrameKind::Repeated | FrameKind::Heartbeat
+        )
+    }
+}
+
+// consumer side: drains the buffer and hands each frame to the handler for its kind.
+// frames without a kind are treated as log frames
+#[derive(Default)]
+pub struct Dispatcher<'a> {
+    handlers: Vec<(FrameKind, Handler<'a>)>,
+}
+
+impl<'a> Dispatcher<'a> {
+    pub fn new() -> Self {
+        Dispatcher {
+            handlers: Vec::new(),
+        }
+    }
+
+    pub fn on(mut self, kind: FrameKind, handler: impl FnMut(Frame) + 'a) -> Self {
+        self.handlers.retain(|(k, _)| *k != kind);
+        self.handlers.push((kind, Box::new(handler)));
+        self
+    }
+
+    // flush every complete frame, returns how many had a handler. corrupt frames and
+    // kinds without a handler are dropped
+    pub fn dispatch(&mut self, ring: &mut RingBuffer) -> usize {
+        let mut handled = 0;
+        while let Some(bytes) = ring.pop_frame_bytes() {
+            let frame = match ring.decode_flushed(&bytes) {
+                FrameResult::Ok(frame) => frame,
+                FrameResult::Err(_) => continue,
+            };
+            let kind = frame.kind.unwrap_or(FrameKind::Log);
+            if let Some((_, handler)) = self.handlers.iter_mut().find(|(k, _)| *k == kind) {
+                handler(frame);
+                handled += 1;
+            }
+        }
+        handled
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_kind_round_trips_through_its_byte() {
+        for value in 0..=11 {
+            assert_eq!(
+                FrameKind::from_u8(value).map(|kind| kind as u8),
+                Some(value)
+            );
+        }
+        assert_eq!(FrameKind::from_u8(12), None);
+    }
+
+    #[test]
+    fn frames_go_to_the_handler_for_their_kind() {
+        let mut ring = RingBuffer::new(256);
+        let _ = ring.log(Frame::new(b"plain"));
+        let _ = ring.log(Frame::new(b"rpm 1200").with_kind(FrameKind::Telemetry));
+        let _ = ring.log(Frame::new(b"reboot").with_kind(FrameKind::Command));
+        let _ = ring.log(Frame::new(b"core").with_kind(FrameKind::Blob));
+
+        let mut logs = Vec::new();
+        let mut telemetry = Vec::new();
+        let handled = Dispatcher::new()
+            .on(FrameKind::Log, |frame| logs.push(frame.payload))
+            .on(FrameKind::Telemetry, |frame| telemetry.push(frame.payload))
+            .dispatch(&mut ring);
+        // commands and blobs had no handler and are gone
+        assert_eq!(handled, 2);
+        assert_eq!(logs, [b"plain"]);
+        assert_eq!(telemetry, [b"rpm 1200"]);
+        assert!(ring.is_empty());
+    }
+}
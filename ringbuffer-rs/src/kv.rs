@@ -0,0 +1,228 @@
+// structured key-value frames
+//
+// payload: one entry per pair, [key len u8][key][type][value]. ints are zigzag varints,
+// floats f64 little endian, strings a varint length plus bytes, bools live in the type
+
+use alloc::borrow::Cow;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::frame::Frame;
+use crate::kind::FrameKind;
+
+const TYPE_STR: u8 = 0;
+const TYPE_INT: u8 = 1;
+const TYPE_FLOAT: u8 = 2;
+const TYPE_FALSE: u8 = 3;
+const TYPE_TRUE: u8 = 4;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value<'a> {
+    Str(Cow<'a, str>),
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+}
+
+impl fmt::Display for Value<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Str(v) => v.fmt(f),
+            Value::Int(v) => v.fmt(f),
+            Value::Float(v) => v.fmt(f),
+            Value::Bool(v) => v.fmt(f),
+        }
+    }
+}
+
+macro_rules! value_from {
+    ($($ty:ty => $variant:ident),*) => {
+        $(
+            impl From<$ty> for Value<'_> {
+                fn from(value: $ty) -> Self {
+                    Value::$variant(value.into())
+                }
+            }
+        )*
+    };
+}
+
+value_from!(
+    u8 => Int, u16 => Int, u32 => Int, i8 => Int, i16 => Int, i32 => Int, i64 => Int,
+    f32 => Float, f64 => Float, bool => Bool
+);
+
+impl<'a> From<&'a str> for Value<'a> {
+    fn from(value: &'a str) -> Self {
+        Value::Str(Cow::Borrowed(value))
+    }
+}
+
+impl From<String> for Value<'_> {
+    fn from(value: String) -> Self {
+        Value::Str(Cow::Owned(value))
+    }
+}
+
+fn push_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_varint(bytes: &mut &[u8]) -> Option<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = bytes.split_first()?;
+        *bytes = rest;
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+// append one pair to a key-value payload. keys longer than 255 bytes are cut off
+pub fn encode_pair(out: &mut Vec<u8>, key: &str, value: &Value) {
+    let key = &key.as_bytes()[..key.len().min(u8::MAX as usize)];
+    out.push(key.len() as u8);
+    out.extend_from_slice(key);
+    match value {
+        Value::Str(v) => {
+            out.push(TYPE_STR);
+            push_varint(out, v.len() as u64);
+            out.extend_from_slice(v.as_bytes());
+        }
+        Value::Int(v) => {
+            out.push(TYPE_INT);
+            push_varint(out, ((v << 1) ^ (v >> 63)) as u64);
+        }
+        Value::Float(v) => {
+            out.push(TYPE_FLOAT);
+            out.extend_from_slice(&v.to_le_bytes());
+        }
+        Value::Bool(false) => out.push(TYPE_FALSE),
+        Value::Bool(true) => out.push(TYPE_TRUE),
+    }
+}
+
+// split a key-value payload back into its pairs, None if it's malformed
+pub fn decode(mut payload: &[u8]) -> Option<Vec<(String, Value<'static>)>> {
+    let mut pairs = Vec::new();
+    while let Some((&key_len, rest)) = payload.split_first() {
+        if rest.len() < key_len as usize {
+            return None;
+        }
+        let (key, rest) = rest.split_at(key_len as usize);
+        let key = String::from_utf8_lossy(key).into_owned();
+        let (&ty, mut rest) = rest.split_first()?;
+        let value = match ty {
+            TYPE_STR => {
+                let len = read_varint(&mut rest)? as usize;
+                if rest.len() < len {
+                    return None;
+                }
+                let (v, tail) = rest.split_at(len);
+                rest = tail;
+                Value::Str(Cow::Owned(String::from_utf8_lossy(v).into_owned()))
+            }
+            TYPE_INT => {
+                let v = read_varint(&mut rest)?;
+                Value::Int((v >> 1) as i64 ^ -((v & 1) as i64))
+            }
+            TYPE_FLOAT => {
+                let (v, tail) = rest.split_first_chunk::<8>()?;
+                rest = tail;
+                Value::Float(f64::from_le_bytes(*v))
+            }
+            TYPE_FALSE => Value::Bool(false),
+            TYPE_TRUE => Value::Bool(true),
+            _ => return None,
+        };
+        pairs.push((key, value));
+        payload = rest;
+    }
+    Some(pairs)
+}
+
+impl Frame {
+    // add a typed pair, turns the frame into a key-value frame:
+    // `Frame::default().kv("temp", 71.2).kv("state", "run")`. a payload that isn't
+    // pairs already is replaced
+    pub fn kv<'a>(mut self, key: &str, value: impl Into<Value<'a>>) -> Self {
+        if self.kind != Some(FrameKind::KeyValue) {
+            self.payload.clear();
+        }
+        self.kind = Some(FrameKind::KeyValue);
+        encode_pair(&mut self.payload, key, &value.into());
+        self
+    }
+
+    // the pairs of a key-value frame, None for other kinds or a malformed payload
+    pub fn pairs(&self) -> Option<Vec<(String, Value<'static>)>> {
+        if self.kind != Some(FrameKind::KeyValue) {
+            return None;
+        }
+        decode(&self.payload)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+
+    use super::*;
+
+    #[test]
+    fn pairs_replace_a_payload_of_another_kind() {
+        let frame = Frame::new(b"text").kv("a", 1u8);
+        let pairs = frame.pairs().unwrap();
+        assert_eq!(pairs.len(), 1);
+        assert_eq!(pairs[0].0, "a");
+    }
+
+    #[test]
+    fn pairs_keep_their_types() {
+        let frame = Frame::default()
+            .kv("temp", 71.5)
+            .kv("state", "run")
+            .kv("delta", -300i32)
+            .kv("big", i64::MIN)
+            .kv("ok", true);
+        let pairs = frame.pairs().unwrap();
+        let expected = [
+            ("temp", Value::Float(71.5)),
+            ("state", Value::from("run")),
+            ("delta", Value::Int(-300)),
+            ("big", Value::Int(i64::MIN)),
+            ("ok", Value::Bool(true)),
+        ];
+        assert_eq!(pairs.len(), expected.len());
+        for ((key, value), (expected_key, expected_value)) in pairs.iter().zip(&expected) {
+            assert_eq!(key, expected_key);
+            assert_eq!(value, expected_value);
+        }
+        assert_eq!(pairs[2].1.to_string(), "-300");
+    }
+
+    #[test]
+    fn malformed_payloads_and_other_kinds_give_none() {
+        let frame = Frame::default().kv("name", "pump");
+        for len in 1..frame.payload.len() {
+            assert!(decode(&frame.payload[..len]).is_none(), "cut at {}", len);
+        }
+        assert!(decode(&[1, b'k', 9]).is_none());
+        assert!(Frame::new(b"plain").pairs().is_none());
+    }
+
+    #[test]
+    fn long_keys_are_cut_off() {
+        let key = "k".repeat(300);
+        let pairs = Frame::default().kv(&key, 1u8).pairs().unwrap();
+        assert_eq!(pairs[0].0.len(), 255);
+    }
+}
@@ -0,0 +1,134 @@
+// keep only the newest frame per key, for status telemetry where a periodic flush should
+// send the current state rather than every update since the last one. the key is the
+// frame's tag:
+//
+//     ring.keep_latest(BATTERY);
+//     ring.keep_latest(TEMPERATURE);
+//     ...
+//     ring.log(Frame::new(&status).with_tag(BATTERY));
+//
+// a frame with a newer one of the same tag behind it when the flush starts is
+// superseded. it stays stored until the flush_to family gets to it and drops it (counted
+// as skipped), the other ways of consuming still see every frame. tags not kept latest
+// are unaffected
+
+use crate::frame::{Frame, FrameResult};
+use crate::RingBuffer;
+
+impl RingBuffer {
+    pub fn keep_latest(&mut self, tag: u16) {
+        self.latest_only.insert(tag);
+    }
+
+    pub fn keep_all(&mut self, tag: u16) {
+        self.latest_only.remove(&tag);
+    }
+
+    // note where the newest frame of each kept-latest tag is, once at the start of a
+    // flush. positions count from where `consumed` does, taking frames out anywhere
+    // during the flush doesn't reorder what's left
+    pub(crate) fn find_newest_latest(&mut self) {
+        self.newest_latest.clear();
+        if self.latest_only.is_empty() {
+            return;
+        }
+        let mut offset = 0;
+        for bytes in self.frame_bytes() {
+            if let FrameResult::Ok(frame) = Frame::decode(&bytes) {
+                if let Some(tag) = frame.tag.filter(|tag| self.latest_only.contains(tag)) {
+                    self.newest_latest
+                        .insert(tag, self.consumed + offset as u64);
+                }
+            }
+            offset += bytes.len() + 1;
+        }
+    }
+
+    // whether `frame`, stored at `offset` from the tail, has a newer frame of the same
+    // kept-latest tag behind it
+    pub(crate) fn is_superseded(&self, frame: &Frame, offset: usize) -> bool {
+        let position = self.consumed + offset as u64;
+        frame
+            .tag
+            .and_then(|tag| self.newest_latest.get(&tag))
+            .is_some_and(|&newest| newest > position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sink::{FlushToResult, MemorySink};
+
+    const BATTERY: u16 = 1;
+    const TEMPERATURE: u16 = 2;
+
+    fn status(tag: u16, value: u8) -> Frame {
+        Frame::new(&[value]).with_tag(tag)
+    }
+
+    #[test]
+    fn flushing_sends_only_the_newest_frame_per_tag() {
+        let mut ring = RingBuffer::new(256);
+        ring.keep_latest(BATTERY);
+        ring.keep_latest(TEMPERATURE);
+        for (tag, value) in [(BATTERY, 1), (TEMPERATURE, 2), (BATTERY, 3), (9, 4), (9, 5)] {
+            let _ = ring.log(status(tag, value));
+        }
+        // a half logged frame doesn't supersede anything yet
+        ring.push_slice(&status(TEMPERATURE, 6).encode()[..3]);
+
+        let mut sink = MemorySink::new();
+        let FlushToResult::Ok(stats) = ring.flush_all_to(&mut sink) else {
+            panic!("flush failed");
+        };
+        assert_eq!(stats.skipped, 1);
+        let values: Vec<u8> = sink.take().iter().map(|frame| frame.payload[0]).collect();
+        assert_eq!(values, [2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn popping_still_sees_every_frame() {
+        let mut ring = RingBuffer::new(256);
+        ring.keep_latest(BATTERY);
+        let _ = ring.log(status(BATTERY, 1));
+        let _ = ring.log(status(BATTERY, 2));
+        for value in [1, 2] {
+            match ring.flush_frame() {
+                FrameResult::Ok(frame) => assert_eq!(frame.payload, [value]),
+                FrameResult::Err(e) => panic!("{e}"),
+            }
+        }
+    }
+
+    #[test]
+    fn keep_all_turns_it_off_again() {
+        let mut ring = RingBuffer::new(256);
+        ring.keep_latest(BATTERY);
+        ring.keep_all(BATTERY);
+        let _ = ring.log(status(BATTERY, 1));
+        let _ = ring.log(status(BATTERY, 2));
+        let mut sink = MemorySink::new();
+        let _ = ring.flush_all_to(&mut sink);
+        assert_eq!(sink.frames().len(), 2);
+    }
+
+    #[test]
+    fn frames_leaving_out_of_order_keep_the_newest() {
+        let mut ring = RingBuffer::new(256);
+        ring.keep_latest(BATTERY);
+        ring.set_priority_flush(true);
+        let _ = ring.log(status(BATTERY, 1));
+        let _ = ring.log(Frame::new(&[2]).with_level(crate::level::Level::Error));
+        let _ = ring.log(status(BATTERY, 3));
+
+        // the error goes first, out of the middle of the stored frames
+        let mut sink = MemorySink::new();
+        let FlushToResult::Ok(stats) = ring.flush_all_to(&mut sink) else {
+            panic!("flush failed");
+        };
+        assert_eq!(stats.skipped, 1);
+        let values: Vec<u8> = sink.take().iter().map(|frame| frame.payload[0]).collect();
+        assert_eq!(values, [2, 3]);
+    }
+}
@@ -0,0 +1,97 @@
+// log levels, most severe first so `level <= max_level` means enabled
+
+use core::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(u8)]
+pub enum Level {
+    Error = 1,
+    Warn = 2,
+    Info = 3,
+    Debug = 4,
+    Trace = 5,
+}
+
+impl Level {
+    pub fn from_u8(value: u8) -> Option<Level> {
+        match value {
+            1 => Some(Level::Error),
+            2 => Some(Level::Warn),
+            3 => Some(Level::Info),
+            4 => Some(Level::Debug),
+            5 => Some(Level::Trace),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Level::Error => "ERROR",
+            Level::Warn => "WARN",
+            Level::Info => "INFO",
+            Level::Debug => "DEBUG",
+            Level::Trace => "TRACE",
+        }
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::format;
+
+    use super::*;
+    use crate::frame::{Frame, FrameResult};
+    use crate::{PushResult, RingBuffer};
+
+    #[test]
+    fn levels_round_trip_through_their_byte() {
+        for level in [
+            Level::Error,
+            Level::Warn,
+            Level::Info,
+            Level::Debug,
+            Level::Trace,
+        ] {
+            assert_eq!(Level::from_u8(level as u8), Some(level));
+        }
+        assert_eq!(Level::from_u8(0), None);
+        assert_eq!(Level::from_u8(6), None);
+        assert_eq!(format!("[{:<5}]", Level::Warn), "[WARN ]");
+    }
+
+    #[test]
+    fn filtered_messages_take_no_space() {
+        let mut ring = RingBuffer::new(64);
+        ring.set_max_level(Level::Warn);
+        assert!(ring.level_enabled(Level::Error));
+        assert!(!ring.level_enabled(Level::Info));
+
+        assert!(matches!(
+            ring.log_with_level(Level::Debug, b"noise"),
+            PushResult::Err(_)
+        ));
+        assert!(ring.is_empty());
+        assert!(matches!(
+            ring.log_with_level(Level::Error, b"fault"),
+            PushResult::Ok
+        ));
+        // frames without a level aren't filtered
+        assert!(matches!(ring.log(Frame::new(b"plain")), PushResult::Ok));
+
+        let FrameResult::Ok(frame) = ring.flush_frame() else {
+            panic!("corrupt frame");
+        };
+        assert_eq!(frame.level, Some(Level::Error));
+        assert_eq!(frame.payload, b"fault");
+        let FrameResult::Ok(frame) = ring.flush_frame() else {
+            panic!("corrupt frame");
+        };
+        assert_eq!(frame.level, None);
+    }
+}
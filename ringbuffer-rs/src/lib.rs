@@ -0,0 +1,1102 @@
+// ringbuffer implementation in Rust
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+pub mod age;
+pub mod broadcast;
+#[cfg(feature = "cbor")]
+pub mod cbor;
+pub mod channel;
+#[cfg(feature = "std")]
+pub mod chrome;
+pub mod clock;
+#[cfg(feature = "codec")]
+pub mod codec;
+pub mod decoder;
+pub mod dedup;
+#[cfg(feature = "defmt")]
+pub mod defmt_logger;
+#[cfg(feature = "embedded-dma")]
+pub mod dma;
+pub mod duplex;
+#[cfg(feature = "embedded-io")]
+pub mod eio;
+#[cfg(feature = "std")]
+pub mod encoder;
+#[cfg(feature = "std")]
+pub mod export;
+pub mod fault;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod flight;
+pub mod frame;
+#[cfg(feature = "std")]
+pub mod global;
+pub mod grant;
+pub mod heartbeat;
+pub mod hooks;
+#[cfg(feature = "std")]
+pub mod influx;
+pub mod interned;
+#[cfg(all(feature = "debug-assert-invariants", debug_assertions))]
+mod invariants;
+#[cfg(feature = "std")]
+pub mod io;
+#[cfg(feature = "itm")]
+pub mod itm;
+#[cfg(all(feature = "journald", target_os = "linux"))]
+pub mod journald;
+pub mod json;
+pub mod kind;
+pub mod kv;
+pub mod latest;
+pub mod level;
+#[cfg(feature = "std")]
+pub mod logfile;
+#[cfg(feature = "log")]
+pub mod logger;
+#[cfg(feature = "mmap")]
+pub mod mmap;
+#[cfg(feature = "mqtt")]
+pub mod mqtt;
+#[cfg(feature = "nrf")]
+pub mod nrf;
+#[cfg(feature = "opentelemetry")]
+pub mod otel;
+pub mod pacing;
+pub mod panic;
+#[cfg(feature = "std")]
+pub mod pcap;
+#[cfg(feature = "async")]
+pub mod pipe;
+pub mod policy;
+pub mod priority;
+#[cfg(feature = "prometheus")]
+pub mod prometheus;
+#[cfg(feature = "protobuf")]
+pub mod protobuf;
+#[cfg(feature = "python")]
+pub mod python;
+pub mod ratelimit;
+pub mod reliable;
+pub mod replay;
+pub mod retained;
+#[cfg(feature = "rpmsg")]
+pub mod rpmsg;
+#[cfg(feature = "rtt")]
+pub mod rtt;
+pub mod sampling;
+pub mod segments;
+#[cfg(feature = "semihosting")]
+pub mod semihosting;
+pub mod sequence;
+#[cfg(feature = "serial")]
+pub mod serial;
+#[cfg(feature = "std")]
+pub mod shared;
+pub mod shutdown;
+pub mod sink;
+pub mod snapshot;
+#[cfg(feature = "std")]
+pub mod spill;
+#[cfg(feature = "sse")]
+pub mod sse;
+#[cfg(feature = "serde")]
+mod state;
+mod stats;
+#[cfg(feature = "stm32")]
+pub mod stm32;
+#[cfg(feature = "std")]
+pub mod syslog;
+#[cfg(feature = "std")]
+pub mod tcp;
+pub mod tiered;
+#[cfg(feature = "tracing")]
+pub mod tracing_layer;
+pub mod ttl;
+#[cfg(any(feature = "embedded-io", feature = "embedded-hal"))]
+pub mod uart;
+#[cfg(feature = "std")]
+pub mod udp;
+#[cfg(all(feature = "std", unix))]
+pub mod unix;
+#[cfg(any(feature = "postcard", feature = "bincode"))]
+pub mod value;
+pub mod verify;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(target_has_atomic = "ptr")]
+pub mod watch;
+pub mod watermark;
+#[cfg(feature = "websocket")]
+pub mod websocket;
+
+use alloc::boxed::Box;
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::sync::atomic::{fence, Ordering};
+
+use priority::rank;
+use retained::Storage;
+use sampling::Sampled;
+
+pub use broadcast::{Filter, Lag, ReaderId, SlowReader};
+pub use channel::{Channel, Demux};
+#[cfg(feature = "std")]
+pub use chrome::ChromeTrace;
+pub use clock::Clock;
+#[cfg(feature = "std")]
+pub use clock::SystemClock;
+#[cfg(feature = "codec")]
+pub use codec::FrameCodec;
+pub use decoder::{FrameDecoder, ItmUnpacker};
+#[cfg(feature = "embedded-dma")]
+pub use dma::{CacheOps, DmaDoubleBuffer, DmaReadGrant, DmaTransfer};
+pub use duplex::{CommandDispatcher, Duplex};
+#[cfg(feature = "std")]
+pub use encoder::FrameEncoder;
+#[cfg(feature = "std")]
+pub use export::{CsvColumn, CsvExporter};
+pub use fault::FaultInfo;
+pub use flight::{FlightRecorder, Recent};
+pub use frame::{Dropped, Frame, FrameResult};
+pub use grant::{ReadView, WriteGrant};
+pub use hooks::Hooks;
+#[cfg(feature = "std")]
+pub use influx::{InfluxSink, LineFormatter};
+pub use interned::{Arg, RehydrateResult, StringTable, TableResult};
+#[cfg(feature = "itm")]
+pub use itm::ItmSink;
+#[cfg(all(feature = "journald", target_os = "linux"))]
+pub use journald::JournaldSink;
+pub use kind::{Dispatcher, FrameKind};
+pub use kv::Value;
+pub use level::Level;
+#[cfg(feature = "std")]
+pub use logfile::{CircularFile, SyncPolicy};
+#[cfg(feature = "log")]
+pub use logger::RingBufferLogger;
+#[cfg(feature = "mmap")]
+pub use mmap::{MmapRingBuffer, Opened};
+#[cfg(feature = "mqtt")]
+pub use mqtt::{MqttPayload, MqttSink};
+#[cfg(feature = "opentelemetry")]
+pub use otel::OtelSink;
+pub use pacing::TokenBucket;
+#[cfg(feature = "std")]
+pub use panic::register_panic_hook;
+#[cfg(feature = "std")]
+pub use pcap::PcapSink;
+#[cfg(feature = "async")]
+pub use pipe::{pipe, AsyncConsumer, AsyncProducer};
+pub use policy::DropPolicy;
+#[cfg(feature = "prometheus")]
+pub use prometheus::MetricsServer;
+#[cfg(feature = "protobuf")]
+pub use protobuf::ProtoDispatcher;
+pub use retained::RecoverResult;
+#[cfg(feature = "macros")]
+pub use ringbuffer_macros::ring_log;
+#[cfg(all(feature = "rpmsg", feature = "std"))]
+pub use rpmsg::RpmsgSource;
+#[cfg(feature = "rpmsg")]
+pub use rpmsg::{RpmsgEndpoint, RpmsgSink};
+#[cfg(feature = "rtt")]
+pub use rtt::RttSink;
+pub use sampling::Sampling;
+pub use segments::SegmentedRing;
+#[cfg(feature = "semihosting")]
+pub use semihosting::SemihostingSink;
+pub use sequence::{Gap, SequenceTracker};
+#[cfg(feature = "serial")]
+pub use serial::SerialSink;
+#[cfg(feature = "std")]
+pub use shared::{Follower, SharedRing};
+#[cfg(feature = "std")]
+pub use sink::{FileSink, StdoutSink, WriterSink};
+pub use sink::{FlushSink, FlushStats, FlushToResult, MemorySink, SinkResult, SwitchSink};
+pub use snapshot::RestoreResult;
+#[cfg(feature = "sse")]
+pub use sse::SseSink;
+pub use stats::{LatencyHistogram, Stats};
+#[cfg(feature = "std")]
+pub use syslog::{SyslogFormatter, SyslogSink};
+#[cfg(feature = "std")]
+pub use tcp::TcpSink;
+pub use tiered::TieredRing;
+#[cfg(feature = "tracing")]
+pub use tracing_layer::RingBufferLayer;
+#[cfg(feature = "embedded-hal")]
+pub use uart::NbUartSink;
+#[cfg(feature = "embedded-io")]
+pub use uart::UartSink;
+#[cfg(feature = "std")]
+pub use udp::UdpSink;
+#[cfg(all(feature = "std", unix))]
+pub use unix::{UnixSink, UnixSource};
+#[cfg(any(feature = "postcard", feature = "bincode"))]
+pub use value::{ValueCodec, ValueResult};
+pub use verify::IntegrityReport;
+#[cfg(target_has_atomic = "ptr")]
+pub use watch::{watch, WatchConsumer, WatchProducer};
+#[cfg(feature = "websocket")]
+pub use websocket::WebSocketSink;
+
+pub struct RingBuffer {
+    buffer: Storage,
+    head: usize,
+    tail: usize,
+    size: usize,
+    max_flush_size: usize,
+    clock: Option<Box<dyn Clock + Send>>,
+    #[cfg(feature = "embedded-dma")]
+    cache: Option<Box<dyn dma::CacheOps + Send>>,
+    next_seq: Option<u16>,
+    dropped: Dropped,
+    max_level: Level,
+    counters: Stats,
+    volatile: bool,
+    frozen: bool,
+    emergency: Option<Box<dyn FlushSink + Send>>,
+    hooks: Option<Box<dyn Hooks + Send>>,
+    watermarks: Option<watermark::Watermarks>,
+    bucket: Option<pacing::TokenBucket>,
+    rate_limits: ratelimit::RateLimits,
+    samplers: BTreeMap<Option<u16>, sampling::Sampler>,
+    priority_flush: bool,
+    policy: DropPolicy,
+    dedup: Option<dedup::Dedup>,
+    heartbeat: Option<heartbeat::Heartbeat>,
+    // tags only the newest frame of is kept, see `latest`
+    latest_only: BTreeSet<u16>,
+    // where the newest frame of each of them was at the start of the flush
+    newest_latest: BTreeMap<u16, u64>,
+    // bytes from the tail on sent and not acknowledged yet, when acks are on
+    sent: Option<usize>,
+    window: Option<reliable::Window>,
+    // bytes ever taken out from the tail, where readers' positions count from
+    consumed: u64,
+    readers: BTreeMap<u32, broadcast::Reader>,
+    next_reader: u32,
+    // stored frames stay until every reader read them, see `broadcast`
+    reader_retention: bool,
+    #[cfg(feature = "std")]
+    spill: Option<spill::Spill>,
+}
+
+pub enum PushResult {
+    Ok,
+    Err(String),
+}
+
+pub enum FlushResult {
+    Ok(Vec<u8>),
+    Err(String),
+}
+
+impl RingBuffer {
+    pub fn new(size: usize) -> Self {
+        Self::with_storage(Storage::Heap(vec![0; size]))
+    }
+
+    fn with_storage(buffer: Storage) -> Self {
+        let size = buffer.len();
+        RingBuffer {
+            buffer,
+            head: 0,
+            tail: 0,
+            size,
+            max_flush_size: 32,
+            clock: None,
+            #[cfg(feature = "embedded-dma")]
+            cache: None,
+            next_seq: None,
+            dropped: Dropped::default(),
+            max_level: Level::Trace,
+            counters: Stats::default(),
+            volatile: false,
+            frozen: false,
+            emergency: None,
+            hooks: None,
+            watermarks: None,
+            bucket: None,
+            rate_limits: ratelimit::RateLimits::default(),
+            samplers: BTreeMap::new(),
+            priority_flush: false,
+            policy: DropPolicy::Reject,
+            dedup: None,
+            heartbeat: None,
+            latest_only: BTreeSet::new(),
+            newest_latest: BTreeMap::new(),
+            sent: None,
+            window: None,
+            consumed: 0,
+            readers: BTreeMap::new(),
+            next_reader: 0,
+            reader_retention: false,
+            #[cfg(feature = "std")]
+            spill: None,
+        }
+    }
+
+    // stamp every logged frame with the time from `clock`
+    pub fn set_clock(&mut self, clock: impl Clock + Send + 'static) {
+        self.clock = Some(Box::new(clock));
+    }
+
+    // stamp every logged frame with a wrapping sequence number. the counter also
+    // advances for frames rejected on a full buffer so the consumer can see the gap
+    pub fn enable_sequence_numbers(&mut self) {
+        self.next_seq.get_or_insert(0);
+    }
+
+    // byte budget for one `dma_flush_with_crc_check` / `flush_to` call
+    pub fn set_max_flush_size(&mut self, max_flush_size: usize) {
+        self.max_flush_size = max_flush_size;
+    }
+
+    pub fn max_flush_size(&self) -> usize {
+        self.max_flush_size
+    }
+
+    // drop messages less severe than `level` before they take up any space
+    pub fn set_max_level(&mut self, level: Level) {
+        self.max_level = level;
+    }
+
+    pub fn max_level(&self) -> Level {
+        self.max_level
+    }
+
+    // lets callers skip formatting a message that would be filtered anyway
+    pub fn level_enabled(&self, level: Level) -> bool {
+        level <= self.max_level
+    }
+
+    // producer handle bound to one channel, e.g. `ring.channel(2).log(b"...")`
+    pub fn channel(&mut self, id: u8) -> Channel<'_> {
+        Channel::new(self, id)
+    }
+
+    // go through read_volatile / write_volatile for every byte of storage, with fences
+    // before publishing and after observing data. for storage a dma engine or another
+    // core writes into directly. the borrowed views (read_view, write_grant, BufRead, dma
+    // transfers) hand out plain slices and bypass this
+    pub fn set_volatile(&mut self, volatile: bool) {
+        self.volatile = volatile;
+    }
+
+    fn byte_at(&self, index: usize) -> u8 {
+        match self.volatile {
+            true => unsafe { core::ptr::read_volatile(&self.buffer[index]) },
+            false => self.buffer[index],
+        }
+    }
+
+    fn set_byte_at(&mut self, index: usize, value: u8) {
+        match self.volatile {
+            true => unsafe { core::ptr::write_volatile(&mut self.buffer[index], value) },
+            false => self.buffer[index] = value,
+        }
+    }
+
+    // every index update goes through these so retained storage keeps its copy current
+    fn set_head(&mut self, head: usize) {
+        self.head = head;
+        self.buffer.store_indices(self.head, self.tail);
+        #[cfg(all(feature = "debug-assert-invariants", debug_assertions))]
+        self.assert_invariants();
+    }
+
+    fn set_tail(&mut self, tail: usize) {
+        self.tail = tail;
+        self.buffer.store_indices(self.head, self.tail);
+        #[cfg(all(feature = "debug-assert-invariants", debug_assertions))]
+        self.assert_invariants();
+    }
+
+    // move past `count` bytes just written / read
+    fn advance_head(&mut self, count: usize) {
+        self.counters.pushed_bytes += count as u64;
+        self.set_head((self.head + count) % self.size);
+    }
+
+    fn advance_tail(&mut self, count: usize) {
+        self.counters.popped_bytes += count as u64;
+        self.pass_readers(count);
+        self.consumed += count as u64;
+        if let Some(sent) = &mut self.sent {
+            *sent = sent.saturating_sub(count);
+        }
+        self.set_tail((self.tail + count) % self.size);
+        self.check_watermarks();
+    }
+
+    pub fn stats(&self) -> Stats {
+        self.counters
+    }
+
+    // start counting from zero, the high water mark from what's stored right now
+    pub fn reset_stats(&mut self) {
+        self.counters = Stats {
+            high_water: self.len(),
+            ..Stats::default()
+        };
+    }
+
+    // the `offset`th stored byte counting from the tail
+    fn stored_byte(&self, offset: usize) -> u8 {
+        self.byte_at((self.tail + offset) % self.size)
+    }
+
+    fn fence(&self, ordering: Ordering) {
+        if self.volatile {
+            fence(ordering);
+        }
+    }
+
+    pub fn push(&mut self, item: u8) -> PushResult {
+        if self.is_full() && self.free_bytes_by_policy(1) == 0 {
+            return PushResult::Err("Buffer is full".to_string());
+        }
+
+        self.set_byte_at(self.head, item);
+        self.fence(Ordering::Release);
+        self.advance_head(1);
+        self.note_occupancy();
+        PushResult::Ok
+    }
+
+    pub fn pop(&mut self) -> Option<u8> {
+        if self.head == self.tail {
+            None
+        } else {
+            self.fence(Ordering::Acquire);
+            let item = self.byte_at(self.tail);
+            self.advance_tail(1);
+            Some(item)
+        }
+    }
+
+    // push as much of `items` as fits (after making room as the drop policy says),
+    // returns how many bytes went in
+    pub fn push_slice(&mut self, items: &[u8]) -> usize {
+        let count = items.len().min(self.free_bytes_by_policy(items.len()));
+        if self.volatile {
+            for (i, &item) in items[..count].iter().enumerate() {
+                self.set_byte_at((self.head + i) % self.size, item);
+            }
+            fence(Ordering::Release);
+        } else {
+            let first = count.min(self.size - self.head);
+            self.buffer[self.head..self.head + first].copy_from_slice(&items[..first]);
+            self.buffer[..count - first].copy_from_slice(&items[first..count]);
+        }
+        self.advance_head(count);
+        self.note_occupancy();
+        count
+    }
+
+    // pop up to `out.len()` bytes, returns how many were copied
+    pub fn pop_slice(&mut self, out: &mut [u8]) -> usize {
+        let count = out.len().min(self.len());
+        if self.volatile {
+            fence(Ordering::Acquire);
+            for (i, byte) in out[..count].iter_mut().enumerate() {
+                *byte = self.stored_byte(i);
+            }
+        } else {
+            let first = count.min(self.size - self.tail);
+            out[..first].copy_from_slice(&self.buffer[self.tail..self.tail + first]);
+            out[first..count].copy_from_slice(&self.buffer[..count - first]);
+        }
+        self.advance_tail(count);
+        count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.head == self.tail
+    }
+
+    pub fn is_full(&self) -> bool {
+        (self.head + 1) % self.size == self.tail
+    }
+
+    // number of bytes currently stored
+    pub fn len(&self) -> usize {
+        (self.head + self.size - self.tail) % self.size
+    }
+
+    // number of bytes that can still be pushed (one slot is kept open to tell full from empty)
+    pub fn free(&self) -> usize {
+        self.size - 1 - self.len()
+    }
+
+    fn note_occupancy(&mut self) {
+        self.counters.high_water = self.counters.high_water.max(self.len());
+        self.check_watermarks();
+    }
+
+    // bytes from the tail up to and including the last terminator, i.e. everything that
+    // belongs to complete frames
+    fn complete_len(&self) -> usize {
+        (1..=self.len())
+            .rev()
+            .find(|&n| self.stored_byte(n - 1) == frame::TERMINATOR)
+            .unwrap_or(0)
+    }
+
+    // get the size of the next message in the buffer
+    fn get_next_message_size(&self) -> Option<usize> {
+        self.fence(Ordering::Acquire);
+        for (size, i) in (0..self.len()).enumerate() {
+            if self.stored_byte(i) == frame::TERMINATOR {
+                return Some(size);
+            }
+        }
+        None
+    }
+
+    pub fn log_message_with_crc(&mut self, message: &[u8]) -> PushResult {
+        self.log(Frame::new(message))
+    }
+
+    pub fn log_with_level(&mut self, level: Level, message: &[u8]) -> PushResult {
+        self.log(Frame::new(message).with_level(level))
+    }
+
+    // log a format string by id, the host turns it back into text with a `StringTable`
+    pub fn log_interned(&mut self, id: u16, args: &[Arg]) -> PushResult {
+        self.log(Frame::new(&interned::encode(id, args)).with_kind(FrameKind::Interned))
+    }
+
+    // apply the level filter, fill in the timestamp and sequence number, then log
+    pub fn log(&mut self, mut frame: Frame) -> PushResult {
+        if let PushResult::Err(e) = self.stamp(&mut frame) {
+            return PushResult::Err(e);
+        }
+        self.log_frame(&frame)
+    }
+
+    fn stamp(&mut self, frame: &mut Frame) -> PushResult {
+        if let Some(level) = frame.level {
+            if !self.level_enabled(level) {
+                return PushResult::Err("Message filtered by log level".to_string());
+            }
+        }
+        if let Some(clock) = &self.clock {
+            frame.timestamp = Some(clock.now());
+        }
+        // sampled frames are numbered once they're kept and repeats not at all, the ones
+        // thrown away aren't a gap
+        if !self.is_sampled(frame) && !self.repeats_last(frame) {
+            self.sequence(frame);
+        }
+        PushResult::Ok
+    }
+
+    fn sequence(&mut self, frame: &mut Frame) {
+        if let Some(seq) = self.next_seq {
+            frame.seq = Some(seq);
+            self.next_seq = Some(seq.wrapping_add(1));
+        }
+    }
+
+    pub fn log_frame(&mut self, frame: &Frame) -> PushResult {
+        match self.sample(frame) {
+            Sampled::Keep if self.is_sampled(frame) => {
+                let mut kept = frame.clone();
+                self.sequence_sampled(&mut kept);
+                self.store_frame(&kept)
+            }
+            Sampled::Keep => self.store_frame(frame),
+            Sampled::Skip => PushResult::Ok,
+            Sampled::Window(frames) => {
+                for mut frame in frames {
+                    self.sequence_sampled(&mut frame);
+                    if let PushResult::Err(e) = self.store_frame(&frame) {
+                        return PushResult::Err(e);
+                    }
+                }
+                PushResult::Ok
+            }
+        }
+    }
+
+    // with a spill file, that takes what doesn't fit instead of the drop policy
+    fn spilling(&self) -> bool {
+        #[cfg(feature = "std")]
+        return self.spill.is_some();
+        #[cfg(not(feature = "std"))]
+        false
+    }
+
+    fn store_frame(&mut self, frame: &Frame) -> PushResult {
+        if self.frozen {
+            return PushResult::Err("Buffer is frozen".to_string());
+        }
+        // only log complete frames, never leave half a message behind
+        let bytes = frame.encode();
+        if self.is_repeat(frame, bytes.len()) {
+            return PushResult::Ok;
+        }
+        if self.rate_limited(frame, bytes.len()) {
+            return PushResult::Err("Message rate limited".to_string());
+        }
+
+        let mut marker = self.markers(frame);
+        let mut fits = marker.len() + bytes.len() <= self.free();
+        if !fits && !self.spilling() {
+            // what the policy throws out goes into the dropped marker, which can take
+            // more room in turn
+            while !fits && self.make_room_by_policy(frame, marker.len() + bytes.len()) {
+                marker = self.markers(frame);
+                fits = marker.len() + bytes.len() <= self.free();
+            }
+        }
+        // while frames wait in the spill file everything queues up behind them
+        #[cfg(feature = "std")]
+        if let Some(spill) = &mut self.spill {
+            if (!fits || !spill.is_empty()) && bytes.len() < self.size {
+                if spill.write(&[&marker, &bytes]).is_ok() {
+                    self.dropped = Dropped::default();
+                    self.clear_suppressed(frame);
+                    self.note_logged(frame);
+                    self.note_activity(frame.timestamp);
+                    self.counters.logged_frames += 1;
+                    return PushResult::Ok;
+                }
+                self.note_overflow(bytes.len());
+                return PushResult::Err("Error spilling message".to_string());
+            }
+        }
+
+        if !fits {
+            self.note_overflow(bytes.len());
+            return PushResult::Err("Error logging message".to_string());
+        }
+        for byte in marker.into_iter().chain(bytes) {
+            self.push(byte);
+        }
+        self.dropped = Dropped::default();
+        self.clear_suppressed(frame);
+        self.note_logged(frame);
+        self.note_activity(frame.timestamp);
+        self.counters.logged_frames += 1;
+        PushResult::Ok
+    }
+
+    // the frames that go in front of `frame`: a dropped marker when messages were lost
+    // since the last successful log, then pending repeat and suppression counts
+    fn markers(&self, frame: &Frame) -> Vec<u8> {
+        let mut marker = Vec::new();
+        if self.dropped.messages > 0 {
+            let mut dropped = Frame::dropped_marker(self.dropped);
+            dropped.timestamp = frame.timestamp;
+            marker = dropped.encode();
+        }
+        if let Some(repeats) = self.pending_repeats(frame) {
+            marker.extend_from_slice(&repeats.encode());
+        }
+        if let Some(suppressed) = self.pending_suppression(frame) {
+            marker.extend_from_slice(&suppressed.encode());
+        }
+        marker
+    }
+
+    // `messages` frames never made it in, the next stored frame says so
+    #[cfg(feature = "std")]
+    pub(crate) fn note_dropped(&mut self, messages: u32) {
+        self.dropped.messages = self.dropped.messages.saturating_add(messages);
+    }
+
+    // a frame of `len` encoded bytes was turned away
+    fn note_overflow(&mut self, len: usize) {
+        self.dropped.messages = self.dropped.messages.saturating_add(1);
+        self.dropped.bytes = self.dropped.bytes.saturating_add(len as u32);
+        self.counters.overflows += 1;
+        if let Some(hooks) = &mut self.hooks {
+            hooks.on_overflow(len, self.dropped);
+        }
+    }
+
+    // escaped bytes of every complete frame, oldest first, without consuming anything
+    fn frame_bytes(&self) -> Vec<Vec<u8>> {
+        let mut frames = Vec::new();
+        let mut current = Vec::new();
+        for i in 0..self.len() {
+            match self.stored_byte(i) {
+                frame::TERMINATOR => frames.push(core::mem::take(&mut current)),
+                byte => current.push(byte),
+            }
+        }
+        frames
+    }
+
+    // throw out the oldest frames until `frame` (and the dropped marker in front of it)
+    // fits, returns how many went. None when it wouldn't fit into the empty ring either,
+    // or with `DropPolicy::Evict` when only more severe frames are in the way
+    fn evict_for(&mut self, frame: &Frame) -> Option<u64> {
+        let mut len = frame.encode().len();
+        if self.dropped.messages > 0 {
+            let mut marker = Frame::dropped_marker(self.dropped);
+            marker.timestamp = frame.timestamp;
+            len += marker.encode().len();
+        }
+        match self.policy {
+            DropPolicy::Evict(level) => {
+                self.evict_ranked(len, rank(frame).max(level as u8), &mut Dropped::default())
+            }
+            _ => self.make_room(len, &mut Dropped::default()),
+        }
+    }
+
+    // throw out the oldest frames until `len` bytes are free, adding what they stood for
+    // to `lost`. returns how many went, None when that's more than the empty ring has
+    fn make_room(&mut self, len: usize, lost: &mut Dropped) -> Option<u64> {
+        if len >= self.size {
+            return None;
+        }
+        let mut evicted = 0;
+        while self.free() < len {
+            match self.get_next_message_size() {
+                Some(size) => {
+                    lost.add(self.lost_at(0, size));
+                    self.skip(size + 1);
+                    evicted += 1;
+                }
+                // only a half written frame left, which log_frame never leaves behind
+                None => {
+                    lost.add(self.lost_at(0, self.len() - 1));
+                    self.skip(self.len());
+                }
+            }
+        }
+        self.counters.overwritten_frames += evicted;
+        Some(evicted)
+    }
+
+    // what throwing out the `len` escaped bytes stored at `offset` (and the byte after
+    // them) loses: one message, or the ones a dropped marker there stood for
+    fn lost_at(&self, offset: usize, len: usize) -> Dropped {
+        let bytes: Vec<u8> = (offset..offset + len)
+            .map(|i| self.stored_byte(i))
+            .collect();
+        match Frame::decode(&bytes) {
+            FrameResult::Ok(Frame {
+                dropped: Some(dropped),
+                payload,
+                ..
+            }) if payload.is_empty() => dropped,
+            _ => Dropped {
+                messages: 1,
+                bytes: len as u32 + 1,
+            },
+        }
+    }
+
+    // copy out the escaped bytes of the next complete frame without removing it
+    fn peek_frame_bytes(&self) -> Option<Vec<u8>> {
+        let message_size = self.get_next_message_size()?;
+        let bytes = (0..message_size).map(|i| self.stored_byte(i)).collect();
+        Some(bytes)
+    }
+
+    // drop `count` bytes from the tail
+    fn skip(&mut self, count: usize) {
+        let count = count.min(self.len());
+        self.advance_tail(count);
+    }
+
+    // take the frame of `len` escaped bytes at the tail out for good, terminator and
+    // all. spilled frames move up into the space
+    fn consume_frame(&mut self, len: usize) {
+        self.skip(len + 1);
+        #[cfg(feature = "std")]
+        self.refill();
+    }
+
+    // pop the escaped bytes of the next complete frame, dropping the terminator
+    fn pop_frame_bytes(&mut self) -> Option<Vec<u8>> {
+        let bytes = self.peek_frame_bytes()?;
+        self.consume_frame(bytes.len());
+        Some(bytes)
+    }
+
+    // keep the counters up to date for a frame (its escaped `bytes`) leaving the ring
+    fn count_flushed(&mut self, bytes: &[u8], decoded: &FrameResult) {
+        match decoded {
+            FrameResult::Ok(frame) => {
+                self.counters.flushed_frames += 1;
+                self.counters.flushed_bytes += bytes.len() as u64 + 1;
+                self.note_latency(frame);
+            }
+            FrameResult::Err(e) => {
+                self.counters.crc_errors += 1;
+                if let Some(hooks) = &mut self.hooks {
+                    hooks.on_crc_error(frame::claimed_seq(bytes), e);
+                }
+            }
+        }
+    }
+
+    // how long `frame` waited in the ring, when both ends have a time
+    fn note_latency(&mut self, frame: &Frame) {
+        if let (Some(clock), Some(logged)) = (&self.clock, frame.timestamp) {
+            let age = clock.now().saturating_sub(logged);
+            self.counters.flush_latency.record(age);
+        }
+    }
+
+    // decode a frame that was just taken out of the ring
+    fn decode_flushed(&mut self, bytes: &[u8]) -> FrameResult {
+        let decoded = Frame::decode(bytes);
+        self.count_flushed(bytes, &decoded);
+        decoded
+    }
+
+    pub fn flush_frame(&mut self) -> FrameResult {
+        match self.pop_frame_bytes() {
+            Some(bytes) => self.decode_flushed(&bytes),
+            None => FrameResult::Err("No complete frame in buffer".to_string()),
+        }
+    }
+
+    // pull out every frame matching `filter`, frames that don't match stay queued in
+    // order for whoever consumes them. corrupt frames are discarded
+    pub fn flush_frames_where(&mut self, mut filter: impl FnMut(&Frame) -> bool) -> Vec<Frame> {
+        let mut matched = Vec::new();
+        let mut taken = Vec::new();
+        let mut offset = 0;
+        for bytes in self.frame_bytes() {
+            let decoded = Frame::decode(&bytes);
+            let take = match &decoded {
+                FrameResult::Ok(frame) => filter(frame),
+                FrameResult::Err(_) => true,
+            };
+            if take {
+                self.count_flushed(&bytes, &decoded);
+                if let FrameResult::Ok(frame) = decoded {
+                    matched.push(frame);
+                }
+                taken.push((offset, bytes.len()));
+            }
+            offset += bytes.len() + 1;
+        }
+
+        // newest first, taking a frame out leaves the older ones where they were
+        for (offset, len) in taken.into_iter().rev() {
+            self.consume_frame_at(offset, len);
+        }
+        matched
+    }
+
+    pub fn flush_message_with_crc_check(&mut self) -> FlushResult {
+        if self.is_empty() {
+            return FlushResult::Ok(Vec::new());
+        }
+        match self.flush_frame() {
+            FrameResult::Ok(frame) => FlushResult::Ok(frame.payload),
+            FrameResult::Err(e) => FlushResult::Err(e),
+        }
+    }
+
+    pub fn dma_flush_with_crc_check(&mut self) -> FlushResult {
+        let mut bytes_sent = 0;
+        let mut message = Vec::new();
+
+        // goal: pop COMPLETE messages until we're out of messages or we've sent max_flush_size
+        // bytes. don't forget the CRC check
+
+        while bytes_sent < self.max_flush_size && !self.is_empty() {
+            if !self.next_frame_paced(bytes_sent) {
+                break;
+            }
+            // pop the next frame, including its terminator
+            let bytes = match self.pop_frame_bytes() {
+                Some(bytes) => bytes,
+                None => break,
+            };
+
+            // check CRC-8 checksum
+            match self.decode_flushed(&bytes) {
+                FrameResult::Ok(frame) => message.extend_from_slice(&frame.payload),
+                FrameResult::Err(e) => return FlushResult::Err(e),
+            }
+            bytes_sent += bytes.len() + 1;
+        }
+        self.pay_flush(bytes_sent);
+        FlushResult::Ok(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the host has no interrupts to mask, a spin lock stands in for the critical section
+    // the defmt logger and rtt channels take
+    #[cfg(any(feature = "defmt", feature = "rtt"))]
+    mod spin_section {
+        use core::sync::atomic::{AtomicBool, Ordering};
+
+        #[cfg(not(feature = "defmt"))]
+        use rtt_target::export::critical_section;
+
+        struct SpinSection;
+
+        static LOCKED: AtomicBool = AtomicBool::new(false);
+
+        critical_section::set_impl!(SpinSection);
+
+        unsafe impl critical_section::Impl for SpinSection {
+            unsafe fn acquire() -> critical_section::RawRestoreState {
+                while LOCKED.swap(true, Ordering::Acquire) {
+                    core::hint::spin_loop();
+                }
+                // whatever restore state type the other crates in the build settled on
+                Default::default()
+            }
+
+            unsafe fn release(_restore: critical_section::RawRestoreState) {
+                LOCKED.store(false, Ordering::Release);
+            }
+        }
+    }
+
+    fn payloads(frames: &[Frame]) -> Vec<&[u8]> {
+        frames.iter().map(|frame| &frame.payload[..]).collect()
+    }
+
+    fn drain(ring: &mut RingBuffer) -> Vec<Frame> {
+        let mut frames = Vec::new();
+        while let FrameResult::Ok(frame) = ring.flush_frame() {
+            frames.push(frame);
+        }
+        frames
+    }
+
+    #[test]
+    fn flush_frames_where_leaves_the_rest_in_place() {
+        let mut ring = RingBuffer::new(128);
+        for (payload, tag) in [(b"a", 1), (b"b", 2), (b"c", 1), (b"d", 2)] {
+            let _ = ring.log(Frame::new(payload).with_tag(tag));
+        }
+        let mut corrupt = Frame::new(b"x").encode();
+        corrupt[1] ^= 0xff;
+        ring.push_slice(&corrupt);
+        // a frame still being written through the byte path
+        let partial = Frame::new(b"e").with_tag(2).encode();
+        ring.push_slice(&partial[..2]);
+
+        let taken = ring.flush_frames_where(|frame| frame.tag == Some(1));
+        assert_eq!(payloads(&taken), [b"a", b"c"]);
+        assert_eq!(ring.stats().flushed_frames, 2);
+        assert_eq!(ring.stats().crc_errors, 1);
+
+        ring.push_slice(&partial[2..]);
+        let mut rest = Vec::new();
+        while let FrameResult::Ok(frame) = ring.flush_frame() {
+            rest.push(frame);
+        }
+        assert_eq!(payloads(&rest), [b"b", b"d", b"e"]);
+        assert!(ring.is_empty());
+    }
+
+    #[test]
+    fn rejected_frames_are_reported_where_they_went_missing() {
+        let mut ring = RingBuffer::new(64);
+        let mut lost = Dropped::default();
+        for payload in [
+            b"message 1",
+            b"message 2",
+            b"message 3",
+            b"message 4",
+            b"message 5",
+            b"message 6",
+        ] {
+            let frame = Frame::new(payload);
+            if let PushResult::Err(_) = ring.log(frame.clone()) {
+                lost.add(Dropped {
+                    messages: 1,
+                    bytes: frame.encode().len() as u32,
+                });
+            }
+        }
+        assert!(lost.messages > 0);
+        let before = drain(&mut ring);
+        assert!(before.iter().all(|frame| frame.dropped.is_none()));
+
+        let _ = ring.log(Frame::new(b"after").with_timestamp(42));
+        let after = drain(&mut ring);
+        assert_eq!(after.len(), 2);
+        assert_eq!(after[0].dropped, Some(lost));
+        assert_eq!(after[0].timestamp, Some(42));
+        assert!(after[0].payload.is_empty());
+        assert_eq!(after[1].payload, b"after");
+
+        // reported once
+        let _ = ring.log(Frame::new(b"later"));
+        assert_eq!(payloads(&drain(&mut ring)), [b"later"]);
+    }
+
+    #[test]
+    fn the_marker_reads_like_a_sentence() {
+        let dropped = Dropped {
+            messages: 3,
+            bytes: 27,
+        };
+        assert_eq!(dropped.to_string(), "dropped 3 messages, 27 bytes");
+        let encoded = Frame::dropped_marker(dropped).encode();
+        let FrameResult::Ok(marker) = Frame::decode(&encoded[..encoded.len() - 1]) else {
+            panic!("marker doesn't decode");
+        };
+        assert_eq!(marker.dropped, Some(dropped));
+    }
+
+    // the same traffic, wrapping a few times, through either storage mode
+    fn traffic(volatile: bool) -> (Vec<u8>, Vec<Frame>) {
+        let mut ring = RingBuffer::new(24);
+        ring.set_volatile(volatile);
+        let mut popped = Vec::new();
+        for round in 0..5u8 {
+            ring.push_slice(&[round; 7]);
+            let _ = ring.push(round);
+            let mut out = [0; 5];
+            let count = ring.pop_slice(&mut out);
+            popped.extend_from_slice(&out[..count]);
+            popped.extend(ring.pop());
+        }
+        popped.extend(core::iter::from_fn(|| ring.pop()));
+        for payload in [b"one", b"two"] {
+            let _ = ring.log(Frame::new(payload));
+        }
+        (popped, drain(&mut ring))
+    }
+
+    #[test]
+    fn volatile_storage_behaves_like_plain_storage() {
+        let (plain_bytes, plain_frames) = traffic(false);
+        let (volatile_bytes, volatile_frames) = traffic(true);
+        assert_eq!(volatile_bytes, plain_bytes);
+        assert_eq!(plain_bytes.len(), 5 * 8);
+        assert_eq!(payloads(&volatile_frames), payloads(&plain_frames));
+        assert_eq!(payloads(&volatile_frames), [b"one", b"two"]);
+    }
+
+    #[test]
+    fn bytes_written_behind_the_ring_are_read_back() {
+        let mut ring = RingBuffer::new(16);
+        ring.set_volatile(true);
+        let encoded = Frame::new(b"dma").encode();
+        // as an engine filling the granted region would
+        let mut grant = ring.write_grant();
+        grant.writable()[..encoded.len()].copy_from_slice(&encoded);
+        grant.commit(encoded.len());
+        assert_eq!(payloads(&drain(&mut ring)), [b"dma"]);
+    }
+}
@@ -0,0 +1,703 @@
+// durable, bounded logging to a plain file: frames are appended until the size limit,
+// then writing wraps around to the start and overwrites the oldest ones.
+//
+//     let mut file = CircularFile::open("/var/log/app.ring", 4 << 20)?
+//         .with_sync_policy(SyncPolicy::EveryBytes(64 * 1024));
+//     ring.flush_to_file(&mut file);
+//     ...
+//     let frames = CircularFile::read_frames("/var/log/app.ring")?;
+//
+// the layout is built so a crash in the middle of a write never costs frames that were
+// already persisted:
+//
+// - two header slots, written alternately with a generation counter and a crc8. a torn
+//   header write leaves the other slot intact, the valid one with the higher generation
+//   wins
+// - every frame is a record `[len u16][seq u32][frame][crc8][len u16]` (little endian,
+//   the frame escaped but without its terminator). the crc8 over everything in front of
+//   it is the commit marker, a half written record fails it. the sequence numbers tell
+//   the current records from stale ones of an earlier lap
+// - two zero bytes mark where writing wrapped to the start
+//
+// version 1 files (a single header, frames in plain wire format) are still read, and
+// opening one converts it, so logs written before an update stay readable after it.
+//
+// the header holds the write position as of the last sync. opening the file walks on
+// from there over the records that made it to disk anyway and truncates at the first one
+// that didn't, readers walk back from that point along the trailing lengths for as long
+// as the sequence numbers keep counting down. that holds as long as less than a lap of
+// the file goes unsynced. the `Every` policy is checked whenever frames are written or
+// flushed, there is no timer of its own
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::frame::{self, crc8, Frame, FrameResult};
+use crate::sink::{FlushSink, FlushToResult, SinkResult};
+use crate::RingBuffer;
+
+const MAGIC: &[u8; 4] = b"RBLF";
+const VERSION: u16 = 2;
+const SLOT_LEN: u64 = 48;
+const DATA_START: u64 = 2 * SLOT_LEN;
+// [len][seq] in front of the frame, [crc8][len] behind it
+const RECORD_HEAD: usize = 6;
+const RECORD_TAIL: usize = 3;
+const RECORD_OVERHEAD: u64 = (RECORD_HEAD + RECORD_TAIL) as u64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncPolicy {
+    // fsync after every frame
+    EveryFrame,
+    // fsync once this many bytes were written since the last one
+    EveryBytes(usize),
+    // fsync when this long has passed since the last one
+    Every(Duration),
+    // leave it to the os, `sync` still works
+    Never,
+}
+
+// where writing stands: offset and sequence number of the next record, and where the
+// previous lap ended (0 before the first wrap)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Position {
+    next: u64,
+    seq: u32,
+    wrap_at: u64,
+}
+
+const START: Position = Position {
+    next: DATA_START,
+    seq: 0,
+    wrap_at: 0,
+};
+
+fn encode_slot(generation: u64, position: &Position) -> [u8; SLOT_LEN as usize] {
+    let mut slot = [0u8; SLOT_LEN as usize];
+    slot[..4].copy_from_slice(MAGIC);
+    slot[4..6].copy_from_slice(&VERSION.to_le_bytes());
+    slot[8..16].copy_from_slice(&generation.to_le_bytes());
+    slot[16..24].copy_from_slice(&position.next.to_le_bytes());
+    slot[24..28].copy_from_slice(&position.seq.to_le_bytes());
+    slot[28..36].copy_from_slice(&position.wrap_at.to_le_bytes());
+    slot[36] = crc8(&slot[..36]);
+    slot
+}
+
+fn decode_slot(slot: &[u8]) -> Option<(u64, Position)> {
+    if &slot[..4] != MAGIC || slot[4..6] != VERSION.to_le_bytes() || slot[36] != crc8(&slot[..36]) {
+        return None;
+    }
+    let u64_at = |at: usize| u64::from_le_bytes(slot[at..at + 8].try_into().unwrap());
+    let position = Position {
+        next: u64_at(16),
+        seq: u32::from_le_bytes(slot[24..28].try_into().unwrap()),
+        wrap_at: u64_at(28),
+    };
+    Some((u64_at(8), position))
+}
+
+// the valid header slot with the higher generation
+fn newest_slot(data: &[u8]) -> Option<(u64, Position)> {
+    let slot = |at: u64| {
+        data.get(at as usize..(at + SLOT_LEN) as usize)
+            .and_then(decode_slot)
+    };
+    match (slot(0), slot(SLOT_LEN)) {
+        (Some(a), Some(b)) => Some(if a.0 > b.0 { a } else { b }),
+        (a, b) => a.or(b),
+    }
+}
+
+fn encode_record(seq: u32, frame: &[u8]) -> Vec<u8> {
+    let len = (frame.len() as u16).to_le_bytes();
+    let mut record = Vec::with_capacity(frame.len() + RECORD_OVERHEAD as usize);
+    record.extend_from_slice(&len);
+    record.extend_from_slice(&seq.to_le_bytes());
+    record.extend_from_slice(frame);
+    record.push(crc8(&record));
+    record.extend_from_slice(&len);
+    record
+}
+
+// the frame in the record at `at`, if it's intact and carries `seq`
+fn record_at(data: &[u8], at: u64, seq: u32) -> Option<&[u8]> {
+    let at = at as usize;
+    let len = u16::from_le_bytes(data.get(at..at + 2)?.try_into().unwrap()) as usize;
+    let crc_at = RECORD_HEAD + len;
+    let record = data.get(at..at + crc_at + RECORD_TAIL)?;
+    let intact = len > 0
+        && record[2..6] == seq.to_le_bytes()
+        && record[crc_at] == crc8(&record[..crc_at])
+        && record[crc_at + 1..] == record[..2];
+    intact.then(|| &record[RECORD_HEAD..crc_at])
+}
+
+// follow the records written after `position` was saved, returns where writing really
+// stands: right behind the last intact one
+fn walk_forward(data: &[u8], mut position: Position) -> Position {
+    loop {
+        if let Some(frame) = record_at(data, position.next, position.seq) {
+            position.next += frame.len() as u64 + RECORD_OVERHEAD;
+            position.seq = position.seq.wrapping_add(1);
+            continue;
+        }
+        // a wrap marker (or no room left for one) only counts if the lap went on at the
+        // start, the sequence number says whether it did
+        let at = position.next as usize;
+        let marker = data.get(at..at + 2).is_none_or(|len| len == [0, 0]);
+        if position.next == DATA_START
+            || !marker
+            || record_at(data, DATA_START, position.seq).is_none()
+        {
+            return position;
+        }
+        position.wrap_at = position.next;
+        position.next = DATA_START;
+    }
+}
+
+// the frames of the intact records in front of `position`, newest first
+fn walk_back(data: &[u8], position: Position) -> Vec<&[u8]> {
+    let mut frames = Vec::new();
+    let (mut end, mut seq) = (position.next, position.seq);
+    // the current lap goes down to the start, the rest of the previous one from where it
+    // wrapped down to where writing is now
+    let mut floor = DATA_START;
+    let mut wrapped = false;
+    loop {
+        if end == DATA_START && !wrapped && position.wrap_at > position.next {
+            end = position.wrap_at;
+            floor = position.next;
+            wrapped = true;
+        }
+        let Some(tail) = end
+            .checked_sub(2)
+            .and_then(|at| data.get(at as usize..end as usize))
+        else {
+            return frames;
+        };
+        let len = u16::from_le_bytes(tail.try_into().unwrap()) as u64;
+        let start = match end.checked_sub(len + RECORD_OVERHEAD) {
+            Some(start) if start >= floor => start,
+            _ => return frames,
+        };
+        seq = seq.wrapping_sub(1);
+        match record_at(data, start, seq) {
+            Some(frame) => frames.push(frame),
+            None => return frames,
+        }
+        end = start;
+    }
+}
+
+// version 1: a 16 byte header (magic, version u32, write offset u64), then frames in
+// wire format with the space left at a wrap padded with terminators
+fn v1_frames(data: &[u8]) -> Option<Vec<&[u8]>> {
+    if data.len() < 16 || &data[..4] != MAGIC || data[4..8] != 1u32.to_le_bytes() {
+        return None;
+    }
+    let offset = u64::from_le_bytes(data[8..16].try_into().unwrap()) as usize;
+    let (newer, older) = data[16..].split_at(offset.clamp(16, data.len()) - 16);
+    // the oldest data is right after the write offset
+    let frames = older
+        .split(|&b| b == frame::TERMINATOR)
+        .chain(newer.split(|&b| b == frame::TERMINATOR))
+        .filter(|chunk| !chunk.is_empty())
+        .collect();
+    Some(frames)
+}
+
+// the escaped frames stored in `data`, oldest first, whichever version wrote it
+fn stored_frames(data: &[u8]) -> Option<Vec<&[u8]>> {
+    let Some((_, saved)) = newest_slot(data) else {
+        return v1_frames(data);
+    };
+    let mut frames = walk_back(data, walk_forward(data, saved));
+    frames.reverse();
+    Some(frames)
+}
+
+pub struct CircularFile {
+    file: File,
+    limit: u64,
+    position: Position,
+    generation: u64,
+    policy: SyncPolicy,
+    unsynced: usize,
+    last_sync: Instant,
+}
+
+impl CircularFile {
+    // open or create the file, wrapping at `limit` bytes (headers included). an existing
+    // file picks up right behind its last intact record
+    pub fn open(path: impl AsRef<Path>, limit: u64) -> io::Result<Self> {
+        let path = path.as_ref();
+        if let Ok(data) = std::fs::read(path) {
+            if newest_slot(&data).is_none() {
+                if let Some(frames) = v1_frames(&data) {
+                    Self::migrate(path, limit, &frames)?;
+                }
+            }
+        }
+
+        let limit = limit.max(DATA_START + RECORD_OVERHEAD + 1);
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        if file.metadata()?.len() > limit {
+            file.set_len(limit)?;
+        }
+        let mut data = Vec::new();
+        file.read_to_end(&mut data)?;
+
+        let (generation, position) = match newest_slot(&data) {
+            Some((generation, saved)) => (generation, walk_forward(&data, saved)),
+            None => (0, START),
+        };
+        let mut circular = CircularFile {
+            file,
+            limit,
+            position,
+            generation,
+            policy: SyncPolicy::EveryBytes(64 * 1024),
+            unsynced: 0,
+            last_sync: Instant::now(),
+        };
+        circular.sync()?;
+        Ok(circular)
+    }
+
+    // rewrite an older file in the current layout next to it, then swap it in. a crash
+    // halfway leaves the old file as it was
+    fn migrate(path: &Path, limit: u64, frames: &[&[u8]]) -> io::Result<()> {
+        let mut converted = path.as_os_str().to_owned();
+        converted.push(".migrate");
+        let _ = std::fs::remove_file(&converted);
+        let mut file = Self::open(&converted, limit)?;
+        for frame in frames {
+            match file.write(frame) {
+                Err(e) if e.kind() != io::ErrorKind::InvalidInput => return Err(e),
+                // too big for the new limit, left behind
+                _ => {}
+            }
+        }
+        drop(file);
+        std::fs::rename(&converted, path)
+    }
+
+    pub fn with_sync_policy(mut self, policy: SyncPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    // fsync the records, then save the position into the older header slot and fsync
+    // that too. the header never points past records that aren't on disk
+    pub fn sync(&mut self) -> io::Result<()> {
+        self.file.sync_data()?;
+        self.write_header()?;
+        self.file.sync_data()?;
+        self.unsynced = 0;
+        self.last_sync = Instant::now();
+        Ok(())
+    }
+
+    fn write_header(&mut self) -> io::Result<()> {
+        self.generation += 1;
+        let slot = encode_slot(self.generation, &self.position);
+        self.file
+            .seek(SeekFrom::Start(self.generation % 2 * SLOT_LEN))?;
+        self.file.write_all(&slot)
+    }
+
+    fn sync_due(&self) -> bool {
+        match self.policy {
+            SyncPolicy::EveryFrame => self.unsynced > 0,
+            SyncPolicy::EveryBytes(bytes) => self.unsynced >= bytes.max(1),
+            SyncPolicy::Every(interval) => {
+                self.unsynced > 0 && self.last_sync.elapsed() >= interval
+            }
+            SyncPolicy::Never => false,
+        }
+    }
+
+    // append `frame` (escaped, without the terminator) as a record
+    fn write(&mut self, frame: &[u8]) -> io::Result<()> {
+        let len = frame.len() as u64 + RECORD_OVERHEAD;
+        if frame.len() > u16::MAX as usize || len > self.limit - DATA_START {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Frame larger than the file",
+            ));
+        }
+        if self.position.next + len > self.limit {
+            if self.position.next + 2 <= self.limit {
+                self.file.seek(SeekFrom::Start(self.position.next))?;
+                self.file.write_all(&[0, 0])?;
+            }
+            self.position.wrap_at = self.position.next;
+            self.position.next = DATA_START;
+        }
+        self.file.seek(SeekFrom::Start(self.position.next))?;
+        self.file
+            .write_all(&encode_record(self.position.seq, frame))?;
+        self.position.next += len;
+        self.position.seq = self.position.seq.wrapping_add(1);
+        self.unsynced += len as usize;
+        if self.sync_due() {
+            self.sync()?;
+        }
+        Ok(())
+    }
+
+    // every intact frame in the file at `path`, oldest first
+    pub fn read_frames(path: impl AsRef<Path>) -> io::Result<Vec<Frame>> {
+        let data = std::fs::read(path)?;
+        let Some(frames) = stored_frames(&data) else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Not a circular log file",
+            ));
+        };
+        let frames = frames
+            .into_iter()
+            .filter_map(|bytes| match Frame::decode(bytes) {
+                FrameResult::Ok(frame) => Some(frame),
+                FrameResult::Err(_) => None,
+            })
+            .collect();
+        Ok(frames)
+    }
+}
+
+impl FlushSink for CircularFile {
+    fn write_frame(&mut self, frame: &Frame) -> SinkResult {
+        let mut bytes = frame.encode();
+        bytes.pop_if(|&mut b| b == frame::TERMINATOR);
+        match self.write(&bytes) {
+            Ok(()) => SinkResult::Ok,
+            Err(e) => SinkResult::Err(e.to_string()),
+        }
+    }
+
+    fn flush(&mut self) -> SinkResult {
+        if !self.sync_due() {
+            return SinkResult::Ok;
+        }
+        match self.sync() {
+            Ok(()) => SinkResult::Ok,
+            Err(e) => SinkResult::Err(e.to_string()),
+        }
+    }
+}
+
+impl Drop for CircularFile {
+    fn drop(&mut self) {
+        let _ = self.sync();
+    }
+}
+
+impl RingBuffer {
+    // every complete frame into `file`, ignoring max_flush_size
+    pub fn flush_to_file(&mut self, file: &mut CircularFile) -> FlushToResult {
+        self.flush_all_to(file)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    // a fresh path per test, cleaned up on drop
+    struct TempPath(PathBuf);
+
+    impl TempPath {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!(
+                "ringbuffer-logfile-{}-{}",
+                std::process::id(),
+                name
+            ));
+            let _ = std::fs::remove_file(&path);
+            TempPath(path)
+        }
+    }
+
+    impl Drop for TempPath {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    fn payloads(frames: &[Frame]) -> Vec<&[u8]> {
+        frames.iter().map(|frame| &frame.payload[..]).collect()
+    }
+
+    #[test]
+    fn sync_policies_decide_when_headers_go_out() {
+        let path = TempPath::new("policies");
+        let frame = Frame::new(b"synced?");
+        // the frame without its terminator, in a record
+        let record = frame.encode().len() - 1 + RECORD_OVERHEAD as usize;
+
+        let mut file = CircularFile::open(&path.0, 4096)
+            .unwrap()
+            .with_sync_policy(SyncPolicy::EveryFrame);
+        let generation = file.generation;
+        let _ = file.write_frame(&frame);
+        assert_eq!((file.unsynced, file.generation), (0, generation + 1));
+
+        file = file.with_sync_policy(SyncPolicy::EveryBytes(2 * record));
+        let _ = file.write_frame(&frame);
+        assert_eq!(file.unsynced, record);
+        let _ = file.write_frame(&frame);
+        assert_eq!((file.unsynced, file.generation), (0, generation + 2));
+
+        file = file.with_sync_policy(SyncPolicy::Every(Duration::from_millis(50)));
+        let _ = file.write_frame(&frame);
+        let _ = file.flush();
+        assert_eq!(file.unsynced, record);
+        std::thread::sleep(Duration::from_millis(60));
+        let _ = file.flush();
+        assert_eq!(file.unsynced, 0);
+
+        file = file.with_sync_policy(SyncPolicy::Never);
+        for _ in 0..3 {
+            let _ = file.write_frame(&frame);
+        }
+        let _ = file.flush();
+        assert_eq!(file.unsynced, 3 * record);
+        file.sync().unwrap();
+        assert_eq!(file.unsynced, 0);
+    }
+
+    #[test]
+    fn writing_wraps_at_the_limit_and_keeps_the_newest_frames() {
+        let path = TempPath::new("wrap");
+        let limit = DATA_START + 200;
+        let mut file = CircularFile::open(&path.0, limit).unwrap();
+        let payloads_written: Vec<Vec<u8>> = (0..40)
+            .map(|i| format!("frame {}", i).into_bytes())
+            .collect();
+        for payload in &payloads_written {
+            assert!(matches!(
+                file.write_frame(&Frame::new(payload)),
+                SinkResult::Ok
+            ));
+        }
+        assert!(file.position.wrap_at > 0);
+        drop(file);
+        assert!(std::fs::metadata(&path.0).unwrap().len() <= limit);
+
+        let frames = CircularFile::read_frames(&path.0).unwrap();
+        let read: Vec<Vec<u8>> = payloads(&frames).into_iter().map(Vec::from).collect();
+        assert!(read.len() > 1 && read.len() < payloads_written.len());
+        assert!(payloads_written.ends_with(&read));
+    }
+
+    #[test]
+    fn flush_to_file_takes_every_frame_from_the_ring() {
+        let path = TempPath::new("flush");
+        let mut file = CircularFile::open(&path.0, 4096).unwrap();
+        let mut ring = RingBuffer::new(256);
+        ring.set_max_flush_size(1);
+        for payload in [b"one", b"two", b"six"] {
+            let _ = ring.log(Frame::new(payload));
+        }
+        assert!(
+            matches!(ring.flush_to_file(&mut file), FlushToResult::Ok(stats) if stats.frames == 3)
+        );
+        assert!(ring.is_empty());
+
+        // a frame that can't ever fit stays put
+        let _ = ring.log(Frame::new(&[7; 200]));
+        let small_path = TempPath::new("small");
+        let mut small = CircularFile::open(&small_path.0, DATA_START + 64).unwrap();
+        assert!(matches!(
+            ring.flush_to_file(&mut small),
+            FlushToResult::Err(_)
+        ));
+        assert!(!ring.is_empty());
+
+        drop(file);
+        let frames = CircularFile::read_frames(&path.0).unwrap();
+        assert_eq!(payloads(&frames), [b"one", b"two", b"six"]);
+    }
+
+    #[test]
+    fn version_1_files_are_read_and_converted() {
+        let path = TempPath::new("v1");
+        // wrapped once: "new" was written over the start of "older"
+        let mut data = Vec::new();
+        data.extend_from_slice(MAGIC);
+        data.extend_from_slice(&1u32.to_le_bytes());
+        let newer = Frame::new(b"new").encode();
+        data.extend_from_slice(&(16 + newer.len() as u64).to_le_bytes());
+        data.extend_from_slice(&newer);
+        data.extend_from_slice(&[frame::TERMINATOR; 3]);
+        data.extend_from_slice(&Frame::new(b"old").encode());
+        std::fs::write(&path.0, &data).unwrap();
+
+        let frames = CircularFile::read_frames(&path.0).unwrap();
+        assert_eq!(payloads(&frames), [b"old", b"new"]);
+
+        let mut file = CircularFile::open(&path.0, 4096).unwrap();
+        assert!(matches!(
+            file.write_frame(&Frame::new(b"after")),
+            SinkResult::Ok
+        ));
+        drop(file);
+        let data = std::fs::read(&path.0).unwrap();
+        assert!(newest_slot(&data).is_some());
+        let frames = CircularFile::read_frames(&path.0).unwrap();
+        assert_eq!(payloads(&frames), [&b"old"[..], b"new", b"after"]);
+    }
+
+    const CRASH_LIMIT: u64 = 256;
+
+    // `before` with `writes` applied up to byte `at` of them: torn leaves the rest
+    // unwritten, otherwise everything lands but byte `at` is garbage
+    fn crashed(before: &[u8], writes: &[(u64, &[u8])], at: usize, torn: bool) -> Vec<u8> {
+        let mut data = before.to_vec();
+        let bytes = writes
+            .iter()
+            .flat_map(|(offset, bytes)| (*offset as usize..).zip(bytes.iter()));
+        for (n, (offset, &byte)) in bytes.enumerate() {
+            if torn && n == at {
+                break;
+            }
+            if data.len() <= offset {
+                data.resize(offset + 1, 0);
+            }
+            data[offset] = if n == at { byte ^ 0xff } else { byte };
+        }
+        data
+    }
+
+    // what a reader and a reopened file make of `data`. the reopened file has to carry on
+    // right behind the frames read
+    fn recover(path: &TempPath, data: &[u8]) -> (Vec<Vec<u8>>, Position) {
+        std::fs::write(&path.0, data).unwrap();
+        let frames = CircularFile::read_frames(&path.0).unwrap();
+        let frames: Vec<Vec<u8>> = payloads(&frames).into_iter().map(Vec::from).collect();
+
+        let mut file = CircularFile::open(&path.0, CRASH_LIMIT).unwrap();
+        let position = file.position;
+        assert!(matches!(
+            file.write_frame(&Frame::new(b"resumed")),
+            SinkResult::Ok
+        ));
+        drop(file);
+        let resumed = CircularFile::read_frames(&path.0).unwrap();
+        let (last, older) = resumed.split_last().unwrap();
+        assert_eq!(last.payload, b"resumed");
+        let older: Vec<Vec<u8>> = payloads(older).into_iter().map(Vec::from).collect();
+        assert!(frames.ends_with(&older));
+        (frames, position)
+    }
+
+    fn is_suffix(frames: &[Vec<u8>], of: &[Vec<u8>], at_least: usize) -> bool {
+        frames.len() >= at_least && of.ends_with(frames)
+    }
+
+    #[test]
+    fn a_crash_anywhere_in_a_write_keeps_synced_frames() {
+        let path = TempPath::new("crash");
+        let scratch = TempPath::new("crash-scratch");
+        let mut file = CircularFile::open(&path.0, CRASH_LIMIT)
+            .unwrap()
+            .with_sync_policy(SyncPolicy::Never);
+        let read = || -> Vec<Vec<u8>> {
+            let frames = CircularFile::read_frames(&path.0).unwrap();
+            payloads(&frames).into_iter().map(Vec::from).collect()
+        };
+
+        // enough frames of varying length to wrap the file a few times
+        for i in 0..24 {
+            let before = std::fs::read(&path.0).unwrap();
+            let before_frames = read();
+            let saved = file.position;
+            let payload = format!("frame {}", i).repeat(i % 3 + 1);
+            assert!(matches!(
+                file.write_frame(&Frame::new(payload.as_bytes())),
+                SinkResult::Ok
+            ));
+            let written = std::fs::read(&path.0).unwrap();
+            let written_frames = read();
+            assert_eq!(written_frames.last().unwrap(), payload.as_bytes());
+
+            // the record, behind a wrap marker if it went back to the start
+            let end = file.position.next as usize;
+            let mut writes = Vec::new();
+            if file.position.next < saved.next {
+                if saved.next + 2 <= CRASH_LIMIT {
+                    writes.push((saved.next, &[0u8, 0][..]));
+                }
+                writes.push((DATA_START, &written[DATA_START as usize..end]));
+            } else {
+                writes.push((saved.next, &written[saved.next as usize..end]));
+            }
+            let total: usize = writes.iter().map(|(_, bytes)| bytes.len()).sum();
+            let survivors = written_frames.len() - 1;
+            for at in 0..=total {
+                for torn in [true, false] {
+                    if at == total && !torn {
+                        continue;
+                    }
+                    let data = crashed(&before, &writes, at, torn);
+                    let (frames, position) = recover(&scratch, &data);
+                    // the record counts once its crc and trailing length read right. a
+                    // torn one can get there by chance (one in 256 for the crc), then the
+                    // frame's own crc still throws out the half written frame
+                    if position == file.position {
+                        let whole = frames == written_frames;
+                        assert!(whole || frames == written_frames[..survivors]);
+                        continue;
+                    }
+                    assert!(at < total, "write {} wasn't picked up", i);
+                    assert!(
+                        is_suffix(&frames, &before_frames, survivors),
+                        "write {} cut at {} (torn {})",
+                        i,
+                        at,
+                        torn
+                    );
+                    assert_eq!((position.next, position.seq), (saved.next, saved.seq));
+                }
+            }
+
+            // then the header pointing past it, into the older slot
+            file.sync().unwrap();
+            let synced = std::fs::read(&path.0).unwrap();
+            let slot_at = file.generation % 2 * SLOT_LEN;
+            let slot = &synced[slot_at as usize..(slot_at + SLOT_LEN) as usize];
+            let writes = [(slot_at, slot)];
+            for at in 0..=SLOT_LEN as usize {
+                for torn in [true, false] {
+                    if at == SLOT_LEN as usize && !torn {
+                        continue;
+                    }
+                    let data = crashed(&written, &writes, at, torn);
+                    // the new slot counts once its crc at byte 36 is in, past it is
+                    // padding. until then the other slot holds the previous position
+                    let generation = if at > 36 {
+                        file.generation
+                    } else {
+                        file.generation - 1
+                    };
+                    assert_eq!(newest_slot(&data).unwrap().0, generation);
+                    let (frames, position) = recover(&scratch, &data);
+                    assert_eq!(frames, written_frames);
+                    assert_eq!(position, file.position);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,119 @@
+// backend for the `log` facade, so `log::info!` anywhere in the dependency tree ends up
+// in the ring as a leveled `target: message` frame
+
+use log::{LevelFilter, Log, Metadata, Record, SetLoggerError};
+
+use crate::frame::Frame;
+use crate::level::Level;
+use crate::shared::SharedRing;
+use crate::RingBuffer;
+
+fn level(level: log::Level) -> Level {
+    match level {
+        log::Level::Error => Level::Error,
+        log::Level::Warn => Level::Warn,
+        log::Level::Info => Level::Info,
+        log::Level::Debug => Level::Debug,
+        log::Level::Trace => Level::Trace,
+    }
+}
+
+fn level_filter(level: Level) -> LevelFilter {
+    match level {
+        Level::Error => LevelFilter::Error,
+        Level::Warn => LevelFilter::Warn,
+        Level::Info => LevelFilter::Info,
+        Level::Debug => LevelFilter::Debug,
+        Level::Trace => LevelFilter::Trace,
+    }
+}
+
+pub struct RingBufferLogger {
+    ring: SharedRing,
+}
+
+impl RingBufferLogger {
+    pub fn new(ring: SharedRing) -> Self {
+        RingBufferLogger { ring }
+    }
+}
+
+impl Log for RingBufferLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        // from inside a flush the record is let through, `log` counts it as dropped
+        self.ring
+            .try_with_ring(|ring| ring.level_enabled(level(metadata.level())))
+            .unwrap_or(true)
+    }
+
+    fn log(&self, record: &Record) {
+        let message = format!("{}: {}", record.target(), record.args());
+        let frame = Frame::new(message.as_bytes()).with_level(level(record.level()));
+        // a full ring (or one a sink logs into while it's being flushed) counts the
+        // message as dropped, there's nobody to report to here
+        let _ = self.ring.log(frame);
+    }
+
+    fn flush(&self) {}
+}
+
+// install `ring` as the global logger, with the `log` max level taken from the ring's.
+// returns the handle to drain it through
+pub fn init(ring: RingBuffer) -> Result<SharedRing, SetLoggerError> {
+    let max_level = level_filter(ring.max_level());
+    let ring = SharedRing::new(ring);
+    log::set_boxed_logger(Box::new(RingBufferLogger::new(ring.clone())))?;
+    log::set_max_level(max_level);
+    Ok(ring)
+}
+
+#[cfg(test)]
+mod tests {
+    use log::{Metadata, Record};
+
+    use super::*;
+    use crate::sink::MemorySink;
+
+    fn record(logger: &RingBufferLogger, level: log::Level, target: &str) {
+        logger.log(
+            &Record::builder()
+                .args(format_args!("ready after {} ms", 12))
+                .level(level)
+                .target(target)
+                .build(),
+        );
+    }
+
+    fn enabled(logger: &RingBufferLogger, level: log::Level) -> bool {
+        logger.enabled(&Metadata::builder().level(level).target("app").build())
+    }
+
+    #[test]
+    fn records_become_leveled_target_message_frames() {
+        let ring = SharedRing::new(RingBuffer::new(256));
+        let logger = RingBufferLogger::new(ring.clone());
+        record(&logger, log::Level::Warn, "app::net");
+
+        let mut sink = MemorySink::new();
+        let _ = ring.flush_all_to(&mut sink);
+        let frames = sink.frames();
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].payload, b"app::net: ready after 12 ms");
+        assert_eq!(frames[0].level, Some(Level::Warn));
+    }
+
+    #[test]
+    fn the_max_level_comes_from_the_ring() {
+        let mut ring = RingBuffer::new(256);
+        ring.set_max_level(Level::Info);
+        assert_eq!(level_filter(ring.max_level()), LevelFilter::Info);
+
+        let ring = SharedRing::new(ring);
+        let logger = RingBufferLogger::new(ring.clone());
+        assert!(enabled(&logger, log::Level::Info));
+        assert!(!enabled(&logger, log::Level::Debug));
+        // and follows it when it changes later
+        ring.with_ring(|ring| ring.set_max_level(Level::Trace));
+        assert!(enabled(&logger, log::Level::Trace));
+    }
+}
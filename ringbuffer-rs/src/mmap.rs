@@ -0,0 +1,217 @@
+// a ring whose header and storage live in a memory-mapped file, the same layout as a
+// retained ram region. for host daemons: the log is bounded, survives a restart of the
+// process (and a crash, the kernel writes the pages back), and other tools can read it
+// while the daemon runs:
+//
+//     let mut ring = MmapRingBuffer::open("/var/lib/app/log.ring", 1 << 20)?;
+//     ring.log_message_with_crc(b"...");
+//     ring.flush_to(&mut sink);
+//
+//     // from another process, without disturbing the owner
+//     let frames = MmapRingBuffer::read_frames("/var/lib/app/log.ring")?;
+//
+// the file is 20 header bytes (magic, version, crc8, index crc8, storage size, head, tail,
+// in native byte order) followed by the storage
+
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::ops::{Deref, DerefMut};
+use std::path::Path;
+
+use memmap2::MmapMut;
+
+use crate::frame::{Frame, FrameResult};
+use crate::retained::{RecoverResult, Storage, HEADER_LEN};
+use crate::RingBuffer;
+
+// how `open` found the file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Opened {
+    // an intact ring from an earlier run, picked up where it was
+    Resumed,
+    // an earlier ring with bad indices, its intact frames were kept
+    Salvaged,
+    // a new, empty ring: the file didn't exist, had another size or no valid header
+    Created,
+}
+
+pub struct MmapRingBuffer {
+    ring: RingBuffer,
+    file: File,
+    opened: Opened,
+}
+
+impl MmapRingBuffer {
+    // open or create the ring in `path` with `capacity` bytes of storage. a file of
+    // another size is started over
+    pub fn open(path: impl AsRef<Path>, capacity: usize) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        let len = (HEADER_LEN + capacity.max(2)) as u64;
+        if file.metadata()?.len() != len {
+            file.set_len(len)?;
+        }
+
+        let map = unsafe { MmapMut::map_mut(&file)? };
+        let (ring, opened) = match RingBuffer::recover(Storage::Mapped(map)) {
+            RecoverResult::Ok(ring) => (ring, Opened::Resumed),
+            RecoverResult::Salvaged(ring) => (ring, Opened::Salvaged),
+            RecoverResult::Err(_) => {
+                let map = unsafe { MmapMut::map_mut(&file)? };
+                (RingBuffer::format(Storage::Mapped(map)), Opened::Created)
+            }
+        };
+        Ok(MmapRingBuffer { ring, file, opened })
+    }
+
+    pub fn opened(&self) -> Opened {
+        self.opened
+    }
+
+    // write dirty pages back to the file now instead of whenever the kernel gets to it
+    pub fn sync(&self) -> io::Result<()> {
+        if let Storage::Mapped(map) = &self.ring.buffer {
+            map.flush()?;
+        }
+        self.file.sync_data()
+    }
+
+    // the stored frames of the ring in `path`, oldest first, read from a copy of the
+    // file. corrupt frames and a frame the owner is halfway through writing are left out
+    pub fn read_frames(path: impl AsRef<Path>) -> io::Result<Vec<Frame>> {
+        let bytes = std::fs::read(path)?;
+        let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+        if bytes.len() <= HEADER_LEN + 1 {
+            return Err(invalid("File too small".to_string()));
+        }
+        let mut region = vec![0u32; bytes.len().div_ceil(4)];
+        let ptr = region.as_mut_ptr().cast::<u8>();
+        let ring = unsafe {
+            core::ptr::copy_nonoverlapping(bytes.as_ptr(), ptr, bytes.len());
+            RingBuffer::recover_from(ptr, bytes.len())
+        };
+        let ring = match ring {
+            RecoverResult::Ok(ring) | RecoverResult::Salvaged(ring) => ring,
+            RecoverResult::Err(e) => return Err(invalid(e)),
+        };
+        let frames = ring
+            .frame_bytes()
+            .iter()
+            .filter_map(|bytes| match Frame::decode(bytes) {
+                FrameResult::Ok(frame) => Some(frame),
+                FrameResult::Err(_) => None,
+            })
+            .collect();
+        drop(ring);
+        drop(region);
+        Ok(frames)
+    }
+}
+
+impl Deref for MmapRingBuffer {
+    type Target = RingBuffer;
+
+    fn deref(&self) -> &RingBuffer {
+        &self.ring
+    }
+}
+
+impl DerefMut for MmapRingBuffer {
+    fn deref_mut(&mut self) -> &mut RingBuffer {
+        &mut self.ring
+    }
+}
+
+impl Drop for MmapRingBuffer {
+    fn drop(&mut self) {
+        let _ = self.sync();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    fn path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("ringbuffer-mmap-{}-{}", std::process::id(), name))
+    }
+
+    fn payloads(frames: &[Frame]) -> Vec<&[u8]> {
+        frames.iter().map(|frame| &frame.payload[..]).collect()
+    }
+
+    #[test]
+    fn the_ring_is_picked_up_again_after_a_restart() {
+        let path = path("restart");
+        let mut ring = MmapRingBuffer::open(&path, 256).unwrap();
+        assert_eq!(ring.opened(), Opened::Created);
+        for payload in [b"one", b"two", b"six"] {
+            let _ = ring.log(Frame::new(payload));
+        }
+        let FrameResult::Ok(_) = ring.flush_frame() else {
+            panic!("no frame");
+        };
+        drop(ring);
+
+        let mut ring = MmapRingBuffer::open(&path, 256).unwrap();
+        assert_eq!(ring.opened(), Opened::Resumed);
+        let FrameResult::Ok(frame) = ring.flush_frame() else {
+            panic!("no frame");
+        };
+        assert_eq!(frame.payload, b"two");
+        drop(ring);
+
+        // another size starts over
+        let ring = MmapRingBuffer::open(&path, 512).unwrap();
+        assert_eq!(ring.opened(), Opened::Created);
+        assert!(ring.is_empty());
+        drop(ring);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn other_readers_see_the_frames_while_the_owner_runs() {
+        let path = path("reader");
+        let mut ring = MmapRingBuffer::open(&path, 256).unwrap();
+        let _ = ring.log(Frame::new(b"live"));
+        // half a frame being written right now
+        ring.push_slice(&Frame::new(b"partial").encode()[..4]);
+        let frames = MmapRingBuffer::read_frames(&path).unwrap();
+        assert_eq!(payloads(&frames), [b"live"]);
+        // reading didn't consume anything
+        assert!(!ring.is_empty());
+        drop(ring);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn bad_indices_salvage_and_garbage_starts_over() {
+        let path = path("damaged");
+        let mut ring = MmapRingBuffer::open(&path, 256).unwrap();
+        let _ = ring.log(Frame::new(b"kept"));
+        drop(ring);
+        let mut bytes = std::fs::read(&path).unwrap();
+        // the index crc
+        bytes[7] ^= 0xff;
+        std::fs::write(&path, &bytes).unwrap();
+        let ring = MmapRingBuffer::open(&path, 256).unwrap();
+        assert_eq!(ring.opened(), Opened::Salvaged);
+        drop(ring);
+
+        std::fs::write(&path, vec![0xa5; HEADER_LEN + 256]).unwrap();
+        let ring = MmapRingBuffer::open(&path, 256).unwrap();
+        assert_eq!(ring.opened(), Opened::Created);
+        assert!(ring.is_empty());
+        drop(ring);
+
+        std::fs::write(&path, [0; 4]).unwrap();
+        assert!(MmapRingBuffer::read_frames(&path).is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,163 @@
+// publishes flushed frames to an mqtt broker, either in wire format or rendered as json
+
+use std::thread;
+use std::time::Duration;
+
+use rumqttc::{Client, MqttOptions, QoS};
+
+use crate::frame::Frame;
+use crate::json;
+use crate::sink::{FlushSink, SinkResult};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MqttPayload {
+    // frame bytes exactly as they sit in the ring
+    Raw,
+    // one json object per message, see `json::frame_to_json`
+    Json,
+}
+
+pub struct MqttSink {
+    client: Client,
+    topic: String,
+    qos: QoS,
+    payload: MqttPayload,
+}
+
+impl MqttSink {
+    // publish through a client whose connection the caller already drives
+    pub fn new(client: Client, topic: &str) -> Self {
+        MqttSink {
+            client,
+            topic: topic.to_string(),
+            qos: QoS::AtLeastOnce,
+            payload: MqttPayload::Raw,
+        }
+    }
+
+    // create the client and drive its connection from a background thread, which keeps
+    // reconnecting for as long as the sink is alive
+    pub fn connect(options: MqttOptions, topic: &str) -> Self {
+        let (client, mut connection) = Client::new(options, 64);
+        thread::spawn(move || {
+            for event in connection.iter() {
+                match event {
+                    Ok(_) => {}
+                    // the client was dropped, nothing left to deliver
+                    Err(rumqttc::ConnectionError::RequestsDone) => break,
+                    Err(_) => thread::sleep(Duration::from_secs(1)),
+                }
+            }
+        });
+        Self::new(client, topic)
+    }
+
+    pub fn with_qos(mut self, qos: QoS) -> Self {
+        self.qos = qos;
+        self
+    }
+
+    pub fn with_payload(mut self, payload: MqttPayload) -> Self {
+        self.payload = payload;
+        self
+    }
+}
+
+impl FlushSink for MqttSink {
+    // never blocks: when the client's request queue is full the frame stays in the ring
+    fn write_frame(&mut self, frame: &Frame) -> SinkResult {
+        let payload = match self.payload {
+            MqttPayload::Raw => frame.encode(),
+            MqttPayload::Json => json::frame_to_json(frame).into_bytes(),
+        };
+        match self
+            .client
+            .try_publish(self.topic.as_str(), self.qos, false, payload)
+        {
+            Ok(()) => SinkResult::Ok,
+            Err(e) => SinkResult::Err(e.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    use super::*;
+    use crate::sink::{FlushToResult, MemorySink};
+    use crate::RingBuffer;
+
+    fn read_packet(stream: &mut impl Read) -> (u8, Vec<u8>) {
+        let mut header = [0u8; 1];
+        stream.read_exact(&mut header).unwrap();
+        let (mut len, mut shift) = (0usize, 0);
+        loop {
+            let mut byte = [0u8; 1];
+            stream.read_exact(&mut byte).unwrap();
+            len |= ((byte[0] & 0x7f) as usize) << shift;
+            shift += 7;
+            if byte[0] & 0x80 == 0 {
+                break;
+            }
+        }
+        let mut body = vec![0u8; len];
+        stream.read_exact(&mut body).unwrap();
+        (header[0], body)
+    }
+
+    // a broker that takes one connection and returns the topic and payload of the first
+    // qos 0 publish
+    fn broker() -> (u16, thread::JoinHandle<(String, Vec<u8>)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let handle = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            assert_eq!(read_packet(&mut stream).0, 0x10);
+            stream.write_all(&[0x20, 0x02, 0x00, 0x00]).unwrap();
+            loop {
+                let (header, body) = read_packet(&mut stream);
+                if header >> 4 == 3 {
+                    let topic_len = u16::from_be_bytes([body[0], body[1]]) as usize;
+                    let topic = String::from_utf8(body[2..2 + topic_len].to_vec()).unwrap();
+                    return (topic, body[2 + topic_len..].to_vec());
+                }
+            }
+        });
+        (port, handle)
+    }
+
+    #[test]
+    fn frames_are_published_to_the_topic() {
+        let (port, broker) = broker();
+        let mut sink = MqttSink::connect(MqttOptions::new("ring", "127.0.0.1", port), "dev/log")
+            .with_qos(QoS::AtMostOnce);
+        let mut ring = RingBuffer::new(64);
+        let _ = ring.log(Frame::new(b"hello broker"));
+        assert!(matches!(ring.flush_all_to(&mut sink), FlushToResult::Ok(_)));
+
+        let (topic, payload) = broker.join().unwrap();
+        assert_eq!(topic, "dev/log");
+        assert_eq!(payload, Frame::new(b"hello broker").encode());
+    }
+
+    #[test]
+    fn a_full_request_queue_leaves_frames_in_the_ring() {
+        // nobody drives the connection, so requests pile up in the client's queue of 4
+        let (client, _connection) = Client::new(MqttOptions::new("ring", "127.0.0.1", 1), 4);
+        let mut sink = MqttSink::new(client, "dev/log").with_payload(MqttPayload::Json);
+        let mut ring = RingBuffer::new(256);
+        for i in 0..6u8 {
+            let _ = ring.log(Frame::new(&[b'0' + i]));
+        }
+        assert!(matches!(
+            ring.flush_all_to(&mut sink),
+            FlushToResult::Err(_)
+        ));
+        let mut rest = MemorySink::new();
+        let _ = ring.flush_all_to(&mut rest);
+        assert_eq!(rest.frames().len(), 2);
+        assert_eq!(rest.frames()[0].payload, b"4");
+    }
+}
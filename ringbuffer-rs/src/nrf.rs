@@ -0,0 +1,202 @@
+// uarte tx through easydma for nrf52 parts. easydma only reads ram and only one
+// contiguous buffer per transfer, which the grants already are: the storage lives on the
+// heap and a wrapped frame goes out in two transfers. transfers are capped at 255 bytes
+// (the nrf52832's maxcnt) and cut at frame boundaries, a longer frame goes out in pieces.
+// the uarte must already be enabled with its pins and baud rate set, e.g. by the hal
+// before `free()`:
+//
+//     let mut uarte = unsafe { UarteDma::new(p.UARTE0) };
+//     ...
+//     ring.log_message_with_crc(b"...");
+//     uarte.poll(&mut ring);
+//
+//     #[interrupt]
+//     fn UARTE0_UART0() {
+//         uarte.on_interrupt(&mut ring);
+//     }
+
+use core::ops::Deref;
+
+use embedded_dma::ReadBuffer;
+use nrf52840_pac::uarte0::RegisterBlock;
+
+use crate::RingBuffer;
+
+pub struct UarteDma<U: Deref<Target = RegisterBlock>> {
+    uarte: U,
+    max_transfer: usize,
+    // length of the transfer on the wire, 0 when idle
+    in_flight: usize,
+}
+
+impl<U: Deref<Target = RegisterBlock>> UarteDma<U> {
+    // safety: every call must pass the same ring, the ring must outlive the adapter (or
+    // the last transfer) and it must only be drained through the adapter, the same rules
+    // as dma_read_grant
+    #[allow(clippy::missing_safety_doc)]
+    pub unsafe fn new(uarte: U) -> Self {
+        uarte.events_endtx.write(|w| w.bits(0));
+        uarte.intenset.write(|w| w.endtx().set());
+        UarteDma {
+            uarte,
+            max_transfer: 255,
+            in_flight: 0,
+        }
+    }
+
+    // the nrf52840 and nrf52833 take up to 65535 bytes per transfer
+    pub fn with_max_transfer(mut self, max_transfer: usize) -> Self {
+        self.max_transfer = max_transfer.clamp(1, u16::MAX as usize);
+        self
+    }
+
+    pub fn is_busy(&self) -> bool {
+        self.in_flight > 0
+    }
+
+    // start a transfer if the uarte is idle and a complete frame is waiting. returns
+    // whether one was started
+    pub fn poll(&mut self, ring: &mut RingBuffer) -> bool {
+        if self.in_flight > 0 {
+            return false;
+        }
+        let grant = ring.dma_grant_within(0, self.max_transfer);
+        if grant.is_empty() {
+            return false;
+        }
+        let (ptr, len) = unsafe { grant.read_buffer() };
+        let (ptr, len) = (ptr as u32, len as u16);
+        self.uarte.txd.ptr.write(|w| unsafe { w.ptr().bits(ptr) });
+        self.uarte
+            .txd
+            .maxcnt
+            .write(|w| unsafe { w.maxcnt().bits(len) });
+        self.uarte.tasks_starttx.write(|w| unsafe { w.bits(1) });
+        self.in_flight = grant.len();
+        true
+    }
+
+    // the transfer finished (or was stopped), release what went out and start the next
+    pub fn on_end_tx(&mut self, ring: &mut RingBuffer) {
+        let amount = self.uarte.txd.amount.read().amount().bits() as usize;
+        ring.dma_read_done(amount.min(self.in_flight));
+        self.in_flight = 0;
+        self.poll(ring);
+    }
+
+    // the uarte interrupt handler: checks and clears ENDTX
+    pub fn on_interrupt(&mut self, ring: &mut RingBuffer) {
+        if self.uarte.events_endtx.read().bits() == 0 {
+            return;
+        }
+        self.uarte.events_endtx.write(|w| unsafe { w.bits(0) });
+        self.on_end_tx(ring);
+    }
+
+    // stop sending and hand back the peripheral. bytes of an interrupted transfer that
+    // didn't go out stay in the ring
+    pub fn release(self, ring: &mut RingBuffer) -> U {
+        if self.in_flight > 0 {
+            self.uarte.tasks_stoptx.write(|w| unsafe { w.bits(1) });
+            while self.uarte.events_txstopped.read().bits() == 0 {}
+            self.uarte.events_txstopped.write(|w| unsafe { w.bits(0) });
+            let amount = self.uarte.txd.amount.read().amount().bits() as usize;
+            ring.dma_read_done(amount.min(self.in_flight));
+        }
+        self.uarte.intenclr.write(|w| w.endtx().clear());
+        self.uarte
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::boxed::Box;
+
+    use super::*;
+    use crate::frame::Frame;
+
+    // the register block in plain memory, standing in for the peripheral
+    struct Uarte(Box<RegisterBlock>);
+
+    impl Deref for Uarte {
+        type Target = RegisterBlock;
+
+        fn deref(&self) -> &RegisterBlock {
+            &self.0
+        }
+    }
+
+    fn uarte() -> UarteDma<Uarte> {
+        unsafe { UarteDma::new(Uarte(Box::new_zeroed().assume_init())) }
+    }
+
+    // what the uarte reports once a transfer ends
+    fn end_tx(uarte: &UarteDma<Uarte>, amount: u32) {
+        unsafe {
+            *uarte.uarte.txd.amount.as_ptr() = amount;
+            *uarte.uarte.events_endtx.as_ptr() = 1;
+        }
+    }
+
+    fn ring(payloads: &[&[u8]]) -> RingBuffer {
+        let mut ring = RingBuffer::new(64);
+        for payload in payloads {
+            let _ = ring.log(Frame::new(payload));
+        }
+        ring
+    }
+
+    #[test]
+    fn a_transfer_starts_on_the_complete_frames() {
+        let mut ring = ring(&[b"one", b"two"]);
+        let mut uarte = uarte();
+        assert!(uarte.uarte.intenset.read().endtx().bit());
+        assert!(uarte.poll(&mut ring));
+        assert!(uarte.is_busy());
+        assert_eq!(uarte.uarte.txd.maxcnt.read().bits() as usize, ring.len());
+        assert_eq!(unsafe { *uarte.uarte.tasks_starttx.as_ptr() }, 1);
+        // one at a time
+        assert!(!uarte.poll(&mut ring));
+    }
+
+    #[test]
+    fn end_tx_releases_what_went_out_and_starts_the_rest() {
+        let mut ring = ring(&[b"one"]);
+        let mut uarte = uarte();
+        let first = Frame::new(b"one").encode().len();
+        uarte.poll(&mut ring);
+        let _ = ring.log(Frame::new(b"two"));
+
+        // nothing pending, nothing happens
+        uarte.on_interrupt(&mut ring);
+        assert!(uarte.is_busy());
+
+        end_tx(&uarte, first as u32);
+        uarte.on_interrupt(&mut ring);
+        assert_eq!(uarte.uarte.events_endtx.read().bits(), 0);
+        assert_eq!(ring.stats().flushed_frames, 1);
+        assert!(uarte.is_busy());
+        assert_eq!(
+            uarte.uarte.txd.maxcnt.read().bits() as usize,
+            Frame::new(b"two").encode().len()
+        );
+    }
+
+    #[test]
+    fn transfers_are_capped_and_a_stopped_one_keeps_the_rest() {
+        let mut ring = ring(&[b"a payload longer than the cap"]);
+        let mut uarte = uarte().with_max_transfer(8);
+        uarte.poll(&mut ring);
+        assert_eq!(uarte.uarte.txd.maxcnt.read().bits(), 8);
+
+        let stored = ring.len();
+        unsafe {
+            *uarte.uarte.txd.amount.as_ptr() = 3;
+            *uarte.uarte.events_txstopped.as_ptr() = 1;
+        }
+        let uarte = uarte.release(&mut ring);
+        assert_eq!(ring.len(), stored - 3);
+        assert_eq!(unsafe { *uarte.tasks_stoptx.as_ptr() }, 1);
+        assert!(uarte.intenclr.read().endtx().bit());
+    }
+}
@@ -0,0 +1,232 @@
+// exports flushed frames as opentelemetry log records through whatever logger provider the
+// application configured (otlp, stdout, ...). severity comes from the level, attributes
+// from key-value pairs and the header fields
+
+use std::time::SystemTime;
+
+use opentelemetry::logs::{AnyValue, LogRecord, Logger, Severity};
+
+use crate::clock::SystemClock;
+use crate::frame::Frame;
+use crate::kv::Value;
+use crate::level::Level;
+use crate::sink::{FlushSink, SinkResult};
+
+fn severity(level: Level) -> Severity {
+    match level {
+        Level::Error => Severity::Error,
+        Level::Warn => Severity::Warn,
+        Level::Info => Severity::Info,
+        Level::Debug => Severity::Debug,
+        Level::Trace => Severity::Trace,
+    }
+}
+
+fn any_value(value: Value) -> AnyValue {
+    match value {
+        Value::Str(v) => AnyValue::from(v.into_owned()),
+        Value::Int(v) => AnyValue::from(v),
+        Value::Float(v) => AnyValue::from(v),
+        Value::Bool(v) => AnyValue::from(v),
+    }
+}
+
+pub struct OtelSink<L: Logger> {
+    logger: L,
+    clock: Option<SystemClock>,
+}
+
+impl<L: Logger> OtelSink<L> {
+    // usually `provider.logger("ringbuffer")`
+    pub fn new(logger: L) -> Self {
+        OtelSink {
+            logger,
+            clock: None,
+        }
+    }
+
+    // how to turn frame timestamps into wall-clock time, without it records only carry
+    // the time they were flushed
+    pub fn with_clock(mut self, clock: SystemClock) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+}
+
+impl<L: Logger> FlushSink for OtelSink<L> {
+    fn write_frame(&mut self, frame: &Frame) -> SinkResult {
+        let mut record = self.logger.create_log_record();
+        record.set_observed_timestamp(SystemTime::now());
+        if let Some(time) = frame
+            .timestamp
+            .zip(self.clock.as_ref())
+            .and_then(|(ticks, clock)| clock.to_system_time(ticks))
+        {
+            record.set_timestamp(time);
+        }
+        if let Some(level) = frame.level {
+            record.set_severity_number(severity(level));
+            record.set_severity_text(level.as_str());
+        }
+        if let Some(seq) = frame.seq {
+            record.add_attribute("ring.seq", seq);
+        }
+        if let Some(tag) = frame.tag {
+            record.add_attribute("ring.tag", tag);
+        }
+        if let Some(channel) = frame.channel {
+            record.add_attribute("ring.channel", channel);
+        }
+
+        if let Some(dropped) = frame.dropped {
+            record.set_severity_number(Severity::Warn);
+            record.set_body(AnyValue::from(dropped.to_string()));
+        } else if let Some(pairs) = frame.pairs() {
+            // a `message` pair (as the tracing layer writes) becomes the body
+            for (key, value) in pairs {
+                if key == "message" {
+                    record.set_body(any_value(value));
+                } else {
+                    record.add_attribute(key, any_value(value));
+                }
+            }
+        } else {
+            let body = String::from_utf8_lossy(&frame.payload).into_owned();
+            record.set_body(AnyValue::from(body));
+        }
+
+        self.logger.emit(record);
+        SinkResult::Ok
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use opentelemetry::Key;
+
+    use super::*;
+
+    // what an sdk would have exported
+    #[derive(Default)]
+    struct Record {
+        timestamp: Option<SystemTime>,
+        observed: Option<SystemTime>,
+        severity: Option<Severity>,
+        severity_text: Option<&'static str>,
+        body: Option<AnyValue>,
+        attributes: Vec<(String, AnyValue)>,
+    }
+
+    impl LogRecord for Record {
+        fn set_event_name(&mut self, _name: &'static str) {}
+
+        fn set_target<T: Into<Cow<'static, str>>>(&mut self, _target: T) {}
+
+        fn set_timestamp(&mut self, timestamp: SystemTime) {
+            self.timestamp = Some(timestamp);
+        }
+
+        fn set_observed_timestamp(&mut self, timestamp: SystemTime) {
+            self.observed = Some(timestamp);
+        }
+
+        fn set_severity_text(&mut self, text: &'static str) {
+            self.severity_text = Some(text);
+        }
+
+        fn set_severity_number(&mut self, number: Severity) {
+            self.severity = Some(number);
+        }
+
+        fn set_body(&mut self, body: AnyValue) {
+            self.body = Some(body);
+        }
+
+        fn add_attributes<I, K, V>(&mut self, attributes: I)
+        where
+            I: IntoIterator<Item = (K, V)>,
+            K: Into<Key>,
+            V: Into<AnyValue>,
+        {
+            for (key, value) in attributes {
+                self.add_attribute(key, value);
+            }
+        }
+
+        fn add_attribute<K: Into<Key>, V: Into<AnyValue>>(&mut self, key: K, value: V) {
+            self.attributes
+                .push((key.into().as_str().to_string(), value.into()));
+        }
+    }
+
+    #[derive(Clone, Default)]
+    struct Recorder(Rc<RefCell<Vec<Record>>>);
+
+    impl Logger for Recorder {
+        type LogRecord = Record;
+
+        fn create_log_record(&self) -> Record {
+            Record::default()
+        }
+
+        fn emit(&self, record: Record) {
+            self.0.borrow_mut().push(record);
+        }
+
+        fn event_enabled(&self, _level: Severity, _target: &str, _name: Option<&str>) -> bool {
+            true
+        }
+    }
+
+    #[test]
+    fn key_value_frames_become_attributes_with_a_message_body() {
+        let logger = Recorder::default();
+        let mut sink = OtelSink::new(logger.clone());
+        let frame = Frame::default()
+            .kv("message", "stalled")
+            .kv("rpm", 1200)
+            .with_level(Level::Warn)
+            .with_seq(5);
+        assert!(matches!(sink.write_frame(&frame), SinkResult::Ok));
+
+        let records = logger.0.borrow();
+        let record = &records[0];
+        assert_eq!(record.severity, Some(Severity::Warn));
+        assert_eq!(record.severity_text, Some("WARN"));
+        assert_eq!(record.body, Some(AnyValue::from("stalled".to_string())));
+        assert_eq!(
+            record.attributes,
+            [
+                ("ring.seq".to_string(), AnyValue::from(5u16)),
+                ("rpm".to_string(), AnyValue::from(1200i64)),
+            ]
+        );
+        assert!(record.observed.is_some());
+        assert_eq!(record.timestamp, None);
+    }
+
+    #[test]
+    fn timestamps_go_through_the_clock() {
+        let logger = Recorder::default();
+        let clock = SystemClock::new().with_resolution(Duration::from_secs(1));
+        let mut sink = OtelSink::new(logger.clone()).with_clock(clock);
+        let frame = Frame::new(b"plain text").with_timestamp(60);
+        let _ = sink.write_frame(&frame);
+
+        let records = logger.0.borrow();
+        assert_eq!(
+            records[0].timestamp,
+            Some(UNIX_EPOCH + Duration::from_secs(60))
+        );
+        assert_eq!(
+            records[0].body,
+            Some(AnyValue::from("plain text".to_string()))
+        );
+        assert_eq!(records[0].severity, None);
+    }
+}
@@ -0,0 +1,217 @@
+// token bucket on the flush path, so draining a full ring doesn't hog a link it shares
+// with other traffic (a uart also carrying a command protocol, a radio with a duty
+// cycle). the rate is in bytes per `period` ticks of the ring's clock:
+//
+//     // ~11.5 kB/s at a 1 ms clock, in bursts of up to 256 bytes
+//     ring.set_flush_rate(TokenBucket::new(11_520, 1000, 256));
+//     ...
+//     ring.flush_to(&mut sink);
+//     let left = ring.flush_budget();
+//
+// flush_to, flush_all_to, flush_older_than and dma_flush_with_crc_check stop at the
+// first frame there aren't enough tokens for, the dma grants get shorter instead (down
+// to nothing) and pay for what dma_read_done releases. a frame longer than the burst
+// goes out whole once the bucket is full. the shutdown drain ignores the bucket, and
+// without a clock the bucket never refills
+
+use crate::RingBuffer;
+
+#[derive(Debug, Clone, Copy)]
+pub struct TokenBucket {
+    rate: u64,
+    period: u64,
+    burst: usize,
+    // tokens times period, so partial tokens carry over between refills
+    level: u64,
+    // time of the last refill, none before first use
+    last: Option<u64>,
+}
+
+impl TokenBucket {
+    // `rate` bytes every `period` ticks, at most `burst` bytes saved up. starts full
+    pub fn new(rate: u64, period: u64, burst: usize) -> Self {
+        assert!(
+            period > 0,
+            "Token bucket period has to be at least one tick"
+        );
+        TokenBucket {
+            rate,
+            period,
+            burst,
+            level: (burst as u64).saturating_mul(period),
+            last: None,
+        }
+    }
+
+    pub fn burst(&self) -> usize {
+        self.burst
+    }
+
+    fn level_at(&self, now: u64) -> u64 {
+        let elapsed = self.last.map_or(0, |last| now.saturating_sub(last));
+        let full = (self.burst as u64).saturating_mul(self.period);
+        self.level
+            .saturating_add(elapsed.saturating_mul(self.rate))
+            .min(full)
+    }
+
+    // bytes that may go out at `now`
+    pub fn available(&self, now: u64) -> usize {
+        (self.level_at(now) / self.period) as usize
+    }
+
+    // whether a frame of `len` bytes may go out at `now`
+    pub fn allows(&self, now: u64, len: usize) -> bool {
+        let available = self.available(now);
+        len <= available || available >= self.burst
+    }
+
+    // pay for `len` bytes sent at `now`
+    pub fn take(&mut self, now: u64, len: usize) {
+        self.level = self
+            .level_at(now)
+            .saturating_sub((len as u64).saturating_mul(self.period));
+        self.last = Some(now);
+    }
+}
+
+impl RingBuffer {
+    pub fn set_flush_rate(&mut self, bucket: TokenBucket) {
+        self.bucket = Some(bucket);
+    }
+
+    pub fn clear_flush_rate(&mut self) {
+        self.bucket = None;
+    }
+
+    // bytes the flush rate allows right now, None when flushing isn't paced
+    pub fn flush_budget(&self) -> Option<usize> {
+        let bucket = self.bucket.as_ref()?;
+        Some(bucket.available(self.now()))
+    }
+
+    pub(crate) fn now(&self) -> u64 {
+        self.clock.as_ref().map_or(0, |clock| clock.now())
+    }
+
+    // whether the bucket lets the frame at the tail out, `sent` bytes into a flush that
+    // hasn't paid yet
+    pub(crate) fn next_frame_paced(&self, sent: usize) -> bool {
+        match self.get_next_message_size() {
+            Some(size) => self.frame_paced(size, sent),
+            None => true,
+        }
+    }
+
+    // same for a frame of `size` escaped bytes
+    pub(crate) fn frame_paced(&self, size: usize, sent: usize) -> bool {
+        let Some(bucket) = &self.bucket else {
+            return true;
+        };
+        let now = self.now();
+        match sent {
+            0 => bucket.allows(now, size + 1),
+            sent => size + 1 + sent <= bucket.available(now),
+        }
+    }
+
+    // bytes a dma grant may cover
+    #[cfg(feature = "embedded-dma")]
+    pub(crate) fn flush_allowance(&self) -> usize {
+        self.flush_budget().unwrap_or(usize::MAX)
+    }
+
+    pub(crate) fn pay_flush(&mut self, sent: usize) {
+        let now = self.now();
+        if let (Some(bucket), true) = (&mut self.bucket, sent > 0) {
+            bucket.take(now, sent);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::frame::Frame;
+    use crate::sink::{FlushToResult, MemorySink};
+
+    #[test]
+    fn the_bucket_refills_at_the_rate_up_to_the_burst() {
+        let mut bucket = TokenBucket::new(3, 2, 10);
+        assert_eq!(bucket.available(0), 10);
+        bucket.take(0, 10);
+        assert_eq!(bucket.available(0), 0);
+        // half a token carries over
+        assert_eq!(bucket.available(1), 1);
+        assert_eq!(bucket.available(2), 3);
+        assert_eq!(bucket.available(1000), 10);
+
+        bucket.take(1, 1);
+        assert_eq!(bucket.available(1), 0);
+        assert_eq!(bucket.available(2), 2);
+    }
+
+    #[test]
+    fn a_frame_longer_than_the_burst_needs_a_full_bucket() {
+        let mut bucket = TokenBucket::new(1, 1, 8);
+        assert!(bucket.allows(0, 20));
+        bucket.take(0, 20);
+        assert_eq!(bucket.available(0), 0);
+        assert!(!bucket.allows(7, 20));
+        assert!(bucket.allows(8, 20));
+    }
+
+    #[test]
+    #[should_panic(expected = "at least one tick")]
+    fn a_zero_period_is_refused() {
+        TokenBucket::new(1, 0, 8);
+    }
+
+    #[test]
+    fn flushes_stop_at_the_first_frame_without_tokens() {
+        let now = Arc::new(AtomicU64::new(0));
+        let mut ring = RingBuffer::new(256);
+        let clock = now.clone();
+        ring.set_clock(move || clock.load(Ordering::SeqCst));
+        for _ in 0..5 {
+            let _ = ring.log(Frame::new(b"abcd"));
+        }
+        // stamped frames, terminator included
+        let len = ring.len() / 5;
+        ring.set_flush_rate(TokenBucket::new(len as u64, 10, 2 * len));
+
+        let mut sink = MemorySink::new();
+        let FlushToResult::Ok(stats) = ring.flush_all_to(&mut sink) else {
+            panic!("flush failed");
+        };
+        assert_eq!(stats.frames, 2);
+        assert_eq!(ring.flush_budget(), Some(0));
+
+        now.store(10, Ordering::SeqCst);
+        assert_eq!(ring.flush_budget(), Some(len));
+        let _ = ring.flush_all_to(&mut sink);
+        assert_eq!(sink.frames().len(), 3);
+
+        ring.clear_flush_rate();
+        assert_eq!(ring.flush_budget(), None);
+        let _ = ring.flush_all_to(&mut sink);
+        assert_eq!(sink.frames().len(), 5);
+    }
+
+    #[test]
+    fn without_a_clock_the_bucket_never_refills() {
+        let len = Frame::new(b"abcd").encode().len();
+        let mut ring = RingBuffer::new(256);
+        ring.set_flush_rate(TokenBucket::new(1000, 1, len));
+        for _ in 0..3 {
+            let _ = ring.log(Frame::new(b"abcd"));
+        }
+        let mut sink = MemorySink::new();
+        let _ = ring.flush_all_to(&mut sink);
+        let _ = ring.flush_all_to(&mut sink);
+        assert_eq!(sink.frames().len(), 1);
+    }
+}
@@ -0,0 +1,152 @@
+// get the last frames out of ram when the program dies. on bare metal, from the panic
+// handler before the reset:
+//
+//     #[panic_handler]
+//     fn panic(info: &PanicInfo) -> ! {
+//         let ring = unsafe { &mut *addr_of_mut!(RING) };
+//         ring.panic_dump(&mut UartSink::new(&mut uart));
+//         cortex_m::peripheral::SCB::sys_reset()
+//     }
+//
+// with std, `register_panic_hook` does it for the global ring and logs the panic message
+// as a last error frame on top
+
+use crate::sink::{FlushSink, FlushToResult};
+use crate::RingBuffer;
+
+impl RingBuffer {
+    // every complete frame to `sink`, ignoring max_flush_size. a half written frame (the
+    // panic hit in the middle of a log) is left behind
+    pub fn panic_dump(&mut self, sink: &mut impl FlushSink) -> FlushToResult {
+        self.flush_all_to(sink)
+    }
+}
+
+#[cfg(feature = "std")]
+pub use hook::register_panic_hook;
+
+#[cfg(feature = "std")]
+mod hook {
+    use std::sync::Mutex;
+    use std::time::{Duration, Instant};
+
+    use crate::global;
+    use crate::level::Level;
+    use crate::sink::FlushSink;
+    use crate::PushResult;
+
+    // on panic, log the panic message to the global ring as an error, dump the ring to
+    // `sink` and then run the hook that was installed before (the default one prints the
+    // message to stderr). replaces a hook registered by an earlier call
+    pub fn register_panic_hook(sink: impl FlushSink + Send + 'static) {
+        let sink = Mutex::new(sink);
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            let mut sink = sink.lock().unwrap_or_else(|e| e.into_inner());
+            let message = info.to_string();
+            // the panicking thread may hold the ring itself, so don't wait for it forever
+            let deadline = Instant::now() + Duration::from_millis(100);
+            let mut global = loop {
+                if let Some(global) = global::try_lock() {
+                    break Some(global);
+                }
+                if Instant::now() >= deadline {
+                    break None;
+                }
+                std::thread::sleep(Duration::from_millis(1));
+            };
+            if let Some(ring) = global.as_mut().and_then(|global| global.as_mut()) {
+                // a full ring makes room first, the panic message belongs last
+                if let PushResult::Err(_) = ring.log_with_level(Level::Error, message.as_bytes()) {
+                    let _ = ring.panic_dump(&mut *sink);
+                    let _ = ring.log_with_level(Level::Error, message.as_bytes());
+                }
+                let _ = ring.panic_dump(&mut *sink);
+            }
+            drop(global);
+            let _ = sink.flush();
+            previous(info);
+        }));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::*;
+    use crate::frame::Frame;
+    use crate::sink::SinkResult;
+
+    #[derive(Default)]
+    struct Collect(Vec<Frame>);
+
+    impl FlushSink for Collect {
+        fn write_frame(&mut self, frame: &Frame) -> SinkResult {
+            self.0.push(frame.clone());
+            SinkResult::Ok
+        }
+    }
+
+    #[test]
+    fn the_dump_ignores_the_flush_budget_and_leaves_half_a_frame() {
+        let mut ring = RingBuffer::new(128);
+        ring.set_max_flush_size(1);
+        for payload in [b"one", b"two", b"six"] {
+            let _ = ring.log(Frame::new(payload));
+        }
+        let half = &Frame::new(b"cut off").encode()[..4];
+        ring.push_slice(half);
+
+        let mut sink = Collect::default();
+        assert!(matches!(ring.panic_dump(&mut sink), FlushToResult::Ok(_)));
+        let payloads = sink
+            .0
+            .iter()
+            .map(|frame| &frame.payload[..])
+            .collect::<Vec<_>>();
+        assert_eq!(payloads, [b"one", b"two", b"six"]);
+        assert_eq!(ring.len(), half.len());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn the_hook_dumps_the_global_ring_with_the_message_last() {
+        use std::sync::{Arc, Mutex};
+
+        use crate::global;
+        use crate::level::Level;
+        use crate::PushResult;
+
+        #[derive(Clone, Default)]
+        struct Shared(Arc<Mutex<Vec<Frame>>>);
+
+        impl FlushSink for Shared {
+            fn write_frame(&mut self, frame: &Frame) -> SinkResult {
+                self.0.lock().unwrap().push(frame.clone());
+                SinkResult::Ok
+            }
+        }
+
+        // without room for the message, what's there goes out first
+        let mut ring = RingBuffer::new(96);
+        let _ = ring.log(Frame::new(b"before"));
+        while let PushResult::Ok = ring.log(Frame::new(b"filler")) {}
+        global::set_global(ring);
+        let sink = Shared::default();
+        register_panic_hook(sink.clone());
+        let _ = std::thread::spawn(|| panic!("boom")).join();
+        // back to the default hook
+        let _ = std::panic::take_hook();
+        global::take_global();
+
+        let frames = sink.0.lock().unwrap();
+        let message = frames
+            .iter()
+            .position(|frame| frame.payload.ends_with(b"boom"))
+            .unwrap();
+        assert_eq!(frames[0].payload, b"before");
+        assert!(message > 1);
+        assert_eq!(frames[message].level, Some(Level::Error));
+    }
+}
@@ -0,0 +1,148 @@
+// writes frames as a pcap capture (link type USER0) so captured buffers open in wireshark.
+// each packet is one unescaped frame, [flags][header fields][payload][crc8], which is what
+// `contrib/ringbuffer.lua` dissects
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+use crate::clock::SystemClock;
+use crate::frame::Frame;
+use crate::sink::{FlushSink, SinkResult};
+
+// LINKTYPE_USER0, reserved for private use
+const LINKTYPE_USER0: u32 = 147;
+
+pub struct PcapSink<W: Write> {
+    writer: W,
+    clock: Option<SystemClock>,
+}
+
+impl<W: Write> PcapSink<W> {
+    // writes the pcap file header straight away
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        let mut header = Vec::with_capacity(24);
+        header.extend_from_slice(&0xa1b2_c3d4u32.to_le_bytes());
+        header.extend_from_slice(&2u16.to_le_bytes());
+        header.extend_from_slice(&4u16.to_le_bytes());
+        // utc offset and timestamp accuracy, both unused
+        header.extend_from_slice(&[0; 8]);
+        header.extend_from_slice(&65535u32.to_le_bytes());
+        header.extend_from_slice(&LINKTYPE_USER0.to_le_bytes());
+        writer.write_all(&header)?;
+        Ok(PcapSink {
+            writer,
+            clock: None,
+        })
+    }
+
+    // how to turn frame timestamps into packet times, without it every packet is
+    // stamped at the epoch
+    pub fn with_clock(mut self, clock: SystemClock) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    fn write_packet(&mut self, frame: &Frame) -> io::Result<()> {
+        let time = match (frame.timestamp, &self.clock) {
+            (Some(ticks), Some(clock)) => clock
+                .to_system_time(ticks)
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .unwrap_or_default(),
+            _ => Default::default(),
+        };
+        let data = frame.to_raw();
+        let mut record = Vec::with_capacity(16 + data.len());
+        record.extend_from_slice(&(time.as_secs() as u32).to_le_bytes());
+        record.extend_from_slice(&time.subsec_micros().to_le_bytes());
+        record.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        record.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        record.extend_from_slice(&data);
+        self.writer.write_all(&record)
+    }
+}
+
+impl PcapSink<BufWriter<File>> {
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::new(BufWriter::new(File::create(path)?))
+    }
+}
+
+impl<W: Write> FlushSink for PcapSink<W> {
+    fn write_frame(&mut self, frame: &Frame) -> SinkResult {
+        match self.write_packet(frame) {
+            Ok(()) => SinkResult::Ok,
+            Err(e) => SinkResult::Err(e.to_string()),
+        }
+    }
+
+    fn flush(&mut self) -> SinkResult {
+        match self.writer.flush() {
+            Ok(()) => SinkResult::Ok,
+            Err(e) => SinkResult::Err(e.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::frame::crc8;
+
+    // (seconds, microseconds, packet data) of each record after the file header
+    fn records(capture: &[u8]) -> Vec<(u32, u32, Vec<u8>)> {
+        let u32_at = |at: usize| u32::from_le_bytes(capture[at..at + 4].try_into().unwrap());
+        let mut records = Vec::new();
+        let mut at = 24;
+        while at < capture.len() {
+            let len = u32_at(at + 8) as usize;
+            assert_eq!(u32_at(at + 12) as usize, len);
+            records.push((
+                u32_at(at),
+                u32_at(at + 4),
+                capture[at + 16..at + 16 + len].to_vec(),
+            ));
+            at += 16 + len;
+        }
+        records
+    }
+
+    #[test]
+    fn the_file_header_names_the_user_link_type() {
+        let sink = PcapSink::new(Vec::new()).unwrap();
+        let header = sink.into_inner();
+        assert_eq!(header.len(), 24);
+        assert_eq!(header[..4], [0xd4, 0xc3, 0xb2, 0xa1]);
+        assert_eq!(header[20..], 147u32.to_le_bytes());
+    }
+
+    #[test]
+    fn packets_hold_unescaped_frames_at_their_time() {
+        let clock = SystemClock::new().with_resolution(Duration::from_micros(250));
+        let mut sink = PcapSink::new(Vec::new()).unwrap().with_clock(clock);
+        let timed = Frame::new(&[0x00, 0xdb]).with_timestamp(4_000_006);
+        let untimed = Frame::new(b"boot");
+        let _ = sink.write_frame(&timed);
+        let _ = sink.write_frame(&untimed);
+
+        let records = records(sink.get_ref());
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0], (1000, 1500, timed.to_raw()));
+        assert_eq!(records[1], (0, 0, untimed.to_raw()));
+        // the zero byte isn't escaped, the crc closes the packet
+        let (crc, body) = records[0].2.split_last().unwrap();
+        assert_eq!(*crc, crc8(body));
+        assert!(body.ends_with(&[0x00, 0xdb]));
+    }
+}
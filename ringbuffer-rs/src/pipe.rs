@@ -0,0 +1,281 @@
+// async in-memory pipe: the ring sits between two tasks, a full buffer parks the
+// writer and an empty one parks the reader until the other side makes progress
+
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll, Waker};
+
+use crate::RingBuffer;
+
+struct Shared {
+    ring: RingBuffer,
+    read_waker: Option<Waker>,
+    write_waker: Option<Waker>,
+    writer_closed: bool,
+    reader_closed: bool,
+}
+
+impl Shared {
+    fn poll_write(&mut self, cx: &mut Context, buf: &[u8]) -> Poll<io::Result<usize>> {
+        if self.reader_closed {
+            return Poll::Ready(Err(io::ErrorKind::BrokenPipe.into()));
+        }
+        let count = self.ring.push_slice(buf);
+        if count == 0 && !buf.is_empty() {
+            self.write_waker = Some(cx.waker().clone());
+            return Poll::Pending;
+        }
+        if let Some(waker) = self.read_waker.take() {
+            waker.wake();
+        }
+        Poll::Ready(Ok(count))
+    }
+
+    fn poll_read(&mut self, cx: &mut Context, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        let count = self.ring.pop_slice(buf);
+        if count == 0 && !buf.is_empty() {
+            // drained and nobody left to write: end of stream
+            if self.writer_closed {
+                return Poll::Ready(Ok(0));
+            }
+            self.read_waker = Some(cx.waker().clone());
+            return Poll::Pending;
+        }
+        if let Some(waker) = self.write_waker.take() {
+            waker.wake();
+        }
+        Poll::Ready(Ok(count))
+    }
+
+    fn close_writer(&mut self) {
+        self.writer_closed = true;
+        if let Some(waker) = self.read_waker.take() {
+            waker.wake();
+        }
+    }
+
+    fn close_reader(&mut self) {
+        self.reader_closed = true;
+        if let Some(waker) = self.write_waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+fn lock(shared: &Mutex<Shared>) -> MutexGuard<'_, Shared> {
+    shared.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+pub struct AsyncProducer {
+    shared: Arc<Mutex<Shared>>,
+}
+
+pub struct AsyncConsumer {
+    shared: Arc<Mutex<Shared>>,
+}
+
+// split a ring into the writing and reading ends of a pipe
+pub fn pipe(ring: RingBuffer) -> (AsyncProducer, AsyncConsumer) {
+    let shared = Arc::new(Mutex::new(Shared {
+        ring,
+        read_waker: None,
+        write_waker: None,
+        writer_closed: false,
+        reader_closed: false,
+    }));
+    (
+        AsyncProducer {
+            shared: shared.clone(),
+        },
+        AsyncConsumer { shared },
+    )
+}
+
+impl AsyncProducer {
+    // use the frame API on the shared ring, waking the reader afterwards
+    pub fn with_ring<R>(&self, f: impl FnOnce(&mut RingBuffer) -> R) -> R {
+        let mut shared = lock(&self.shared);
+        let result = f(&mut shared.ring);
+        if let Some(waker) = shared.read_waker.take() {
+            waker.wake();
+        }
+        result
+    }
+}
+
+impl AsyncConsumer {
+    // use the frame API on the shared ring, waking the writer afterwards
+    pub fn with_ring<R>(&self, f: impl FnOnce(&mut RingBuffer) -> R) -> R {
+        let mut shared = lock(&self.shared);
+        let result = f(&mut shared.ring);
+        if let Some(waker) = shared.write_waker.take() {
+            waker.wake();
+        }
+        result
+    }
+}
+
+impl Drop for AsyncProducer {
+    fn drop(&mut self) {
+        lock(&self.shared).close_writer();
+    }
+}
+
+impl Drop for AsyncConsumer {
+    fn drop(&mut self) {
+        lock(&self.shared).close_reader();
+    }
+}
+
+impl futures_io::AsyncWrite for AsyncProducer {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context, buf: &[u8]) -> Poll<io::Result<usize>> {
+        lock(&self.shared).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<io::Result<()>> {
+        lock(&self.shared).close_writer();
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl futures_io::AsyncRead for AsyncConsumer {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        lock(&self.shared).poll_read(cx, buf)
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl tokio::io::AsyncWrite for AsyncProducer {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context, buf: &[u8]) -> Poll<io::Result<usize>> {
+        lock(&self.shared).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<io::Result<()>> {
+        lock(&self.shared).close_writer();
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl tokio::io::AsyncRead for AsyncConsumer {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &mut tokio::io::ReadBuf,
+    ) -> Poll<io::Result<()>> {
+        let unfilled = buf.initialize_unfilled();
+        match lock(&self.shared).poll_read(cx, unfilled) {
+            Poll::Ready(Ok(count)) => {
+                buf.advance(count);
+                Poll::Ready(Ok(()))
+            }
+            Poll::Ready(Err(e)) => Poll::Ready(Err(e)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::task::Wake;
+
+    use futures_io::{AsyncRead, AsyncWrite};
+
+    use super::*;
+
+    #[derive(Default)]
+    struct CountingWaker(AtomicUsize);
+
+    impl Wake for CountingWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    fn waker() -> (Arc<CountingWaker>, Waker) {
+        let counter = Arc::new(CountingWaker::default());
+        (counter.clone(), Waker::from(counter))
+    }
+
+    fn wakes(counter: &CountingWaker) -> usize {
+        counter.0.load(Ordering::SeqCst)
+    }
+
+    #[test]
+    fn a_full_ring_parks_the_writer_until_the_reader_takes_some() {
+        let (mut producer, mut consumer) = pipe(RingBuffer::new(8));
+        let (writes, write_waker) = waker();
+        let mut cx = Context::from_waker(&write_waker);
+        let written = Pin::new(&mut producer).poll_write(&mut cx, b"0123456789");
+        assert!(matches!(written, Poll::Ready(Ok(count)) if count < 10));
+        assert!(Pin::new(&mut producer)
+            .poll_write(&mut cx, b"x")
+            .is_pending());
+        assert_eq!(wakes(&writes), 0);
+
+        let (_, read_waker) = waker();
+        let mut out = [0u8; 4];
+        let read =
+            Pin::new(&mut consumer).poll_read(&mut Context::from_waker(&read_waker), &mut out);
+        assert!(matches!(read, Poll::Ready(Ok(4))));
+        assert_eq!(wakes(&writes), 1);
+        assert!(matches!(
+            Pin::new(&mut producer).poll_write(&mut cx, b"x"),
+            Poll::Ready(Ok(1))
+        ));
+    }
+
+    #[test]
+    fn an_empty_ring_parks_the_reader_until_data_or_close() {
+        let (mut producer, mut consumer) = pipe(RingBuffer::new(16));
+        let (reads, read_waker) = waker();
+        let mut cx = Context::from_waker(&read_waker);
+        let mut out = [0u8; 8];
+        assert!(Pin::new(&mut consumer)
+            .poll_read(&mut cx, &mut out)
+            .is_pending());
+
+        let (_, write_waker) = waker();
+        let _ = Pin::new(&mut producer).poll_write(&mut Context::from_waker(&write_waker), b"hi");
+        assert_eq!(wakes(&reads), 1);
+        assert!(matches!(
+            Pin::new(&mut consumer).poll_read(&mut cx, &mut out),
+            Poll::Ready(Ok(2))
+        ));
+
+        assert!(Pin::new(&mut consumer)
+            .poll_read(&mut cx, &mut out)
+            .is_pending());
+        drop(producer);
+        assert_eq!(wakes(&reads), 2);
+        // drained and closed reads as end of stream
+        assert!(matches!(
+            Pin::new(&mut consumer).poll_read(&mut cx, &mut out),
+            Poll::Ready(Ok(0))
+        ));
+    }
+
+    #[test]
+    fn writing_after_the_reader_went_away_is_a_broken_pipe() {
+        let (mut producer, consumer) = pipe(RingBuffer::new(16));
+        drop(consumer);
+        let (_, write_waker) = waker();
+        let written =
+            Pin::new(&mut producer).poll_write(&mut Context::from_waker(&write_waker), b"x");
+        assert!(matches!(written, Poll::Ready(Err(e)) if e.kind() == io::ErrorKind::BrokenPipe));
+    }
+}
@@ -0,0 +1,277 @@
+// what happens when something doesn't fit. one policy for the whole ring, honored by
+// the frame paths (log, log_frame, log_message_with_crc, ...) and the byte paths (push,
+// push_slice, write grants) alike:
+//
+//     let mut ring = RingBuffer::new(4096).with_drop_policy(DropPolicy::OverwriteOldest);
+//
+// both make room a whole frame at a time, the byte paths count a half written frame at
+// the head as one. frames thrown out count as overwritten, not as overflows, and the
+// next frame logged carries a dropped marker for them like it does for frames turned
+// away. the policy only kicks in without a spill file, with one the spill file
+// takes what doesn't fit. the flight recorder and fault records always make room,
+// oldest first unless the policy is `Evict`. while broadcast readers hold on to what
+// they haven't read (see `broadcast`) their slow reader policies decide instead
+
+use crate::frame::{Dropped, Frame};
+use crate::level::Level;
+use crate::priority::rank;
+use crate::RingBuffer;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DropPolicy {
+    // turn the new data away and count it as dropped
+    #[default]
+    Reject,
+    // throw out the oldest stored data
+    OverwriteOldest,
+    // throw out the newest stored data, keeping what led up to the overflow
+    OverwriteNewest,
+    // throw out the least severe frames first, never ones more severe than the level
+    // (see `priority`). raw bytes rank at the level
+    Evict(Level),
+}
+
+impl RingBuffer {
+    pub fn with_drop_policy(mut self, policy: DropPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub fn set_drop_policy(&mut self, policy: DropPolicy) {
+        self.policy = policy;
+    }
+
+    pub fn drop_policy(&self) -> DropPolicy {
+        self.policy
+    }
+
+    // make `len` bytes of room for `frame` as the policy says, whether it fits now
+    pub(crate) fn make_room_by_policy(&mut self, frame: &Frame, len: usize) -> bool {
+        if len <= self.free() {
+            return true;
+        }
+        if self.retaining() {
+            return self.make_room_for_readers(len);
+        }
+        let mut lost = Dropped::default();
+        let made = match self.policy {
+            DropPolicy::Reject => None,
+            DropPolicy::OverwriteOldest => self.make_room(len, &mut lost),
+            DropPolicy::OverwriteNewest => self.drop_newest_frames(len, &mut lost),
+            DropPolicy::Evict(level) => {
+                self.evict_ranked(len, rank(frame).max(level as u8), &mut lost)
+            }
+        };
+        // the next stored frame reports what the policy threw out
+        self.dropped.add(lost);
+        made.is_some()
+    }
+
+    // make room for `len` raw bytes as the policy says, returns the free space after
+    pub(crate) fn free_bytes_by_policy(&mut self, len: usize) -> usize {
+        let needed = len.saturating_sub(self.free());
+        if needed == 0 || self.retaining() {
+            return self.free();
+        }
+        let mut lost = Dropped::default();
+        let len = len.min(self.size - 1);
+        let _ = match self.policy {
+            DropPolicy::Reject => None,
+            DropPolicy::OverwriteOldest => self.make_room(len, &mut lost),
+            DropPolicy::OverwriteNewest => self.drop_newest_frames(len, &mut lost),
+            DropPolicy::Evict(level) => self.evict_ranked(len, level as u8, &mut lost),
+        };
+        self.dropped.add(lost);
+        self.free()
+    }
+
+    // take frames off the head end until `len` bytes are free, a half written frame
+    // first. None when that isn't enough
+    fn drop_newest_frames(&mut self, len: usize, lost: &mut Dropped) -> Option<u64> {
+        if len >= self.size {
+            return None;
+        }
+        let complete = self.complete_len();
+        if complete < self.len() {
+            lost.add(self.lost_at(complete, self.len() - complete - 1));
+        }
+        self.set_head((self.tail + complete) % self.size);
+        let mut spans = self.spans();
+        let mut dropped = 0;
+        while self.free() < len {
+            let span = spans.pop()?;
+            lost.add(self.lost_at(span.offset, span.len));
+            self.set_head((self.tail + span.offset) % self.size);
+            dropped += 1;
+        }
+        self.counters.overwritten_frames += dropped;
+        Some(dropped)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sink::{FlushToResult, MemorySink};
+
+    // flush everything, then log one more frame into the empty ring so the losses still
+    // waiting for a marker get one
+    fn drain(ring: &mut RingBuffer) -> Vec<Frame> {
+        let mut frames = flush(ring);
+        assert!(matches!(
+            ring.log(Frame::new(b"end")),
+            crate::PushResult::Ok
+        ));
+        frames.extend(flush(ring));
+        assert_eq!(frames.pop().unwrap().payload, b"end");
+        frames
+    }
+
+    fn flush(ring: &mut RingBuffer) -> Vec<Frame> {
+        let mut sink = MemorySink::new();
+        match ring.flush_all_to(&mut sink) {
+            FlushToResult::Ok(stats) => assert_eq!(stats.corrupt, 0),
+            FlushToResult::Err(e) => panic!("{e}"),
+        }
+        sink.take()
+    }
+
+    // every message logged either comes out or is counted by a dropped marker
+    fn accounted(frames: &[Frame]) -> u32 {
+        frames
+            .iter()
+            .map(|frame| frame.dropped.map_or(1, |dropped| dropped.messages))
+            .sum()
+    }
+
+    // log `count` frames of rotating levels, returns the payloads turned away
+    fn log_levels(ring: &mut RingBuffer, count: u8) -> Vec<Vec<u8>> {
+        let levels = [Level::Error, Level::Debug, Level::Info, Level::Warn];
+        let mut rejected = Vec::new();
+        for i in 0..count {
+            let frame = Frame::new(&[b'a' + i; 6]).with_level(levels[i as usize % 4]);
+            if let crate::PushResult::Err(_) = ring.log(frame.clone()) {
+                rejected.push(frame.payload);
+            }
+        }
+        rejected
+    }
+
+    #[test]
+    fn overwrite_oldest_reports_what_it_threw_out() {
+        let mut ring = RingBuffer::new(64).with_drop_policy(DropPolicy::OverwriteOldest);
+        assert!(log_levels(&mut ring, 12).is_empty());
+        let frames = drain(&mut ring);
+        assert!(ring.stats().overwritten_frames > 0);
+        assert_eq!(ring.stats().overflows, 0);
+        assert_eq!(accounted(&frames), 12);
+        assert_eq!(frames.last().unwrap().payload, [b'a' + 11; 6]);
+    }
+
+    #[test]
+    fn overwrite_newest_reports_what_it_threw_out() {
+        let mut ring = RingBuffer::new(64).with_drop_policy(DropPolicy::OverwriteNewest);
+        assert!(log_levels(&mut ring, 12).is_empty());
+        let frames = drain(&mut ring);
+        assert!(ring.stats().overwritten_frames > 0);
+        assert_eq!(accounted(&frames), 12);
+        assert_eq!(frames[0].payload, [b'a'; 6]);
+        assert_eq!(frames.last().unwrap().payload, [b'a' + 11; 6]);
+    }
+
+    #[test]
+    fn evict_reports_what_it_threw_out() {
+        let mut ring = RingBuffer::new(64).with_drop_policy(DropPolicy::Evict(Level::Info));
+        let rejected = log_levels(&mut ring, 12);
+        let frames = drain(&mut ring);
+        assert!(ring.stats().overwritten_frames > 0);
+        assert_eq!(accounted(&frames), 12);
+        // nothing more severe than info is thrown out, only turned away when it doesn't fit
+        for i in (0..12).filter(|i| i % 4 == 0 || i % 4 == 3) {
+            let payload = [b'a' + i; 6];
+            let out = frames.iter().any(|frame| frame.payload == payload);
+            assert!(out || rejected.iter().any(|turned| turned[..] == payload));
+        }
+    }
+
+    #[test]
+    fn byte_path_overwrites_whole_frames() {
+        let mut ring = RingBuffer::new(64).with_drop_policy(DropPolicy::OverwriteOldest);
+        for i in 0..12 {
+            let bytes = Frame::new(&[b'a' + i; 6]).encode();
+            assert_eq!(ring.push_slice(&bytes), bytes.len());
+        }
+        let frames = drain(&mut ring);
+        assert!(ring.stats().overwritten_frames > 0);
+        assert_eq!(accounted(&frames), 12);
+        let last = frames.iter().rfind(|frame| frame.dropped.is_none());
+        assert_eq!(last.unwrap().payload, [b'a' + 11; 6]);
+    }
+
+    #[test]
+    fn byte_path_overwrite_newest_keeps_whole_frames() {
+        let mut ring = RingBuffer::new(64).with_drop_policy(DropPolicy::OverwriteNewest);
+        for i in 0..12 {
+            let _ = ring.push_slice(&Frame::new(&[b'a' + i; 6]).encode());
+        }
+        let frames = flush(&mut ring);
+        assert_eq!(frames[0].payload, [b'a'; 6]);
+        assert_eq!(frames.last().unwrap().payload, [b'a' + 11; 6]);
+    }
+
+    // fill the ring with 16 byte frames, returns how many went in
+    fn fill(ring: &mut RingBuffer) -> u32 {
+        let mut logged = 0;
+        while ring.free() >= 24 {
+            assert!(matches!(
+                ring.log(Frame::new(&[b'a' + logged as u8; 16])),
+                crate::PushResult::Ok
+            ));
+            logged += 1;
+        }
+        logged
+    }
+
+    #[test]
+    fn grant_reserve_makes_room_and_reports_it() {
+        for policy in [DropPolicy::OverwriteOldest, DropPolicy::Evict(Level::Info)] {
+            let mut ring = RingBuffer::new(64).with_drop_policy(policy);
+            let logged = fill(&mut ring);
+            assert!(ring.write_grant().reserve(40) >= 40);
+            assert!(ring.stats().overwritten_frames > 0);
+
+            let frames = drain(&mut ring);
+            assert!(frames.last().unwrap().dropped.is_some());
+            assert_eq!(accounted(&frames), logged);
+        }
+    }
+
+    #[test]
+    fn grant_reserve_under_reject_leaves_the_frames() {
+        let mut ring = RingBuffer::new(64);
+        let logged = fill(&mut ring);
+        let free = ring.free();
+        assert_eq!(ring.write_grant().reserve(40), free);
+        let frames = drain(&mut ring);
+        assert_eq!(frames.len() as u32, logged);
+        assert!(frames.iter().all(|frame| frame.dropped.is_none()));
+    }
+
+    #[test]
+    fn log_message_with_crc_follows_the_policy() {
+        for policy in [DropPolicy::OverwriteOldest, DropPolicy::Evict(Level::Info)] {
+            let mut ring = RingBuffer::new(64).with_drop_policy(policy);
+            for i in 0..12 {
+                assert!(matches!(
+                    ring.log_message_with_crc(&[b'a' + i; 6]),
+                    crate::PushResult::Ok
+                ));
+            }
+            let frames = drain(&mut ring);
+            assert!(ring.stats().overwritten_frames > 0);
+            assert_eq!(ring.stats().overflows, 0);
+            assert_eq!(accounted(&frames), 12);
+            assert_eq!(frames.last().unwrap().payload, [b'a' + 11; 6]);
+        }
+    }
+}
@@ -0,0 +1,247 @@
+// priority lanes by log level: with the `DropPolicy::Evict` policy debug chatter goes
+// before warnings and warnings before errors when frames have to make room, and
+// flushing can send the most severe frames first so they're out before a crash or a
+// lost link:
+//
+//     ring.set_drop_policy(DropPolicy::Evict(Level::Warn));
+//     ring.set_priority_flush(true);
+//
+// frames without a level rank as info. within a level it's still oldest first. a new
+// frame only pushes out frames of its own level or below, and never ones more severe
+// than the policy's level, so a flood of debug frames can't displace a single error:
+// with only more severe frames left it's turned away instead. eviction and the flush_to
+// family go through the lanes, the other ways of consuming (pop, dma grants, read views)
+// still take frames in order. taking a frame out of the middle moves the older ones up,
+// which makes both cost a pass over the stored frames
+
+use alloc::vec::Vec;
+
+use crate::frame::{Dropped, Frame, FrameResult};
+use crate::level::Level;
+use crate::RingBuffer;
+
+// a stored frame: offset from the tail, escaped length without the terminator, and its
+// rank, lower is more severe. corrupt frames rank last
+pub(crate) struct Span {
+    pub(crate) offset: usize,
+    pub(crate) len: usize,
+    rank: u8,
+}
+
+pub(crate) fn rank(frame: &Frame) -> u8 {
+    frame.level.unwrap_or(Level::Info) as u8
+}
+
+impl RingBuffer {
+    pub fn set_priority_flush(&mut self, enabled: bool) {
+        self.priority_flush = enabled;
+    }
+
+    pub(crate) fn spans(&self) -> Vec<Span> {
+        let mut offset = 0;
+        self.frame_bytes()
+            .iter()
+            .map(|bytes| {
+                let rank = match Frame::decode(bytes) {
+                    FrameResult::Ok(frame) => rank(&frame),
+                    FrameResult::Err(_) => u8::MAX,
+                };
+                let span = Span {
+                    offset,
+                    len: bytes.len(),
+                    rank,
+                };
+                offset += bytes.len() + 1;
+                span
+            })
+            .collect()
+    }
+
+    // take the `len` bytes at `offset` from the tail out, moving the ones before them up
+    pub(crate) fn remove_stored(&mut self, offset: usize, len: usize) {
+        for i in (0..offset).rev() {
+            let byte = self.stored_byte(i);
+            self.set_byte_at((self.tail + i + len) % self.size, byte);
+        }
+        // sent bytes before the removed ones didn't move
+        let sent = self.sent;
+        self.readers_removed(offset, len);
+        self.advance_tail(len);
+        if sent.is_some_and(|sent| sent <= offset) {
+            self.sent = sent;
+        }
+    }
+
+    // throw out the least severe frames, none more severe than `floor`, until `len`
+    // bytes are free, adding what they stood for to `lost`. returns how many went, None
+    // when that isn't enough (nothing's evicted then)
+    pub(crate) fn evict_ranked(
+        &mut self,
+        len: usize,
+        floor: u8,
+        lost: &mut Dropped,
+    ) -> Option<u64> {
+        if len >= self.size {
+            return None;
+        }
+        let mut spans = self.spans();
+        let evictable: usize = spans
+            .iter()
+            .filter(|span| span.rank >= floor)
+            .map(|span| span.len + 1)
+            .sum();
+        if self.free() + evictable < len {
+            return None;
+        }
+
+        let mut evicted = 0;
+        while self.free() < len {
+            // least severe, and the oldest of those
+            let Some(victim) = (0..spans.len()).max_by_key(|&i| (spans[i].rank, usize::MAX - i))
+            else {
+                break;
+            };
+            let span = spans.remove(victim);
+            lost.add(self.lost_at(span.offset, span.len));
+            self.remove_stored(span.offset, span.len + 1);
+            // the older frames moved up with the tail, only the newer ones are closer now
+            for later in &mut spans[victim..] {
+                later.offset -= span.len + 1;
+            }
+            evicted += 1;
+        }
+        self.counters.overwritten_frames += evicted;
+        Some(evicted)
+    }
+
+    // the escaped bytes of the frame to flush next and its offset from the tail: the most
+    // severe one when flushing by priority, else the one at the tail. with acks on only
+    // frames that haven't been sent count
+    pub(crate) fn next_flush_bytes(&self) -> Option<(usize, Vec<u8>)> {
+        let start = self.unsent_start();
+        if !self.priority_flush {
+            return match start {
+                0 => self.peek_frame_bytes().map(|bytes| (0, bytes)),
+                start => self.frame_bytes_at(start).map(|bytes| (start, bytes)),
+            };
+        }
+        let span = self
+            .spans()
+            .into_iter()
+            .filter(|span| span.offset >= start)
+            .min_by_key(|span| (span.rank, span.offset))?;
+        let bytes = (0..span.len)
+            .map(|i| self.stored_byte(span.offset + i))
+            .collect();
+        Some((span.offset, bytes))
+    }
+
+    // take the flushed frame of `len` escaped bytes at `offset` out, terminator and all
+    pub(crate) fn consume_frame_at(&mut self, offset: usize, len: usize) {
+        match offset {
+            0 => self.consume_frame(len),
+            offset => self.remove_stored(offset, len + 1),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy::DropPolicy;
+    use crate::sink::MemorySink;
+    use crate::PushResult;
+
+    fn frame(level: Level, i: u8) -> Frame {
+        Frame::new(&[i; 16]).with_level(level)
+    }
+
+    // payload ids of the flushed frames, dropped markers left out
+    fn flushed(ring: &mut RingBuffer) -> Vec<u8> {
+        let mut sink = MemorySink::new();
+        let _ = ring.flush_all_to(&mut sink);
+        sink.take()
+            .iter()
+            .filter(|frame| !frame.payload.is_empty())
+            .map(|frame| frame.payload[0])
+            .collect()
+    }
+
+    // room for exactly four frames
+    fn full_ring(levels: [Level; 4]) -> RingBuffer {
+        let len = frame(Level::Info, 1).encode().len();
+        let mut ring =
+            RingBuffer::new(4 * len + 1).with_drop_policy(DropPolicy::Evict(Level::Warn));
+        for (i, level) in levels.into_iter().enumerate() {
+            assert!(matches!(
+                ring.log(frame(level, i as u8 + 1)),
+                PushResult::Ok
+            ));
+        }
+        assert_eq!(ring.free(), 0);
+        ring
+    }
+
+    #[test]
+    fn the_least_severe_and_oldest_frames_go_first() {
+        let mut ring = full_ring([Level::Debug, Level::Warn, Level::Debug, Level::Warn]);
+        assert!(matches!(ring.log(frame(Level::Error, 5)), PushResult::Ok));
+        // the dropped marker needs room too, so both debug frames went
+        assert_eq!(ring.stats().overwritten_frames, 2);
+        assert_eq!(flushed(&mut ring), [2, 4, 5]);
+    }
+
+    #[test]
+    fn a_flood_of_chatter_cant_displace_more_severe_frames() {
+        let mut ring = full_ring([Level::Warn, Level::Error, Level::Warn, Level::Error]);
+        assert!(matches!(
+            ring.log(frame(Level::Debug, 5)),
+            PushResult::Err(_)
+        ));
+        // nor can an error push out frames more severe than the policy's level
+        let mut errors = full_ring([Level::Error; 4]);
+        assert!(matches!(
+            errors.log(frame(Level::Error, 5)),
+            PushResult::Err(_)
+        ));
+        assert_eq!(flushed(&mut ring), [1, 2, 3, 4]);
+        assert_eq!(flushed(&mut errors), [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn frames_without_a_level_rank_as_info() {
+        let mut ring = full_ring([Level::Debug, Level::Info, Level::Info, Level::Info]);
+        let _ = ring.log(Frame::new(&[5; 16]));
+        // the debug frame first, then the oldest info one to fit the dropped marker
+        assert_eq!(flushed(&mut ring), [3, 4, 5]);
+
+        // and can't push out warnings
+        let mut ring = full_ring([Level::Warn; 4]);
+        assert!(matches!(ring.log(Frame::new(&[5; 16])), PushResult::Err(_)));
+    }
+
+    #[test]
+    fn priority_flushing_sends_the_most_severe_first() {
+        let mut ring = RingBuffer::new(256);
+        ring.set_priority_flush(true);
+        for (i, level) in [
+            Level::Debug,
+            Level::Error,
+            Level::Info,
+            Level::Warn,
+            Level::Error,
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let _ = ring.log(frame(level, i as u8 + 1));
+        }
+        assert_eq!(flushed(&mut ring), [2, 5, 4, 3, 1]);
+        assert!(ring.is_empty());
+
+        ring.set_priority_flush(false);
+        let _ = ring.log(frame(Level::Debug, 1));
+        let _ = ring.log(frame(Level::Error, 2));
+        assert_eq!(flushed(&mut ring), [1, 2]);
+    }
+}
@@ -0,0 +1,227 @@
+// buffer health in the prometheus text exposition format, plus a tiny http endpoint
+// serving it so operators can alert on log loss
+
+use std::fmt::Write as _;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::thread;
+use std::time::Duration;
+
+use crate::shared::SharedRing;
+use crate::stats::LatencyHistogram;
+use crate::RingBuffer;
+
+impl RingBuffer {
+    // every metric is named `<prefix>_...`, e.g. `ringbuffer`
+    pub fn prometheus_metrics(&self, prefix: &str) -> String {
+        let counters = &self.counters;
+        let mut out = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, value: u64| {
+            let _ = writeln!(out, "# HELP {}_{} {}", prefix, name, help);
+            let _ = writeln!(out, "# TYPE {}_{} {}", prefix, name, kind);
+            let _ = writeln!(out, "{}_{} {}", prefix, name, value);
+        };
+        metric(
+            "capacity_bytes",
+            "gauge",
+            "Usable buffer size",
+            self.size as u64 - 1,
+        );
+        metric(
+            "occupancy_bytes",
+            "gauge",
+            "Bytes currently stored",
+            self.len() as u64,
+        );
+        metric(
+            "high_water_bytes",
+            "gauge",
+            "Most bytes stored at once",
+            counters.high_water as u64,
+        );
+        metric(
+            "pushed_bytes_total",
+            "counter",
+            "Bytes written into the buffer",
+            counters.pushed_bytes,
+        );
+        metric(
+            "popped_bytes_total",
+            "counter",
+            "Bytes taken out of the buffer",
+            counters.popped_bytes,
+        );
+        metric(
+            "logged_frames_total",
+            "counter",
+            "Frames logged",
+            counters.logged_frames,
+        );
+        metric(
+            "overflows_total",
+            "counter",
+            "Frames rejected for lack of space",
+            counters.overflows,
+        );
+        metric(
+            "overwritten_frames_total",
+            "counter",
+            "Frames thrown out to make room for newer ones",
+            counters.overwritten_frames,
+        );
+        metric(
+            "crc_errors_total",
+            "counter",
+            "Frames that failed to decode",
+            counters.crc_errors,
+        );
+        metric(
+            "flushed_frames_total",
+            "counter",
+            "Frames flushed intact",
+            counters.flushed_frames,
+        );
+        metric(
+            "flushed_bytes_total",
+            "counter",
+            "Bytes of flushed frames",
+            counters.flushed_bytes,
+        );
+        metric(
+            "window_stalls_total",
+            "counter",
+            "Flushes held back by a full ack window",
+            counters.window_stalls,
+        );
+
+        let latency = &counters.flush_latency;
+        let name = format!("{}_flush_latency_ticks", prefix);
+        let _ = writeln!(
+            out,
+            "# HELP {} Clock ticks frames waited before flushing",
+            name
+        );
+        let _ = writeln!(out, "# TYPE {} histogram", name);
+        let mut cumulative = 0;
+        for (index, &count) in latency.buckets().iter().enumerate() {
+            cumulative += count;
+            let le =
+                LatencyHistogram::upper_bound(index).map_or("+Inf".to_string(), |b| b.to_string());
+            let _ = writeln!(out, "{}_bucket{{le=\"{}\"}} {}", name, le, cumulative);
+        }
+        let _ = writeln!(out, "{}_sum {}", name, latency.sum());
+        let _ = writeln!(out, "{}_count {}", name, latency.count());
+        out
+    }
+}
+
+// serves `GET /metrics` for a shared ring from a background thread
+pub struct MetricsServer {
+    local_addr: SocketAddr,
+}
+
+impl MetricsServer {
+    pub fn bind(addr: impl ToSocketAddrs, ring: SharedRing, prefix: &str) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        let local_addr = listener.local_addr()?;
+        let prefix = prefix.to_string();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                // scrapes are tiny, a stuck client shouldn't hold up the next one for long
+                let _ = stream.set_read_timeout(Some(Duration::from_secs(5)));
+                let _ = serve(stream, &ring, &prefix);
+            }
+        });
+        Ok(MetricsServer { local_addr })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+}
+
+fn serve(stream: TcpStream, ring: &SharedRing, prefix: &str) -> io::Result<()> {
+    let mut reader = BufReader::new(stream);
+    let mut request = String::new();
+    reader.read_line(&mut request)?;
+    // skip the headers
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 2 {
+        line.clear();
+    }
+
+    let mut stream = reader.into_inner();
+    let path = request.split_whitespace().nth(1).unwrap_or("");
+    if path != "/metrics" {
+        return stream.write_all(
+            b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        );
+    }
+    let body = ring.with_ring(|ring| ring.prometheus_metrics(prefix));
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        body.len(),
+        body
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::*;
+    use crate::frame::Frame;
+
+    // the value of one sample line
+    fn sample(metrics: &str, name: &str) -> u64 {
+        metrics
+            .lines()
+            .find_map(|line| line.strip_prefix(name)?.strip_prefix(' '))
+            .unwrap()
+            .parse()
+            .unwrap()
+    }
+
+    #[test]
+    fn metrics_follow_the_ring() {
+        let mut ring = RingBuffer::new(32);
+        let _ = ring.log(Frame::new(b"kept"));
+        let _ = ring.log(Frame::new(&[7; 40]));
+        let stored = ring.len() as u64;
+
+        let metrics = ring.prometheus_metrics("rb");
+        assert!(metrics.contains("# TYPE rb_overflows_total counter\n"));
+        assert_eq!(sample(&metrics, "rb_capacity_bytes"), 31);
+        assert_eq!(sample(&metrics, "rb_occupancy_bytes"), stored);
+        assert_eq!(sample(&metrics, "rb_logged_frames_total"), 1);
+        assert_eq!(sample(&metrics, "rb_overflows_total"), 1);
+        assert_eq!(
+            sample(&metrics, "rb_flush_latency_ticks_bucket{le=\"+Inf\"}"),
+            0
+        );
+    }
+
+    fn get(addr: SocketAddr, path: &str) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(stream, "GET {} HTTP/1.1\r\nHost: test\r\n\r\n", path).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn the_server_answers_scrapes() {
+        let ring = SharedRing::new(RingBuffer::new(64));
+        let _ = ring.log(Frame::new(b"x"));
+        let server = MetricsServer::bind("127.0.0.1:0", ring.clone(), "rb").unwrap();
+
+        let response = get(server.local_addr(), "/metrics");
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        assert!(head.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(head.contains(&format!("Content-Length: {}", body.len())));
+        assert_eq!(body, ring.with_ring(|ring| ring.prometheus_metrics("rb")));
+
+        assert!(get(server.local_addr(), "/").starts_with("HTTP/1.1 404 Not Found\r\n"));
+    }
+}
@@ -0,0 +1,161 @@
+// protobuf payloads for schemas already defined in .proto files. the payload starts with
+// a varint naming the message type, the message follows. the tag stays free for the
+// producer (subsystem ids, routing), `ProtoDispatcher` decodes by type on the host:
+//
+//     ring.log_protobuf(MOTOR_STATUS, &status);
+//     ring.log(Frame::protobuf(MOTOR_STATUS, &status).with_tag(MOTOR));
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+use prost::encoding::{decode_varint, encode_varint};
+use prost::Message;
+
+use crate::frame::{Frame, FrameResult, Handler};
+use crate::kind::FrameKind;
+use crate::{PushResult, RingBuffer};
+
+impl Frame {
+    // a protobuf frame carrying `message`, with `type_id` identifying its type
+    pub fn protobuf(type_id: u32, message: &impl Message) -> Frame {
+        let mut payload = Vec::with_capacity(5 + message.encoded_len());
+        encode_varint(u64::from(type_id), &mut payload);
+        message.encode_raw(&mut payload);
+        Frame::new(&payload).with_kind(FrameKind::Protobuf)
+    }
+
+    // the type id and encoded message of a protobuf frame, None for other kinds or when
+    // the type prefix is malformed
+    pub fn protobuf_parts(&self) -> Option<(u32, &[u8])> {
+        if self.kind != Some(FrameKind::Protobuf) {
+            return None;
+        }
+        let mut rest = self.payload.as_slice();
+        let type_id = decode_varint(&mut rest).ok()?;
+        Some((u32::try_from(type_id).ok()?, rest))
+    }
+
+    pub fn protobuf_type(&self) -> Option<u32> {
+        self.protobuf_parts().map(|(type_id, _)| type_id)
+    }
+
+    // the message of a protobuf frame as `M`, None for other kinds or if it doesn't parse.
+    // checking the type is up to the caller
+    pub fn protobuf_as<M: Message + Default>(&self) -> Option<M> {
+        let (_, message) = self.protobuf_parts()?;
+        M::decode(message).ok()
+    }
+}
+
+impl RingBuffer {
+    pub fn log_protobuf(&mut self, type_id: u32, message: &impl Message) -> PushResult {
+        self.log(Frame::protobuf(type_id, message))
+    }
+}
+
+// decodes a frame into its message type and handles it, false if it didn't parse
+type Decoder<'a> = Box<dyn FnMut(&Frame) -> bool + 'a>;
+
+// consumer side: drains the buffer, decodes protobuf frames by their type id and hands
+// the messages to the handler registered for it
+#[derive(Default)]
+pub struct ProtoDispatcher<'a> {
+    handlers: Vec<(u32, Decoder<'a>)>,
+    fallback: Option<Handler<'a>>,
+}
+
+impl<'a> ProtoDispatcher<'a> {
+    pub fn new() -> Self {
+        ProtoDispatcher {
+            handlers: Vec::new(),
+            fallback: None,
+        }
+    }
+
+    pub fn on<M: Message + Default>(
+        mut self,
+        type_id: u32,
+        mut handler: impl FnMut(M) + 'a,
+    ) -> Self {
+        self.handlers.retain(|(id, _)| *id != type_id);
+        let decode = move |frame: &Frame| match frame.protobuf_as::<M>() {
+            Some(message) => {
+                handler(message);
+                true
+            }
+            None => false,
+        };
+        self.handlers.push((type_id, Box::new(decode)));
+        self
+    }
+
+    // gets non-protobuf frames and protobuf frames of an unregistered type, otherwise
+    // they're dropped
+    pub fn fallback(mut self, sink: impl FnMut(Frame) + 'a) -> Self {
+        self.fallback = Some(Box::new(sink));
+        self
+    }
+
+    // flush every complete frame, returns how many messages were decoded and handled.
+    // corrupt frames and payloads that don't parse as their type are dropped
+    pub fn dispatch(&mut self, ring: &mut RingBuffer) -> usize {
+        let mut handled = 0;
+        while let Some(bytes) = ring.pop_frame_bytes() {
+            let frame = match ring.decode_flushed(&bytes) {
+                FrameResult::Ok(frame) => frame,
+                FrameResult::Err(_) => continue,
+            };
+            let handler = frame.protobuf_type().and_then(|type_id| {
+                self.handlers
+                    .iter_mut()
+                    .find(|(id, _)| *id == type_id)
+                    .map(|(_, handler)| handler)
+            });
+            match (handler, self.fallback.as_mut()) {
+                (Some(handler), _) => {
+                    if handler(&frame) {
+                        handled += 1;
+                    }
+                }
+                (None, Some(fallback)) => fallback(frame),
+                (None, None) => {}
+            }
+        }
+        handled
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::String;
+
+    use super::*;
+
+    #[test]
+    fn the_type_travels_in_the_payload() {
+        let frame = Frame::protobuf(300, &1200u32).with_tag(7);
+        assert_eq!(frame.tag, Some(7));
+        assert_eq!(frame.protobuf_type(), Some(300));
+        assert_eq!(frame.protobuf_as::<u32>(), Some(1200));
+        assert_eq!(Frame::new(b"text").protobuf_type(), None);
+    }
+
+    #[test]
+    fn dispatch_goes_by_type_whatever_the_tag() {
+        let mut ring = RingBuffer::new(256);
+        let _ = ring.log(Frame::protobuf(1, &10u32).with_tag(99));
+        let _ = ring.log_protobuf(2, &String::from("running"));
+        let _ = ring.log(Frame::new(b"plain").with_tag(1));
+
+        let mut ones = Vec::new();
+        let mut others = Vec::new();
+        let handled = ProtoDispatcher::new()
+            .on(1, |rpm: u32| ones.push(rpm))
+            .fallback(|frame| others.push(frame))
+            .dispatch(&mut ring);
+        assert_eq!(handled, 1);
+        assert_eq!(ones, [10]);
+        assert_eq!(others.len(), 2);
+        assert_eq!(others[0].protobuf_type(), Some(2));
+    }
+}
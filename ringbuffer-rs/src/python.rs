@@ -0,0 +1,365 @@
+// python bindings, so test benches and notebooks parse device dumps with the same code the
+// firmware uses instead of a python port of the framing. pyproject.toml has the build
+// settings, so the extension builds with
+//
+//     maturin develop
+//
+// and then
+//
+//     import ringbuffer
+//     decoder = ringbuffer.FrameDecoder()
+//     for frame in decoder.feed(open("dump.bin", "rb").read()):
+//         print(frame.seq, frame.level, frame.text)
+
+use pyo3::exceptions::{PyBufferError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict};
+
+use crate::decoder::FrameDecoder;
+use crate::frame::{Frame, FrameResult};
+use crate::interned::{RehydrateResult, StringTable, TableResult};
+use crate::json;
+use crate::kv::Value;
+use crate::level::Level;
+use crate::{PushResult, RingBuffer};
+
+fn level(value: u8) -> PyResult<Level> {
+    Level::from_u8(value).ok_or_else(|| PyValueError::new_err(format!("bad level {}", value)))
+}
+
+#[pyclass(name = "Frame", frozen)]
+pub struct PyFrame(Frame);
+
+#[pymethods]
+impl PyFrame {
+    // parse one frame in wire format, with or without its terminator
+    #[staticmethod]
+    fn decode(bytes: &[u8]) -> PyResult<Self> {
+        let bytes = bytes
+            .strip_suffix(&[crate::frame::TERMINATOR])
+            .unwrap_or(bytes);
+        match Frame::decode(bytes) {
+            FrameResult::Ok(frame) => Ok(PyFrame(frame)),
+            FrameResult::Err(e) => Err(PyValueError::new_err(e)),
+        }
+    }
+
+    fn encode<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.0.encode())
+    }
+
+    #[getter]
+    fn payload<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.0.payload)
+    }
+
+    // the payload as text, invalid utf-8 replaced
+    #[getter]
+    fn text(&self) -> String {
+        String::from_utf8_lossy(&self.0.payload).into_owned()
+    }
+
+    #[getter]
+    fn timestamp(&self) -> Option<u64> {
+        self.0.timestamp
+    }
+
+    #[getter]
+    fn seq(&self) -> Option<u16> {
+        self.0.seq
+    }
+
+    #[getter]
+    fn level(&self) -> Option<&'static str> {
+        self.0.level.map(|level| level.as_str())
+    }
+
+    #[getter]
+    fn tag(&self) -> Option<u16> {
+        self.0.tag
+    }
+
+    #[getter]
+    fn channel(&self) -> Option<u8> {
+        self.0.channel
+    }
+
+    #[getter]
+    fn kind(&self) -> Option<&'static str> {
+        self.0.kind.map(json::kind_name)
+    }
+
+    // (messages, bytes) lost in front of this frame, for drop markers
+    #[getter]
+    fn dropped(&self) -> Option<(u32, u32)> {
+        self.0
+            .dropped
+            .map(|dropped| (dropped.messages, dropped.bytes))
+    }
+
+    // the pairs of a key-value frame as a dict, None for other kinds
+    fn pairs<'py>(&self, py: Python<'py>) -> PyResult<Option<Bound<'py, PyDict>>> {
+        let Some(pairs) = self.0.pairs() else {
+            return Ok(None);
+        };
+        let dict = PyDict::new(py);
+        for (key, value) in pairs {
+            match value {
+                Value::Str(v) => dict.set_item(key, v.as_ref())?,
+                Value::Int(v) => dict.set_item(key, v)?,
+                Value::Float(v) => dict.set_item(key, v)?,
+                Value::Bool(v) => dict.set_item(key, v)?,
+            }
+        }
+        Ok(Some(dict))
+    }
+
+    fn to_json(&self) -> String {
+        let mut out = String::from("{");
+        json::write_frame_fields(&mut out, &self.0);
+        out.push('}');
+        out
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Frame({})", self.to_json())
+    }
+}
+
+#[pyclass(name = "FrameDecoder")]
+pub struct PyFrameDecoder(FrameDecoder);
+
+#[pymethods]
+impl PyFrameDecoder {
+    #[new]
+    #[pyo3(signature = (max_frame_len = None))]
+    fn new(max_frame_len: Option<usize>) -> Self {
+        let decoder = FrameDecoder::new();
+        PyFrameDecoder(match max_frame_len {
+            Some(max_frame_len) => decoder.with_max_frame_len(max_frame_len),
+            None => decoder,
+        })
+    }
+
+    fn feed(&mut self, bytes: &[u8]) -> Vec<PyFrame> {
+        self.0.feed(bytes).into_iter().map(PyFrame).collect()
+    }
+
+    #[getter]
+    fn pending(&self) -> usize {
+        self.0.pending()
+    }
+
+    #[getter]
+    fn corrupt(&self) -> usize {
+        self.0.corrupt()
+    }
+
+    #[getter]
+    fn oversized(&self) -> usize {
+        self.0.oversized()
+    }
+
+    fn reset(&mut self) {
+        self.0.reset();
+    }
+}
+
+#[pyclass(name = "StringTable")]
+pub struct PyStringTable(StringTable);
+
+// colliding message ids come out as ValueError
+fn table(result: TableResult) -> PyResult<PyStringTable> {
+    match result {
+        TableResult::Ok(table) => Ok(PyStringTable(table)),
+        TableResult::Err(e) => Err(PyValueError::new_err(e)),
+    }
+}
+
+#[pymethods]
+impl PyStringTable {
+    #[new]
+    fn new() -> Self {
+        PyStringTable(StringTable::new())
+    }
+
+    // `<id> <format>` lines
+    #[staticmethod]
+    fn parse(text: &str) -> PyResult<Self> {
+        table(StringTable::parse(text))
+    }
+
+    // the raw contents of the firmware's `ring_log` section
+    #[staticmethod]
+    fn from_section(bytes: &[u8]) -> PyResult<Self> {
+        table(StringTable::from_section(bytes))
+    }
+
+    // false when `id` is already taken by another format string
+    fn insert(&mut self, id: u16, format: &str) -> bool {
+        self.0.insert(id, format)
+    }
+
+    fn rehydrate(&self, frame: &PyFrame) -> PyResult<String> {
+        match self.0.rehydrate(&frame.0) {
+            RehydrateResult::Ok(text) => Ok(text),
+            RehydrateResult::Err(e) => Err(PyValueError::new_err(e)),
+        }
+    }
+}
+
+// the ring itself, mostly useful for producing test streams from python
+#[pyclass(name = "RingBuffer", unsendable)]
+pub struct PyRingBuffer(RingBuffer);
+
+#[pymethods]
+impl PyRingBuffer {
+    #[new]
+    fn new(size: usize) -> PyResult<Self> {
+        if size == 0 {
+            return Err(PyValueError::new_err("size must be at least 1"));
+        }
+        Ok(PyRingBuffer(RingBuffer::new(size)))
+    }
+
+    // log one frame, raises BufferError when it doesn't fit or is filtered by level
+    #[pyo3(signature = (message, level = None))]
+    fn log(&mut self, message: &[u8], level: Option<u8>) -> PyResult<()> {
+        let result = match level {
+            Some(value) => self.0.log_with_level(self::level(value)?, message),
+            None => self.0.log_message_with_crc(message),
+        };
+        match result {
+            PushResult::Ok => Ok(()),
+            PushResult::Err(e) => Err(PyBufferError::new_err(e)),
+        }
+    }
+
+    // the next frame, None when there's no complete one. raises ValueError on a bad crc
+    fn flush_frame(&mut self) -> PyResult<Option<PyFrame>> {
+        if self.0.peek_frame_bytes().is_none() {
+            return Ok(None);
+        }
+        match self.0.flush_frame() {
+            FrameResult::Ok(frame) => Ok(Some(PyFrame(frame))),
+            FrameResult::Err(e) => Err(PyValueError::new_err(e)),
+        }
+    }
+
+    fn enable_sequence_numbers(&mut self) {
+        self.0.enable_sequence_numbers();
+    }
+
+    fn set_max_level(&mut self, level: u8) -> PyResult<()> {
+        self.0.set_max_level(self::level(level)?);
+        Ok(())
+    }
+
+    fn free(&self) -> usize {
+        self.0.free()
+    }
+
+    fn __len__(&self) -> usize {
+        self.0.len()
+    }
+}
+
+#[pymodule]
+#[pyo3(name = "ringbuffer")]
+fn init(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyFrame>()?;
+    m.add_class::<PyFrameDecoder>()?;
+    m.add_class::<PyStringTable>()?;
+    m.add_class::<PyRingBuffer>()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CStr;
+
+    use super::*;
+    use crate::interned;
+    use crate::kind::FrameKind;
+
+    // run a python snippet with the module imported as `ringbuffer` and `bytes` bound
+    // as globals
+    fn python(code: &CStr, bytes: &[(&str, Vec<u8>)]) {
+        Python::initialize();
+        Python::attach(|py| {
+            let module = PyModule::new(py, "ringbuffer").unwrap();
+            init(&module).unwrap();
+            let globals = PyDict::new(py);
+            globals.set_item("ringbuffer", module).unwrap();
+            for (name, value) in bytes {
+                globals.set_item(name, PyBytes::new(py, value)).unwrap();
+            }
+            if let Err(e) = py.run(code, Some(&globals), None) {
+                e.display(py);
+                panic!("python failed");
+            }
+        });
+    }
+
+    #[test]
+    fn a_ring_stream_decodes_in_python() {
+        python(
+            c"
+ring = ringbuffer.RingBuffer(256)
+ring.enable_sequence_numbers()
+ring.log(b'boot')
+ring.log(b'stalled', level=1)
+stream = b''
+while (frame := ring.flush_frame()) is not None:
+    stream += frame.encode()
+assert len(ring) == 0
+
+decoder = ringbuffer.FrameDecoder()
+frames = decoder.feed(stream[:5]) + decoder.feed(stream[5:])
+assert [f.text for f in frames] == ['boot', 'stalled']
+assert [f.seq for f in frames] == [0, 1]
+assert frames[1].level == 'ERROR'
+assert decoder.pending == 0 and decoder.corrupt == 0
+",
+            &[],
+        );
+    }
+
+    #[test]
+    fn errors_come_out_as_exceptions() {
+        python(
+            c"
+def raises(error, call):
+    try:
+        call()
+    except error:
+        return True
+    return False
+
+assert raises(ValueError, lambda: ringbuffer.Frame.decode(b'\\x05\\x01\\x02'))
+assert raises(BufferError, lambda: ringbuffer.RingBuffer(8).log(b'far too long for this ring'))
+assert raises(ValueError, lambda: ringbuffer.RingBuffer(0))
+assert raises(ValueError, lambda: ringbuffer.RingBuffer(8).log(b'x', level=9))
+",
+            &[],
+        );
+    }
+
+    #[test]
+    fn pairs_come_out_as_a_dict_and_interned_frames_as_text() {
+        let kv = Frame::default().kv("rpm", 1200).kv("ok", true).encode();
+        let interned = Frame::new(&interned::encode(7, &[]))
+            .with_kind(FrameKind::Interned)
+            .encode();
+        python(
+            c"
+frame = ringbuffer.Frame.decode(kv)
+assert frame.kind == 'kv'
+assert frame.pairs() == {'rpm': 1200, 'ok': True}
+table = ringbuffer.StringTable.parse('7 motor ready')
+assert table.rehydrate(ringbuffer.Frame.decode(interned)) == 'motor ready'
+",
+            &[("kv", kv), ("interned", interned)],
+        );
+    }
+}
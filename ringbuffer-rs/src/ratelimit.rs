@@ -0,0 +1,255 @@
+// per tag rate limits on the producer side, so one subsystem stuck in a loop logging
+// the same error can't push everyone else's frames out of the ring. the buckets count
+// frames, not bytes:
+//
+//     // the radio driver gets 10 frames a second (1 ms clock), 50 in a burst
+//     ring.set_rate_limit(Some(RADIO), TokenBucket::new(10, 1000, 50));
+//     // every other tag 100 a second
+//     ring.set_default_rate_limit(TokenBucket::new(100, 1000, 200));
+//
+// a rejected frame is counted against its tag. the count goes out as a
+// `FrameKind::Suppressed` frame with that tag and the count in its dropped field, in
+// front of the tag's next frame that gets through, so a tag that keeps spamming reports
+// about once per refill. call `log_suppressed` from a timer to also hear about tags that
+// went quiet while limited
+
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+use crate::frame::{Dropped, Frame};
+use crate::kind::FrameKind;
+use crate::pacing::TokenBucket;
+use crate::{PushResult, RingBuffer};
+
+#[derive(Default)]
+pub(crate) struct RateLimits {
+    // copied for every tag without a limit of its own
+    default: Option<TokenBucket>,
+    // keyed by tag, None for untagged frames
+    limits: BTreeMap<Option<u16>, Limit>,
+}
+
+struct Limit {
+    bucket: TokenBucket,
+    // set by `set_rate_limit`, not copied from the default
+    explicit: bool,
+    suppressed: Dropped,
+}
+
+impl Limit {
+    fn new(bucket: TokenBucket, explicit: bool) -> Self {
+        Limit {
+            bucket,
+            explicit,
+            suppressed: Dropped::default(),
+        }
+    }
+}
+
+fn suppression_marker(tag: Option<u16>, suppressed: Dropped) -> Frame {
+    Frame {
+        tag,
+        kind: Some(FrameKind::Suppressed),
+        ..Frame::dropped_marker(suppressed)
+    }
+}
+
+impl RingBuffer {
+    // limit the frames logged with `tag` (None for untagged ones)
+    pub fn set_rate_limit(&mut self, tag: Option<u16>, bucket: TokenBucket) {
+        self.rate_limits
+            .limits
+            .insert(tag, Limit::new(bucket, true));
+    }
+
+    pub fn clear_rate_limit(&mut self, tag: Option<u16>) {
+        self.rate_limits.limits.remove(&tag);
+    }
+
+    // limit every tag without a limit of its own, each to a bucket of its own
+    pub fn set_default_rate_limit(&mut self, bucket: TokenBucket) {
+        self.rate_limits.default = Some(bucket);
+    }
+
+    pub fn clear_default_rate_limit(&mut self) {
+        self.rate_limits.default = None;
+        self.rate_limits.limits.retain(|_, limit| limit.explicit);
+    }
+
+    // frames rejected for `tag` and not reported yet
+    pub fn suppressed(&self, tag: Option<u16>) -> Dropped {
+        self.rate_limits
+            .limits
+            .get(&tag)
+            .map_or(Dropped::default(), |limit| limit.suppressed)
+    }
+
+    // log a suppression frame for every tag with unreported rejections
+    pub fn log_suppressed(&mut self) -> PushResult {
+        let pending: Vec<(Option<u16>, Dropped)> = self
+            .rate_limits
+            .limits
+            .iter()
+            .filter(|(_, limit)| limit.suppressed.messages > 0)
+            .map(|(&tag, limit)| (tag, limit.suppressed))
+            .collect();
+        for (tag, suppressed) in pending {
+            if let PushResult::Err(e) = self.log(suppression_marker(tag, suppressed)) {
+                return PushResult::Err(e);
+            }
+        }
+        PushResult::Ok
+    }
+
+    // whether `frame` (`len` bytes encoded) is over its tag's limit, counts it if so
+    pub(crate) fn rate_limited(&mut self, frame: &Frame, len: usize) -> bool {
+        if frame.kind.is_some_and(FrameKind::is_control) {
+            return false;
+        }
+        let now = frame.timestamp.unwrap_or_else(|| self.now());
+        let limits = &mut self.rate_limits;
+        let limit = match (limits.limits.contains_key(&frame.tag), limits.default) {
+            (true, _) => limits.limits.get_mut(&frame.tag).unwrap(),
+            (false, Some(bucket)) => limits
+                .limits
+                .entry(frame.tag)
+                .or_insert(Limit::new(bucket, false)),
+            (false, None) => return false,
+        };
+        if limit.bucket.allows(now, 1) {
+            limit.bucket.take(now, 1);
+            return false;
+        }
+        limit.suppressed.messages = limit.suppressed.messages.saturating_add(1);
+        limit.suppressed.bytes = limit.suppressed.bytes.saturating_add(len as u32);
+        true
+    }
+
+    // the suppression frame to log in front of `frame`, if its tag has one pending
+    pub(crate) fn pending_suppression(&self, frame: &Frame) -> Option<Frame> {
+        if frame.kind.is_some_and(FrameKind::is_control) {
+            return None;
+        }
+        let suppressed = self.suppressed(frame.tag);
+        let mut marker = suppression_marker(frame.tag, suppressed);
+        marker.timestamp = frame.timestamp;
+        (suppressed.messages > 0).then_some(marker)
+    }
+
+    // `frame` got in, so did the suppression frame for its tag (or it was one)
+    pub(crate) fn clear_suppressed(&mut self, frame: &Frame) {
+        if let Some(limit) = self.rate_limits.limits.get_mut(&frame.tag) {
+            limit.suppressed = Dropped::default();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sink::MemorySink;
+
+    const RADIO: u16 = 7;
+
+    fn flushed(ring: &mut RingBuffer) -> Vec<Frame> {
+        let mut sink = MemorySink::new();
+        let _ = ring.flush_all_to(&mut sink);
+        sink.take()
+    }
+
+    #[test]
+    fn a_tag_over_its_limit_is_suppressed_and_reported_later() {
+        let mut ring = RingBuffer::new(512);
+        ring.set_rate_limit(Some(RADIO), TokenBucket::new(1, 10, 2));
+        let frame = |at| Frame::new(b"spam").with_tag(RADIO).with_timestamp(at);
+        for _ in 0..2 {
+            assert!(matches!(ring.log(frame(0)), PushResult::Ok));
+        }
+        assert!(matches!(
+            ring.log(frame(0)),
+            PushResult::Err(e) if e == "Message rate limited"
+        ));
+        let _ = ring.log(frame(5));
+        // the escaped length, a zero timestamp takes one more byte
+        let len = |at| frame(at).encode().len() as u32;
+        assert_eq!(
+            ring.suppressed(Some(RADIO)),
+            Dropped {
+                messages: 2,
+                bytes: len(0) + len(5)
+            }
+        );
+        // other tags aren't limited
+        assert!(matches!(ring.log(Frame::new(b"other")), PushResult::Ok));
+
+        // a token is back, the next frame goes in behind the count
+        assert!(matches!(ring.log(frame(10)), PushResult::Ok));
+        assert_eq!(ring.suppressed(Some(RADIO)), Dropped::default());
+        let frames = flushed(&mut ring);
+        assert_eq!(frames.len(), 5);
+        assert_eq!(frames[3].kind, Some(FrameKind::Suppressed));
+        assert_eq!(frames[3].tag, Some(RADIO));
+        assert_eq!(frames[3].dropped.map(|dropped| dropped.messages), Some(2));
+        assert_eq!(frames[4].timestamp, Some(10));
+    }
+
+    #[test]
+    fn the_default_limit_gives_every_tag_a_bucket_of_its_own() {
+        let mut ring = RingBuffer::new(512);
+        ring.set_default_rate_limit(TokenBucket::new(1, 10, 1));
+        ring.set_rate_limit(Some(RADIO), TokenBucket::new(1, 10, 3));
+        for tag in [1, 2, 1, 2] {
+            let _ = ring.log(Frame::new(b"x").with_tag(tag));
+        }
+        let _ = ring.log(Frame::new(b"x"));
+        let _ = ring.log(Frame::new(b"x"));
+        for _ in 0..3 {
+            assert!(matches!(
+                ring.log(Frame::new(b"x").with_tag(RADIO)),
+                PushResult::Ok
+            ));
+        }
+        assert_eq!(ring.suppressed(Some(1)).messages, 1);
+        assert_eq!(ring.suppressed(Some(2)).messages, 1);
+        assert_eq!(ring.suppressed(None).messages, 1);
+
+        // only the explicit limit stays
+        ring.clear_default_rate_limit();
+        assert_eq!(ring.suppressed(Some(1)), Dropped::default());
+        assert!(matches!(
+            ring.log(Frame::new(b"x").with_tag(1)),
+            PushResult::Ok
+        ));
+        assert!(matches!(
+            ring.log(Frame::new(b"x").with_tag(RADIO)),
+            PushResult::Err(_)
+        ));
+        ring.clear_rate_limit(Some(RADIO));
+        assert!(matches!(
+            ring.log(Frame::new(b"x").with_tag(RADIO)),
+            PushResult::Ok
+        ));
+    }
+
+    #[test]
+    fn quiet_tags_are_reported_by_log_suppressed() {
+        let mut ring = RingBuffer::new(512);
+        ring.set_rate_limit(Some(RADIO), TokenBucket::new(1, 10, 1));
+        ring.set_rate_limit(None, TokenBucket::new(1, 10, 1));
+        for _ in 0..3 {
+            let _ = ring.log(Frame::new(b"x").with_tag(RADIO));
+        }
+        let _ = ring.log(Frame::new(b"x"));
+        let _ = flushed(&mut ring);
+
+        assert!(matches!(ring.log_suppressed(), PushResult::Ok));
+        let frames = flushed(&mut ring);
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].kind, Some(FrameKind::Suppressed));
+        assert_eq!(frames[0].tag, Some(RADIO));
+        assert_eq!(frames[0].dropped.map(|dropped| dropped.messages), Some(2));
+        // reported once
+        assert!(matches!(ring.log_suppressed(), PushResult::Ok));
+        assert!(flushed(&mut ring).is_empty());
+    }
+}
@@ -0,0 +1,359 @@
+// reliable delivery over lossy links (radio, flaky serial): with acks on, flush_to and
+// flush_all_to send frames without taking them out, they stay stored until the far end
+// acknowledges their sequence number. after a link error, send again from the first
+// frame that didn't make it:
+//
+//     ring.enable_acks();
+//     ring.flush_to(&mut radio);
+//     ...
+//     // ack from the base station, everything up to and including 41 arrived
+//     ring.ack(41);
+//     ...
+//     // the base station saw a gap after 41
+//     ring.retransmit_from(42);
+//
+// acks are cumulative, in the order frames went out. frames without a sequence number
+// (dropped markers, suppression and repeat frames) go with the numbered frame after them.
+// sending goes the same way as a flush without acks, pacing, flushing by priority,
+// expiry and keep-latest tags included, and counts as flushed. unacknowledged frames
+// still take up space, when the drop policy overwrites them they're gone. the other ways
+// of consuming (pop, dma grants, the shutdown drain) take frames out as usual
+//
+// a window caps what's in flight on a congested link, flushes stop once that many frames
+// or bytes wait for an ack and pick up again as acks come in:
+//
+//     ring.set_ack_window(8, 512);
+
+use alloc::vec::Vec;
+
+use crate::frame::{self, Frame, FrameResult};
+use crate::RingBuffer;
+
+// a complete stored frame: where it starts and where its terminator ends, as offsets
+// from the tail, and its sequence number
+pub(crate) struct Stored {
+    pub(crate) start: usize,
+    pub(crate) end: usize,
+    pub(crate) seq: Option<u16>,
+}
+
+// most unacknowledged frames and bytes at once
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Window {
+    frames: usize,
+    bytes: usize,
+}
+
+impl RingBuffer {
+    // turns sequence numbers on too, acks go by them
+    pub fn enable_acks(&mut self) {
+        self.enable_sequence_numbers();
+        self.sent.get_or_insert(0);
+    }
+
+    // unacknowledged frames are taken out by the next flush
+    pub fn disable_acks(&mut self) {
+        self.sent = None;
+    }
+
+    // a frame longer than `bytes` still goes out when nothing else is in flight
+    pub fn set_ack_window(&mut self, frames: usize, bytes: usize) {
+        self.window = Some(Window { frames, bytes });
+    }
+
+    pub fn clear_ack_window(&mut self) {
+        self.window = None;
+    }
+
+    // frames sent and waiting for an ack
+    pub fn unacked_frames(&self) -> usize {
+        let sent = self.unacked();
+        (0..sent)
+            .filter(|&i| self.stored_byte(i) == frame::TERMINATOR)
+            .count()
+    }
+
+    // stored bytes sent and waiting for an ack
+    pub fn unacked(&self) -> usize {
+        self.sent.unwrap_or(0).min(self.complete_len())
+    }
+
+    // the sent frames up to and including the one numbered `seq` arrived, take them out.
+    // returns how many frames went, none when `seq` isn't among the sent frames
+    pub fn ack(&mut self, seq: u16) -> usize {
+        let sent = self.unacked();
+        let Some(end) = self
+            .stored_frames()
+            .into_iter()
+            .take_while(|stored| stored.end <= sent)
+            .find(|stored| stored.seq == Some(seq))
+            .map(|stored| stored.end)
+        else {
+            return 0;
+        };
+        let mut acked = 0;
+        let mut taken = 0;
+        while taken < end {
+            let Some(bytes) = self.peek_frame_bytes() else {
+                break;
+            };
+            // counted as flushed when it went out
+            self.consume_frame(bytes.len());
+            taken += bytes.len() + 1;
+            acked += 1;
+        }
+        acked
+    }
+
+    // send again from the frame numbered `seq` (and the frames without a number in front
+    // of it) on the next flush. false when it isn't stored anymore or acks are off
+    pub fn retransmit_from(&mut self, seq: u16) -> bool {
+        let frames = self.stored_frames();
+        let (Some(at), Some(sent)) = (
+            frames.iter().position(|stored| stored.seq == Some(seq)),
+            self.sent,
+        ) else {
+            return false;
+        };
+        let start = frames[..at]
+            .iter()
+            .rev()
+            .find(|stored| stored.seq.is_some())
+            .map_or(0, |stored| stored.end);
+        self.sent = Some(start.min(sent));
+        true
+    }
+
+    // send every unacknowledged frame again on the next flush, after a reconnect where
+    // the far end can't say what it's missing
+    pub fn retransmit_unacked(&mut self) {
+        if let Some(sent) = &mut self.sent {
+            *sent = 0;
+        }
+    }
+
+    pub(crate) fn stored_frames(&self) -> Vec<Stored> {
+        let mut frames = Vec::new();
+        let mut start = 0;
+        let mut bytes = Vec::new();
+        for i in 0..self.complete_len() {
+            match self.stored_byte(i) {
+                frame::TERMINATOR => {
+                    let seq = match Frame::decode(&bytes) {
+                        FrameResult::Ok(frame) => frame.seq,
+                        FrameResult::Err(_) => None,
+                    };
+                    frames.push(Stored {
+                        start,
+                        end: i + 1,
+                        seq,
+                    });
+                    start = i + 1;
+                    bytes.clear();
+                }
+                byte => bytes.push(byte),
+            }
+        }
+        frames
+    }
+
+    // where the frames still to send start: after the sent ones with acks on, else the tail
+    pub(crate) fn unsent_start(&self) -> usize {
+        match self.sent {
+            Some(_) => self.unacked(),
+            None => 0,
+        }
+    }
+
+    // whether the ack window lets a frame of `len` escaped bytes out, counting the stall
+    // when it doesn't
+    pub(crate) fn window_allows(&mut self, len: usize) -> bool {
+        let (Some(window), Some(_)) = (self.window, self.sent) else {
+            return true;
+        };
+        let in_flight = self.unacked_frames();
+        if in_flight > 0 && (in_flight >= window.frames || self.unacked() + len + 1 > window.bytes)
+        {
+            self.counters.window_stalls += 1;
+            return false;
+        }
+        true
+    }
+
+    // the frame of `len` escaped bytes at `offset` went out with acks on: it joins the
+    // sent ones. a frame picked from further back (flushing by priority) moves in front of
+    // the other unsent frames, sent frames stay in the order they went out
+    pub(crate) fn mark_sent(&mut self, offset: usize, len: usize) {
+        let sent = self.unacked();
+        if offset > sent {
+            let moved: Vec<u8> = (offset..=offset + len)
+                .map(|i| self.stored_byte(i))
+                .collect();
+            for i in (sent..offset).rev() {
+                let byte = self.stored_byte(i);
+                self.set_byte_at((self.tail + i + len + 1) % self.size, byte);
+            }
+            for (i, byte) in moved.into_iter().enumerate() {
+                self.set_byte_at((self.tail + sent + i) % self.size, byte);
+            }
+        }
+        self.sent = Some(sent + len + 1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::sync::atomic::{AtomicU64, Ordering};
+
+    use super::*;
+    use crate::level::Level;
+    use crate::pacing::TokenBucket;
+    use crate::sink::{FlushToResult, MemorySink};
+
+    fn payloads(sink: &mut MemorySink) -> Vec<Vec<u8>> {
+        sink.take().into_iter().map(|frame| frame.payload).collect()
+    }
+
+    #[test]
+    fn sent_frames_stay_until_acked() {
+        let mut ring = RingBuffer::new(256);
+        ring.enable_acks();
+        let _ = ring.log(Frame::new(b"a"));
+        let _ = ring.log(Frame::new(b"b"));
+        let mut sink = MemorySink::new();
+        let _ = ring.flush_to(&mut sink);
+        let sent = sink.take();
+        assert_eq!(sent.len(), 2);
+        assert_eq!(ring.unacked_frames(), 2);
+        assert_eq!(ring.stats().flushed_frames, 2);
+
+        // nothing new to send
+        let _ = ring.flush_to(&mut sink);
+        assert!(sink.frames().is_empty());
+
+        assert_eq!(ring.ack(sent[0].seq.unwrap()), 1);
+        assert_eq!(ring.unacked_frames(), 1);
+        assert!(ring.retransmit_from(sent[1].seq.unwrap()));
+        let _ = ring.flush_to(&mut sink);
+        assert_eq!(payloads(&mut sink), [b"b".to_vec()]);
+    }
+
+    #[test]
+    fn priority_flush_sends_the_most_severe_unsent_frame_first() {
+        let mut ring = RingBuffer::new(256);
+        ring.enable_acks();
+        ring.set_priority_flush(true);
+        let _ = ring.log(Frame::new(b"info").with_level(Level::Info));
+        let _ = ring.log(Frame::new(b"error").with_level(Level::Error));
+        let _ = ring.log(Frame::new(b"warn").with_level(Level::Warn));
+        let mut sink = MemorySink::new();
+        let _ = ring.flush_to(&mut sink);
+        let sent = sink.take();
+        let order: Vec<&[u8]> = sent.iter().map(|frame| &frame.payload[..]).collect();
+        assert_eq!(order, [&b"error"[..], b"warn", b"info"]);
+
+        // acks go in sending order, the error frame went first
+        assert_eq!(ring.ack(sent[0].seq.unwrap()), 1);
+        assert_eq!(ring.ack(sent[1].seq.unwrap()), 1);
+        assert_eq!(ring.unacked_frames(), 1);
+        assert!(ring.retransmit_from(sent[2].seq.unwrap()));
+        let _ = ring.flush_to(&mut sink);
+        assert_eq!(payloads(&mut sink), [b"info".to_vec()]);
+    }
+
+    #[test]
+    fn expired_and_superseded_frames_are_not_sent() {
+        static NOW: AtomicU64 = AtomicU64::new(0);
+        let mut ring = RingBuffer::new(256);
+        ring.set_clock(|| NOW.load(Ordering::Relaxed));
+        ring.enable_acks();
+        ring.keep_latest(7);
+        let _ = ring.log(Frame::new(b"stale").with_tag(7));
+        let _ = ring.log(Frame::new(b"fresh").with_tag(7));
+        let _ = ring.log(Frame::new(b"gone").with_ttl(5));
+        NOW.store(10, Ordering::Relaxed);
+        let mut sink = MemorySink::new();
+        match ring.flush_to(&mut sink) {
+            FlushToResult::Ok(stats) => assert_eq!(stats.skipped, 2),
+            FlushToResult::Err(e) => panic!("{e}"),
+        }
+        assert_eq!(payloads(&mut sink), [b"fresh".to_vec()]);
+        assert_eq!(ring.unacked_frames(), 1);
+    }
+
+    #[test]
+    fn pacing_holds_sending_back() {
+        let mut ring = RingBuffer::new(256);
+        ring.set_clock(|| 0);
+        ring.enable_acks();
+        ring.set_flush_rate(TokenBucket::new(1, 1, 8));
+        let _ = ring.log(Frame::new(b"a"));
+        let _ = ring.log(Frame::new(b"b"));
+        let mut sink = MemorySink::new();
+        let _ = ring.flush_to(&mut sink);
+        assert_eq!(sink.take().len(), 1);
+        assert_eq!(ring.unacked_frames(), 1);
+    }
+
+    #[test]
+    fn window_stalls_are_counted() {
+        let mut ring = RingBuffer::new(256);
+        ring.enable_acks();
+        ring.set_ack_window(1, 256);
+        let _ = ring.log(Frame::new(b"a"));
+        let _ = ring.log(Frame::new(b"b"));
+        let mut sink = MemorySink::new();
+        let _ = ring.flush_to(&mut sink);
+        let sent = sink.take();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(ring.stats().window_stalls, 1);
+        ring.ack(sent[0].seq.unwrap());
+        let _ = ring.flush_to(&mut sink);
+        assert_eq!(payloads(&mut sink), [b"b".to_vec()]);
+    }
+
+    #[test]
+    fn the_byte_window_caps_whats_in_flight() {
+        let mut ring = RingBuffer::new(256);
+        ring.enable_acks();
+        for _ in 0..4 {
+            let _ = ring.log(Frame::new(b"abcd"));
+        }
+        // room for the first two, sequence numbers with zero bytes escape longer
+        let two = ring.stored_frames()[1].end;
+        ring.set_ack_window(8, two);
+        let mut sink = MemorySink::new();
+        let _ = ring.flush_to(&mut sink);
+        let sent = sink.take();
+        assert_eq!(sent.len(), 2);
+        assert_eq!(ring.unacked(), two);
+
+        // acks make room again
+        ring.ack(sent[0].seq.unwrap());
+        let _ = ring.flush_to(&mut sink);
+        assert_eq!(sink.take().len(), 1);
+
+        ring.clear_ack_window();
+        let _ = ring.flush_to(&mut sink);
+        assert_eq!(sink.take().len(), 1);
+        assert_eq!(ring.unacked_frames(), 3);
+    }
+
+    #[test]
+    fn a_frame_longer_than_the_window_goes_out_alone() {
+        let mut ring = RingBuffer::new(256);
+        ring.enable_acks();
+        ring.set_ack_window(8, 4);
+        let _ = ring.log(Frame::new(b"longer than four"));
+        let _ = ring.log(Frame::new(b"next"));
+        let mut sink = MemorySink::new();
+        let _ = ring.flush_to(&mut sink);
+        let sent = sink.take();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(ring.stats().window_stalls, 1);
+
+        ring.ack(sent[0].seq.unwrap());
+        let _ = ring.flush_to(&mut sink);
+        assert_eq!(payloads(&mut sink), [b"next".to_vec()]);
+    }
+}
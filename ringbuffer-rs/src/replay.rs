@@ -0,0 +1,124 @@
+// send recent frames again without taking them out, for a debugger that just attached or
+// a client that reconnected and wants the context it missed:
+//
+//     // new connection
+//     ring.replay_last(&mut socket, 50);
+//     // client says the last frame it got was number 1234
+//     ring.replay_since(&mut socket, 1234);
+//
+// nothing is consumed and the flush counters don't move. corrupt frames are skipped and
+// counted. broadcast readers go back with `rewind`
+
+use alloc::vec::Vec;
+
+use crate::frame::{Frame, FrameResult};
+use crate::reliable::Stored;
+use crate::sink::{FlushSink, FlushStats, FlushToResult, SinkResult};
+use crate::RingBuffer;
+
+impl RingBuffer {
+    // the newest `count` complete frames, oldest first
+    pub fn replay_last(&self, sink: &mut (impl FlushSink + ?Sized), count: usize) -> FlushToResult {
+        let frames = self.stored_frames();
+        let first = frames.len().saturating_sub(count);
+        self.replay(sink, &frames[first..])
+    }
+
+    // the frames after the one numbered `seq`. when it isn't stored anymore the client
+    // missed more than the ring holds, and gets all of it
+    pub fn replay_since(&self, sink: &mut (impl FlushSink + ?Sized), seq: u16) -> FlushToResult {
+        let frames = self.stored_frames();
+        let first = frames
+            .iter()
+            .rposition(|stored| stored.seq == Some(seq))
+            .map_or(0, |at| at + 1);
+        self.replay(sink, &frames[first..])
+    }
+
+    fn replay(&self, sink: &mut (impl FlushSink + ?Sized), frames: &[Stored]) -> FlushToResult {
+        let mut stats = FlushStats::default();
+        for stored in frames {
+            let bytes: Vec<u8> = (stored.start..stored.end - 1)
+                .map(|i| self.stored_byte(i))
+                .collect();
+            match Frame::decode(&bytes) {
+                FrameResult::Ok(frame) => {
+                    if let SinkResult::Err(e) = sink.write_frame(&frame) {
+                        return FlushToResult::Err(e);
+                    }
+                    stats.frames += 1;
+                    stats.bytes += bytes.len() + 1;
+                }
+                FrameResult::Err(_) => stats.corrupt += 1,
+            }
+        }
+        if let SinkResult::Err(e) = sink.flush() {
+            return FlushToResult::Err(e);
+        }
+        FlushToResult::Ok(stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sink::MemorySink;
+
+    fn numbered(count: u8) -> RingBuffer {
+        let mut ring = RingBuffer::new(256);
+        ring.enable_sequence_numbers();
+        for i in 1..=count {
+            let _ = ring.log(Frame::new(&[i]));
+        }
+        ring
+    }
+
+    fn replayed(result: FlushToResult, sink: &mut MemorySink) -> (FlushStats, Vec<u8>) {
+        let FlushToResult::Ok(stats) = result else {
+            panic!("replay failed");
+        };
+        let payloads = sink.take().iter().map(|frame| frame.payload[0]).collect();
+        (stats, payloads)
+    }
+
+    #[test]
+    fn the_last_frames_go_out_again_without_leaving() {
+        let ring = numbered(5);
+        let stored = ring.len();
+        let mut sink = MemorySink::new();
+        let (stats, payloads) = replayed(ring.replay_last(&mut sink, 2), &mut sink);
+        assert_eq!(payloads, [4, 5]);
+        assert_eq!(stats.frames, 2);
+        assert_eq!(ring.len(), stored);
+        assert_eq!(ring.stats().flushed_frames, 0);
+
+        let (_, payloads) = replayed(ring.replay_last(&mut sink, 10), &mut sink);
+        assert_eq!(payloads, [1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn replay_since_picks_up_after_the_last_frame_the_client_got() {
+        let ring = numbered(5);
+        let mut sink = MemorySink::new();
+        // numbers start at 0, the client got the third frame
+        let (_, payloads) = replayed(ring.replay_since(&mut sink, 2), &mut sink);
+        assert_eq!(payloads, [4, 5]);
+        let (_, payloads) = replayed(ring.replay_since(&mut sink, 4), &mut sink);
+        assert!(payloads.is_empty());
+        // a number that's gone already, the client gets everything
+        let (_, payloads) = replayed(ring.replay_since(&mut sink, 999), &mut sink);
+        assert_eq!(payloads, [1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn corrupt_frames_are_counted_and_skipped() {
+        let mut ring = numbered(1);
+        let mut corrupt = Frame::new(b"bad").encode();
+        corrupt[1] ^= 0xff;
+        ring.push_slice(&corrupt);
+        let mut sink = MemorySink::new();
+        let (stats, payloads) = replayed(ring.replay_last(&mut sink, 2), &mut sink);
+        assert_eq!(stats.corrupt, 1);
+        assert_eq!(payloads, [1]);
+    }
+}
@@ -0,0 +1,394 @@
+// black box recorder: the ring keeps its storage and its indices in a region that
+// survives a reset (a `.noinit` section, backup sram, battery-backed ram), so after the
+// crash or watchdog reset the frames logged just before it are still there:
+//
+//     #[link_section = ".noinit"]
+//     static mut RETAINED: MaybeUninit<[u8; 4096]> = MaybeUninit::uninit();
+//
+//     let ptr = unsafe { addr_of_mut!(RETAINED).cast::<u8>() };
+//     let mut ring = match unsafe { RingBuffer::recover_from(ptr, 4096) } {
+//         RecoverResult::Ok(ring) | RecoverResult::Salvaged(ring) => ring,
+//         RecoverResult::Err(_) => unsafe { RingBuffer::retained(ptr, 4096) },
+//     };
+//     // the frames from before the reset go out first
+//     ring.flush_all_to(&mut sink);
+//
+// the region starts with a header (magic, layout version, size and a crc8 over them,
+// then the indices with a crc8 of their own), the rest is frame storage. the indices are
+// written through on every change, a reset halfway through one leaves a bad index crc
+// and recovery falls back to salvaging frames from the storage. there's only the one
+// header version so far, recovery turns away any other as `Err`. a new layout has to
+// add its decoder next to this one, or a firmware update loses the crash log of the
+// firmware it replaced
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::ops::{Deref, DerefMut};
+use core::ptr::{addr_of, addr_of_mut, read_volatile, write_volatile};
+
+use crate::frame::{self, crc8, Frame, FrameResult};
+use crate::RingBuffer;
+
+const MAGIC: u32 = 0x5242_4c47;
+const VERSION: u16 = 1;
+
+#[repr(C)]
+pub(crate) struct Header {
+    magic: u32,
+    version: u16,
+    crc: u8,
+    index_crc: u8,
+    size: u32,
+    head: u32,
+    tail: u32,
+}
+
+pub(crate) const HEADER_LEN: usize = core::mem::size_of::<Header>();
+
+impl Header {
+    fn crc(magic: u32, version: u16, size: u32) -> u8 {
+        let mut bytes = [0u8; 10];
+        bytes[..4].copy_from_slice(&magic.to_le_bytes());
+        bytes[4..6].copy_from_slice(&version.to_le_bytes());
+        bytes[6..].copy_from_slice(&size.to_le_bytes());
+        crc8(&bytes)
+    }
+
+    fn index_crc(head: u32, tail: u32) -> u8 {
+        let mut bytes = [0u8; 8];
+        bytes[..4].copy_from_slice(&head.to_le_bytes());
+        bytes[4..].copy_from_slice(&tail.to_le_bytes());
+        crc8(&bytes)
+    }
+}
+
+pub(crate) enum Storage {
+    Heap(Vec<u8>),
+    Retained {
+        header: *mut Header,
+        data: &'static mut [u8],
+    },
+    // header and storage in a file mapping, see `mmap`
+    #[cfg(feature = "mmap")]
+    Mapped(memmap2::MmapMut),
+}
+
+// the header pointer is only ever used through the ring that owns the region
+unsafe impl Send for Storage {}
+
+impl Deref for Storage {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Storage::Heap(buffer) => buffer,
+            Storage::Retained { data, .. } => data,
+            #[cfg(feature = "mmap")]
+            Storage::Mapped(map) => &map[HEADER_LEN..],
+        }
+    }
+}
+
+impl DerefMut for Storage {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        match self {
+            Storage::Heap(buffer) => buffer,
+            Storage::Retained { data, .. } => data,
+            #[cfg(feature = "mmap")]
+            Storage::Mapped(map) => &mut map[HEADER_LEN..],
+        }
+    }
+}
+
+impl Storage {
+    fn header(&mut self) -> Option<*mut Header> {
+        match self {
+            Storage::Heap(_) => None,
+            Storage::Retained { header, .. } => Some(*header),
+            #[cfg(feature = "mmap")]
+            Storage::Mapped(map) => Some(map.as_mut_ptr().cast()),
+        }
+    }
+
+    // the indices as written through, and whether their crc matches
+    #[cfg(all(feature = "debug-assert-invariants", debug_assertions))]
+    pub(crate) fn load_indices(&mut self) -> Option<(usize, usize, bool)> {
+        let header = self.header()?;
+        let (head, tail, index_crc) = unsafe {
+            (
+                read_volatile(addr_of!((*header).head)),
+                read_volatile(addr_of!((*header).tail)),
+                read_volatile(addr_of!((*header).index_crc)),
+            )
+        };
+        let crc_ok = index_crc == Header::index_crc(head, tail);
+        Some((head as usize, tail as usize, crc_ok))
+    }
+
+    pub(crate) fn store_indices(&mut self, head: usize, tail: usize) {
+        if let Some(header) = self.header() {
+            let (head, tail) = (head as u32, tail as u32);
+            unsafe {
+                write_volatile(addr_of_mut!((*header).head), head);
+                write_volatile(addr_of_mut!((*header).tail), tail);
+                write_volatile(
+                    addr_of_mut!((*header).index_crc),
+                    Header::index_crc(head, tail),
+                );
+            }
+        }
+    }
+}
+
+pub enum RecoverResult {
+    // header and indices intact, the ring carries on where it was. a frame that was
+    // half written at the reset is dropped
+    Ok(RingBuffer),
+    // the header is intact but the indices aren't: every frame in storage that still
+    // passes its crc is kept, in storage order. this can bring back frames that
+    // were already flushed before the reset
+    Salvaged(RingBuffer),
+    // no ring in the region (first boot, lost power, other layout), format it with
+    // `RingBuffer::retained`
+    Err(String),
+}
+
+// safety for both constructors: `ptr` points to `len` bytes that nothing else uses for
+// as long as the ring lives, aligned to 4 bytes and more than 21 bytes long
+impl RingBuffer {
+    // format the region as an empty ring, whatever was in it is lost
+    #[allow(clippy::missing_safety_doc)]
+    pub unsafe fn retained(ptr: *mut u8, len: usize) -> RingBuffer {
+        assert!((ptr as usize).is_multiple_of(core::mem::align_of::<Header>()));
+        assert!(len > HEADER_LEN + 1);
+        Self::format(Self::region(ptr, len))
+    }
+
+    // pick up the ring a previous run left in the region. everything besides storage and
+    // indices (clock, level filter, sequence numbers, ...) starts from the defaults and
+    // has to be set up again
+    #[allow(clippy::missing_safety_doc)]
+    pub unsafe fn recover_from(ptr: *mut u8, len: usize) -> RecoverResult {
+        if !(ptr as usize).is_multiple_of(core::mem::align_of::<Header>()) || len <= HEADER_LEN + 1
+        {
+            return RecoverResult::Err("Region too small or misaligned".to_string());
+        }
+        Self::recover(Self::region(ptr, len))
+    }
+
+    unsafe fn region(ptr: *mut u8, len: usize) -> Storage {
+        Storage::Retained {
+            header: ptr.cast(),
+            data: core::slice::from_raw_parts_mut(ptr.add(HEADER_LEN), len - HEADER_LEN),
+        }
+    }
+
+    // write a fresh header in front of `storage`
+    pub(crate) fn format(mut storage: Storage) -> RingBuffer {
+        let size = storage.len() as u32;
+        if let Some(header) = storage.header() {
+            let fresh = Header {
+                magic: MAGIC,
+                version: VERSION,
+                crc: Header::crc(MAGIC, VERSION, size),
+                index_crc: Header::index_crc(0, 0),
+                size,
+                head: 0,
+                tail: 0,
+            };
+            unsafe { write_volatile(header, fresh) };
+        }
+        Self::with_storage(storage)
+    }
+
+    // validate the header in front of `storage` and take over its indices
+    pub(crate) fn recover(mut storage: Storage) -> RecoverResult {
+        let Some(header) = storage.header() else {
+            return RecoverResult::Err("No ring buffer header".to_string());
+        };
+        let (magic, version, size, crc, head, tail, index_crc) = unsafe {
+            (
+                read_volatile(addr_of!((*header).magic)),
+                read_volatile(addr_of!((*header).version)),
+                read_volatile(addr_of!((*header).size)),
+                read_volatile(addr_of!((*header).crc)),
+                read_volatile(addr_of!((*header).head)),
+                read_volatile(addr_of!((*header).tail)),
+                read_volatile(addr_of!((*header).index_crc)),
+            )
+        };
+        if magic != MAGIC {
+            return RecoverResult::Err("No ring buffer header".to_string());
+        }
+        if crc != Header::crc(magic, version, size) {
+            return RecoverResult::Err("Header crc mismatch".to_string());
+        }
+        if version != VERSION {
+            return RecoverResult::Err("Unsupported header version".to_string());
+        }
+        if size as usize != storage.len() {
+            return RecoverResult::Err("Region size changed".to_string());
+        }
+
+        let mut ring = Self::with_storage(storage);
+        let indices_ok = index_crc == Header::index_crc(head, tail) && head < size && tail < size;
+        if indices_ok {
+            ring.head = head as usize;
+            ring.tail = tail as usize;
+            ring.set_head((ring.tail + ring.complete_len()) % ring.size);
+            RecoverResult::Ok(ring)
+        } else {
+            ring.salvage();
+            RecoverResult::Salvaged(ring)
+        }
+    }
+
+    // move every intact frame in storage to the front, in storage order, and make them
+    // the contents. the copy never overtakes the scan, frames only ever move down
+    fn salvage(&mut self) {
+        // storage order starts after the last terminator, so a frame wrapping the end of
+        // storage is scanned in one piece
+        let storage = &mut self.buffer[..self.size];
+        if let Some(last) = storage.iter().rposition(|&b| b == frame::TERMINATOR) {
+            storage.rotate_left(last + 1);
+        }
+        let mut kept = 0;
+        let mut start = 0;
+        for end in 0..self.size {
+            if self.buffer[end] != frame::TERMINATOR {
+                continue;
+            }
+            let chunk = start..end;
+            start = end + 1;
+            // one slot always stays free
+            let fits = kept + chunk.len() + 1 < self.size;
+            let intact = matches!(
+                Frame::decode(&self.buffer[chunk.clone()]),
+                FrameResult::Ok(_)
+            );
+            if chunk.is_empty() || !fits || !intact {
+                continue;
+            }
+            let len = chunk.len();
+            self.buffer.copy_within(chunk.start..=end, kept);
+            kept += len + 1;
+        }
+        self.tail = 0;
+        self.set_head(kept);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::boxed::Box;
+    use alloc::vec;
+
+    use super::*;
+
+    fn region(len: usize) -> *mut u8 {
+        Box::leak(vec![0u32; len / 4].into_boxed_slice())
+            .as_mut_ptr()
+            .cast()
+    }
+
+    fn payloads(ring: &mut RingBuffer) -> Vec<Vec<u8>> {
+        let mut out = Vec::new();
+        while let FrameResult::Ok(frame) = ring.flush_frame() {
+            out.push(frame.payload);
+        }
+        out
+    }
+
+    fn header(ptr: *mut u8) -> *mut Header {
+        ptr.cast()
+    }
+
+    #[test]
+    fn frames_survive_a_reset() {
+        let ptr = region(256);
+        let mut ring = unsafe { RingBuffer::retained(ptr, 256) };
+        let _ = ring.log(Frame::new(b"before"));
+        let _ = ring.log(Frame::new(b"reset"));
+        // half a frame written when the reset hit
+        ring.push_slice(&Frame::new(b"lost").encode()[..3]);
+        core::mem::forget(ring);
+
+        match unsafe { RingBuffer::recover_from(ptr, 256) } {
+            RecoverResult::Ok(mut ring) => {
+                assert_eq!(payloads(&mut ring), [&b"before"[..], b"reset"])
+            }
+            _ => panic!("not recovered"),
+        }
+    }
+
+    #[test]
+    fn bad_indices_salvage_intact_frames() {
+        let ptr = region(256);
+        let mut ring = unsafe { RingBuffer::retained(ptr, 256) };
+        let _ = ring.log(Frame::new(b"one"));
+        let _ = ring.log(Frame::new(b"two"));
+        core::mem::forget(ring);
+        unsafe { (*header(ptr)).index_crc ^= 0xff };
+
+        match unsafe { RingBuffer::recover_from(ptr, 256) } {
+            RecoverResult::Salvaged(mut ring) => assert_eq!(payloads(&mut ring), [b"one", b"two"]),
+            _ => panic!("not salvaged"),
+        }
+    }
+
+    #[test]
+    fn unknown_versions_are_turned_away() {
+        let ptr = region(256);
+        core::mem::forget(unsafe { RingBuffer::retained(ptr, 256) });
+        unsafe {
+            let header = header(ptr);
+            (*header).version = VERSION + 1;
+            (*header).crc = Header::crc(MAGIC, VERSION + 1, (*header).size);
+        }
+        assert!(matches!(
+            unsafe { RingBuffer::recover_from(ptr, 256) },
+            RecoverResult::Err(e) if e == "Unsupported header version"
+        ));
+    }
+
+    #[test]
+    fn a_different_region_size_is_turned_away() {
+        let ptr = region(256);
+        core::mem::forget(unsafe { RingBuffer::retained(ptr, 256) });
+        assert!(matches!(
+            unsafe { RingBuffer::recover_from(ptr, 128) },
+            RecoverResult::Err(_)
+        ));
+    }
+
+    #[test]
+    fn salvage_keeps_a_frame_wrapping_the_end_of_storage() {
+        let ptr = region(64 + HEADER_LEN);
+        let mut ring = unsafe { RingBuffer::retained(ptr, 64 + HEADER_LEN) };
+        for payload in [&b"first, gone"[..], b"second frame, out"] {
+            let _ = ring.log(Frame::new(payload));
+            let _ = ring.flush_frame();
+        }
+        let _ = ring.log(Frame::new(b"third frame, kept"));
+        let _ = ring.log(Frame::new(b"wraps, kept"));
+        // the last one starts near the end of storage and ends at the front
+        assert!(ring.head < ring.tail);
+        core::mem::forget(ring);
+        unsafe { (*header(ptr)).index_crc ^= 0xff };
+
+        match unsafe { RingBuffer::recover_from(ptr, 64 + HEADER_LEN) } {
+            RecoverResult::Salvaged(mut ring) => {
+                // the flushed second frame is still intact in storage and comes back too
+                assert_eq!(
+                    payloads(&mut ring),
+                    [
+                        &b"wraps, kept"[..],
+                        b"second frame, out",
+                        b"third frame, kept"
+                    ]
+                );
+            }
+            _ => panic!("not salvaged"),
+        }
+    }
+}
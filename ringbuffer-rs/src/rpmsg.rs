@@ -0,0 +1,239 @@
+// frames over rpmsg between the cores of a heterogeneous soc (i.mx8, stm32mp1, ...): the
+// m-core logs into the ring as usual and flushes into an rpmsg endpoint, the a-core reads
+// the endpoint's character device on linux and decodes.
+//
+// m-core side, wrapping whatever rpmsg stack the firmware uses (openamp's rpmsg_send,
+// rpmsg-lite's rpmsg_lite_send, ...):
+//
+//     impl RpmsgEndpoint for LogEndpoint {
+//         type Error = i32;
+//         fn mtu(&self) -> usize { 496 }
+//         fn send(&mut self, msg: &[u8]) -> Result<(), i32> { ... }
+//     }
+//
+//     ring.flush_to(&mut RpmsgSink::new(endpoint));
+//
+// linux side, once the endpoint is bound to rpmsg_char:
+//
+//     let mut source = RpmsgSource::open("/dev/rpmsg0")?;
+//     loop {
+//         for frame in source.read_frames()? { ... }
+//     }
+//
+// frames are packed into messages of up to the endpoint's mtu and stay in wire format, a
+// frame bigger than the mtu is split over several messages and the decoder puts it back
+// together
+
+use alloc::format;
+use alloc::vec::Vec;
+use core::fmt::Debug;
+
+use crate::frame::Frame;
+use crate::sink::{FlushSink, SinkResult};
+
+pub trait RpmsgEndpoint {
+    type Error: Debug;
+
+    // largest payload of a single message, 496 bytes with the default openamp buffers
+    fn mtu(&self) -> usize;
+
+    fn send(&mut self, msg: &[u8]) -> Result<(), Self::Error>;
+}
+
+pub struct RpmsgSink<E: RpmsgEndpoint> {
+    endpoint: E,
+    pending: Vec<u8>,
+}
+
+impl<E: RpmsgEndpoint> RpmsgSink<E> {
+    pub fn new(endpoint: E) -> Self {
+        RpmsgSink {
+            endpoint,
+            pending: Vec::new(),
+        }
+    }
+
+    pub fn endpoint(&mut self) -> &mut E {
+        &mut self.endpoint
+    }
+
+    pub fn into_inner(self) -> E {
+        self.endpoint
+    }
+
+    // send full messages, or everything when `all` is set. what the endpoint refused
+    // stays pending and goes out first next time
+    fn drain(&mut self, all: bool) -> SinkResult {
+        let mtu = self.endpoint.mtu().max(1);
+        while self.pending.len() >= mtu || (all && !self.pending.is_empty()) {
+            let count = self.pending.len().min(mtu);
+            if let Err(e) = self.endpoint.send(&self.pending[..count]) {
+                return SinkResult::Err(format!("{:?}", e));
+            }
+            self.pending.drain(..count);
+        }
+        SinkResult::Ok
+    }
+}
+
+impl<E: RpmsgEndpoint> FlushSink for RpmsgSink<E> {
+    fn write_frame(&mut self, frame: &Frame) -> SinkResult {
+        let bytes = frame.encode();
+        // don't split a frame that would fit a message of its own
+        if self.pending.len() + bytes.len() > self.endpoint.mtu() {
+            if let SinkResult::Err(e) = self.drain(true) {
+                return SinkResult::Err(e);
+            }
+        }
+        self.pending.extend_from_slice(&bytes);
+        self.drain(false)
+    }
+
+    fn flush(&mut self) -> SinkResult {
+        self.drain(true)
+    }
+}
+
+// reads messages from an rpmsg character device and decodes the frames in them
+#[cfg(feature = "std")]
+pub struct RpmsgSource {
+    device: std::fs::File,
+    decoder: crate::decoder::FrameDecoder,
+    buf: Vec<u8>,
+}
+
+#[cfg(feature = "std")]
+impl RpmsgSource {
+    pub fn open(path: &str) -> std::io::Result<Self> {
+        Ok(Self::from_file(std::fs::File::open(path)?))
+    }
+
+    pub fn from_file(device: std::fs::File) -> Self {
+        RpmsgSource {
+            device,
+            decoder: crate::decoder::FrameDecoder::new(),
+            // rpmsg_char hands out one message per read, never more than a vring buffer
+            buf: alloc::vec![0; 4096],
+        }
+    }
+
+    pub fn decoder(&self) -> &crate::decoder::FrameDecoder {
+        &self.decoder
+    }
+
+    // block for the next message and return the frames it completed, possibly none
+    pub fn read_frames(&mut self) -> std::io::Result<Vec<Frame>> {
+        use std::io::Read;
+
+        let count = self.device.read(&mut self.buf)?;
+        Ok(self.decoder.feed(&self.buf[..count]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoder::FrameDecoder;
+
+    #[derive(Default)]
+    struct Endpoint {
+        messages: Vec<Vec<u8>>,
+        refuse: bool,
+    }
+
+    impl RpmsgEndpoint for Endpoint {
+        type Error = &'static str;
+
+        fn mtu(&self) -> usize {
+            16
+        }
+
+        fn send(&mut self, msg: &[u8]) -> Result<(), &'static str> {
+            if self.refuse {
+                return Err("no buffer");
+            }
+            self.messages.push(msg.to_vec());
+            Ok(())
+        }
+    }
+
+    fn payloads(messages: &[Vec<u8>]) -> Vec<Vec<u8>> {
+        FrameDecoder::new()
+            .feed(&messages.concat())
+            .into_iter()
+            .map(|frame| frame.payload)
+            .collect()
+    }
+
+    #[test]
+    fn frames_are_packed_without_splitting_ones_that_fit() {
+        let mut sink = RpmsgSink::new(Endpoint::default());
+        for payload in [b"aaa", b"bbb", b"ccc"] {
+            assert!(matches!(
+                sink.write_frame(&Frame::new(payload)),
+                SinkResult::Ok
+            ));
+        }
+        assert!(matches!(sink.flush(), SinkResult::Ok));
+
+        let messages = sink.into_inner().messages;
+        let len = Frame::new(b"aaa").encode().len();
+        // two frames to a message, the third doesn't fit behind them
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].len(), 2 * len);
+        assert_eq!(messages[1].len(), len);
+        assert_eq!(payloads(&messages), [b"aaa", b"bbb", b"ccc"]);
+    }
+
+    #[test]
+    fn a_frame_over_the_mtu_is_split() {
+        let mut sink = RpmsgSink::new(Endpoint::default());
+        let payload = [7u8; 40];
+        let _ = sink.write_frame(&Frame::new(&payload));
+        let _ = sink.flush();
+        let messages = sink.into_inner().messages;
+        assert!(messages.len() > 2);
+        assert!(messages.iter().all(|msg| msg.len() <= 16));
+        assert_eq!(payloads(&messages), [payload]);
+    }
+
+    #[test]
+    fn refused_messages_go_out_first_next_time() {
+        let mut sink = RpmsgSink::new(Endpoint {
+            refuse: true,
+            ..Endpoint::default()
+        });
+        let _ = sink.write_frame(&Frame::new(b"first"));
+        assert!(matches!(sink.flush(), SinkResult::Err(e) if e == "\"no buffer\""));
+
+        sink.endpoint().refuse = false;
+        let _ = sink.write_frame(&Frame::new(b"second"));
+        let _ = sink.flush();
+        assert_eq!(
+            payloads(&sink.into_inner().messages),
+            [&b"first"[..], b"second"]
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn the_source_decodes_frames_from_the_device() {
+        use std::io::Write;
+
+        let path = std::env::temp_dir().join(format!("ringbuffer-rpmsg-{}", std::process::id()));
+        let mut sink = RpmsgSink::new(Endpoint::default());
+        let _ = sink.write_frame(&Frame::new(b"from the m-core"));
+        let _ = sink.flush();
+        let mut device = std::fs::File::create(&path).unwrap();
+        for msg in sink.into_inner().messages {
+            device.write_all(&msg).unwrap();
+        }
+
+        let mut source = RpmsgSource::open(path.to_str().unwrap()).unwrap();
+        let frames = source.read_frames().unwrap();
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].payload, b"from the m-core");
+        assert!(source.read_frames().unwrap().is_empty());
+        std::fs::remove_file(path).unwrap();
+    }
+}
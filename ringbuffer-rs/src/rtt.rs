@@ -0,0 +1,124 @@
+// drains frames into a segger rtt up-channel, so a debug probe can tail the ring during
+// bring-up with no extra hardware. frames go out in the normal wire format
+
+use alloc::string::ToString;
+
+use rtt_target::{ChannelMode, UpChannel};
+
+use crate::frame::Frame;
+use crate::sink::{FlushSink, SinkResult};
+
+pub struct RttSink {
+    channel: UpChannel,
+}
+
+impl RttSink {
+    // switches the channel to skip mode: a frame goes in whole or not at all. frames
+    // larger than the channel's buffer can never be written
+    pub fn new(mut channel: UpChannel) -> Self {
+        channel.set_mode(ChannelMode::NoBlockSkip);
+        RttSink { channel }
+    }
+
+    pub fn channel(&mut self) -> &mut UpChannel {
+        &mut self.channel
+    }
+}
+
+impl FlushSink for RttSink {
+    fn write_frame(&mut self, frame: &Frame) -> SinkResult {
+        let bytes = frame.encode();
+        if self.channel.write(&bytes) < bytes.len() {
+            // the probe hasn't caught up, the frame stays in the ring for the next flush
+            return SinkResult::Err("RTT channel is full".to_string());
+        }
+        SinkResult::Ok
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+    use core::ptr::{addr_of, addr_of_mut};
+
+    use rtt_target::rtt_init;
+
+    use super::*;
+    use crate::sink::FlushToResult;
+    use crate::RingBuffer;
+
+    // the control block as a debug probe sees it, one up channel and no down channels
+    #[repr(C)]
+    struct Channel {
+        name: *const u8,
+        buffer: *mut u8,
+        size: usize,
+        write: usize,
+        read: usize,
+        flags: usize,
+    }
+
+    #[repr(C)]
+    struct ControlBlock {
+        id: [u8; 16],
+        max_up_channels: usize,
+        max_down_channels: usize,
+        up: Channel,
+    }
+
+    extern "C" {
+        static mut _SEGGER_RTT: ControlBlock;
+    }
+
+    // what the probe reads off the up channel, moving its read index along
+    fn probe_read() -> Vec<u8> {
+        unsafe {
+            let channel = addr_of_mut!(_SEGGER_RTT.up);
+            let write = addr_of!((*channel).write).read_volatile();
+            let mut read = addr_of!((*channel).read).read_volatile();
+            let mut out = Vec::new();
+            while read != write {
+                out.push((*channel).buffer.add(read).read_volatile());
+                read = (read + 1) % (*channel).size;
+            }
+            addr_of_mut!((*channel).read).write_volatile(read);
+            out
+        }
+    }
+
+    // rtt_init can only run once per program, so one test covers the channel
+    #[test]
+    fn frames_go_in_whole_or_wait_for_the_probe() {
+        let channels = rtt_init! {
+            up: {
+                0: {
+                    size: 32,
+                    name: "ring"
+                }
+            }
+        };
+        assert_eq!(
+            unsafe { &*addr_of!(_SEGGER_RTT.id) },
+            b"SEGGER RTT\0\0\0\0\0\0"
+        );
+        let mut sink = RttSink::new(channels.up.0);
+
+        let first = Frame::new(&[1; 20]);
+        let second = Frame::new(&[2; 20]);
+        let mut ring = RingBuffer::new(128);
+        let _ = ring.log(first.clone());
+        let _ = ring.log(second.clone());
+
+        // only the first fits the channel
+        assert!(matches!(
+            ring.flush_all_to(&mut sink),
+            FlushToResult::Err(e) if e == "RTT channel is full"
+        ));
+        assert_eq!(probe_read(), first.encode());
+        assert!(!ring.is_empty());
+
+        assert!(matches!(ring.flush_all_to(&mut sink), FlushToResult::Ok(_)));
+        assert_eq!(probe_read(), second.encode());
+        assert!(ring.is_empty());
+    }
+}
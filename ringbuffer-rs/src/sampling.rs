@@ -0,0 +1,218 @@
+// thinning out high rate frames of one tag so 10 kHz sensor data can share the ring with
+// logs without drowning them:
+//
+//     // keep one of every 100 frames of the accelerometer
+//     ring.set_sampling(Some(ACCEL), Sampling::EveryNth(100));
+//     // or 10 frames picked at random out of every 1000
+//     ring.set_sampling(Some(ACCEL), Sampling::Reservoir { keep: 10, window: 1000 });
+//
+// every nth is cheap and keeps a fixed phase, which aliases with anything periodic in
+// the signal. the reservoir keeps a uniform random pick of each window instead, but holds
+// the picked frames back until the window is complete and then logs them in their
+// original order (with their original timestamps). frames thrown away by either are
+// reported as logged, they weren't lost to a full ring
+
+use alloc::vec::Vec;
+
+use crate::frame::Frame;
+use crate::kind::FrameKind;
+use crate::RingBuffer;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sampling {
+    EveryNth(u32),
+    Reservoir { keep: usize, window: usize },
+}
+
+pub(crate) struct Sampler {
+    sampling: Sampling,
+    // frames seen in the current window (every frame so far for EveryNth)
+    seen: u64,
+    // picked frames and where in the window they arrived
+    reservoir: Vec<(u64, Frame)>,
+    rng: u32,
+}
+
+pub(crate) enum Sampled {
+    Keep,
+    Skip,
+    // a reservoir window completed, log these instead
+    Window(Vec<Frame>),
+}
+
+impl Sampler {
+    fn sample(&mut self, frame: &Frame) -> Sampled {
+        let seen = self.seen;
+        self.seen += 1;
+        match self.sampling {
+            Sampling::EveryNth(n) if seen.is_multiple_of(n as u64) => Sampled::Keep,
+            Sampling::EveryNth(_) => Sampled::Skip,
+            Sampling::Reservoir { keep, window } => {
+                if self.reservoir.len() < keep {
+                    self.reservoir.push((seen, frame.clone()));
+                } else {
+                    let pick = self.next_random() as u64 % (seen + 1);
+                    if let Some(slot) = self.reservoir.get_mut(pick as usize) {
+                        *slot = (seen, frame.clone());
+                    }
+                }
+                if self.seen < window as u64 {
+                    return Sampled::Skip;
+                }
+                self.seen = 0;
+                self.reservoir.sort_by_key(|(arrived, _)| *arrived);
+                Sampled::Window(self.reservoir.drain(..).map(|(_, f)| f).collect())
+            }
+        }
+    }
+
+    // xorshift, good enough to pick samples
+    fn next_random(&mut self) -> u32 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 17;
+        self.rng ^= self.rng << 5;
+        self.rng
+    }
+}
+
+impl RingBuffer {
+    // sample the frames logged with `tag` (None for untagged ones), replaces whatever
+    // sampling the tag had. frames held back in a reservoir are dropped
+    pub fn set_sampling(&mut self, tag: Option<u16>, sampling: Sampling) {
+        match sampling {
+            Sampling::EveryNth(n) => assert!(n > 0, "Need to keep every nth frame, n >= 1"),
+            Sampling::Reservoir { keep, window } => {
+                assert!(keep > 0 && keep <= window, "Need 0 < keep <= window")
+            }
+        }
+        self.samplers.insert(
+            tag,
+            Sampler {
+                sampling,
+                seen: 0,
+                reservoir: Vec::new(),
+                rng: 0x9e37_79b9 ^ tag.map_or(0, u32::from),
+            },
+        );
+    }
+
+    pub fn clear_sampling(&mut self, tag: Option<u16>) {
+        self.samplers.remove(&tag);
+    }
+
+    pub(crate) fn is_sampled(&self, frame: &Frame) -> bool {
+        !frame.kind.is_some_and(FrameKind::is_control) && self.samplers.contains_key(&frame.tag)
+    }
+
+    pub(crate) fn sample(&mut self, frame: &Frame) -> Sampled {
+        if !self.is_sampled(frame) {
+            return Sampled::Keep;
+        }
+        match self.samplers.get_mut(&frame.tag) {
+            Some(sampler) => sampler.sample(frame),
+            None => Sampled::Keep,
+        }
+    }
+
+    // number a kept sampled frame that `log` left without one
+    pub(crate) fn sequence_sampled(&mut self, frame: &mut Frame) {
+        if frame.seq.is_none() {
+            self.sequence(frame);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sink::MemorySink;
+
+    const ACCEL: u16 = 3;
+
+    fn payloads(ring: &mut RingBuffer) -> Vec<u8> {
+        let mut sink = MemorySink::new();
+        let _ = ring.flush_all_to(&mut sink);
+        sink.take().iter().map(|frame| frame.payload[0]).collect()
+    }
+
+    #[test]
+    fn every_nth_keeps_a_fixed_phase() {
+        let mut ring = RingBuffer::new(512);
+        ring.set_sampling(Some(ACCEL), Sampling::EveryNth(3));
+        for i in 1..=10 {
+            let _ = ring.log(Frame::new(&[i]).with_tag(ACCEL));
+        }
+        assert_eq!(payloads(&mut ring), [1, 4, 7, 10]);
+
+        // other tags go in untouched
+        for i in 1..=3 {
+            let _ = ring.log(Frame::new(&[i]));
+        }
+        assert_eq!(payloads(&mut ring), [1, 2, 3]);
+
+        ring.clear_sampling(Some(ACCEL));
+        for i in 1..=3 {
+            let _ = ring.log(Frame::new(&[i]).with_tag(ACCEL));
+        }
+        assert_eq!(payloads(&mut ring), [1, 2, 3]);
+    }
+
+    #[test]
+    fn a_reservoir_logs_its_picks_in_order_once_the_window_completes() {
+        let mut ring = RingBuffer::new(512);
+        ring.set_sampling(
+            Some(ACCEL),
+            Sampling::Reservoir {
+                keep: 3,
+                window: 10,
+            },
+        );
+        for i in 1..=9 {
+            let _ = ring.log(Frame::new(&[i]).with_tag(ACCEL));
+        }
+        assert!(ring.is_empty());
+
+        let _ = ring.log(Frame::new(&[10]).with_tag(ACCEL));
+        let picked = payloads(&mut ring);
+        assert_eq!(picked.len(), 3);
+        assert!(picked.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(picked.iter().all(|i| (1..=10).contains(i)));
+
+        // the next window starts over
+        for i in 11..=20 {
+            let _ = ring.log(Frame::new(&[i]).with_tag(ACCEL));
+        }
+        let picked = payloads(&mut ring);
+        assert_eq!(picked.len(), 3);
+        assert!(picked.iter().all(|i| (11..=20).contains(i)));
+    }
+
+    #[test]
+    fn replacing_the_sampling_drops_held_back_frames() {
+        let mut ring = RingBuffer::new(512);
+        let reservoir = Sampling::Reservoir { keep: 2, window: 4 };
+        ring.set_sampling(None, reservoir);
+        for i in 1..=3 {
+            let _ = ring.log(Frame::new(&[i]));
+        }
+        ring.set_sampling(None, reservoir);
+        for i in 4..=7 {
+            let _ = ring.log(Frame::new(&[i]));
+        }
+        let picked = payloads(&mut ring);
+        assert_eq!(picked.len(), 2);
+        assert!(picked.iter().all(|&i| i >= 4));
+    }
+
+    #[test]
+    #[should_panic(expected = "keep <= window")]
+    fn a_reservoir_larger_than_its_window_is_refused() {
+        RingBuffer::new(64).set_sampling(None, Sampling::Reservoir { keep: 5, window: 4 });
+    }
+
+    #[test]
+    #[should_panic(expected = "n >= 1")]
+    fn every_zeroth_is_refused() {
+        RingBuffer::new(64).set_sampling(None, Sampling::EveryNth(0));
+    }
+}
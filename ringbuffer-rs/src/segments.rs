@@ -0,0 +1,266 @@
+// storage split into n fixed segments that are filled one after the other. a full
+// segment is sealed and the producer moves on to the next, while the consumer takes the
+// sealed ones as single contiguous blocks of wire format bytes: one dma transfer or one
+// file write per segment instead of a frame at a time:
+//
+//     let mut segments = SegmentedRing::new(4, 4096);
+//     segments.log_message_with_crc(b"...");
+//     ...
+//     while let Some(bytes) = segments.peek_sealed() {
+//         file.write_all(bytes)?;
+//         segments.release();
+//     }
+//
+// a frame never straddles two segments, the space it doesn't fit into at the end of one
+// stays unused. when the next segment is still sealed (the consumer fell behind), frames
+// are rejected and counted, the next frame that gets in carries the dropped marker. call
+// `seal` to hand over a partly filled segment, e.g. on a timer
+
+use alloc::collections::VecDeque;
+use alloc::string::ToString;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::frame::{self, Dropped, Frame, FrameResult};
+use crate::sink::{FlushSink, FlushStats, FlushToResult, SinkResult};
+use crate::PushResult;
+
+pub struct SegmentedRing {
+    storage: Vec<u8>,
+    segment_size: usize,
+    // bytes used in each segment
+    fill: Vec<usize>,
+    // the segment being written
+    active: usize,
+    // segments handed over to the consumer, oldest first
+    sealed: VecDeque<usize>,
+    dropped: Dropped,
+}
+
+impl SegmentedRing {
+    pub fn new(segments: usize, segment_size: usize) -> Self {
+        assert!(segments >= 2, "Need at least two segments");
+        SegmentedRing {
+            storage: vec![0; segments * segment_size],
+            segment_size,
+            fill: vec![0; segments],
+            active: 0,
+            sealed: VecDeque::new(),
+            dropped: Dropped::default(),
+        }
+    }
+
+    pub fn segment_size(&self) -> usize {
+        self.segment_size
+    }
+
+    pub fn segments(&self) -> usize {
+        self.fill.len()
+    }
+
+    // number of segments waiting for the consumer
+    pub fn sealed_count(&self) -> usize {
+        self.sealed.len()
+    }
+
+    pub fn log_message_with_crc(&mut self, message: &[u8]) -> PushResult {
+        self.log_frame(&Frame::new(message))
+    }
+
+    pub fn log_frame(&mut self, frame: &Frame) -> PushResult {
+        let mut bytes = Vec::new();
+        if self.dropped.messages > 0 {
+            let mut dropped = Frame::dropped_marker(self.dropped);
+            dropped.timestamp = frame.timestamp;
+            bytes = dropped.encode();
+        }
+        let encoded = frame.encode();
+        bytes.extend_from_slice(&encoded);
+
+        if bytes.len() > self.segment_size {
+            return self.drop_frame(encoded.len(), "Frame larger than a segment");
+        }
+        if self.fill[self.active] + bytes.len() > self.segment_size && !self.rotate() {
+            return self.drop_frame(encoded.len(), "Error logging message");
+        }
+        let start = self.active * self.segment_size + self.fill[self.active];
+        self.storage[start..start + bytes.len()].copy_from_slice(&bytes);
+        self.fill[self.active] += bytes.len();
+        self.dropped = Dropped::default();
+        PushResult::Ok
+    }
+
+    fn drop_frame(&mut self, len: usize, reason: &str) -> PushResult {
+        self.dropped.messages = self.dropped.messages.saturating_add(1);
+        self.dropped.bytes = self.dropped.bytes.saturating_add(len as u32);
+        PushResult::Err(reason.to_string())
+    }
+
+    // seal the active segment and move on to the next one, false when that one is
+    // still waiting for the consumer
+    fn rotate(&mut self) -> bool {
+        let next = (self.active + 1) % self.fill.len();
+        if self.sealed.contains(&next) {
+            return false;
+        }
+        self.sealed.push_back(self.active);
+        self.active = next;
+        true
+    }
+
+    // hand the active segment over now if anything is in it, returns whether it was
+    pub fn seal(&mut self) -> bool {
+        self.fill[self.active] > 0 && self.rotate()
+    }
+
+    // the bytes of the oldest sealed segment: complete frames in wire format, back to back
+    pub fn peek_sealed(&self) -> Option<&[u8]> {
+        let &segment = self.sealed.front()?;
+        let start = segment * self.segment_size;
+        Some(&self.storage[start..start + self.fill[segment]])
+    }
+
+    // give the oldest sealed segment back to the producer
+    pub fn release(&mut self) {
+        if let Some(segment) = self.sealed.pop_front() {
+            self.fill[segment] = 0;
+        }
+    }
+
+    // decode the oldest sealed segment into `sink` and release it. when the sink fails
+    // the segment stays sealed and is handed over whole again next time
+    pub fn flush_sealed_to(&mut self, sink: &mut (impl FlushSink + ?Sized)) -> FlushToResult {
+        let mut stats = FlushStats::default();
+        let Some(bytes) = self.peek_sealed() else {
+            return FlushToResult::Ok(stats);
+        };
+        let mut chunks: Vec<&[u8]> = bytes.split(|&b| b == frame::TERMINATOR).collect();
+        // everything ends in a terminator, which leaves an empty chunk behind
+        chunks.pop();
+        for chunk in chunks {
+            match Frame::decode(chunk) {
+                FrameResult::Ok(frame) => {
+                    if let SinkResult::Err(e) = sink.write_frame(&frame) {
+                        return FlushToResult::Err(e);
+                    }
+                    stats.frames += 1;
+                }
+                FrameResult::Err(_) => stats.corrupt += 1,
+            }
+            stats.bytes += chunk.len() + 1;
+        }
+        if let SinkResult::Err(e) = sink.flush() {
+            return FlushToResult::Err(e);
+        }
+        self.release();
+        FlushToResult::Ok(stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sink::MemorySink;
+
+    fn payloads(frames: &[Frame]) -> Vec<&[u8]> {
+        frames.iter().map(|frame| &frame.payload[..]).collect()
+    }
+
+    // room for two `frame`s per segment, not three
+    fn segments(count: usize) -> SegmentedRing {
+        let len = Frame::new(b"frame").encode().len();
+        SegmentedRing::new(count, 3 * len - 1)
+    }
+
+    #[test]
+    fn a_frame_that_doesnt_fit_seals_the_segment() {
+        let mut segments = segments(3);
+        for _ in 0..2 {
+            let _ = segments.log_message_with_crc(b"frame");
+        }
+        assert_eq!(segments.sealed_count(), 0);
+        assert!(segments.peek_sealed().is_none());
+        let _ = segments.log_message_with_crc(b"frame");
+        assert_eq!(segments.sealed_count(), 1);
+        let encoded = Frame::new(b"frame").encode();
+        assert_eq!(
+            segments.peek_sealed().unwrap(),
+            [&encoded[..], &encoded].concat()
+        );
+
+        segments.release();
+        assert_eq!(segments.sealed_count(), 0);
+        assert!(segments.seal());
+        assert_eq!(segments.peek_sealed().unwrap(), encoded);
+        // nothing in the new active segment
+        assert!(!segments.seal());
+    }
+
+    #[test]
+    fn a_consumer_falling_behind_costs_frames_and_gets_a_marker() {
+        let mut segments = segments(2);
+        for _ in 0..4 {
+            assert!(matches!(
+                segments.log_message_with_crc(b"frame"),
+                PushResult::Ok
+            ));
+        }
+        assert!(matches!(
+            segments.log_message_with_crc(b"frame"),
+            PushResult::Err(_)
+        ));
+        assert!(matches!(
+            segments.log_frame(&Frame::new(&[0; 64])),
+            PushResult::Err(e) if e == "Frame larger than a segment"
+        ));
+
+        let mut sink = MemorySink::new();
+        let _ = segments.flush_sealed_to(&mut sink);
+        // seals the full segment on its way in
+        let _ = segments.log_message_with_crc(b"after");
+        let _ = segments.flush_sealed_to(&mut sink);
+        assert!(segments.seal());
+        let _ = segments.flush_sealed_to(&mut sink);
+        let frames = sink.take();
+        assert_eq!(
+            payloads(&frames),
+            [&b"frame"[..], b"frame", b"frame", b"frame", b"", b"after"]
+        );
+        assert_eq!(frames[4].dropped.unwrap().messages, 2);
+    }
+
+    #[test]
+    fn a_failing_sink_leaves_the_segment_sealed() {
+        struct Refuse;
+
+        impl FlushSink for Refuse {
+            fn write_frame(&mut self, _: &Frame) -> SinkResult {
+                SinkResult::Err("offline".to_string())
+            }
+        }
+
+        let mut segments = segments(2);
+        let _ = segments.log_message_with_crc(b"frame");
+        segments.seal();
+        assert!(matches!(
+            segments.flush_sealed_to(&mut Refuse),
+            FlushToResult::Err(_)
+        ));
+        assert_eq!(segments.sealed_count(), 1);
+
+        let mut sink = MemorySink::new();
+        assert!(
+            matches!(segments.flush_sealed_to(&mut sink), FlushToResult::Ok(stats) if stats.frames == 1)
+        );
+        assert_eq!(segments.sealed_count(), 0);
+        assert!(
+            matches!(segments.flush_sealed_to(&mut sink), FlushToResult::Ok(stats) if stats.frames == 0)
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn one_segment_isnt_enough() {
+        SegmentedRing::new(1, 64);
+    }
+}
@@ -0,0 +1,57 @@
+// prints frames through arm semihosting for the very first bring-up, before any uart, dma
+// or rtt path works. every call halts the core for the debugger, so swap in a real sink
+// (see `SwitchSink`) once one is up
+
+use alloc::format;
+use alloc::string::{String, ToString};
+
+use cortex_m_semihosting::hio::{self, HostStream};
+
+use crate::frame::Frame;
+use crate::sink::{FlushSink, SinkResult};
+
+// one line of text per frame on the debugger's stdout, like `StdoutSink`
+pub struct SemihostingSink {
+    stream: HostStream,
+}
+
+impl SemihostingSink {
+    // None without a debugger attached that handles semihosting
+    pub fn new() -> Option<Self> {
+        let stream = hio::hstdout().ok()?;
+        Some(SemihostingSink { stream })
+    }
+}
+
+fn line(frame: &Frame) -> String {
+    let text = String::from_utf8_lossy(&frame.payload);
+    match frame.level {
+        Some(level) => format!("[{:<5}] {}\n", level, text),
+        None => format!("{}\n", text),
+    }
+}
+
+impl FlushSink for SemihostingSink {
+    fn write_frame(&mut self, frame: &Frame) -> SinkResult {
+        match self.stream.write_all(line(frame).as_bytes()) {
+            Ok(()) => SinkResult::Ok,
+            Err(()) => SinkResult::Err("Semihosting write failed".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level::Level;
+
+    #[test]
+    fn frames_print_as_stdout_lines() {
+        assert_eq!(
+            line(&Frame::new(b"clock up").with_level(Level::Info)),
+            "[INFO ] clock up\n"
+        );
+        assert_eq!(line(&Frame::new(b"no level")), "no level\n");
+        assert_eq!(line(&Frame::new(&[b'a', 0xff])), "a\u{fffd}\n");
+    }
+}
@@ -0,0 +1,110 @@
+// consumer-side gap detection over frame sequence numbers
+
+use core::fmt;
+
+// frames that never made it to the consumer, between two frames that did
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Gap {
+    pub missing: u16,
+    pub after: u16,
+    pub before: u16,
+}
+
+impl fmt::Display for Gap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} frames missing between seq {} and {}",
+            self.missing, self.after, self.before
+        )
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct SequenceTracker {
+    last: Option<u16>,
+}
+
+impl SequenceTracker {
+    pub fn new() -> Self {
+        SequenceTracker { last: None }
+    }
+
+    // feed the seq of every decoded frame in order, returns the gap in front of it if any.
+    // a seq that goes backwards (producer reset, duplicate) just resyncs the tracker
+    pub fn check(&mut self, seq: u16) -> Option<Gap> {
+        let last = self.last.replace(seq)?;
+        let missing = seq.wrapping_sub(last).wrapping_sub(1);
+        if missing == 0 || missing >= u16::MAX / 2 {
+            return None;
+        }
+        Some(Gap {
+            missing,
+            after: last,
+            before: seq,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+
+    use super::*;
+    use crate::frame::{Frame, FrameResult};
+    use crate::{PushResult, RingBuffer};
+
+    #[test]
+    fn gaps_are_reported_across_the_wrap() {
+        let mut tracker = SequenceTracker::new();
+        assert_eq!(tracker.check(u16::MAX - 1), None);
+        assert_eq!(tracker.check(u16::MAX), None);
+        assert_eq!(tracker.check(0), None);
+        let gap = tracker.check(3).unwrap();
+        assert_eq!(
+            gap,
+            Gap {
+                missing: 2,
+                after: 0,
+                before: 3
+            }
+        );
+        assert_eq!(gap.to_string(), "2 frames missing between seq 0 and 3");
+    }
+
+    #[test]
+    fn going_backwards_resyncs() {
+        let mut tracker = SequenceTracker::new();
+        tracker.check(100);
+        assert_eq!(tracker.check(7), None);
+        assert_eq!(tracker.check(7), None);
+        assert_eq!(tracker.check(8), None);
+    }
+
+    #[test]
+    fn frames_rejected_on_a_full_ring_show_up_as_a_gap() {
+        let mut ring = RingBuffer::new(32);
+        ring.enable_sequence_numbers();
+        let mut rejected = 0;
+        for _ in 0..6 {
+            if let PushResult::Err(_) = ring.log(Frame::new(b"message")) {
+                rejected += 1;
+            }
+        }
+        assert!(rejected > 0);
+
+        let mut tracker = SequenceTracker::new();
+        let mut gaps = Vec::new();
+        let mut check = |ring: &mut RingBuffer| {
+            // the dropped marker in front of "after" isn't numbered
+            while let FrameResult::Ok(frame) = ring.flush_frame() {
+                gaps.extend(frame.seq.and_then(|seq| tracker.check(seq)));
+            }
+        };
+        check(&mut ring);
+        let _ = ring.log(Frame::new(b"after"));
+        check(&mut ring);
+        assert_eq!(gaps.len(), 1);
+        assert_eq!(gaps[0].missing, rejected);
+    }
+}
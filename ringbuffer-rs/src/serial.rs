@@ -0,0 +1,139 @@
+// host side of the uart: writes flushed frames to a serial device. frames are batched and
+// written in chunks of at most `write_budget` bytes, the same budget a firmware dma flush
+// works with, so timing on the wire looks like the device side
+
+use std::io::Write;
+use std::time::Duration;
+
+use serialport::{FlowControl, SerialPort};
+
+use crate::frame::Frame;
+use crate::sink::{FlushSink, SinkResult};
+
+pub struct SerialSink {
+    port: Box<dyn SerialPort>,
+    write_budget: usize,
+    pending: Vec<u8>,
+}
+
+impl SerialSink {
+    pub fn open(path: &str, baud_rate: u32, flow_control: FlowControl) -> serialport::Result<Self> {
+        let port = serialport::new(path, baud_rate)
+            .flow_control(flow_control)
+            .timeout(Duration::from_secs(1))
+            .open()?;
+        Ok(Self::from_port(port))
+    }
+
+    pub fn from_port(port: Box<dyn SerialPort>) -> Self {
+        SerialSink {
+            port,
+            write_budget: 32,
+            pending: Vec::new(),
+        }
+    }
+
+    // largest single write to the port, usually the ring's max_flush_size
+    pub fn with_write_budget(mut self, write_budget: usize) -> Self {
+        self.write_budget = write_budget.max(1);
+        self
+    }
+
+    pub fn port(&mut self) -> &mut dyn SerialPort {
+        self.port.as_mut()
+    }
+
+    // write out whole budget-sized chunks, or everything when `all` is set
+    fn drain(&mut self, all: bool) -> SinkResult {
+        while self.pending.len() >= self.write_budget || (all && !self.pending.is_empty()) {
+            let count = self.pending.len().min(self.write_budget);
+            if let Err(e) = self.port.write_all(&self.pending[..count]) {
+                return SinkResult::Err(e.to_string());
+            }
+            self.pending.drain(..count);
+        }
+        SinkResult::Ok
+    }
+}
+
+impl FlushSink for SerialSink {
+    fn write_frame(&mut self, frame: &Frame) -> SinkResult {
+        // bytes from an earlier failed write go out first, in order
+        if let SinkResult::Err(e) = self.drain(false) {
+            return SinkResult::Err(e);
+        }
+        self.pending.extend_from_slice(&frame.encode());
+        self.drain(false)
+    }
+
+    fn flush(&mut self) -> SinkResult {
+        if let SinkResult::Err(e) = self.drain(true) {
+            return SinkResult::Err(e);
+        }
+        match self.port.flush() {
+            Ok(()) => SinkResult::Ok,
+            Err(e) => SinkResult::Err(e.to_string()),
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::io::Read;
+
+    use serialport::TTYPort;
+
+    use super::*;
+    use crate::decoder::FrameDecoder;
+    use crate::RingBuffer;
+
+    fn available(port: &mut TTYPort) -> Vec<u8> {
+        let mut bytes = vec![0u8; port.bytes_to_read().unwrap() as usize];
+        port.read_exact(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn frames_go_out_in_budget_sized_writes() {
+        let (master, mut device) = TTYPort::pair().unwrap();
+        let mut sink = SerialSink::from_port(Box::new(master)).with_write_budget(16);
+        let frame = Frame::new(b"boot ok").encode();
+        assert!(frame.len() < 16);
+
+        // less than a budget's worth waits for more or for the flush
+        assert!(matches!(
+            sink.write_frame(&Frame::new(b"boot ok")),
+            SinkResult::Ok
+        ));
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(available(&mut device).is_empty());
+        assert!(matches!(
+            sink.write_frame(&Frame::new(b"boot ok")),
+            SinkResult::Ok
+        ));
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(
+            available(&mut device),
+            [&frame[..], &frame[..16 - frame.len()]].concat()
+        );
+
+        assert!(matches!(sink.flush(), SinkResult::Ok));
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(available(&mut device), &frame[16 - frame.len()..]);
+    }
+
+    #[test]
+    fn a_ring_flush_arrives_whole() {
+        let (master, mut device) = TTYPort::pair().unwrap();
+        let mut sink = SerialSink::from_port(Box::new(master));
+        let mut ring = RingBuffer::new(256);
+        for payload in [&b"one"[..], b"two", b"\x00three"] {
+            let _ = ring.log(Frame::new(payload));
+        }
+        let _ = ring.flush_all_to(&mut sink);
+        std::thread::sleep(Duration::from_millis(20));
+        let frames = FrameDecoder::new().feed(&available(&mut device));
+        let payloads: Vec<_> = frames.iter().map(|frame| &frame.payload[..]).collect();
+        assert_eq!(payloads, [&b"one"[..], b"two", b"\x00three"]);
+    }
+}
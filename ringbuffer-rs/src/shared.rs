@@ -0,0 +1,512 @@
+// a ring shared between threads, for producers that live behind global hooks (the `log`
+// facade, tracing subscribers) while the application keeps the consumer end
+//
+// a sink may log itself while a flush holds the ring (websocket and mqtt clients log
+// through the `log` facade from inside write_frame). locking again on the same thread
+// would deadlock, so `log` turns such a frame away and counts it as dropped instead. a
+// follower dropped there is unsubscribed by whoever locks the ring next
+
+use std::cell::RefCell;
+use std::future::poll_fn;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::task::{Poll, Waker};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::broadcast::{Filter, ReaderId};
+use crate::frame::Frame;
+use crate::sink::{FlushSink, FlushToResult};
+use crate::{PushResult, RingBuffer};
+
+#[derive(Clone)]
+pub struct SharedRing {
+    ring: Arc<Mutex<RingBuffer>>,
+    arrivals: Arc<Arrivals>,
+    deferred: Arc<Deferred>,
+}
+
+// what couldn't be done to the ring because the thread held it already, caught up on by
+// the next lock
+#[derive(Default)]
+struct Deferred {
+    // frames turned away
+    reentered: AtomicU32,
+    // followers dropped
+    unsubscribed: Mutex<Vec<ReaderId>>,
+}
+
+std::thread_local! {
+    // the rings this thread holds right now, by address
+    static HELD: RefCell<Vec<usize>> = const { RefCell::new(Vec::new()) };
+}
+
+// forgets the ring again when the lock goes, panics included
+struct Held(usize);
+
+impl Drop for Held {
+    fn drop(&mut self) {
+        HELD.with(|held| held.borrow_mut().retain(|&ring| ring != self.0));
+    }
+}
+
+// run `f` on the locked ring, None when this thread holds it already
+fn hold<R>(
+    ring: &Mutex<RingBuffer>,
+    deferred: &Deferred,
+    f: impl FnOnce(&mut RingBuffer) -> R,
+) -> Option<R> {
+    let (mut ring, _held) = lock(ring, deferred)?;
+    Some(f(&mut ring))
+}
+
+// the lock behind `hold`, for waiting on a condvar with it. the ring counts as held
+// until both go
+fn lock<'a>(
+    ring: &'a Mutex<RingBuffer>,
+    deferred: &Deferred,
+) -> Option<(MutexGuard<'a, RingBuffer>, Held)> {
+    let key = ring as *const Mutex<RingBuffer> as usize;
+    if HELD.with(|held| held.borrow().contains(&key)) {
+        return None;
+    }
+    // a panic while holding the lock doesn't leave the ring itself in a bad state
+    let mut ring = ring.lock().unwrap_or_else(|e| e.into_inner());
+    HELD.with(|held| held.borrow_mut().push(key));
+    let held = Held(key);
+    let missed = deferred.reentered.swap(0, Ordering::Relaxed);
+    if missed > 0 {
+        ring.note_dropped(missed);
+    }
+    let unsubscribed = std::mem::take(
+        &mut *deferred
+            .unsubscribed
+            .lock()
+            .unwrap_or_else(|e| e.into_inner()),
+    );
+    for reader in unsubscribed {
+        ring.unsubscribe(reader);
+    }
+    Some((ring, held))
+}
+
+// followers waiting for frames, woken after every `with_ring`
+#[derive(Default)]
+struct Arrivals {
+    logged: Condvar,
+    wakers: Mutex<Vec<Waker>>,
+}
+
+impl SharedRing {
+    pub fn new(ring: RingBuffer) -> Self {
+        SharedRing {
+            ring: Arc::new(Mutex::new(ring)),
+            arrivals: Arc::default(),
+            deferred: Arc::default(),
+        }
+    }
+
+    // the way producers log into the ring, followers get to see what they logged. panics
+    // when called from inside a flush of this ring on the same thread (a sink), where
+    // waiting for the lock would never end. use `log` or `try_with_ring` there
+    pub fn with_ring<R>(&self, f: impl FnOnce(&mut RingBuffer) -> R) -> R {
+        self.try_with_ring(f)
+            .expect("ring is already locked by this thread")
+    }
+
+    // with_ring, None instead of a deadlock when this thread holds the ring already
+    pub fn try_with_ring<R>(&self, f: impl FnOnce(&mut RingBuffer) -> R) -> Option<R> {
+        let result = hold(&self.ring, &self.deferred, f)?;
+        self.arrivals.logged.notify_all();
+        let wakers = std::mem::take(
+            &mut *self
+                .arrivals
+                .wakers
+                .lock()
+                .unwrap_or_else(|e| e.into_inner()),
+        );
+        for waker in wakers {
+            waker.wake();
+        }
+        Some(result)
+    }
+
+    // log from anywhere, sinks running inside a flush of this ring included. a frame
+    // that can't go in right now counts as dropped
+    pub fn log(&self, frame: Frame) -> PushResult {
+        match self.try_with_ring(|ring| ring.log(frame)) {
+            Some(result) => result,
+            None => {
+                self.deferred.reentered.fetch_add(1, Ordering::Relaxed);
+                PushResult::Err("Ring is being flushed on this thread".to_string())
+            }
+        }
+    }
+
+    // `tail -f`: frames logged from now on that pass `filter`, see `RingBuffer::follow`.
+    // panics inside a flush of this ring on the same thread, like `with_ring`
+    pub fn follow(&self, filter: Filter) -> Follower {
+        let reader = self.with_ring(|ring| ring.follow(filter));
+        Follower {
+            ring: self.clone(),
+            reader,
+        }
+    }
+
+    // the flushes run the sink with the ring held, a sink flushing the same ring again
+    // gets an error
+    fn flush_with(&self, f: impl FnOnce(&mut RingBuffer) -> FlushToResult) -> FlushToResult {
+        hold(&self.ring, &self.deferred, f).unwrap_or_else(|| {
+            FlushToResult::Err("Ring is being flushed on this thread".to_string())
+        })
+    }
+
+    pub fn flush_to(&self, sink: &mut impl FlushSink) -> FlushToResult {
+        self.flush_with(|ring| ring.flush_to(sink))
+    }
+
+    pub fn flush_all_to(&self, sink: &mut impl FlushSink) -> FlushToResult {
+        self.flush_with(|ring| ring.flush_all_to(sink))
+    }
+
+    pub fn flush_older_than(&self, sink: &mut impl FlushSink, max_age: u64) -> FlushToResult {
+        self.flush_with(|ring| ring.flush_older_than(sink, max_age))
+    }
+
+    // flush frames older than `max_age` clock ticks into `sink` every `period` from a
+    // thread of its own. a failing sink is retried on the next round. the thread only
+    // holds on to the ring weakly and ends once the last handle to it is gone
+    pub fn spawn_age_flush(
+        &self,
+        mut sink: impl FlushSink + Send + 'static,
+        max_age: u64,
+        period: Duration,
+    ) -> JoinHandle<()> {
+        let ring = Arc::downgrade(&self.ring);
+        let deferred = self.deferred.clone();
+        thread::spawn(move || loop {
+            thread::sleep(period);
+            let Some(ring) = ring.upgrade() else {
+                break;
+            };
+            let _ = hold(&ring, &deferred, |ring| {
+                ring.flush_older_than(&mut sink, max_age)
+            });
+        })
+    }
+}
+
+// a reader of a shared ring that waits for frames to arrive. dropping it unsubscribes.
+// a lapsed follower gets nothing until it catches up (or the ring resyncs it), the
+// receiving calls return None for it rather than wait. so do they inside a flush of this
+// ring on the same thread, where waiting would never end
+pub struct Follower {
+    ring: SharedRing,
+    reader: ReaderId,
+}
+
+impl Follower {
+    pub fn reader(&self) -> ReaderId {
+        self.reader
+    }
+
+    fn hold<R>(&self, f: impl FnOnce(&mut RingBuffer) -> R) -> Option<R> {
+        hold(&self.ring.ring, &self.ring.deferred, f)
+    }
+
+    // copy the stored frames it hasn't read out to read first, see `RingBuffer::catch_up`
+    pub fn catch_up(&self) -> usize {
+        self.hold(|ring| ring.catch_up(self.reader))
+            .flatten()
+            .unwrap_or(0)
+    }
+
+    // the next frame if there is one already
+    pub fn try_recv(&self) -> Option<Frame> {
+        self.hold(|ring| ring.read(self.reader)).flatten()
+    }
+
+    // the next frame, waiting for it as long as it takes
+    pub fn recv(&self) -> Option<Frame> {
+        let (mut ring, _held) = lock(&self.ring.ring, &self.ring.deferred)?;
+        loop {
+            if let Some(frame) = self.next(&mut ring) {
+                return frame;
+            }
+            ring = self
+                .ring
+                .arrivals
+                .logged
+                .wait(ring)
+                .unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    // the next frame, None if none came in within `timeout`
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<Frame> {
+        let deadline = Instant::now() + timeout;
+        let (mut ring, _held) = lock(&self.ring.ring, &self.ring.deferred)?;
+        loop {
+            if let Some(frame) = self.next(&mut ring) {
+                return frame;
+            }
+            let left = deadline.checked_duration_since(Instant::now())?;
+            ring = self
+                .ring
+                .arrivals
+                .logged
+                .wait_timeout(ring, left)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+    }
+
+    // recv for async code, on any executor
+    pub async fn recv_async(&self) -> Option<Frame> {
+        poll_fn(|cx| {
+            let Some((mut ring, _held)) = lock(&self.ring.ring, &self.ring.deferred) else {
+                return Poll::Ready(None);
+            };
+            if let Some(frame) = self.next(&mut ring) {
+                return Poll::Ready(frame);
+            }
+            // registered while holding the ring, a frame logged after the read wakes it
+            let mut wakers = self
+                .ring
+                .arrivals
+                .wakers
+                .lock()
+                .unwrap_or_else(|e| e.into_inner());
+            if !wakers.iter().any(|waker| waker.will_wake(cx.waker())) {
+                wakers.push(cx.waker().clone());
+            }
+            Poll::Pending
+        })
+        .await
+    }
+
+    // the next frame, Some(None) when there won't be one without a catch up, None when
+    // it's worth waiting
+    fn next(&self, ring: &mut RingBuffer) -> Option<Option<Frame>> {
+        if let Some(frame) = ring.read(self.reader) {
+            return Some(Some(frame));
+        }
+        let stuck = ring.is_lapsed(self.reader) || ring.backlog(self.reader).is_none();
+        stuck.then_some(None)
+    }
+}
+
+impl Drop for Follower {
+    fn drop(&mut self) {
+        let unsubscribed = self.hold(|ring| ring.unsubscribe(self.reader));
+        if unsubscribed.is_none() {
+            self.ring
+                .deferred
+                .unsubscribed
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .push(self.reader);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::broadcast::SlowReader;
+    use crate::sink::SinkResult;
+
+    #[test]
+    fn sink_logging_into_the_ring_it_flushes_is_counted_as_dropped() {
+        let shared = SharedRing::new(RingBuffer::new(256));
+        let _ = shared.log(Frame::new(b"first"));
+        let inner = shared.clone();
+        let mut sink = |_: &Frame| {
+            // what a websocket or mqtt client does through the `log` facade
+            assert!(matches!(
+                inner.log(Frame::new(b"nested")),
+                PushResult::Err(_)
+            ));
+            SinkResult::Ok
+        };
+        assert!(matches!(shared.flush_to(&mut sink), FlushToResult::Ok(_)));
+
+        let _ = shared.log(Frame::new(b"second"));
+        let mut frames = Vec::new();
+        let _ = shared.flush_to(&mut |frame: &Frame| {
+            frames.push(frame.clone());
+            SinkResult::Ok
+        });
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].dropped.map(|dropped| dropped.messages), Some(1));
+        assert_eq!(frames[1].payload, b"second");
+    }
+
+    #[test]
+    fn other_threads_still_wait_for_the_lock() {
+        let shared = SharedRing::new(RingBuffer::new(256));
+        let _ = shared.log(Frame::new(b"x"));
+        let other = shared.clone();
+        let mut logger = None;
+        let mut sink = |_: &Frame| {
+            let other = other.clone();
+            logger = Some(thread::spawn(move || {
+                other.log(Frame::new(b"other thread"))
+            }));
+            SinkResult::Ok
+        };
+        let _ = shared.flush_to(&mut sink);
+        let logged = logger.unwrap().join().unwrap();
+        assert!(matches!(logged, PushResult::Ok));
+        assert!(shared.with_ring(|ring| !ring.is_empty()));
+    }
+
+    #[test]
+    fn the_age_flush_thread_sends_old_frames_and_ends_with_the_ring() {
+        let now = Arc::new(std::sync::atomic::AtomicU64::new(0));
+        let mut ring = RingBuffer::new(256);
+        let clock = now.clone();
+        ring.set_clock(move || clock.load(Ordering::SeqCst));
+        let shared = SharedRing::new(ring);
+        let _ = shared.log(Frame::new(b"aging"));
+
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let sink = {
+            let sent = sent.clone();
+            move |frame: &Frame| {
+                sent.lock().unwrap().push(frame.payload.clone());
+                SinkResult::Ok
+            }
+        };
+        let flusher = shared.spawn_age_flush(sink, 10, Duration::from_millis(5));
+        thread::sleep(Duration::from_millis(30));
+        assert!(sent.lock().unwrap().is_empty());
+
+        now.store(11, Ordering::SeqCst);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while sent.lock().unwrap().is_empty() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(5));
+        }
+        assert_eq!(*sent.lock().unwrap(), [b"aging".to_vec()]);
+
+        drop(shared);
+        flusher.join().unwrap();
+    }
+
+    #[test]
+    fn a_follower_waits_for_frames_logged_from_other_threads() {
+        let shared = SharedRing::new(RingBuffer::new(256));
+        let _ = shared.log(Frame::new(b"history"));
+        let follower = shared.follow(Filter::new());
+        assert!(follower.try_recv().is_none());
+        assert!(follower.recv_timeout(Duration::from_millis(10)).is_none());
+
+        let producer = shared.clone();
+        let logger = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            let _ = producer.log(Frame::new(b"live"));
+        });
+        assert_eq!(follower.recv().unwrap().payload, b"live");
+        logger.join().unwrap();
+
+        let _ = shared.log(Frame::new(b"ready"));
+        assert_eq!(
+            follower
+                .recv_timeout(Duration::from_secs(5))
+                .map(|frame| frame.payload),
+            Some(b"ready".to_vec())
+        );
+    }
+
+    #[test]
+    fn an_async_follower_is_woken_by_the_next_frame() {
+        struct Unpark(thread::Thread);
+
+        impl std::task::Wake for Unpark {
+            fn wake(self: Arc<Self>) {
+                self.0.unpark();
+            }
+        }
+
+        let shared = SharedRing::new(RingBuffer::new(256));
+        let follower = shared.follow(Filter::new());
+        let producer = shared.clone();
+        let logger = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            let _ = producer.log(Frame::new(b"async"));
+        });
+
+        let waker = Waker::from(Arc::new(Unpark(thread::current())));
+        let mut cx = std::task::Context::from_waker(&waker);
+        let mut recv = std::pin::pin!(follower.recv_async());
+        let frame = loop {
+            match std::future::Future::poll(recv.as_mut(), &mut cx) {
+                Poll::Ready(frame) => break frame,
+                Poll::Pending => thread::park(),
+            }
+        };
+        assert_eq!(frame.unwrap().payload, b"async");
+        logger.join().unwrap();
+    }
+
+    #[test]
+    fn dropping_a_follower_unsubscribes_it() {
+        let shared = SharedRing::new(RingBuffer::new(256));
+        let follower = shared.follow(Filter::new());
+        assert_eq!(shared.with_ring(|ring| ring.readers()), 1);
+        drop(follower);
+        assert_eq!(shared.with_ring(|ring| ring.readers()), 0);
+    }
+
+    #[test]
+    fn a_lapsed_follower_returns_instead_of_waiting() {
+        let shared = SharedRing::new(RingBuffer::new(256));
+        let follower = shared.follow(Filter::new());
+        shared.with_ring(|ring| ring.set_slow_reader(follower.reader(), SlowReader::Drop));
+        let _ = shared.log(Frame::new(b"missed"));
+        // flushed before the follower read it
+        let _ = shared.flush_all_to(&mut |_: &Frame| SinkResult::Ok);
+        let _ = shared.log(Frame::new(b"after"));
+
+        assert!(follower.recv().is_none());
+        assert!(follower.recv_timeout(Duration::from_secs(5)).is_none());
+        let waker = Waker::noop();
+        let mut cx = std::task::Context::from_waker(waker);
+        let mut recv = std::pin::pin!(follower.recv_async());
+        assert!(matches!(
+            std::future::Future::poll(recv.as_mut(), &mut cx),
+            Poll::Ready(None)
+        ));
+
+        assert_eq!(follower.catch_up(), 1);
+        assert_eq!(follower.recv().unwrap().payload, b"after");
+    }
+
+    #[test]
+    fn an_unsubscribed_follower_returns_instead_of_waiting() {
+        let shared = SharedRing::new(RingBuffer::new(256));
+        let follower = shared.follow(Filter::new());
+        shared.with_ring(|ring| ring.unsubscribe(follower.reader()));
+        assert!(follower.recv().is_none());
+    }
+
+    #[test]
+    fn a_follower_inside_a_flush_of_its_ring_doesnt_deadlock() {
+        let shared = SharedRing::new(RingBuffer::new(256));
+        let _ = shared.log(Frame::new(b"x"));
+        let follower = shared.follow(Filter::new());
+        let mut follower = Some(follower);
+        let mut sink = |_: &Frame| {
+            let inner = follower.as_ref().unwrap();
+            assert_eq!(inner.catch_up(), 0);
+            assert!(inner.try_recv().is_none());
+            assert!(inner.recv().is_none());
+            assert!(inner.recv_timeout(Duration::from_secs(5)).is_none());
+            // unsubscribed once the flush lets go of the ring
+            drop(follower.take());
+            SinkResult::Ok
+        };
+        assert!(matches!(shared.flush_to(&mut sink), FlushToResult::Ok(_)));
+        assert_eq!(shared.with_ring(|ring| ring.readers()), 0);
+    }
+}
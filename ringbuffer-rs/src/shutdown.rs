@@ -0,0 +1,178 @@
+// last chance flush when power or the process is about to go away (brown-out interrupt,
+// SIGTERM, watchdog early warning). the emergency sink is registered up front, usually
+// something that can't fail at that point (a blocking uart, flash, stderr):
+//
+//     ring.set_emergency_sink(UartSink::new(uart));
+//     ...
+//     // brown-out interrupt, about 2ms left at 1 tick per µs
+//     ring.on_imminent_shutdown(ring_clock_now() + 2000);
+//
+// the deadline is in ticks of the ring's clock. frames go out oldest first, and once the
+// sink's pace so far says the rest won't make it, the oldest ones are skipped so the
+// time left goes to the newest frames
+
+use alloc::boxed::Box;
+use alloc::string::ToString;
+
+use crate::sink::{FlushSink, FlushStats, FlushToResult, SinkResult};
+use crate::RingBuffer;
+
+impl RingBuffer {
+    pub fn set_emergency_sink(&mut self, sink: impl FlushSink + Send + 'static) {
+        self.emergency = Some(Box::new(sink));
+    }
+
+    // drain into the emergency sink until `deadline`, then freeze so nothing logged
+    // during the shutdown gets in the way
+    pub fn on_imminent_shutdown(&mut self, deadline: u64) -> FlushToResult {
+        let Some(mut sink) = self.emergency.take() else {
+            return FlushToResult::Err("No emergency sink".to_string());
+        };
+        let result = self.drain_before(sink.as_mut(), deadline);
+        self.emergency = Some(sink);
+        self.freeze();
+        result
+    }
+
+    // drain every complete frame into `sink`, giving up on the oldest ones when the
+    // deadline (in clock ticks) can't be met otherwise. without a clock this is
+    // flush_all_to
+    pub fn drain_before(
+        &mut self,
+        sink: &mut (impl FlushSink + ?Sized),
+        deadline: u64,
+    ) -> FlushToResult {
+        let Some(start) = self.clock.as_ref().map(|clock| clock.now()) else {
+            return self.flush_all_to(sink);
+        };
+        self.find_newest_latest();
+        let mut stats = FlushStats::default();
+        loop {
+            let now = self.clock.as_ref().map_or(start, |clock| clock.now());
+            // bytes the sink can still take at its pace so far, nothing once time is up
+            let affordable = match (now < deadline, stats.bytes) {
+                (false, _) => Some(0),
+                (true, 0) => None,
+                (true, sent) => {
+                    let elapsed = now.saturating_sub(start).max(1) as u128;
+                    Some((sent as u128 * (deadline - now) as u128 / elapsed) as usize)
+                }
+            };
+            if let Some(affordable) = affordable {
+                while self.complete_len() > affordable {
+                    let Some(size) = self.get_next_message_size() else {
+                        break;
+                    };
+                    self.skip(size + 1);
+                    stats.skipped += 1;
+                }
+            }
+            match self.flush_next_to(sink, &mut stats) {
+                Some(SinkResult::Ok) => {}
+                Some(SinkResult::Err(e)) => return FlushToResult::Err(e),
+                None => break,
+            }
+        }
+
+        if let SinkResult::Err(e) = sink.flush() {
+            return FlushToResult::Err(e);
+        }
+        FlushToResult::Ok(stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::sync::Arc;
+    use alloc::vec::Vec;
+    use core::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::frame::Frame;
+    use crate::PushResult;
+
+    // a sink that takes 10 ticks of `clock` for every frame
+    #[derive(Clone, Default)]
+    struct Slow {
+        clock: Arc<AtomicU64>,
+        payloads: Arc<Mutex<Vec<Vec<u8>>>>,
+    }
+
+    impl FlushSink for Slow {
+        fn write_frame(&mut self, frame: &Frame) -> SinkResult {
+            self.clock.fetch_add(10, Ordering::Relaxed);
+            self.payloads.lock().unwrap().push(frame.payload.clone());
+            SinkResult::Ok
+        }
+    }
+
+    // frames of the same length, numbered from 1
+    fn ring(frames: u8) -> RingBuffer {
+        let mut ring = RingBuffer::new(256);
+        for i in 1..=frames {
+            let _ = ring.log(Frame::new(&[i; 4]));
+        }
+        ring
+    }
+
+    #[test]
+    fn everything_goes_out_without_a_clock_and_the_ring_freezes() {
+        let mut ring = ring(5);
+        assert!(
+            matches!(ring.on_imminent_shutdown(0), FlushToResult::Err(e) if e == "No emergency sink")
+        );
+
+        let sink = Slow::default();
+        ring.set_emergency_sink(sink.clone());
+        assert!(
+            matches!(ring.on_imminent_shutdown(0), FlushToResult::Ok(stats) if stats.frames == 5)
+        );
+        assert!(ring.is_empty());
+        assert!(matches!(ring.log(Frame::new(b"late")), PushResult::Err(_)));
+
+        // still registered for the next time
+        ring.unfreeze();
+        let _ = ring.log(Frame::new(b"again"));
+        let _ = ring.on_imminent_shutdown(0);
+        assert_eq!(sink.payloads.lock().unwrap().last().unwrap(), b"again");
+    }
+
+    #[test]
+    fn the_newest_frames_win_when_time_runs_short() {
+        let mut ring = ring(10);
+        let sink = Slow::default();
+        let clock = sink.clock.clone();
+        ring.set_clock(move || clock.load(Ordering::Relaxed));
+
+        // room for four frames at the sink's pace
+        let result = ring.drain_before(&mut sink.clone(), 40);
+        assert!(
+            matches!(result, FlushToResult::Ok(stats) if stats.frames == 4 && stats.skipped == 6)
+        );
+        let firsts = sink
+            .payloads
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|p| p[0])
+            .collect::<Vec<_>>();
+        // the first one goes out before the pace is known
+        assert_eq!(firsts, [1, 8, 9, 10]);
+        assert!(ring.is_empty());
+    }
+
+    #[test]
+    fn a_deadline_already_passed_sends_nothing() {
+        let mut ring = ring(3);
+        let sink = Slow::default();
+        sink.clock.store(50, Ordering::Relaxed);
+        let clock = sink.clock.clone();
+        ring.set_clock(move || clock.load(Ordering::Relaxed));
+        let result = ring.drain_before(&mut sink.clone(), 40);
+        assert!(
+            matches!(result, FlushToResult::Ok(stats) if stats.frames == 0 && stats.skipped == 3)
+        );
+        assert!(sink.payloads.lock().unwrap().is_empty());
+    }
+}
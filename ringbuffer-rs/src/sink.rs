@@ -0,0 +1,395 @@
+// pluggable flush targets. the ring validates frames and hands them over one at a time,
+// a sink only has to move them somewhere (uart, radio, file, socket, ...)
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::frame::{Frame, FrameResult};
+use crate::RingBuffer;
+
+pub enum SinkResult {
+    Ok,
+    Err(String),
+}
+
+pub trait FlushSink {
+    fn write_frame(&mut self, frame: &Frame) -> SinkResult;
+
+    // called once at the end of every flush, even one that had nothing to deliver
+    fn flush(&mut self) -> SinkResult {
+        SinkResult::Ok
+    }
+}
+
+impl<F: FnMut(&Frame) -> SinkResult> FlushSink for F {
+    fn write_frame(&mut self, frame: &Frame) -> SinkResult {
+        self(frame)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FlushStats {
+    // frames handed to the sink
+    pub frames: usize,
+    // encoded bytes taken out of the ring for them, terminators included
+    pub bytes: usize,
+    // frames that failed their crc and were dropped
+    pub corrupt: usize,
+    // frames given up on to make a deadline (see `drain_before`), past their ttl or
+    // superseded by a newer one (see `latest`)
+    pub skipped: usize,
+}
+
+pub enum FlushToResult {
+    Ok(FlushStats),
+    // the sink failed, the frame it rejected is still at the front of the ring
+    Err(String),
+}
+
+impl RingBuffer {
+    // hand complete frames to `sink` until max_flush_size bytes have gone out
+    pub fn flush_to(&mut self, sink: &mut (impl FlushSink + ?Sized)) -> FlushToResult {
+        self.flush_to_budget(sink, self.max_flush_size)
+    }
+
+    // same as flush_to but ignores the byte budget, drains every complete frame
+    pub fn flush_all_to(&mut self, sink: &mut (impl FlushSink + ?Sized)) -> FlushToResult {
+        self.flush_to_budget(sink, usize::MAX)
+    }
+
+    fn flush_to_budget(
+        &mut self,
+        sink: &mut (impl FlushSink + ?Sized),
+        budget: usize,
+    ) -> FlushToResult {
+        self.heartbeat_if_due();
+        self.find_newest_latest();
+        let mut stats = FlushStats::default();
+        while stats.bytes < budget {
+            match self.flush_next_to(sink, &mut stats) {
+                Some(SinkResult::Ok) => {}
+                Some(SinkResult::Err(e)) => {
+                    self.pay_flush(stats.bytes);
+                    return FlushToResult::Err(e);
+                }
+                None => break,
+            }
+        }
+        self.pay_flush(stats.bytes);
+
+        if let SinkResult::Err(e) = sink.flush() {
+            return FlushToResult::Err(e);
+        }
+        if let Some(hooks) = &mut self.hooks {
+            hooks.on_flush(&stats);
+        }
+        FlushToResult::Ok(stats)
+    }
+
+    // hand the frame at the tail (the most severe one when flushing by priority, the first
+    // unsent one with acks on) to `sink`, or drop it when corrupt, and count it in
+    // `stats`. None when there's no complete frame or pacing and the ack window hold it
+    // back
+    pub(crate) fn flush_next_to(
+        &mut self,
+        sink: &mut (impl FlushSink + ?Sized),
+        stats: &mut FlushStats,
+    ) -> Option<SinkResult> {
+        let (offset, bytes) = self.next_flush_bytes()?;
+        let frame = match Frame::decode(&bytes) {
+            FrameResult::Ok(frame) => frame,
+            decoded @ FrameResult::Err(_) => {
+                self.consume_frame_at(offset, bytes.len());
+                self.count_flushed(&bytes, &decoded);
+                stats.corrupt += 1;
+                return Some(SinkResult::Ok);
+            }
+        };
+        if self.is_expired(&frame) || self.is_superseded(&frame, offset) {
+            self.consume_frame_at(offset, bytes.len());
+            stats.skipped += 1;
+            return Some(SinkResult::Ok);
+        }
+
+        if !self.frame_paced(bytes.len(), stats.bytes) || !self.window_allows(bytes.len()) {
+            return None;
+        }
+
+        // only take the frame out once the sink accepted it, with acks on it stays until
+        // it's acknowledged
+        if let SinkResult::Err(e) = sink.write_frame(&frame) {
+            return Some(SinkResult::Err(e));
+        }
+        match self.sent {
+            Some(_) => self.mark_sent(offset, bytes.len()),
+            None => self.consume_frame_at(offset, bytes.len()),
+        }
+        self.counters.flushed_frames += 1;
+        self.counters.flushed_bytes += bytes.len() as u64 + 1;
+        self.note_latency(&frame);
+        stats.frames += 1;
+        stats.bytes += bytes.len() + 1;
+        Some(SinkResult::Ok)
+    }
+}
+
+// collects frames in memory, handy for tests and for handing batches to other code
+#[derive(Debug, Clone, Default)]
+pub struct MemorySink {
+    frames: Vec<Frame>,
+}
+
+impl MemorySink {
+    pub fn new() -> Self {
+        MemorySink { frames: Vec::new() }
+    }
+
+    pub fn frames(&self) -> &[Frame] {
+        &self.frames
+    }
+
+    pub fn take(&mut self) -> Vec<Frame> {
+        core::mem::take(&mut self.frames)
+    }
+}
+
+impl FlushSink for MemorySink {
+    fn write_frame(&mut self, frame: &Frame) -> SinkResult {
+        self.frames.push(frame.clone());
+        SinkResult::Ok
+    }
+}
+
+// a sink that can be swapped at runtime, e.g. semihosting during bring-up and the uart
+// once it's initialised. flush code keeps taking the same `&mut SwitchSink`
+pub struct SwitchSink<'a> {
+    sink: Box<dyn FlushSink + 'a>,
+}
+
+impl<'a> SwitchSink<'a> {
+    pub fn new(sink: impl FlushSink + 'a) -> Self {
+        SwitchSink {
+            sink: Box::new(sink),
+        }
+    }
+
+    // route every later flush to `sink`, the old one gets a final flush first
+    pub fn switch_to(&mut self, sink: impl FlushSink + 'a) -> SinkResult {
+        let result = self.sink.flush();
+        self.sink = Box::new(sink);
+        result
+    }
+}
+
+impl FlushSink for SwitchSink<'_> {
+    fn write_frame(&mut self, frame: &Frame) -> SinkResult {
+        self.sink.write_frame(frame)
+    }
+
+    fn flush(&mut self) -> SinkResult {
+        self.sink.flush()
+    }
+}
+
+// prints each payload as a line of text, prefixed with the level when there is one
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StdoutSink;
+
+#[cfg(feature = "std")]
+impl FlushSink for StdoutSink {
+    fn write_frame(&mut self, frame: &Frame) -> SinkResult {
+        use std::io::Write;
+
+        let text = String::from_utf8_lossy(&frame.payload);
+        let mut stdout = std::io::stdout().lock();
+        let result = match frame.level {
+            Some(level) => writeln!(stdout, "[{:<5}] {}", level, text),
+            None => writeln!(stdout, "{}", text),
+        };
+        match result {
+            Ok(()) => SinkResult::Ok,
+            Err(e) => SinkResult::Err(e.to_string()),
+        }
+    }
+}
+
+// writes frames in wire format to any writer, readable again with `FrameDecoder`
+#[cfg(feature = "std")]
+pub struct WriterSink<W: std::io::Write> {
+    writer: W,
+}
+
+#[cfg(feature = "std")]
+impl<W: std::io::Write> WriterSink<W> {
+    pub fn new(writer: W) -> Self {
+        WriterSink { writer }
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+#[cfg(feature = "std")]
+impl<W: std::io::Write> FlushSink for WriterSink<W> {
+    fn write_frame(&mut self, frame: &Frame) -> SinkResult {
+        match self.writer.write_all(&frame.encode()) {
+            Ok(()) => SinkResult::Ok,
+            Err(e) => SinkResult::Err(e.to_string()),
+        }
+    }
+
+    fn flush(&mut self) -> SinkResult {
+        match self.writer.flush() {
+            Ok(()) => SinkResult::Ok,
+            Err(e) => SinkResult::Err(e.to_string()),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+pub type FileSink = WriterSink<std::io::BufWriter<std::fs::File>>;
+
+#[cfg(feature = "std")]
+impl WriterSink<std::io::BufWriter<std::fs::File>> {
+    // truncates an existing file
+    pub fn create(path: impl AsRef<std::path::Path>) -> std::io::Result<Self> {
+        let file = std::fs::File::create(path)?;
+        Ok(WriterSink::new(std::io::BufWriter::new(file)))
+    }
+
+    pub fn append(path: impl AsRef<std::path::Path>) -> std::io::Result<Self> {
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        Ok(WriterSink::new(std::io::BufWriter::new(file)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+
+    use super::*;
+
+    fn filled(payloads: &[&[u8]]) -> RingBuffer {
+        let mut ring = RingBuffer::new(256);
+        for payload in payloads {
+            let _ = ring.log(Frame::new(payload));
+        }
+        ring
+    }
+
+    fn payloads(frames: &[Frame]) -> Vec<&[u8]> {
+        frames.iter().map(|frame| &frame.payload[..]).collect()
+    }
+
+    #[test]
+    fn flush_to_stops_at_the_byte_budget() {
+        let mut ring = filled(&[b"aaaa", b"bbbb", b"cccc"]);
+        let len = Frame::new(b"aaaa").encode().len();
+        // the budget is checked before each frame, so a frame that starts under it
+        // still goes out whole
+        ring.set_max_flush_size(len + 1);
+        let mut sink = MemorySink::new();
+        let FlushToResult::Ok(stats) = ring.flush_to(&mut sink) else {
+            panic!("flush failed");
+        };
+        assert_eq!((stats.frames, stats.bytes), (2, 2 * len));
+        assert_eq!(payloads(sink.frames()), [b"aaaa", b"bbbb"]);
+
+        let FlushToResult::Ok(stats) = ring.flush_all_to(&mut sink) else {
+            panic!("flush failed");
+        };
+        assert_eq!(stats.frames, 1);
+        assert!(ring.is_empty());
+    }
+
+    #[test]
+    fn a_rejected_frame_stays_at_the_front() {
+        let mut ring = filled(&[b"one", b"two"]);
+        let mut seen = Vec::new();
+        let mut failing = |frame: &Frame| {
+            seen.push(frame.payload.clone());
+            if frame.payload == b"two" {
+                SinkResult::Err("link down".to_string())
+            } else {
+                SinkResult::Ok
+            }
+        };
+        assert!(
+            matches!(ring.flush_all_to(&mut failing), FlushToResult::Err(e) if e == "link down")
+        );
+        assert_eq!(seen, [b"one", b"two"]);
+
+        let mut sink = MemorySink::new();
+        let _ = ring.flush_all_to(&mut sink);
+        assert_eq!(payloads(sink.frames()), [b"two"]);
+    }
+
+    #[test]
+    fn corrupt_frames_are_dropped_and_counted() {
+        let mut ring = filled(&[b"good"]);
+        let mut bad = Frame::new(b"bad").encode();
+        bad[1] ^= 0x01;
+        ring.push_slice(&bad);
+        let _ = ring.log(Frame::new(b"after"));
+
+        let mut sink = MemorySink::new();
+        let FlushToResult::Ok(stats) = ring.flush_all_to(&mut sink) else {
+            panic!("flush failed");
+        };
+        assert_eq!((stats.frames, stats.corrupt), (2, 1));
+        assert_eq!(payloads(&sink.take()), [&b"good"[..], b"after"]);
+        assert_eq!(ring.stats().crc_errors, 1);
+    }
+
+    // notes what it was asked to do
+    struct Recorder<'a>(&'a mut Vec<String>);
+
+    impl FlushSink for Recorder<'_> {
+        fn write_frame(&mut self, frame: &Frame) -> SinkResult {
+            self.0
+                .push(String::from_utf8_lossy(&frame.payload).into_owned());
+            SinkResult::Ok
+        }
+
+        fn flush(&mut self) -> SinkResult {
+            self.0.push("flush".to_string());
+            SinkResult::Ok
+        }
+    }
+
+    #[test]
+    fn switching_sinks_flushes_the_old_one() {
+        let mut ring = filled(&[b"early"]);
+        let (mut early, mut late) = (Vec::new(), Vec::new());
+        let mut switch = SwitchSink::new(Recorder(&mut early));
+        let _ = ring.flush_all_to(&mut switch);
+        let _ = ring.log(Frame::new(b"late"));
+        assert!(matches!(
+            switch.switch_to(Recorder(&mut late)),
+            SinkResult::Ok
+        ));
+        let _ = ring.flush_all_to(&mut switch);
+        drop(switch);
+        assert_eq!(early, ["early", "flush", "flush"]);
+        assert_eq!(late, ["late", "flush"]);
+    }
+
+    #[test]
+    fn writer_sink_output_decodes_again() {
+        let mut ring = filled(&[b"one", b"\x00two"]);
+        let mut sink = WriterSink::new(Vec::new());
+        let _ = ring.flush_all_to(&mut sink);
+        let frames = crate::decoder::FrameDecoder::new().feed(sink.get_ref());
+        assert_eq!(payloads(&frames), [&b"one"[..], b"\x00two"]);
+    }
+}
@@ -0,0 +1,306 @@
+// the whole logical state of a ring as a portable byte blob: stored bytes in order,
+// configuration and counters. for test fixtures, moving state between versions of a
+// program, or a blob pulled out with a debugger and attached to a bug report:
+//
+//     let blob = ring.snapshot();
+//     ...
+//     let ring = match RingBuffer::restore(&blob) {
+//         RestoreResult::Ok(ring) => ring,
+//         RestoreResult::Err(e) => panic!("{e}"),
+//     };
+//
+// layout, all little endian: magic "RBSN", version u16, size u32, max_flush_size u32,
+// max level u8, flags u8 (volatile, frozen, sequence numbers on), next sequence number
+// u16, dropped messages and bytes u32 each, then the counters as u64 (high water,
+// overflows, crc errors, flushed frames, flushed bytes, and since version 2 pushed
+// bytes, popped bytes, logged frames, overwritten frames), the stored length u32 and
+// the stored bytes from the tail on, and a crc8 over all of it. the clock, sinks and storage
+// placement aren't part of it, the restored ring lives on the heap. `restore` still reads
+// version 1 blobs, their missing counters start at zero
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use crate::frame::{crc8, Dropped};
+use crate::level::Level;
+use crate::stats::Stats;
+use crate::RingBuffer;
+
+const MAGIC: &[u8; 4] = b"RBSN";
+const VERSION: u16 = 2;
+// largest ring `restore` allocates for a blob
+const MAX_SIZE: usize = 16 * 1024 * 1024;
+
+const VOLATILE: u8 = 1 << 0;
+const FROZEN: u8 = 1 << 1;
+const SEQUENCED: u8 = 1 << 2;
+
+// only ever returned, never stored, the size difference doesn't matter
+#[allow(clippy::large_enum_variant)]
+pub enum RestoreResult {
+    Ok(RingBuffer),
+    Err(String),
+}
+
+// takes fields off the front of a blob
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        if self.bytes.len() < len {
+            return Err("Snapshot truncated".to_string());
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, String> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+}
+
+impl RingBuffer {
+    pub fn snapshot(&self) -> Vec<u8> {
+        let mut blob = Vec::with_capacity(self.len() + 80);
+        blob.extend_from_slice(MAGIC);
+        blob.extend_from_slice(&VERSION.to_le_bytes());
+        blob.extend_from_slice(&(self.size as u32).to_le_bytes());
+        blob.extend_from_slice(&(self.max_flush_size as u32).to_le_bytes());
+        blob.push(self.max_level as u8);
+        let mut flags = 0;
+        if self.volatile {
+            flags |= VOLATILE;
+        }
+        if self.frozen {
+            flags |= FROZEN;
+        }
+        if self.next_seq.is_some() {
+            flags |= SEQUENCED;
+        }
+        blob.push(flags);
+        blob.extend_from_slice(&self.next_seq.unwrap_or(0).to_le_bytes());
+        blob.extend_from_slice(&self.dropped.messages.to_le_bytes());
+        blob.extend_from_slice(&self.dropped.bytes.to_le_bytes());
+        for counter in [
+            self.counters.high_water as u64,
+            self.counters.overflows,
+            self.counters.crc_errors,
+            self.counters.flushed_frames,
+            self.counters.flushed_bytes,
+            self.counters.pushed_bytes,
+            self.counters.popped_bytes,
+            self.counters.logged_frames,
+            self.counters.overwritten_frames,
+        ] {
+            blob.extend_from_slice(&counter.to_le_bytes());
+        }
+        blob.extend_from_slice(&(self.len() as u32).to_le_bytes());
+        blob.extend((0..self.len()).map(|i| self.stored_byte(i)));
+        blob.push(crc8(&blob));
+        blob
+    }
+
+    // blobs of rings over 16 MiB are turned away, see `restore_with_max_size`
+    pub fn restore(blob: &[u8]) -> RestoreResult {
+        Self::restore_with_max_size(blob, MAX_SIZE)
+    }
+
+    // the ring is only allocated once the blob checked out, and only up to `max_size`
+    // bytes whatever size it claims
+    pub fn restore_with_max_size(blob: &[u8], max_size: usize) -> RestoreResult {
+        match Self::read_snapshot(blob, max_size) {
+            Ok(ring) => RestoreResult::Ok(ring),
+            Err(e) => RestoreResult::Err(e),
+        }
+    }
+
+    fn read_snapshot(blob: &[u8], max_size: usize) -> Result<RingBuffer, String> {
+        let Some((&crc, body)) = blob.split_last() else {
+            return Err("Snapshot truncated".to_string());
+        };
+        let mut reader = Reader { bytes: body };
+        if reader.take(4)? != MAGIC {
+            return Err("Not a ring buffer snapshot".to_string());
+        }
+        if crc != crc8(body) {
+            return Err("Snapshot crc mismatch".to_string());
+        }
+        // version 1 only lacks the counters added in 2
+        let version = reader.u16()?;
+        if version != 1 && version != VERSION {
+            return Err("Unsupported snapshot version".to_string());
+        }
+
+        let size = reader.u32()? as usize;
+        if size == 0 {
+            return Err("Snapshot of a zero size ring".to_string());
+        }
+        if size > max_size {
+            return Err("Snapshot ring larger than the max size".to_string());
+        }
+        let max_flush_size = reader.u32()? as usize;
+        let max_level = Level::from_u8(reader.u8()?).ok_or("Invalid log level".to_string())?;
+        let flags = reader.u8()?;
+        let next_seq = reader.u16()?;
+        let dropped = Dropped {
+            messages: reader.u32()?,
+            bytes: reader.u32()?,
+        };
+        let mut counters = Stats {
+            high_water: reader.u64()? as usize,
+            overflows: reader.u64()?,
+            crc_errors: reader.u64()?,
+            flushed_frames: reader.u64()?,
+            flushed_bytes: reader.u64()?,
+            ..Stats::default()
+        };
+        if version >= 2 {
+            counters.pushed_bytes = reader.u64()?;
+            counters.popped_bytes = reader.u64()?;
+            counters.logged_frames = reader.u64()?;
+            counters.overwritten_frames = reader.u64()?;
+        }
+
+        let len = reader.u32()? as usize;
+        if len >= size {
+            return Err("Snapshot contents larger than the ring".to_string());
+        }
+        let contents = reader.take(len)?;
+        if !reader.bytes.is_empty() {
+            return Err("Trailing bytes after snapshot".to_string());
+        }
+
+        let mut ring = RingBuffer::new(size);
+        ring.max_flush_size = max_flush_size;
+        ring.max_level = max_level;
+        ring.volatile = flags & VOLATILE != 0;
+        ring.frozen = flags & FROZEN != 0;
+        ring.next_seq = (flags & SEQUENCED != 0).then_some(next_seq);
+        ring.dropped = dropped;
+        // putting the contents back isn't traffic
+        ring.push_slice(contents);
+        ring.counters = Stats {
+            high_water: ring.counters.high_water,
+            ..counters
+        };
+        Ok(ring)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::{Frame, FrameResult};
+
+    fn restore(blob: &[u8]) -> RingBuffer {
+        match RingBuffer::restore(blob) {
+            RestoreResult::Ok(ring) => ring,
+            RestoreResult::Err(e) => panic!("{e}"),
+        }
+    }
+
+    #[test]
+    fn version_1_blobs_still_restore() {
+        let mut ring = RingBuffer::new(64);
+        let _ = ring.log(Frame::new(b"kept"));
+        let mut blob = ring.snapshot();
+        // version 1 ends the counters after flushed bytes
+        blob.drain(66..98);
+        blob[4..6].copy_from_slice(&1u16.to_le_bytes());
+        blob.pop();
+        blob.push(crc8(&blob));
+
+        let mut restored = restore(&blob);
+        assert_eq!(restored.stats().logged_frames, 0);
+        match restored.flush_frame() {
+            FrameResult::Ok(frame) => assert_eq!(frame.payload, b"kept"),
+            FrameResult::Err(e) => panic!("{e}"),
+        }
+    }
+
+    #[test]
+    fn unknown_versions_are_turned_away() {
+        let mut blob = RingBuffer::new(64).snapshot();
+        blob[4..6].copy_from_slice(&(VERSION + 1).to_le_bytes());
+        blob.pop();
+        blob.push(crc8(&blob));
+        assert!(matches!(
+            RingBuffer::restore(&blob),
+            RestoreResult::Err(e) if e == "Unsupported snapshot version"
+        ));
+    }
+
+    // rewrite the claimed ring size and fix up the crc
+    fn with_size(mut blob: Vec<u8>, size: u32) -> Vec<u8> {
+        blob[6..10].copy_from_slice(&size.to_le_bytes());
+        blob.pop();
+        blob.push(crc8(&blob));
+        blob
+    }
+
+    fn error(result: RestoreResult) -> String {
+        match result {
+            RestoreResult::Ok(_) => panic!("restored"),
+            RestoreResult::Err(e) => e,
+        }
+    }
+
+    #[test]
+    fn a_huge_claimed_size_isnt_allocated() {
+        let blob = with_size(RingBuffer::new(64).snapshot(), u32::MAX);
+        assert_eq!(
+            error(RingBuffer::restore(&blob)),
+            "Snapshot ring larger than the max size"
+        );
+    }
+
+    #[test]
+    fn the_max_size_can_be_raised_or_lowered() {
+        let mut ring = RingBuffer::new(64);
+        let _ = ring.log(Frame::new(b"kept"));
+        let blob = ring.snapshot();
+        assert!(matches!(
+            RingBuffer::restore_with_max_size(&blob, 32),
+            RestoreResult::Err(_)
+        ));
+        assert!(matches!(
+            RingBuffer::restore_with_max_size(&blob, 64),
+            RestoreResult::Ok(_)
+        ));
+    }
+
+    #[test]
+    fn contents_are_checked_against_the_blob_before_allocating() {
+        let mut ring = RingBuffer::new(64);
+        let _ = ring.log(Frame::new(b"kept"));
+        let mut blob = ring.snapshot();
+        // stored length claims more than the blob carries
+        let len_at = blob.len() - 1 - ring.len() - 4;
+        blob[len_at..len_at + 4].copy_from_slice(&40u32.to_le_bytes());
+        blob.pop();
+        blob.push(crc8(&blob));
+        assert_eq!(error(RingBuffer::restore(&blob)), "Snapshot truncated");
+
+        // and more than the claimed ring holds
+        let blob = with_size(ring.snapshot(), 4);
+        assert_eq!(
+            error(RingBuffer::restore(&blob)),
+            "Snapshot contents larger than the ring"
+        );
+    }
+}
@@ -0,0 +1,257 @@
+// overflow to disk instead of dropping: once a frame doesn't fit, it and everything
+// logged after it go to a temporary file. as consumers take frames out, the spilled ones
+// move back into the ring oldest first, so the flush stream stays in log order and a
+// burst costs latency instead of frames:
+//
+//     ring.enable_spill()?;
+//     // a burst bigger than the ring
+//     for _ in 0..10_000 { ring.log_message_with_crc(b"..."); }
+//     ring.flush_all_to(&mut sink);   // keeps refilling from the file until both are empty
+//
+// new frames keep going to the file for as long as anything is waiting there. frames
+// too big for the ring itself are still dropped, and so is a frame the file can't take
+// (the dropped marker says so as usual). the file is removed when the ring goes away
+
+use std::collections::VecDeque;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::RingBuffer;
+
+static NEXT_FILE: AtomicUsize = AtomicUsize::new(0);
+
+pub(crate) struct Spill {
+    file: File,
+    path: PathBuf,
+    // encoded length of every waiting frame, terminator included, oldest first
+    lens: VecDeque<usize>,
+    read_pos: u64,
+}
+
+impl Spill {
+    fn create(dir: &Path) -> io::Result<Self> {
+        let name = format!(
+            "ringbuffer-spill-{}-{}",
+            std::process::id(),
+            NEXT_FILE.fetch_add(1, Ordering::Relaxed)
+        );
+        let path = dir.join(name);
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+        Ok(Spill {
+            file,
+            path,
+            lens: VecDeque::new(),
+            read_pos: 0,
+        })
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.lens.is_empty()
+    }
+
+    // append encoded frames, all or nothing. empty ones are left out
+    pub(crate) fn write(&mut self, frames: &[&[u8]]) -> io::Result<()> {
+        let end = self.file.seek(SeekFrom::End(0))?;
+        let frames: Vec<&[u8]> = frames.iter().copied().filter(|f| !f.is_empty()).collect();
+        if let Err(e) = self.file.write_all(&frames.concat()) {
+            self.file.set_len(end)?;
+            return Err(e);
+        }
+        self.lens.extend(frames.iter().map(|frame| frame.len()));
+        Ok(())
+    }
+
+    // the oldest frame if it's no longer than `free` bytes
+    fn read(&mut self, free: usize) -> io::Result<Option<Vec<u8>>> {
+        let Some(&len) = self.lens.front().filter(|&&len| len <= free) else {
+            return Ok(None);
+        };
+        let mut bytes = vec![0; len];
+        self.file.seek(SeekFrom::Start(self.read_pos))?;
+        self.file.read_exact(&mut bytes)?;
+        self.lens.pop_front();
+        self.read_pos += len as u64;
+        if self.lens.is_empty() {
+            self.clear()?;
+        }
+        Ok(Some(bytes))
+    }
+
+    // forget everything waiting, the file starts over
+    fn clear(&mut self) -> io::Result<()> {
+        self.lens.clear();
+        self.read_pos = 0;
+        self.file.set_len(0)
+    }
+}
+
+impl Drop for Spill {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+impl RingBuffer {
+    // spill overflowing frames to a file in the system temp directory
+    pub fn enable_spill(&mut self) -> io::Result<()> {
+        self.enable_spill_in(std::env::temp_dir())
+    }
+
+    // same as enable_spill with the file in `dir`. frames waiting in an earlier spill
+    // file are lost
+    pub fn enable_spill_in(&mut self, dir: impl AsRef<Path>) -> io::Result<()> {
+        self.spill = Some(Spill::create(dir.as_ref())?);
+        Ok(())
+    }
+
+    // back to dropping on overflow, frames still waiting in the file are lost
+    pub fn disable_spill(&mut self) {
+        self.spill = None;
+    }
+
+    // frames waiting in the spill file
+    pub fn spilled(&self) -> usize {
+        self.spill.as_ref().map_or(0, |spill| spill.lens.len())
+    }
+
+    // move spilled frames back in for as long as they fit. a file that can't be read
+    // back any more is given up on, its frames count as dropped
+    pub(crate) fn refill(&mut self) {
+        let Some(mut spill) = self.spill.take() else {
+            return;
+        };
+        loop {
+            match spill.read(self.free()) {
+                Ok(Some(bytes)) => {
+                    self.push_slice(&bytes);
+                }
+                Ok(None) => break,
+                Err(_) => {
+                    let lost = spill.lens.len() as u32;
+                    let bytes = spill.lens.iter().sum::<usize>() as u32;
+                    self.dropped.messages = self.dropped.messages.saturating_add(lost);
+                    self.dropped.bytes = self.dropped.bytes.saturating_add(bytes);
+                    let _ = spill.clear();
+                    break;
+                }
+            }
+        }
+        self.spill = Some(spill);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::{Frame, FrameResult};
+    use crate::sink::{FlushToResult, MemorySink};
+    use crate::PushResult;
+
+    // a directory of its own, to see the spill file come and go
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!(
+                "ringbuffer-spill-test-{}-{}",
+                std::process::id(),
+                name
+            ));
+            let _ = fs::remove_dir_all(&path);
+            fs::create_dir(&path).unwrap();
+            TempDir(path)
+        }
+
+        fn files(&self) -> usize {
+            fs::read_dir(&self.0).unwrap().count()
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn payloads(frames: &[Frame]) -> Vec<Vec<u8>> {
+        frames.iter().map(|frame| frame.payload.clone()).collect()
+    }
+
+    #[test]
+    fn a_burst_bigger_than_the_ring_comes_out_whole_and_in_order() {
+        let dir = TempDir::new("burst");
+        let mut ring = RingBuffer::new(64);
+        ring.enable_spill_in(&dir.0).unwrap();
+        let burst: Vec<Vec<u8>> = (0..50)
+            .map(|i| format!("burst {}", i).into_bytes())
+            .collect();
+        for payload in &burst {
+            assert!(matches!(ring.log(Frame::new(payload)), PushResult::Ok));
+        }
+        assert!(ring.spilled() > 40);
+
+        let mut sink = MemorySink::new();
+        assert!(
+            matches!(ring.flush_all_to(&mut sink), FlushToResult::Ok(stats) if stats.frames == 50)
+        );
+        assert_eq!(payloads(sink.frames()), burst);
+        assert_eq!(ring.spilled(), 0);
+        assert!(ring.is_empty());
+    }
+
+    #[test]
+    fn new_frames_queue_up_behind_spilled_ones() {
+        let dir = TempDir::new("queue");
+        let mut ring = RingBuffer::new(32);
+        ring.enable_spill_in(&dir.0).unwrap();
+        for payload in [b"one", b"two", b"six", b"ten"] {
+            let _ = ring.log(Frame::new(payload));
+        }
+        let FrameResult::Ok(first) = ring.flush_frame() else {
+            panic!("no frame");
+        };
+        assert_eq!(first.payload, b"one");
+        // there's room now, but older frames are still waiting in the file
+        let _ = ring.log(Frame::new(b"new"));
+        let mut sink = MemorySink::new();
+        let _ = ring.flush_all_to(&mut sink);
+        assert_eq!(
+            payloads(sink.frames()),
+            [&b"two"[..], b"six", b"ten", b"new"]
+        );
+    }
+
+    #[test]
+    fn the_file_goes_away_with_the_spill() {
+        let dir = TempDir::new("file");
+        let mut ring = RingBuffer::new(32);
+        ring.enable_spill_in(&dir.0).unwrap();
+        assert_eq!(dir.files(), 1);
+        // too big for the ring, dropped even with a file to spill into
+        assert!(matches!(ring.log(Frame::new(&[1; 40])), PushResult::Err(_)));
+        assert_eq!(ring.spilled(), 0);
+
+        for payload in [b"one", b"two", b"six", b"ten"] {
+            let _ = ring.log(Frame::new(payload));
+        }
+        let spilled = ring.spilled();
+        assert!(spilled > 0);
+        ring.disable_spill();
+        assert_eq!(dir.files(), 0);
+        let mut sink = MemorySink::new();
+        let _ = ring.flush_all_to(&mut sink);
+        // behind the marker for the frame that was too big
+        let kept = payloads(&sink.frames()[1..]);
+        assert_eq!(kept, [&b"one"[..], b"two", b"six", b"ten"][..4 - spilled]);
+
+        ring.enable_spill_in(&dir.0).unwrap();
+        drop(ring);
+        assert_eq!(dir.files(), 0);
+    }
+}
@@ -0,0 +1,165 @@
+// live logs for the browser over server-sent events. the sink fans flushed frames out to
+// every connected client as json events, the axum route does the http side:
+//
+//     let mut sse = SseSink::new();
+//     let app = Router::new().route("/logs", sse.route());
+//     tokio::spawn(axum::serve(listener, app).into_future());
+//     ...
+//     ring.flush_to(&mut sse);
+//
+// and on the page:
+//
+//     new EventSource("/logs").addEventListener("frame", (e) => {
+//         const frame = JSON.parse(e.data);
+//     });
+//
+// the event id is the frame's sequence number when it has one. a client that falls more
+// than `capacity` frames behind gets a `lagged` event with the number of frames it
+// missed. like the websocket sink this is a live tail, with nobody connected frames are
+// dropped
+
+use std::convert::Infallible;
+
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::routing::{get, MethodRouter};
+use futures_util::stream::{self, Stream};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::frame::Frame;
+use crate::json;
+use crate::sink::{FlushSink, SinkResult};
+
+#[derive(Clone)]
+pub struct SseSink {
+    frames: broadcast::Sender<Frame>,
+}
+
+impl Default for SseSink {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SseSink {
+    pub fn new() -> Self {
+        Self::with_capacity(256)
+    }
+
+    // frames kept per client before it counts as lagging
+    pub fn with_capacity(capacity: usize) -> Self {
+        let (frames, _) = broadcast::channel(capacity.max(1));
+        SseSink { frames }
+    }
+
+    pub fn clients(&self) -> usize {
+        self.frames.receiver_count()
+    }
+
+    // a GET route streaming to one more client on every request
+    pub fn route(&self) -> MethodRouter {
+        let sse = self.clone();
+        get(move || {
+            let sse = sse.clone();
+            async move { sse.stream() }
+        })
+    }
+
+    // the response for a single client, for handlers that do their own routing or auth
+    pub fn stream(&self) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+        let events = stream::unfold(self.frames.subscribe(), |mut frames| async move {
+            let event = match frames.recv().await {
+                Ok(frame) => {
+                    let event = Event::default()
+                        .event("frame")
+                        .data(json::frame_to_json(&frame));
+                    match frame.seq {
+                        Some(seq) => event.id(seq.to_string()),
+                        None => event,
+                    }
+                }
+                Err(RecvError::Lagged(missed)) => {
+                    Event::default().event("lagged").data(missed.to_string())
+                }
+                Err(RecvError::Closed) => return None,
+            };
+            Some((Ok(event), frames))
+        });
+        Sse::new(events).keep_alive(KeepAlive::default())
+    }
+}
+
+impl FlushSink for SseSink {
+    fn write_frame(&mut self, frame: &Frame) -> SinkResult {
+        // only fails when nobody is listening
+        let _ = self.frames.send(frame.clone());
+        SinkResult::Ok
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::response::IntoResponse;
+    use futures_util::StreamExt;
+
+    use super::*;
+
+    // the next `count` events of the response as the browser sees them
+    fn events(sse: &SseSink, send: impl FnOnce(), count: usize) -> String {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let mut body = sse.stream().into_response().into_body().into_data_stream();
+            send();
+            let mut text = String::new();
+            for _ in 0..count {
+                let chunk = body.next().await.unwrap().unwrap();
+                text.push_str(core::str::from_utf8(&chunk).unwrap());
+            }
+            text
+        })
+    }
+
+    #[test]
+    fn frames_go_out_as_json_events_with_their_sequence_number() {
+        let mut sse = SseSink::new();
+        let frame = Frame::new(b"hi").with_seq(7);
+        let mut sink = sse.clone();
+        let text = events(
+            &sse,
+            || assert!(matches!(sink.write_frame(&frame), SinkResult::Ok)),
+            1,
+        );
+        assert_eq!(
+            text,
+            format!(
+                "event: frame\ndata: {}\nid: 7\n\n",
+                json::frame_to_json(&frame)
+            )
+        );
+        assert_eq!(sse.clients(), 0);
+        // nobody listening
+        assert!(matches!(sse.write_frame(&frame), SinkResult::Ok));
+    }
+
+    #[test]
+    fn a_lagging_client_hears_how_many_frames_it_missed() {
+        let sse = SseSink::with_capacity(1);
+        let mut sink = sse.clone();
+        let text = events(
+            &sse,
+            || {
+                for payload in [b"a", b"b", b"c"] {
+                    let _ = sink.write_frame(&Frame::new(payload));
+                }
+            },
+            2,
+        );
+        let c = json::frame_to_json(&Frame::new(b"c"));
+        assert_eq!(
+            text,
+            format!("event: lagged\ndata: 2\n\nevent: frame\ndata: {c}\n\n")
+        );
+    }
+}
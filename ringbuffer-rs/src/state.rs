@@ -0,0 +1,217 @@
+// serde support for the ring itself, so an application can keep it inside its own
+// state or config files in whatever format it already uses:
+//
+//     #[derive(Serialize, Deserialize)]
+//     struct AppState {
+//         settings: Settings,
+//         log: RingBuffer,
+//     }
+//
+// covers the same ground as `snapshot`: configuration, counters and the stored bytes
+// from the tail on (still escaped, a half written frame included). the clock, sinks and
+// storage placement stay out, a deserialized ring lives on the heap
+
+use alloc::format;
+use alloc::vec::Vec;
+
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::frame::Dropped;
+use crate::level::Level;
+use crate::stats::Stats;
+use crate::RingBuffer;
+
+#[derive(Serialize, Deserialize)]
+struct State {
+    size: usize,
+    max_flush_size: usize,
+    max_level: u8,
+    volatile: bool,
+    frozen: bool,
+    next_seq: Option<u16>,
+    dropped_messages: u32,
+    dropped_bytes: u32,
+    high_water: usize,
+    overflows: u64,
+    crc_errors: u64,
+    flushed_frames: u64,
+    flushed_bytes: u64,
+    // missing in state saved before these were counted
+    #[serde(default)]
+    pushed_bytes: u64,
+    #[serde(default)]
+    popped_bytes: u64,
+    #[serde(default)]
+    logged_frames: u64,
+    #[serde(default)]
+    overwritten_frames: u64,
+    #[serde(default)]
+    window_stalls: u64,
+    contents: Vec<u8>,
+}
+
+impl Serialize for RingBuffer {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        State {
+            size: self.size,
+            max_flush_size: self.max_flush_size,
+            max_level: self.max_level as u8,
+            volatile: self.volatile,
+            frozen: self.frozen,
+            next_seq: self.next_seq,
+            dropped_messages: self.dropped.messages,
+            dropped_bytes: self.dropped.bytes,
+            high_water: self.counters.high_water,
+            overflows: self.counters.overflows,
+            crc_errors: self.counters.crc_errors,
+            flushed_frames: self.counters.flushed_frames,
+            flushed_bytes: self.counters.flushed_bytes,
+            pushed_bytes: self.counters.pushed_bytes,
+            popped_bytes: self.counters.popped_bytes,
+            logged_frames: self.counters.logged_frames,
+            overwritten_frames: self.counters.overwritten_frames,
+            window_stalls: self.counters.window_stalls,
+            contents: (0..self.len()).map(|i| self.stored_byte(i)).collect(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for RingBuffer {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let state = State::deserialize(deserializer)?;
+        if state.contents.len() >= state.size {
+            return Err(D::Error::custom(format!(
+                "{} stored bytes don't fit a ring of {}",
+                state.contents.len(),
+                state.size
+            )));
+        }
+        let max_level = Level::from_u8(state.max_level)
+            .ok_or_else(|| D::Error::custom(format!("Invalid log level {}", state.max_level)))?;
+
+        let mut ring = RingBuffer::new(state.size);
+        ring.max_flush_size = state.max_flush_size;
+        ring.max_level = max_level;
+        ring.volatile = state.volatile;
+        ring.frozen = state.frozen;
+        ring.next_seq = state.next_seq;
+        ring.dropped = Dropped {
+            messages: state.dropped_messages,
+            bytes: state.dropped_bytes,
+        };
+        ring.push_slice(&state.contents);
+        ring.counters = Stats {
+            pushed_bytes: state.pushed_bytes,
+            popped_bytes: state.popped_bytes,
+            logged_frames: state.logged_frames,
+            overflows: state.overflows,
+            overwritten_frames: state.overwritten_frames,
+            window_stalls: state.window_stalls,
+            crc_errors: state.crc_errors,
+            flushed_frames: state.flushed_frames,
+            flushed_bytes: state.flushed_bytes,
+            high_water: state.high_water.max(state.contents.len()),
+            // the latency histogram isn't kept
+            ..Stats::default()
+        };
+        Ok(ring)
+    }
+}
+
+// through cbor, which keeps the field names so older state can be checked too
+#[cfg(all(test, feature = "cbor"))]
+mod tests {
+    use ciborium::Value;
+
+    use super::*;
+    use crate::frame::{Frame, FrameResult};
+
+    fn fields(ring: &RingBuffer) -> Vec<(Value, Value)> {
+        match Value::serialized(ring).unwrap() {
+            Value::Map(fields) => fields,
+            _ => panic!("not a map"),
+        }
+    }
+
+    fn set(fields: &mut [(Value, Value)], name: &str, value: Value) {
+        let field = fields
+            .iter_mut()
+            .find(|(key, _)| key.as_text() == Some(name))
+            .unwrap();
+        field.1 = value;
+    }
+
+    #[test]
+    fn a_ring_comes_back_with_its_settings_counters_and_contents() {
+        let mut ring = RingBuffer::new(32);
+        ring.set_max_flush_size(100);
+        ring.set_max_level(Level::Warn);
+        // wrapped, with half a frame at the end
+        ring.push_slice(&[0; 20]);
+        ring.pop_slice(&mut [0; 20]);
+        let _ = ring.log(Frame::new(b"kept"));
+        ring.push_slice(&Frame::new(b"half").encode()[..3]);
+
+        let mut bytes = Vec::new();
+        ciborium::into_writer(&ring, &mut bytes).unwrap();
+        let mut restored: RingBuffer = ciborium::from_reader(&bytes[..]).unwrap();
+        assert_eq!(restored.len(), ring.len());
+        assert_eq!(restored.stats(), ring.stats());
+        assert_eq!(restored.max_flush_size, 100);
+        assert_eq!(restored.max_level, Level::Warn);
+        let FrameResult::Ok(frame) = restored.flush_frame() else {
+            panic!("no frame");
+        };
+        assert_eq!(frame.payload, b"kept");
+        assert_eq!(restored.len(), 3);
+    }
+
+    #[test]
+    fn state_from_before_the_newer_counters_still_reads() {
+        let mut ring = RingBuffer::new(32);
+        let _ = ring.log(Frame::new(b"old"));
+        let newer = [
+            "pushed_bytes",
+            "popped_bytes",
+            "logged_frames",
+            "overwritten_frames",
+            "window_stalls",
+        ];
+        let fields = fields(&ring)
+            .into_iter()
+            .filter(|(key, _)| !newer.contains(&key.as_text().unwrap()))
+            .collect();
+        let restored: RingBuffer = Value::Map(fields).deserialized().unwrap();
+        assert_eq!(restored.stats().logged_frames, 0);
+        assert_eq!(restored.stats().flushed_frames, 0);
+        assert_eq!(restored.len(), ring.len());
+    }
+
+    #[test]
+    fn inconsistent_state_is_turned_away() {
+        let ring = RingBuffer::new(8);
+        let mut too_much = fields(&ring);
+        set(
+            &mut too_much,
+            "contents",
+            Value::Array(vec![Value::Integer(1.into()); 8]),
+        );
+        let error = Value::Map(too_much)
+            .deserialized::<RingBuffer>()
+            .err()
+            .unwrap();
+        assert!(error
+            .to_string()
+            .contains("8 stored bytes don't fit a ring of 8"));
+
+        let mut bad_level = fields(&ring);
+        set(&mut bad_level, "max_level", Value::Integer(200.into()));
+        let error = Value::Map(bad_level)
+            .deserialized::<RingBuffer>()
+            .err()
+            .unwrap();
+        assert!(error.to_string().contains("Invalid log level 200"));
+    }
+}
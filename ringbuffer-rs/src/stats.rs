@@ -0,0 +1,196 @@
+// running counters about buffer health, for sizing a buffer and for the metrics
+// exporters. `RingBuffer::stats` hands out a copy
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Stats {
+    // bytes that went into / came out of storage, however they got there
+    pub pushed_bytes: u64,
+    pub popped_bytes: u64,
+    // frames stored by log_frame
+    pub logged_frames: u64,
+    // frames rejected by log_frame because they didn't fit
+    pub overflows: u64,
+    // stored frames thrown out to make room for newer ones
+    pub overwritten_frames: u64,
+    // frames that failed to decode on their way out
+    pub crc_errors: u64,
+    // frames taken out intact, and their encoded bytes including terminators
+    pub flushed_frames: u64,
+    pub flushed_bytes: u64,
+    // flushes held back by a full ack window with frames still waiting
+    pub window_stalls: u64,
+    // most bytes ever stored at once
+    pub high_water: usize,
+    // clock ticks between logging and flushing, for frames that carry a timestamp and
+    // leave a ring with a clock
+    pub flush_latency: LatencyHistogram,
+}
+
+const BUCKETS: usize = 16;
+
+// counts per power of two: bucket 0 holds an age of 0, bucket i ages from 2^(i-1) up to
+// 2^i - 1, and the last one everything from 2^14 on
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LatencyHistogram {
+    buckets: [u64; BUCKETS],
+    sum: u64,
+    max: u64,
+}
+
+impl LatencyHistogram {
+    pub fn record(&mut self, age: u64) {
+        let bucket = (u64::BITS - age.leading_zeros()) as usize;
+        self.buckets[bucket.min(BUCKETS - 1)] += 1;
+        self.sum = self.sum.saturating_add(age);
+        self.max = self.max.max(age);
+    }
+
+    pub fn buckets(&self) -> &[u64; BUCKETS] {
+        &self.buckets
+    }
+
+    // the largest age bucket `index` holds, None for the open-ended last one
+    pub fn upper_bound(index: usize) -> Option<u64> {
+        (index < BUCKETS - 1).then(|| (1u64 << index) - 1)
+    }
+
+    pub fn count(&self) -> u64 {
+        self.buckets.iter().sum()
+    }
+
+    // all recorded ages added up
+    pub fn sum(&self) -> u64 {
+        self.sum
+    }
+
+    pub fn max(&self) -> u64 {
+        self.max
+    }
+
+    // an upper bound on the age `quantile` (0.0 to 1.0) of the frames stayed under, None
+    // before anything was recorded
+    pub fn quantile(&self, quantile: f64) -> Option<u64> {
+        let count = self.count();
+        if count == 0 {
+            return None;
+        }
+        let rank = ((quantile.clamp(0.0, 1.0) * count as f64) as u64).max(1);
+        let mut seen = 0;
+        for (index, &n) in self.buckets.iter().enumerate() {
+            seen += n;
+            if seen >= rank {
+                return Some(
+                    Self::upper_bound(index).map_or(self.max, |bound| bound.min(self.max)),
+                );
+            }
+        }
+        Some(self.max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::{Frame, FrameResult};
+    use crate::policy::DropPolicy;
+    use crate::{PushResult, RingBuffer};
+
+    #[test]
+    fn counters_follow_the_traffic() {
+        let mut ring = RingBuffer::new(32);
+        let len = Frame::new(b"frame").encode().len();
+        for _ in 0..3 {
+            let _ = ring.log(Frame::new(b"frame"));
+        }
+        let mut corrupt = Frame::new(b"bad").encode();
+        corrupt[1] ^= 0xff;
+        let full = ring.len();
+        let stats = ring.stats();
+        assert_eq!(stats.logged_frames, (full / len) as u64);
+        assert_eq!(stats.overflows, 3 - stats.logged_frames);
+        assert_eq!(stats.pushed_bytes, full as u64);
+
+        while let FrameResult::Ok(_) = ring.flush_frame() {}
+        ring.push_slice(&corrupt);
+        let _ = ring.flush_frame();
+        let stats = ring.stats();
+        assert_eq!(stats.flushed_frames, (full / len) as u64);
+        assert_eq!(stats.flushed_bytes, full as u64);
+        assert_eq!(stats.crc_errors, 1);
+        assert_eq!(stats.popped_bytes, (full + corrupt.len()) as u64);
+        assert_eq!(stats.high_water, full);
+    }
+
+    #[test]
+    fn overwritten_frames_are_counted() {
+        let mut ring = RingBuffer::new(32).with_drop_policy(DropPolicy::OverwriteOldest);
+        for _ in 0..10 {
+            assert!(matches!(ring.log(Frame::new(b"frame")), PushResult::Ok));
+        }
+        let stats = ring.stats();
+        assert_eq!(stats.logged_frames, 10);
+        assert!(stats.overwritten_frames > 0);
+        assert_eq!(stats.overflows, 0);
+    }
+
+    #[test]
+    fn a_reset_starts_over_from_what_is_stored() {
+        let mut ring = RingBuffer::new(64);
+        for _ in 0..3 {
+            let _ = ring.log(Frame::new(b"frame"));
+        }
+        let _ = ring.flush_frame();
+        ring.reset_stats();
+        assert_eq!(
+            ring.stats(),
+            Stats {
+                high_water: ring.len(),
+                ..Stats::default()
+            }
+        );
+    }
+
+    #[test]
+    fn ages_land_in_power_of_two_buckets() {
+        let mut histogram = LatencyHistogram::default();
+        for age in [0, 1, 2, 3, 4, 1000, u64::MAX] {
+            histogram.record(age);
+        }
+        let buckets = histogram.buckets();
+        assert_eq!(buckets[..4], [1, 1, 2, 1]);
+        assert_eq!(buckets[10], 1);
+        assert_eq!(buckets[BUCKETS - 1], 1);
+        assert_eq!(histogram.count(), 7);
+        assert_eq!(histogram.max(), u64::MAX);
+        assert_eq!(histogram.sum(), u64::MAX);
+
+        assert_eq!(LatencyHistogram::upper_bound(0), Some(0));
+        assert_eq!(LatencyHistogram::upper_bound(3), Some(7));
+        assert_eq!(LatencyHistogram::upper_bound(BUCKETS - 1), None);
+    }
+
+    #[test]
+    fn quantiles_are_bucket_bounds_capped_at_the_max() {
+        let mut histogram = LatencyHistogram::default();
+        assert_eq!(histogram.quantile(0.5), None);
+        for age in [1, 1, 1, 5, 9] {
+            histogram.record(age);
+        }
+        assert_eq!(histogram.quantile(0.0), Some(1));
+        assert_eq!(histogram.quantile(0.6), Some(1));
+        assert_eq!(histogram.quantile(0.8), Some(7));
+        assert_eq!(histogram.quantile(1.0), Some(9));
+    }
+
+    #[test]
+    fn frames_leaving_a_ring_with_a_clock_record_their_age() {
+        let mut ring = RingBuffer::new(64);
+        let _ = ring.log(Frame::new(b"untimed"));
+        let _ = ring.log(Frame::new(b"timed").with_timestamp(40));
+        ring.set_clock(|| 100);
+        while let FrameResult::Ok(_) = ring.flush_frame() {}
+        let latency = ring.stats().flush_latency;
+        assert_eq!(latency.count(), 1);
+        assert_eq!(latency.max(), 60);
+    }
+}
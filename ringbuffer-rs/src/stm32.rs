@@ -0,0 +1,222 @@
+// usart tx over a dma stream for stm32f4 parts, on top of the dma grant api. the serial
+// port needs dma requests enabled (`.dma(serial::config::DmaConfig::Tx)`), then:
+//
+//     let mut uart = unsafe { UartDma::new(dma1.6, tx) };
+//     ...
+//     ring.log_message_with_crc(b"...");
+//     uart.poll(&mut ring);
+//
+//     #[interrupt]
+//     fn DMA1_STREAM6() {
+//         uart.on_interrupt(&mut ring);
+//     }
+//
+// the half-transfer interrupt already gives the first half of a transfer back to the
+// producers, transfer-complete releases the rest and starts on whatever was logged
+// meanwhile. the feature is wired to the stm32f411, switch the hal's chip feature for
+// other parts
+
+use stm32f4xx_hal::dma::config::DmaConfig;
+use stm32f4xx_hal::dma::traits::{Channel, DMASet, PeriAddress, Stream, StreamISR};
+use stm32f4xx_hal::dma::{ChannelX, MemoryToPeripheral, Transfer};
+
+use crate::dma::DmaReadGrant;
+use crate::RingBuffer;
+
+type TxTransfer<S, const C: u8, P> = Transfer<S, C, P, MemoryToPeripheral, DmaReadGrant>;
+
+enum State<S, const C: u8, P>
+where
+    S: Stream,
+    ChannelX<C>: Channel,
+    P: PeriAddress<MemSize = u8> + DMASet<S, C, MemoryToPeripheral>,
+{
+    // nothing sent yet, the stream isn't configured
+    Idle(S, P),
+    Configured(TxTransfer<S, C, P>),
+}
+
+pub struct UartDma<S, const C: u8, P>
+where
+    S: Stream,
+    ChannelX<C>: Channel,
+    P: PeriAddress<MemSize = u8> + DMASet<S, C, MemoryToPeripheral>,
+{
+    state: Option<State<S, C, P>>,
+    progress: Progress,
+}
+
+// the transfer on the wire and how much of it went back to the ring
+#[derive(Default)]
+struct Progress {
+    // length of the transfer, 0 when the stream is idle
+    in_flight: usize,
+    // bytes of it already given back to the ring at half transfer
+    released: usize,
+}
+
+impl Progress {
+    fn start(&mut self, len: usize) {
+        self.in_flight = len;
+        self.released = 0;
+    }
+
+    fn half(&mut self, ring: &mut RingBuffer) {
+        let half = self.in_flight / 2;
+        if half > self.released {
+            ring.dma_read_done(half - self.released);
+            self.released = half;
+        }
+    }
+
+    fn complete(&mut self, ring: &mut RingBuffer) {
+        ring.dma_read_done(self.in_flight - self.released);
+        self.start(0);
+    }
+}
+
+impl<S, const C: u8, P> UartDma<S, C, P>
+where
+    S: Stream,
+    ChannelX<C>: Channel,
+    P: PeriAddress<MemSize = u8> + DMASet<S, C, MemoryToPeripheral>,
+{
+    // safety: every call must pass the same ring, the ring must outlive the adapter (or
+    // the last transfer) and it must only be drained through the adapter, the same rules
+    // as dma_read_grant
+    #[allow(clippy::missing_safety_doc)]
+    pub unsafe fn new(stream: S, tx: P) -> Self {
+        UartDma {
+            state: Some(State::Idle(stream, tx)),
+            progress: Progress::default(),
+        }
+    }
+
+    pub fn is_busy(&self) -> bool {
+        self.progress.in_flight > 0
+    }
+
+    // start a transfer if the stream is idle and a complete frame is waiting. returns
+    // whether one was started
+    pub fn poll(&mut self, ring: &mut RingBuffer) -> bool {
+        if self.is_busy() {
+            return false;
+        }
+        // safe under the rules `new` was called with
+        let grant = unsafe { ring.dma_read_grant() };
+        if grant.is_empty() {
+            return false;
+        }
+        let len = grant.len();
+        let transfer = match self.state.take() {
+            Some(State::Idle(stream, tx)) => {
+                let config = DmaConfig::default()
+                    .memory_increment(true)
+                    .half_transfer_interrupt(true)
+                    .transfer_complete_interrupt(true);
+                let mut transfer =
+                    Transfer::init_memory_to_peripheral(stream, tx, grant, None, config);
+                transfer.start(|_| {});
+                transfer
+            }
+            Some(State::Configured(mut transfer)) => {
+                // the previous grant is done with, the returned one is dropped
+                if transfer.next_transfer(grant).is_err() {
+                    self.state = Some(State::Configured(transfer));
+                    return false;
+                }
+                transfer
+            }
+            None => return false,
+        };
+        self.state = Some(State::Configured(transfer));
+        self.progress.start(len);
+        true
+    }
+
+    // the first half of the transfer is out, hand that space back to producers early
+    pub fn on_half_transfer(&mut self, ring: &mut RingBuffer) {
+        self.progress.half(ring);
+    }
+
+    // release what's left of the finished transfer and start the next one
+    pub fn on_transfer_complete(&mut self, ring: &mut RingBuffer) {
+        self.progress.complete(ring);
+        self.poll(ring);
+    }
+
+    // the dma stream interrupt handler: checks and clears the flags, then calls the
+    // handlers above
+    pub fn on_interrupt(&mut self, ring: &mut RingBuffer) {
+        let Some(State::Configured(transfer)) = &mut self.state else {
+            return;
+        };
+        let (half, complete) = (transfer.is_half_transfer(), transfer.is_transfer_complete());
+        transfer.clear_half_transfer();
+        transfer.clear_transfer_complete();
+        if half || complete {
+            self.on_half_transfer(ring);
+        }
+        if complete {
+            self.on_transfer_complete(ring);
+        }
+    }
+
+    // stop the stream and hand back the hardware. bytes of an interrupted transfer stay
+    // in the ring
+    pub fn release(mut self) -> (S, P) {
+        match self.state.take() {
+            Some(State::Idle(stream, tx)) => (stream, tx),
+            Some(State::Configured(transfer)) => {
+                let (stream, tx, _, _) = transfer.release();
+                (stream, tx)
+            }
+            None => unreachable!(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::Frame;
+
+    #[test]
+    fn half_transfer_gives_back_the_first_half_early() {
+        let mut ring = RingBuffer::new(64);
+        let _ = ring.log(Frame::new(b"on the wire"));
+        let len = unsafe { ring.dma_read_grant() }.len();
+        let mut progress = Progress::default();
+        progress.start(len);
+
+        progress.half(&mut ring);
+        assert_eq!(ring.len(), len - len / 2);
+        // the flag can show up again with transfer complete
+        progress.half(&mut ring);
+        assert_eq!(ring.len(), len - len / 2);
+
+        let _ = ring.log(Frame::new(b"next"));
+        progress.complete(&mut ring);
+        assert_eq!(ring.len(), Frame::new(b"next").encode().len());
+        assert_eq!(ring.stats().flushed_frames, 1);
+        assert_eq!(progress.in_flight, 0);
+    }
+
+    #[test]
+    fn complete_without_half_releases_it_all() {
+        let mut ring = RingBuffer::new(64);
+        let _ = ring.log(Frame::new(b"short"));
+        let mut progress = Progress::default();
+        progress.start(ring.len());
+        progress.complete(&mut ring);
+        assert!(ring.is_empty());
+
+        // an idle stream releases nothing
+        progress.complete(&mut ring);
+        progress.half(&mut ring);
+        assert_eq!(
+            ring.stats().popped_bytes,
+            Frame::new(b"short").encode().len() as u64
+        );
+    }
+}
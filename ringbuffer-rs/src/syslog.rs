@@ -0,0 +1,236 @@
+// rfc 5424 rendering of frames plus a sink that sends them to a syslog daemon, either the
+// local one on /dev/log or a remote collector over udp
+
+use std::io;
+use std::net::{ToSocketAddrs, UdpSocket};
+#[cfg(unix)]
+use std::os::unix::net::UnixDatagram;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::clock::SystemClock;
+use crate::frame::Frame;
+use crate::level::Level;
+use crate::sink::{FlushSink, SinkResult};
+use crate::udp;
+
+// private enterprise number reserved for documentation, used for our structured data id
+const SD_ID: &str = "ring@32473";
+
+pub struct SyslogFormatter {
+    facility: u8,
+    hostname: String,
+    app_name: String,
+    clock: Option<SystemClock>,
+}
+
+impl Default for SyslogFormatter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SyslogFormatter {
+    // facility user, no hostname or app name
+    pub fn new() -> Self {
+        SyslogFormatter {
+            facility: 1,
+            hostname: "-".to_string(),
+            app_name: "-".to_string(),
+            clock: None,
+        }
+    }
+
+    // 0..=23, e.g. 16 for local0
+    pub fn with_facility(mut self, facility: u8) -> Self {
+        self.facility = facility.min(23);
+        self
+    }
+
+    pub fn with_hostname(mut self, hostname: &str) -> Self {
+        self.hostname = header_field(hostname, 255);
+        self
+    }
+
+    pub fn with_app_name(mut self, app_name: &str) -> Self {
+        self.app_name = header_field(app_name, 48);
+        self
+    }
+
+    // how to turn frame timestamps into wall-clock time, without it frames go out
+    // with the nil timestamp
+    pub fn with_clock(mut self, clock: SystemClock) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+
+    pub fn format(&self, frame: &Frame) -> String {
+        let time = frame
+            .timestamp
+            .zip(self.clock.as_ref())
+            .and_then(|(ticks, clock)| clock.to_system_time(ticks));
+        let timestamp = time.map_or("-".to_string(), rfc3339);
+
+        let mut params = Vec::new();
+        if let Some(seq) = frame.seq {
+            params.push(format!("seq=\"{}\"", seq));
+        }
+        if let Some(tag) = frame.tag {
+            params.push(format!("tag=\"{}\"", tag));
+        }
+        if let Some(channel) = frame.channel {
+            params.push(format!("channel=\"{}\"", channel));
+        }
+        let data = if params.is_empty() {
+            "-".to_string()
+        } else {
+            format!("[{} {}]", SD_ID, params.join(" "))
+        };
+
+        let message = match frame.dropped {
+            Some(dropped) => dropped.to_string(),
+            None => String::from_utf8_lossy(&frame.payload).into_owned(),
+        };
+        format!(
+            "<{}>1 {} {} {} {} - {} {}",
+            self.facility as u32 * 8 + severity(frame.level) as u32,
+            timestamp,
+            self.hostname,
+            self.app_name,
+            std::process::id(),
+            data,
+            message
+        )
+    }
+}
+
+// syslog severity for a frame level, frames without one count as informational
+pub(crate) fn severity(level: Option<Level>) -> u8 {
+    match level {
+        Some(Level::Error) => 3,
+        Some(Level::Warn) => 4,
+        Some(Level::Info) | None => 6,
+        Some(Level::Debug) | Some(Level::Trace) => 7,
+    }
+}
+
+// header fields are printable ascii without spaces, nil when empty
+fn header_field(value: &str, max_len: usize) -> String {
+    let value: String = value
+        .chars()
+        .filter(|c| c.is_ascii_graphic())
+        .take(max_len)
+        .collect();
+    if value.is_empty() {
+        "-".to_string()
+    } else {
+        value
+    }
+}
+
+// utc, microsecond precision
+fn rfc3339(time: SystemTime) -> String {
+    let elapsed = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = elapsed.as_secs();
+    let (hour, minute, second) = ((secs / 3600) % 24, (secs / 60) % 60, secs % 60);
+
+    // days since the epoch to a civil date, from Howard Hinnant's date algorithms
+    let days = (secs / 86400) as i64 + 719_468;
+    let era = days / 146_097;
+    let doe = days - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:06}Z",
+        year,
+        month,
+        day,
+        hour,
+        minute,
+        second,
+        elapsed.subsec_micros()
+    )
+}
+
+enum Transport {
+    #[cfg(unix)]
+    Local(UnixDatagram),
+    Remote(UdpSocket),
+}
+
+pub struct SyslogSink {
+    transport: Transport,
+    formatter: SyslogFormatter,
+}
+
+impl SyslogSink {
+    // the local daemon's /dev/log socket
+    #[cfg(unix)]
+    pub fn local(formatter: SyslogFormatter) -> io::Result<Self> {
+        let socket = UnixDatagram::unbound()?;
+        socket.connect("/dev/log")?;
+        Ok(SyslogSink {
+            transport: Transport::Local(socket),
+            formatter,
+        })
+    }
+
+    // a remote collector over udp, usually port 514. ipv6 collectors work too, the local
+    // socket is bound in the collector's address family
+    pub fn remote(addr: impl ToSocketAddrs, formatter: SyslogFormatter) -> io::Result<Self> {
+        Ok(SyslogSink {
+            transport: Transport::Remote(udp::connect(addr)?),
+            formatter,
+        })
+    }
+}
+
+impl FlushSink for SyslogSink {
+    fn write_frame(&mut self, frame: &Frame) -> SinkResult {
+        let message = self.formatter.format(frame);
+        let result = match &self.transport {
+            #[cfg(unix)]
+            Transport::Local(socket) => socket.send(message.as_bytes()),
+            Transport::Remote(socket) => socket.send(message.as_bytes()),
+        };
+        match result {
+            Ok(_) => SinkResult::Ok,
+            Err(e) => SinkResult::Err(e.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn received_by(collector: UdpSocket) {
+        let addr = collector.local_addr().unwrap();
+        let mut sink = SyslogSink::remote(addr, SyslogFormatter::new()).unwrap();
+        assert!(matches!(
+            sink.write_frame(&Frame::new(b"over the wire")),
+            SinkResult::Ok
+        ));
+        let mut buf = [0u8; 512];
+        let len = collector.recv(&mut buf).unwrap();
+        let message = String::from_utf8_lossy(&buf[..len]);
+        assert!(message.ends_with("over the wire"), "{}", message);
+    }
+
+    #[test]
+    fn remote_reaches_an_ipv4_collector() {
+        received_by(UdpSocket::bind("127.0.0.1:0").unwrap());
+    }
+
+    #[test]
+    fn remote_reaches_an_ipv6_collector() {
+        // hosts without ipv6 have nothing to test
+        if let Ok(collector) = UdpSocket::bind("[::1]:0") {
+            received_by(collector);
+        }
+    }
+}
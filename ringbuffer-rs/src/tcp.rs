@@ -0,0 +1,352 @@
+// streams frames to a collector over tcp and reconnects on its own, with the ring as a
+// durable send queue.
+//
+// each frame goes out as [len u32][frame bytes], little endian. the collector answers
+// with the u16 sequence number of the newest frame it has, little endian, as often as
+// it likes (after every frame or every few). flush through the ring:
+//
+//     ring.enable_acks();
+//     let mut collector = TcpSink::new("collector.local:7000")?;
+//     ...
+//     ring.flush_to_tcp(&mut collector);
+//
+// acks go through the ring (see `reliable`), turn them on before logging so every frame
+// carries a number. flush_to_tcp turns them on if they aren't and hands the collector's
+// acks back to the ring. sent frames stay stored until they're acknowledged, and everything
+// unacknowledged goes out again after a reconnect. while the connection is down
+// nothing is sent and frames wait in the ring. `set_ack_window` caps what's in flight.
+// a collector that stops reading fails the write after the write timeout rather than
+// holding the flush up
+
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+use crate::frame::Frame;
+use crate::sink::{FlushSink, FlushToResult, SinkResult};
+use crate::RingBuffer;
+
+pub struct TcpSink {
+    addrs: Vec<SocketAddr>,
+    stream: Option<TcpStream>,
+    connect_timeout: Duration,
+    write_timeout: Duration,
+    reconnect_interval: Duration,
+    last_attempt: Option<Instant>,
+    // newest ack not handed to the ring yet
+    ack: Option<u16>,
+    ack_buf: Vec<u8>,
+    // connected since the ring last heard about it, what it sent before needs resending
+    reconnected: bool,
+}
+
+impl TcpSink {
+    // doesn't connect yet, that happens on the first flush
+    pub fn new(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        Ok(TcpSink {
+            addrs: addr.to_socket_addrs()?.collect(),
+            stream: None,
+            connect_timeout: Duration::from_secs(1),
+            write_timeout: Duration::from_secs(1),
+            reconnect_interval: Duration::from_secs(1),
+            last_attempt: None,
+            ack: None,
+            ack_buf: Vec::new(),
+            reconnected: false,
+        })
+    }
+
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    // the most a write may block on a collector that doesn't read, the connection is
+    // dropped after that
+    pub fn with_write_timeout(mut self, timeout: Duration) -> Self {
+        self.write_timeout = timeout;
+        self
+    }
+
+    // minimum time between two connection attempts
+    pub fn with_reconnect_interval(mut self, interval: Duration) -> Self {
+        self.reconnect_interval = interval;
+        self
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.stream.is_some()
+    }
+
+    fn connect(&mut self) -> io::Result<()> {
+        if self.stream.is_some() {
+            return Ok(());
+        }
+        if let Some(last) = self.last_attempt {
+            if last.elapsed() < self.reconnect_interval {
+                return Err(io::ErrorKind::NotConnected.into());
+            }
+        }
+        self.last_attempt = Some(Instant::now());
+
+        let mut last_err = io::Error::new(io::ErrorKind::InvalidInput, "no address");
+        for addr in &self.addrs {
+            match TcpStream::connect_timeout(addr, self.connect_timeout) {
+                Ok(stream) => {
+                    stream.set_nodelay(true)?;
+                    stream.set_write_timeout(Some(self.write_timeout))?;
+                    self.stream = Some(stream);
+                    self.ack_buf.clear();
+                    self.reconnected = true;
+                    return Ok(());
+                }
+                Err(e) => last_err = e,
+            }
+        }
+        Err(last_err)
+    }
+
+    // pick up whatever acks arrived without blocking. the ones read before the
+    // connection turned out dead still count
+    fn read_acks(&mut self) -> io::Result<()> {
+        let Some(stream) = &mut self.stream else {
+            return Ok(());
+        };
+        stream.set_nonblocking(true)?;
+        let mut buf = [0u8; 64];
+        let result = loop {
+            match stream.read(&mut buf) {
+                Ok(0) => break Err(io::ErrorKind::ConnectionAborted.into()),
+                Ok(n) => self.ack_buf.extend_from_slice(&buf[..n]),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break Ok(()),
+                Err(e) => break Err(e),
+            }
+        };
+
+        for ack in self.ack_buf.chunks_exact(2) {
+            self.ack = Some(u16::from_le_bytes(ack.try_into().unwrap()));
+        }
+        let leftover = self.ack_buf.len() % 2;
+        self.ack_buf.drain(..self.ack_buf.len() - leftover);
+
+        let result = result.and_then(|()| stream.set_nonblocking(false));
+        if result.is_err() {
+            self.stream = None;
+        }
+        result
+    }
+}
+
+fn send(stream: &mut TcpStream, bytes: &[u8]) -> io::Result<()> {
+    stream.write_all(&(bytes.len() as u32).to_le_bytes())?;
+    stream.write_all(bytes)
+}
+
+// doesn't connect by itself, `flush_to_tcp` does so it can resend what wasn't acked
+impl FlushSink for TcpSink {
+    fn write_frame(&mut self, frame: &Frame) -> SinkResult {
+        let Some(stream) = &mut self.stream else {
+            return SinkResult::Err("Not connected".to_string());
+        };
+        if let Err(e) = send(stream, &frame.encode()) {
+            self.stream = None;
+            return SinkResult::Err(e.to_string());
+        }
+        SinkResult::Ok
+    }
+
+    fn flush(&mut self) -> SinkResult {
+        if let Some(stream) = &mut self.stream {
+            if let Err(e) = stream.flush() {
+                self.stream = None;
+                return SinkResult::Err(e.to_string());
+            }
+        }
+        match self.read_acks() {
+            Ok(()) => SinkResult::Ok,
+            Err(e) => SinkResult::Err(e.to_string()),
+        }
+    }
+}
+
+impl RingBuffer {
+    // send the frames the collector doesn't have yet, as far as max_flush_size and the
+    // ack window allow. connects first if needed, a failed attempt leaves every frame
+    // stored for the next call
+    pub fn flush_to_tcp(&mut self, collector: &mut TcpSink) -> FlushToResult {
+        self.enable_acks();
+        // a dead connection shows up as not connected below
+        let _ = collector.read_acks();
+        self.take_tcp_ack(collector);
+        if let Err(e) = collector.connect() {
+            return FlushToResult::Err(e.to_string());
+        }
+        if core::mem::take(&mut collector.reconnected) {
+            self.retransmit_unacked();
+        }
+        let result = self.flush_to(collector);
+        self.take_tcp_ack(collector);
+        result
+    }
+
+    fn take_tcp_ack(&mut self, collector: &mut TcpSink) {
+        if let Some(seq) = collector.ack.take() {
+            self.ack(seq);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+
+    use super::*;
+    use crate::frame::FrameResult;
+
+    // read one [len u32][frame] record
+    fn receive(stream: &mut TcpStream) -> Frame {
+        let mut len = [0u8; 4];
+        stream.read_exact(&mut len).unwrap();
+        let mut bytes = vec![0u8; u32::from_le_bytes(len) as usize];
+        stream.read_exact(&mut bytes).unwrap();
+        match Frame::decode(&bytes[..bytes.len() - 1]) {
+            FrameResult::Ok(frame) => frame,
+            FrameResult::Err(e) => panic!("{e}"),
+        }
+    }
+
+    fn ack(collector: &mut TcpStream, frame: &Frame) {
+        collector
+            .write_all(&frame.seq.unwrap().to_le_bytes())
+            .unwrap();
+    }
+
+    fn sink(addr: SocketAddr) -> TcpSink {
+        TcpSink::new(addr)
+            .unwrap()
+            .with_reconnect_interval(Duration::ZERO)
+    }
+
+    // flush until the acks leave `unacked` frames waiting
+    fn flush_until_acked(ring: &mut RingBuffer, sink: &mut TcpSink, unacked: usize) {
+        for _ in 0..100 {
+            let _ = ring.flush_to_tcp(sink);
+            if ring.unacked_frames() == unacked {
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        panic!("acks never arrived");
+    }
+
+    #[test]
+    fn unacked_frames_stay_in_the_ring_and_go_out_again_after_a_reconnect() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut sink = sink(listener.local_addr().unwrap());
+        let mut ring = RingBuffer::new(256);
+        ring.enable_acks();
+        let _ = ring.log(Frame::new(b"a"));
+        let _ = ring.log(Frame::new(b"b"));
+        assert!(matches!(ring.flush_to_tcp(&mut sink), FlushToResult::Ok(_)));
+        assert_eq!(ring.unacked_frames(), 2);
+
+        let (mut collector, _) = listener.accept().unwrap();
+        let a = receive(&mut collector);
+        assert_eq!(a.payload, b"a");
+        assert_eq!(receive(&mut collector).payload, b"b");
+        // only the first one is acked before the collector goes away
+        ack(&mut collector, &a);
+        flush_until_acked(&mut ring, &mut sink, 1);
+        drop(collector);
+
+        // the sink notices the next time it looks for acks
+        for _ in 0..50 {
+            std::thread::sleep(Duration::from_millis(10));
+            let _ = ring.flush_to_tcp(&mut sink);
+            if !sink.is_connected() {
+                break;
+            }
+        }
+        let _ = ring.log(Frame::new(b"c"));
+        assert!(matches!(ring.flush_to_tcp(&mut sink), FlushToResult::Ok(_)));
+        let (mut collector, _) = listener.accept().unwrap();
+        assert_eq!(receive(&mut collector).payload, b"b");
+        let c = receive(&mut collector);
+        assert_eq!(c.payload, b"c");
+        ack(&mut collector, &c);
+        flush_until_acked(&mut ring, &mut sink, 0);
+        assert!(ring.is_empty());
+    }
+
+    #[test]
+    fn frames_wait_in_the_ring_while_the_collector_is_down() {
+        // a port nobody listens on until the collector comes up
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let mut sink = sink(addr);
+        let mut ring = RingBuffer::new(128);
+        ring.enable_acks();
+        let _ = ring.log(Frame::new(b"queued"));
+        assert!(matches!(
+            ring.flush_to_tcp(&mut sink),
+            FlushToResult::Err(_)
+        ));
+        assert!(!ring.is_empty());
+        // written straight to, it turns frames away rather than connect
+        assert!(matches!(
+            sink.write_frame(&Frame::new(b"direct")),
+            SinkResult::Err(_)
+        ));
+
+        let listener = TcpListener::bind(addr).unwrap();
+        assert!(matches!(ring.flush_to_tcp(&mut sink), FlushToResult::Ok(_)));
+        let (mut collector, _) = listener.accept().unwrap();
+        let queued = receive(&mut collector);
+        assert_eq!(queued.payload, b"queued");
+        ack(&mut collector, &queued);
+        flush_until_acked(&mut ring, &mut sink, 0);
+        assert!(ring.is_empty());
+    }
+
+    #[test]
+    fn frames_outlive_the_sink_until_acked() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let mut ring = RingBuffer::new(128);
+        ring.enable_acks();
+        let _ = ring.log(Frame::new(b"kept"));
+        let _ = ring.flush_to_tcp(&mut sink(addr));
+        let (mut first, _) = listener.accept().unwrap();
+        assert_eq!(receive(&mut first).payload, b"kept");
+        assert_eq!(ring.unacked_frames(), 1);
+
+        // a new sink starts over from what the ring still holds
+        let _ = ring.flush_to_tcp(&mut sink(addr));
+        let (mut second, _) = listener.accept().unwrap();
+        assert_eq!(receive(&mut second).payload, b"kept");
+    }
+
+    #[test]
+    fn a_collector_that_stops_reading_fails_the_flush_in_time() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut sink =
+            sink(listener.local_addr().unwrap()).with_write_timeout(Duration::from_millis(100));
+        let mut ring = RingBuffer::new(32 * 1024 * 1024);
+        ring.set_max_flush_size(usize::MAX);
+        while let crate::PushResult::Ok = ring.log(Frame::new(&[1; 1024])) {}
+        // connected but never accepted, let alone read from
+        let started = Instant::now();
+        let mut failed = false;
+        for _ in 0..100 {
+            if let FlushToResult::Err(_) = ring.flush_to_tcp(&mut sink) {
+                failed = true;
+                break;
+            }
+        }
+        assert!(failed);
+        assert!(started.elapsed() < Duration::from_secs(10));
+        assert!(!ring.is_empty());
+    }
+}
@@ -0,0 +1,177 @@
+// two tiers: producers (interrupt handlers included) log into a small, fast ring, and a
+// background task periodically moves its frames over into a much bigger one, e.g. in
+// slower external ram. logging stays as cheap as it is on a small ring, and the long
+// history is there for a post-mortem:
+//
+//     let slow = unsafe { RingBuffer::retained(EXT_RAM, 512 * 1024) };
+//     let mut tiers = TieredRing::from_rings(RingBuffer::new(1024), slow);
+//
+//     // isr
+//     tiers.fast_mut().log_message_with_crc(b"...");
+//     // idle loop / low priority task
+//     tiers.drain();
+//     tiers.flush_to(&mut sink);
+//
+// frames move over as they are, still escaped, without a crc check. once the big ring
+// is full the oldest history makes room, like in a flight recorder. the big ring can
+// also be consumed directly through `slow_mut`
+
+use alloc::vec::Vec;
+
+use crate::frame::{self, Dropped, Frame, FrameResult};
+use crate::sink::{FlushSink, FlushToResult};
+use crate::RingBuffer;
+
+pub struct TieredRing {
+    fast: RingBuffer,
+    slow: RingBuffer,
+    evicted: u64,
+}
+
+impl TieredRing {
+    pub fn new(fast_size: usize, slow_size: usize) -> Self {
+        Self::from_rings(RingBuffer::new(fast_size), RingBuffer::new(slow_size))
+    }
+
+    pub fn from_rings(fast: RingBuffer, slow: RingBuffer) -> Self {
+        TieredRing {
+            fast,
+            slow,
+            evicted: 0,
+        }
+    }
+
+    pub fn fast(&self) -> &RingBuffer {
+        &self.fast
+    }
+
+    // the producer side, log into this
+    pub fn fast_mut(&mut self) -> &mut RingBuffer {
+        &mut self.fast
+    }
+
+    pub fn slow(&self) -> &RingBuffer {
+        &self.slow
+    }
+
+    pub fn slow_mut(&mut self) -> &mut RingBuffer {
+        &mut self.slow
+    }
+
+    pub fn into_rings(self) -> (RingBuffer, RingBuffer) {
+        (self.fast, self.slow)
+    }
+
+    // history frames thrown out of the big ring to make room
+    pub fn evicted(&self) -> u64 {
+        self.evicted
+    }
+
+    // move every complete frame from the fast ring to the slow one, returns how many
+    // moved. a frame too big for the slow ring is dropped
+    pub fn drain(&mut self) -> usize {
+        let mut moved = 0;
+        while let Some(bytes) = self.fast.pop_frame_bytes() {
+            let Some(evicted) = self
+                .slow
+                .make_room(bytes.len() + 1, &mut Dropped::default())
+            else {
+                continue;
+            };
+            self.evicted += evicted;
+            self.slow.push_slice(&bytes);
+            self.slow.push(frame::TERMINATOR);
+            moved += 1;
+        }
+        moved
+    }
+
+    // drain, then hand frames from the slow ring to `sink` up to its max_flush_size
+    pub fn flush_to(&mut self, sink: &mut (impl FlushSink + ?Sized)) -> FlushToResult {
+        self.drain();
+        self.slow.flush_to(sink)
+    }
+
+    pub fn flush_all_to(&mut self, sink: &mut (impl FlushSink + ?Sized)) -> FlushToResult {
+        self.drain();
+        self.slow.flush_all_to(sink)
+    }
+
+    // every stored frame of both tiers, oldest first, without consuming anything. for
+    // reading out the history after something went wrong. corrupt frames are skipped
+    pub fn history(&self) -> Vec<Frame> {
+        let mut frames = self.slow.frame_bytes();
+        frames.extend(self.fast.frame_bytes());
+        frames
+            .iter()
+            .filter_map(|bytes| match Frame::decode(bytes) {
+                FrameResult::Ok(frame) => Some(frame),
+                FrameResult::Err(_) => None,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sink::MemorySink;
+
+    fn payloads(frames: &[Frame]) -> Vec<&[u8]> {
+        frames.iter().map(|frame| &frame.payload[..]).collect()
+    }
+
+    #[test]
+    fn frames_move_over_and_flush_in_order() {
+        let mut tiers = TieredRing::new(32, 256);
+        for payload in [b"one", b"two"] {
+            let _ = tiers.fast_mut().log(Frame::new(payload));
+        }
+        assert_eq!(tiers.drain(), 2);
+        assert!(tiers.fast().is_empty());
+        let _ = tiers.fast_mut().log(Frame::new(b"six"));
+        // history spans both tiers without touching either
+        assert_eq!(payloads(&tiers.history()), [b"one", b"two", b"six"]);
+        assert_eq!(payloads(&tiers.history()), [b"one", b"two", b"six"]);
+
+        let mut sink = MemorySink::new();
+        assert!(
+            matches!(tiers.flush_all_to(&mut sink), FlushToResult::Ok(stats) if stats.frames == 3)
+        );
+        assert_eq!(payloads(sink.frames()), [b"one", b"two", b"six"]);
+        assert!(tiers.fast().is_empty() && tiers.slow().is_empty());
+    }
+
+    #[test]
+    fn a_full_history_gives_up_its_oldest_frames() {
+        let mut tiers = TieredRing::new(32, 64);
+        for i in 0..20u8 {
+            let _ = tiers.fast_mut().log(Frame::new(&[b'a' + i; 6]));
+            tiers.drain();
+        }
+        let history = tiers.history();
+        assert_eq!(history.len() as u64 + tiers.evicted(), 20);
+        assert_eq!(history.last().unwrap().payload, [b'a' + 19; 6]);
+        assert!(history
+            .windows(2)
+            .all(|pair| pair[0].payload[0] + 1 == pair[1].payload[0]));
+    }
+
+    #[test]
+    fn frames_move_unchecked_and_oversized_ones_are_dropped() {
+        let mut tiers = TieredRing::new(64, 16);
+        let mut corrupt = Frame::new(b"bad").encode();
+        corrupt[1] ^= 0xff;
+        tiers.fast_mut().push_slice(&corrupt);
+        let _ = tiers.fast_mut().log(Frame::new(&[9; 20]));
+        let _ = tiers.fast_mut().log(Frame::new(b"ok"));
+
+        assert_eq!(tiers.drain(), 2);
+        assert!(tiers.fast().is_empty());
+        assert_eq!(payloads(&tiers.history()), [b"ok"]);
+        assert_eq!(
+            tiers.slow().len(),
+            corrupt.len() + Frame::new(b"ok").encode().len()
+        );
+    }
+}
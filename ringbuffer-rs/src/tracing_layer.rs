@@ -0,0 +1,281 @@
+// tracing-subscriber layer: events become key-value frames (the message is the `message`
+// pair, the target `target`), optionally with span open/close frames around them
+
+use core::fmt;
+
+use tracing_core::field::{Field, Visit};
+use tracing_core::span::{Attributes, Id};
+use tracing_core::{Event, Metadata, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::frame::Frame;
+use crate::kv::Value;
+use crate::level::Level;
+use crate::shared::SharedRing;
+
+fn level(metadata: &Metadata) -> Level {
+    match *metadata.level() {
+        tracing_core::Level::ERROR => Level::Error,
+        tracing_core::Level::WARN => Level::Warn,
+        tracing_core::Level::INFO => Level::Info,
+        tracing_core::Level::DEBUG => Level::Debug,
+        _ => Level::Trace,
+    }
+}
+
+// collects an event's or span's fields as pairs on a key-value frame
+struct Pairs(Frame);
+
+impl Pairs {
+    fn add<'a>(&mut self, field: &Field, value: impl Into<Value<'a>>) {
+        self.0 = core::mem::take(&mut self.0).kv(field.name(), value);
+    }
+}
+
+impl Visit for Pairs {
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.add(field, value);
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        match i64::try_from(value) {
+            Ok(value) => self.add(field, value),
+            Err(_) => self.add(field, value.to_string()),
+        }
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.add(field, value);
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.add(field, value);
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.add(field, value);
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.add(field, format!("{:?}", value));
+    }
+}
+
+pub struct RingBufferLayer {
+    ring: SharedRing,
+    spans: bool,
+}
+
+impl RingBufferLayer {
+    pub fn new(ring: SharedRing) -> Self {
+        RingBufferLayer { ring, spans: false }
+    }
+
+    // also log a frame when a span opens (with its fields) and when it closes
+    pub fn with_spans(mut self, spans: bool) -> Self {
+        self.spans = spans;
+        self
+    }
+
+    fn log(&self, frame: Frame) {
+        // a full ring (or one a sink logs into while it's being flushed) counts the frame
+        // as dropped, there's nobody to report to here
+        let _ = self.ring.log(frame);
+    }
+}
+
+impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for RingBufferLayer {
+    fn enabled(&self, metadata: &Metadata, _ctx: Context<S>) -> bool {
+        self.ring
+            .try_with_ring(|ring| ring.level_enabled(level(metadata)))
+            .unwrap_or(true)
+    }
+
+    fn on_event(&self, event: &Event, _ctx: Context<S>) {
+        let metadata = event.metadata();
+        let mut pairs = Pairs(Frame::default().kv("target", metadata.target()));
+        event.record(&mut pairs);
+        self.log(pairs.0.with_level(level(metadata)));
+    }
+
+    fn on_new_span(&self, attrs: &Attributes, _id: &Id, _ctx: Context<S>) {
+        if !self.spans {
+            return;
+        }
+        let metadata = attrs.metadata();
+        let frame = Frame::default()
+            .kv("span", metadata.name())
+            .kv("event", "open");
+        let mut pairs = Pairs(frame);
+        attrs.record(&mut pairs);
+        self.log(pairs.0.with_level(level(metadata)));
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<S>) {
+        if !self.spans {
+            return;
+        }
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        let metadata = span.metadata();
+        let frame = Frame::default()
+            .kv("span", metadata.name())
+            .kv("event", "close");
+        self.log(frame.with_level(level(metadata)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tracing_core::callsite::{Callsite, Identifier};
+    use tracing_core::field::{FieldSet, Value as FieldValue};
+    use tracing_core::subscriber::Interest;
+    use tracing_core::{dispatcher, Dispatch, Kind};
+    use tracing_subscriber::layer::SubscriberExt;
+    use tracing_subscriber::Registry;
+
+    use super::*;
+    use crate::frame::FrameResult;
+    use crate::RingBuffer;
+
+    // hand-rolled callsites, what the tracing macros would generate
+    struct EventSite;
+    struct SpanSite;
+
+    static EVENT: Metadata<'static> = Metadata::new(
+        "event",
+        "motor",
+        tracing_core::Level::WARN,
+        None,
+        None,
+        None,
+        FieldSet::new(&["message", "rpm", "big"], Identifier(&EventSite)),
+        Kind::EVENT,
+    );
+
+    static SPAN: Metadata<'static> = Metadata::new(
+        "spin_up",
+        "motor",
+        tracing_core::Level::DEBUG,
+        None,
+        None,
+        None,
+        FieldSet::new(&["axis"], Identifier(&SpanSite)),
+        Kind::SPAN,
+    );
+
+    impl Callsite for EventSite {
+        fn set_interest(&self, _: Interest) {}
+
+        fn metadata(&self) -> &Metadata<'_> {
+            &EVENT
+        }
+    }
+
+    impl Callsite for SpanSite {
+        fn set_interest(&self, _: Interest) {}
+
+        fn metadata(&self) -> &Metadata<'_> {
+            &SPAN
+        }
+    }
+
+    fn event() {
+        let fields = EVENT.fields();
+        let message = fields.field("message").unwrap();
+        let rpm = fields.field("rpm").unwrap();
+        let big = fields.field("big").unwrap();
+        let values: [(&Field, Option<&dyn FieldValue>); 3] = [
+            (&message, Some(&format_args!("stalled") as &dyn FieldValue)),
+            (&rpm, Some(&1200i64 as &dyn FieldValue)),
+            (&big, Some(&u64::MAX as &dyn FieldValue)),
+        ];
+        Event::dispatch(&EVENT, &fields.value_set(&values));
+    }
+
+    fn span(dispatch: &Dispatch) {
+        let fields = SPAN.fields();
+        let axis = fields.field("axis").unwrap();
+        let values: [(&Field, Option<&dyn FieldValue>); 1] =
+            [(&axis, Some(&"x" as &dyn FieldValue))];
+        let id = dispatch.new_span(&Attributes::new(&SPAN, &fields.value_set(&values)));
+        dispatch.try_close(id);
+    }
+
+    // each frame as "key=value" strings
+    fn drain(ring: &SharedRing) -> Vec<(Option<Level>, Vec<String>)> {
+        ring.with_ring(|ring| {
+            let mut frames = Vec::new();
+            while let FrameResult::Ok(frame) = ring.flush_frame() {
+                let pairs = frame.pairs().unwrap_or_default();
+                let pairs = pairs
+                    .iter()
+                    .map(|(key, value)| format!("{}={}", key, value))
+                    .collect();
+                frames.push((frame.level, pairs));
+            }
+            frames
+        })
+    }
+
+    #[test]
+    fn events_become_key_value_frames() {
+        let ring = SharedRing::new(RingBuffer::new(256));
+        let dispatch = Dispatch::new(Registry::default().with(RingBufferLayer::new(ring.clone())));
+        dispatcher::with_default(&dispatch, event);
+
+        assert_eq!(
+            drain(&ring),
+            [(
+                Some(Level::Warn),
+                vec![
+                    "target=motor".to_string(),
+                    "message=stalled".to_string(),
+                    "rpm=1200".to_string(),
+                    format!("big={}", u64::MAX),
+                ]
+            )]
+        );
+    }
+
+    #[test]
+    fn spans_are_only_logged_when_asked_for() {
+        let ring = SharedRing::new(RingBuffer::new(256));
+        let quiet = Dispatch::new(Registry::default().with(RingBufferLayer::new(ring.clone())));
+        span(&quiet);
+        assert!(drain(&ring).is_empty());
+
+        let layer = RingBufferLayer::new(ring.clone()).with_spans(true);
+        let dispatch = Dispatch::new(Registry::default().with(layer));
+        span(&dispatch);
+        assert_eq!(
+            drain(&ring),
+            [
+                (
+                    Some(Level::Debug),
+                    vec![
+                        "span=spin_up".to_string(),
+                        "event=open".to_string(),
+                        "axis=x".to_string(),
+                    ]
+                ),
+                (
+                    Some(Level::Debug),
+                    vec!["span=spin_up".to_string(), "event=close".to_string()]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn events_below_the_ring_level_are_filtered() {
+        let mut inner = RingBuffer::new(256);
+        inner.set_max_level(Level::Error);
+        let ring = SharedRing::new(inner);
+        let dispatch = Dispatch::new(Registry::default().with(RingBufferLayer::new(ring.clone())));
+        dispatcher::with_default(&dispatch, event);
+        assert!(drain(&ring).is_empty());
+    }
+}
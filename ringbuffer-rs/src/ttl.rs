@@ -0,0 +1,143 @@
+// frames that are only worth something while they're fresh, telemetry samples that a
+// newer one replaces. a frame with a ttl expires that many clock ticks after its
+// timestamp, from then on the flush_to family drops it instead of sending it (counted
+// as skipped), and `expire` takes every expired frame out ahead of time:
+//
+//     ring.log(Frame::new(&sample).with_tag(TEMPERATURE).with_ttl(5000));
+//     ...
+//     // periodic housekeeping, frees the space for fresh frames
+//     ring.expire();
+//
+// frames without a timestamp never expire, neither does anything without a clock on
+// the ring. the ttl is a header field of its own, older decoders reject frames that
+// carry one
+
+use alloc::vec::Vec;
+
+use crate::frame::{Frame, FrameResult};
+use crate::RingBuffer;
+
+impl RingBuffer {
+    // take every expired frame out, wherever it is. returns how many went
+    pub fn expire(&mut self) -> usize {
+        if self.clock.is_none() {
+            return 0;
+        }
+        let expired: Vec<_> = self
+            .spans()
+            .into_iter()
+            .filter(|span| {
+                let bytes: Vec<u8> = (0..span.len)
+                    .map(|i| self.stored_byte(span.offset + i))
+                    .collect();
+                match Frame::decode(&bytes) {
+                    FrameResult::Ok(frame) => self.is_expired(&frame),
+                    FrameResult::Err(_) => false,
+                }
+            })
+            .collect();
+        // newest first, taking a frame out leaves the older ones at the same offsets
+        for span in expired.iter().rev() {
+            self.remove_stored(span.offset, span.len + 1);
+        }
+        expired.len()
+    }
+
+    pub(crate) fn is_expired(&self, frame: &Frame) -> bool {
+        match (&self.clock, frame.timestamp, frame.ttl) {
+            (Some(clock), Some(logged), Some(ttl)) => {
+                clock.now().saturating_sub(logged) > u64::from(ttl)
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::sink::{FlushToResult, MemorySink};
+
+    fn clocked(now: &Arc<AtomicU64>) -> RingBuffer {
+        let mut ring = RingBuffer::new(256);
+        let now = now.clone();
+        ring.set_clock(move || now.load(Ordering::SeqCst));
+        ring
+    }
+
+    fn payloads(sink: &MemorySink) -> Vec<Vec<u8>> {
+        sink.frames()
+            .iter()
+            .map(|frame| frame.payload.clone())
+            .collect()
+    }
+
+    #[test]
+    fn expire_takes_out_only_expired_frames() {
+        let now = Arc::new(AtomicU64::new(100));
+        let mut ring = clocked(&now);
+        let _ = ring.log(Frame::new(b"stale").with_ttl(10));
+        let _ = ring.log(Frame::new(b"forever"));
+        let _ = ring.log(Frame::new(b"also stale").with_ttl(20));
+        now.store(115, Ordering::SeqCst);
+        let _ = ring.log(Frame::new(b"fresh").with_ttl(10));
+
+        // exactly ttl old isn't expired yet
+        now.store(120, Ordering::SeqCst);
+        assert_eq!(ring.expire(), 1);
+        now.store(121, Ordering::SeqCst);
+        assert_eq!(ring.expire(), 1);
+        assert_eq!(ring.expire(), 0);
+
+        let mut sink = MemorySink::new();
+        let _ = ring.flush_all_to(&mut sink);
+        assert_eq!(payloads(&sink), [b"forever".to_vec(), b"fresh".to_vec()]);
+    }
+
+    #[test]
+    fn frames_without_a_timestamp_or_clock_never_expire() {
+        // logged before the ring had a clock, so without a timestamp
+        let mut ring = RingBuffer::new(256);
+        let _ = ring.log(Frame::new(b"untimed").with_ttl(1));
+        let now = Arc::new(AtomicU64::new(1_000));
+        let clock = now.clone();
+        ring.set_clock(move || clock.load(Ordering::SeqCst));
+        assert_eq!(ring.expire(), 0);
+
+        // a timestamp but no clock to compare it with
+        let mut unclocked = RingBuffer::new(256);
+        let _ = unclocked.push_slice(
+            &Frame::new(b"stamped")
+                .with_timestamp(1)
+                .with_ttl(1)
+                .encode(),
+        );
+        assert_eq!(unclocked.expire(), 0);
+
+        let mut sink = MemorySink::new();
+        let _ = ring.flush_all_to(&mut sink);
+        let _ = unclocked.flush_all_to(&mut sink);
+        assert_eq!(payloads(&sink), [b"untimed".to_vec(), b"stamped".to_vec()]);
+    }
+
+    #[test]
+    fn flushing_skips_expired_frames() {
+        let now = Arc::new(AtomicU64::new(100));
+        let mut ring = clocked(&now);
+        let _ = ring.log(Frame::new(b"stale").with_ttl(5));
+        let _ = ring.log(Frame::new(b"kept").with_ttl(50));
+        now.store(110, Ordering::SeqCst);
+
+        let mut sink = MemorySink::new();
+        let FlushToResult::Ok(stats) = ring.flush_all_to(&mut sink) else {
+            panic!("flush failed");
+        };
+        assert_eq!(stats.frames, 1);
+        assert_eq!(stats.skipped, 1);
+        assert_eq!(payloads(&sink), [b"kept".to_vec()]);
+        assert!(ring.is_empty());
+    }
+}
@@ -0,0 +1,258 @@
+// flush frames straight into a uart driver, so wiring the ring up is
+//
+//     ring.flush_to(&mut UartSink::new(&mut uart))
+//
+// from the uart interrupt or the idle loop. `flush_to` keeps to max_flush_size and only
+// takes a frame out once the driver accepted it. `UartSink` is for embedded-io drivers,
+// `NbUartSink` for the embedded-hal-nb serial traits
+
+use alloc::format;
+
+use crate::frame::Frame;
+use crate::sink::{FlushSink, SinkResult};
+
+#[cfg(feature = "embedded-io")]
+pub struct UartSink<W: embedded_io::Write> {
+    uart: W,
+}
+
+#[cfg(feature = "embedded-io")]
+impl<W: embedded_io::Write> UartSink<W> {
+    pub fn new(uart: W) -> Self {
+        UartSink { uart }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.uart
+    }
+}
+
+#[cfg(feature = "embedded-io")]
+impl<W: embedded_io::Write> FlushSink for UartSink<W> {
+    fn write_frame(&mut self, frame: &Frame) -> SinkResult {
+        match self.uart.write_all(&frame.encode()) {
+            Ok(()) => SinkResult::Ok,
+            Err(e) => SinkResult::Err(format!("{:?}", e)),
+        }
+    }
+
+    fn flush(&mut self) -> SinkResult {
+        match self.uart.flush() {
+            Ok(()) => SinkResult::Ok,
+            Err(e) => SinkResult::Err(format!("{:?}", e)),
+        }
+    }
+}
+
+// blocks on the driver's WouldBlock, one byte at a time
+#[cfg(feature = "embedded-hal")]
+pub struct NbUartSink<W: embedded_hal_nb::serial::Write<u8>> {
+    uart: W,
+}
+
+#[cfg(feature = "embedded-hal")]
+impl<W: embedded_hal_nb::serial::Write<u8>> NbUartSink<W> {
+    pub fn new(uart: W) -> Self {
+        NbUartSink { uart }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.uart
+    }
+}
+
+#[cfg(feature = "embedded-hal")]
+impl<W: embedded_hal_nb::serial::Write<u8>> FlushSink for NbUartSink<W> {
+    fn write_frame(&mut self, frame: &Frame) -> SinkResult {
+        use embedded_hal_nb::nb::block;
+
+        for byte in frame.encode() {
+            if let Err(e) = block!(self.uart.write(byte)) {
+                return SinkResult::Err(format!("{:?}", e));
+            }
+        }
+        SinkResult::Ok
+    }
+
+    fn flush(&mut self) -> SinkResult {
+        use embedded_hal_nb::nb::block;
+
+        match block!(self.uart.flush()) {
+            Ok(()) => SinkResult::Ok,
+            Err(e) => SinkResult::Err(format!("{:?}", e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::*;
+    use crate::decoder::FrameDecoder;
+    use crate::sink::FlushToResult;
+    use crate::RingBuffer;
+
+    // a driver that takes up to `room` bytes and then reports an error
+    #[derive(Default)]
+    struct Uart {
+        sent: Vec<u8>,
+        room: usize,
+        // calls before the next byte is taken, for the nb driver
+        #[cfg(feature = "embedded-hal")]
+        busy: u8,
+    }
+
+    #[derive(Debug)]
+    struct Overrun;
+
+    impl core::fmt::Display for Overrun {
+        fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+            f.write_str("overrun")
+        }
+    }
+
+    impl core::error::Error for Overrun {}
+
+    #[cfg(feature = "embedded-io")]
+    impl embedded_io::Error for Overrun {
+        fn kind(&self) -> embedded_io::ErrorKind {
+            embedded_io::ErrorKind::Other
+        }
+    }
+
+    #[cfg(feature = "embedded-io")]
+    impl embedded_io::ErrorType for Uart {
+        type Error = Overrun;
+    }
+
+    #[cfg(feature = "embedded-io")]
+    impl embedded_io::Write for Uart {
+        fn write(&mut self, buf: &[u8]) -> Result<usize, Overrun> {
+            let count = buf.len().min(self.room - self.sent.len());
+            if count == 0 {
+                return Err(Overrun);
+            }
+            self.sent.extend_from_slice(&buf[..count]);
+            Ok(count)
+        }
+
+        fn flush(&mut self) -> Result<(), Overrun> {
+            Ok(())
+        }
+    }
+
+    fn ring(payloads: &[&[u8]]) -> RingBuffer {
+        let mut ring = RingBuffer::new(128);
+        for payload in payloads {
+            let _ = ring.log(Frame::new(payload));
+        }
+        ring
+    }
+
+    fn payloads(bytes: &[u8]) -> Vec<Vec<u8>> {
+        FrameDecoder::new()
+            .feed(bytes)
+            .into_iter()
+            .map(|frame| frame.payload)
+            .collect()
+    }
+
+    #[cfg(feature = "embedded-io")]
+    #[test]
+    fn frames_go_out_up_to_the_flush_budget() {
+        let mut ring = ring(&[b"one", b"two", b"three"]);
+        ring.set_max_flush_size(Frame::new(b"one").encode().len() + 1);
+        let mut sink = UartSink::new(Uart {
+            room: usize::MAX,
+            ..Uart::default()
+        });
+        assert!(matches!(ring.flush_to(&mut sink), FlushToResult::Ok(stats) if stats.frames == 2));
+        assert!(matches!(ring.flush_to(&mut sink), FlushToResult::Ok(stats) if stats.frames == 1));
+        assert_eq!(
+            payloads(&sink.into_inner().sent),
+            [&b"one"[..], b"two", b"three"]
+        );
+    }
+
+    #[cfg(feature = "embedded-io")]
+    #[test]
+    fn a_driver_error_keeps_the_frame() {
+        let mut ring = ring(&[b"one", b"two"]);
+        let room = Frame::new(b"one").encode().len() + 2;
+        let mut sink = UartSink::new(Uart {
+            room,
+            ..Uart::default()
+        });
+        assert!(matches!(
+            ring.flush_all_to(&mut sink),
+            FlushToResult::Err(e) if e == "Overrun"
+        ));
+        let mut rest = UartSink::new(Uart {
+            room: usize::MAX,
+            ..Uart::default()
+        });
+        assert!(matches!(ring.flush_all_to(&mut rest), FlushToResult::Ok(_)));
+        assert_eq!(payloads(&rest.into_inner().sent), [&b"two"[..]]);
+    }
+
+    #[cfg(feature = "embedded-hal")]
+    impl embedded_hal_nb::serial::Error for Overrun {
+        fn kind(&self) -> embedded_hal_nb::serial::ErrorKind {
+            embedded_hal_nb::serial::ErrorKind::Overrun
+        }
+    }
+
+    #[cfg(feature = "embedded-hal")]
+    impl embedded_hal_nb::serial::ErrorType for Uart {
+        type Error = Overrun;
+    }
+
+    // busy every other call, like a transmit register that needs a moment to empty
+    #[cfg(feature = "embedded-hal")]
+    impl embedded_hal_nb::serial::Write<u8> for Uart {
+        fn write(&mut self, byte: u8) -> embedded_hal_nb::nb::Result<(), Overrun> {
+            if self.busy > 0 {
+                self.busy -= 1;
+                return Err(embedded_hal_nb::nb::Error::WouldBlock);
+            }
+            if self.sent.len() == self.room {
+                return Err(embedded_hal_nb::nb::Error::Other(Overrun));
+            }
+            self.sent.push(byte);
+            self.busy = 1;
+            Ok(())
+        }
+
+        fn flush(&mut self) -> embedded_hal_nb::nb::Result<(), Overrun> {
+            Ok(())
+        }
+    }
+
+    #[cfg(feature = "embedded-hal")]
+    #[test]
+    fn the_nb_sink_waits_out_a_busy_driver() {
+        let mut ring = ring(&[b"one", b"two"]);
+        let mut sink = NbUartSink::new(Uart {
+            room: usize::MAX,
+            ..Uart::default()
+        });
+        assert!(matches!(ring.flush_all_to(&mut sink), FlushToResult::Ok(_)));
+        assert_eq!(payloads(&sink.into_inner().sent), [&b"one"[..], b"two"]);
+    }
+
+    #[cfg(feature = "embedded-hal")]
+    #[test]
+    fn the_nb_sink_stops_at_a_driver_error() {
+        let mut ring = ring(&[b"one"]);
+        let mut sink = NbUartSink::new(Uart {
+            room: 2,
+            ..Uart::default()
+        });
+        assert!(matches!(
+            ring.flush_all_to(&mut sink),
+            FlushToResult::Err(e) if e == "Overrun"
+        ));
+        assert!(!ring.is_empty());
+    }
+}
@@ -0,0 +1,159 @@
+// ships flushed frames to a collector as udp datagrams, one frame per datagram or small
+// frames batched together. frames stay in wire format so the receiving end just runs a
+// `FrameDecoder` over each datagram
+
+use std::io;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs, UdpSocket};
+
+use crate::frame::Frame;
+use crate::sink::{FlushSink, SinkResult};
+
+pub struct UdpSink {
+    socket: UdpSocket,
+    max_batch: Option<usize>,
+    pending: Vec<u8>,
+}
+
+impl UdpSink {
+    // bind an ephemeral local port and send everything to `addr`
+    pub fn connect(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        Ok(Self::from_socket(connect(addr)?))
+    }
+
+    // use an already connected socket
+    pub fn from_socket(socket: UdpSocket) -> Self {
+        UdpSink {
+            socket,
+            max_batch: None,
+            pending: Vec::new(),
+        }
+    }
+
+    // pack consecutive frames into datagrams of up to `max_datagram` bytes, sent when
+    // the next frame wouldn't fit or at the end of the flush
+    pub fn with_batching(mut self, max_datagram: usize) -> Self {
+        self.max_batch = Some(max_datagram);
+        self
+    }
+
+    pub fn socket(&self) -> &UdpSocket {
+        &self.socket
+    }
+
+    fn send_pending(&mut self) -> SinkResult {
+        if self.pending.is_empty() {
+            return SinkResult::Ok;
+        }
+        let result = self.socket.send(&self.pending);
+        self.pending.clear();
+        match result {
+            Ok(_) => SinkResult::Ok,
+            Err(e) => SinkResult::Err(e.to_string()),
+        }
+    }
+}
+
+// a socket on an ephemeral port of the unspecified address of `addr`'s family, connected
+// to it
+pub(crate) fn connect(addr: impl ToSocketAddrs) -> io::Result<UdpSocket> {
+    let addr = addr
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no address"))?;
+    let local: SocketAddr = match addr {
+        SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+        SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+    };
+    let socket = UdpSocket::bind(local)?;
+    socket.connect(addr)?;
+    Ok(socket)
+}
+
+impl FlushSink for UdpSink {
+    fn write_frame(&mut self, frame: &Frame) -> SinkResult {
+        let bytes = frame.encode();
+        let Some(max_batch) = self.max_batch else {
+            return match self.socket.send(&bytes) {
+                Ok(_) => SinkResult::Ok,
+                Err(e) => SinkResult::Err(e.to_string()),
+            };
+        };
+
+        if self.pending.len() + bytes.len() > max_batch {
+            if let SinkResult::Err(e) = self.send_pending() {
+                return SinkResult::Err(e);
+            }
+        }
+        self.pending.extend_from_slice(&bytes);
+        // a single frame bigger than the batch goes out on its own
+        if self.pending.len() >= max_batch {
+            return self.send_pending();
+        }
+        SinkResult::Ok
+    }
+
+    fn flush(&mut self) -> SinkResult {
+        self.send_pending()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::decoder::FrameDecoder;
+    use crate::RingBuffer;
+
+    fn collector() -> UdpSocket {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket
+            .set_read_timeout(Some(Duration::from_secs(1)))
+            .unwrap();
+        socket
+    }
+
+    // payloads of every frame in each datagram
+    fn datagrams(socket: &UdpSocket, count: usize) -> Vec<Vec<Vec<u8>>> {
+        let mut buf = [0u8; 2048];
+        (0..count)
+            .map(|_| {
+                let len = socket.recv(&mut buf).unwrap();
+                let frames = FrameDecoder::new().feed(&buf[..len]);
+                frames.into_iter().map(|frame| frame.payload).collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn one_datagram_per_frame() {
+        let collector = collector();
+        let mut sink = UdpSink::connect(collector.local_addr().unwrap()).unwrap();
+        let mut ring = RingBuffer::new(128);
+        let _ = ring.log(Frame::new(b"one"));
+        let _ = ring.log(Frame::new(b"two"));
+        let _ = ring.flush_all_to(&mut sink);
+        assert_eq!(
+            datagrams(&collector, 2),
+            [[b"one".to_vec()], [b"two".to_vec()]]
+        );
+    }
+
+    #[test]
+    fn batching_packs_frames_up_to_the_datagram_size() {
+        let collector = collector();
+        let frame_len = Frame::new(b"12345").encode().len();
+        let mut sink = UdpSink::connect(collector.local_addr().unwrap())
+            .unwrap()
+            .with_batching(2 * frame_len + 1);
+        let mut ring = RingBuffer::new(128);
+        for payload in [b"12345", b"23456", b"34567"] {
+            let _ = ring.log(Frame::new(payload));
+        }
+        let _ = ring.flush_all_to(&mut sink);
+        // the last one goes out with the flush at the end
+        let received = datagrams(&collector, 2);
+        assert_eq!(received[0], [b"12345".to_vec(), b"23456".to_vec()]);
+        assert_eq!(received[1], [b"34567".to_vec()]);
+    }
+}
@@ -0,0 +1,305 @@
+// frames between local processes over unix domain sockets, in wire format with nothing
+// added, so a reader is just a `FrameDecoder` on the stream. either end can listen:
+//
+//     // one logger daemon collecting from many processes
+//     let mut source = UnixSource::bind("/run/ringlog.sock")?;
+//     ring.flush_to(&mut UnixSink::connect("/run/ringlog.sock"));
+//
+//     // one process serving its output to any number of readers
+//     ring.flush_to(&mut UnixSink::bind("/run/app-log.sock")?);
+//     let mut source = UnixSource::connect("/run/app-log.sock")?;
+//
+// a connecting sink reconnects on its own, while it's down write_frame fails and the
+// frames stay in the ring. a listening sink is a live tail like the websocket sink, a
+// reader that can't keep up is dropped
+
+use std::fs;
+use std::io::{self, Read, Write};
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::decoder::FrameDecoder;
+use crate::frame::Frame;
+use crate::sink::{FlushSink, SinkResult};
+
+enum Target {
+    Connect {
+        path: PathBuf,
+        stream: Option<UnixStream>,
+    },
+    Serve {
+        listener: UnixListener,
+        clients: Vec<UnixStream>,
+    },
+}
+
+pub struct UnixSink {
+    target: Target,
+    write_timeout: Duration,
+}
+
+impl UnixSink {
+    // doesn't connect yet, that happens on the first write
+    pub fn connect(path: impl AsRef<Path>) -> Self {
+        UnixSink {
+            target: Target::Connect {
+                path: path.as_ref().to_path_buf(),
+                stream: None,
+            },
+            write_timeout: Duration::from_secs(1),
+        }
+    }
+
+    // listen on `path`, replacing a stale socket left there by an earlier run
+    pub fn bind(path: impl AsRef<Path>) -> io::Result<Self> {
+        let listener = bind(path.as_ref())?;
+        listener.set_nonblocking(true)?;
+        Ok(UnixSink {
+            target: Target::Serve {
+                listener,
+                clients: Vec::new(),
+            },
+            write_timeout: Duration::from_secs(1),
+        })
+    }
+
+    // a peer that blocks a write for longer is disconnected
+    pub fn with_write_timeout(mut self, timeout: Duration) -> Self {
+        self.write_timeout = timeout;
+        self
+    }
+
+    // 1 while a connecting sink is connected, the number of readers for a listening one
+    pub fn clients(&self) -> usize {
+        match &self.target {
+            Target::Connect { stream, .. } => stream.is_some() as usize,
+            Target::Serve { clients, .. } => clients.len(),
+        }
+    }
+
+    fn prepare(&mut self) -> io::Result<()> {
+        let timeout = Some(self.write_timeout);
+        match &mut self.target {
+            Target::Connect { path, stream } => {
+                if stream.is_none() {
+                    let connected = UnixStream::connect(&*path)?;
+                    connected.set_write_timeout(timeout)?;
+                    *stream = Some(connected);
+                }
+            }
+            Target::Serve { listener, clients } => {
+                while let Ok((client, _)) = listener.accept() {
+                    let configured = client.set_nonblocking(false).is_ok()
+                        && client.set_write_timeout(timeout).is_ok();
+                    if configured {
+                        clients.push(client);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FlushSink for UnixSink {
+    fn write_frame(&mut self, frame: &Frame) -> SinkResult {
+        if let Err(e) = self.prepare() {
+            return SinkResult::Err(e.to_string());
+        }
+        let bytes = frame.encode();
+        match &mut self.target {
+            Target::Connect { stream, .. } => {
+                let Some(connected) = stream else {
+                    return SinkResult::Err("Not connected".to_string());
+                };
+                if let Err(e) = connected.write_all(&bytes) {
+                    *stream = None;
+                    return SinkResult::Err(e.to_string());
+                }
+            }
+            Target::Serve { clients, .. } => {
+                clients.retain_mut(|client| client.write_all(&bytes).is_ok());
+            }
+        }
+        SinkResult::Ok
+    }
+
+    fn flush(&mut self) -> SinkResult {
+        if let Target::Serve { .. } = self.target {
+            let _ = self.prepare();
+        }
+        SinkResult::Ok
+    }
+}
+
+fn bind(path: &Path) -> io::Result<UnixListener> {
+    if let Ok(metadata) = fs::symlink_metadata(path) {
+        if metadata.file_type().is_socket() {
+            fs::remove_file(path)?;
+        }
+    }
+    UnixListener::bind(path)
+}
+
+struct Peer {
+    stream: UnixStream,
+    decoder: FrameDecoder,
+}
+
+// the receiving end, decoding each peer's stream separately so frames from different
+// producers never get mixed up
+pub struct UnixSource {
+    listener: Option<UnixListener>,
+    peers: Vec<Peer>,
+}
+
+impl UnixSource {
+    // accept any number of connecting sinks
+    pub fn bind(path: impl AsRef<Path>) -> io::Result<Self> {
+        let listener = bind(path.as_ref())?;
+        listener.set_nonblocking(true)?;
+        Ok(UnixSource {
+            listener: Some(listener),
+            peers: Vec::new(),
+        })
+    }
+
+    // read from a listening sink
+    pub fn connect(path: impl AsRef<Path>) -> io::Result<Self> {
+        let stream = UnixStream::connect(path)?;
+        stream.set_nonblocking(true)?;
+        Ok(UnixSource {
+            listener: None,
+            peers: vec![Peer {
+                stream,
+                decoder: FrameDecoder::new(),
+            }],
+        })
+    }
+
+    pub fn peers(&self) -> usize {
+        self.peers.len()
+    }
+
+    // the frames completed since the last call, never blocks. peers that hung up are
+    // dropped, for a connected source that means no more frames will come
+    pub fn poll(&mut self) -> Vec<Frame> {
+        if let Some(listener) = &self.listener {
+            while let Ok((stream, _)) = listener.accept() {
+                if stream.set_nonblocking(true).is_ok() {
+                    self.peers.push(Peer {
+                        stream,
+                        decoder: FrameDecoder::new(),
+                    });
+                }
+            }
+        }
+
+        let mut frames = Vec::new();
+        let mut buf = [0u8; 4096];
+        self.peers.retain_mut(|peer| loop {
+            match peer.stream.read(&mut buf) {
+                Ok(0) => return false,
+                Ok(n) => frames.extend(peer.decoder.feed(&buf[..n])),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return true,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(_) => return false,
+            }
+        });
+        frames
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+    use crate::sink::FlushToResult;
+    use crate::RingBuffer;
+
+    fn path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("ringbuffer-unix-{}-{}", std::process::id(), name))
+    }
+
+    // poll until `count` frames came in, or give up after a second
+    fn payloads(source: &mut UnixSource, count: usize) -> Vec<Vec<u8>> {
+        let started = Instant::now();
+        let mut payloads = Vec::new();
+        while payloads.len() < count && started.elapsed() < Duration::from_secs(1) {
+            payloads.extend(source.poll().into_iter().map(|frame| frame.payload));
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        payloads
+    }
+
+    #[test]
+    fn peers_are_decoded_separately() {
+        let path = path("collect");
+        let mut source = UnixSource::bind(&path).unwrap();
+        let mut sink = UnixSink::connect(&path);
+        let mut raw = UnixStream::connect(&path).unwrap();
+
+        // half a frame from one peer, a whole one from the other in between
+        let split = Frame::new(b"split").encode();
+        raw.write_all(&split[..3]).unwrap();
+        assert!(matches!(
+            sink.write_frame(&Frame::new(b"whole")),
+            SinkResult::Ok
+        ));
+        assert_eq!(payloads(&mut source, 1), [b"whole"]);
+        raw.write_all(&split[3..]).unwrap();
+        assert_eq!(payloads(&mut source, 1), [b"split"]);
+        assert_eq!(source.peers(), 2);
+
+        drop(raw);
+        assert!(payloads(&mut source, 1).is_empty());
+        assert_eq!(source.peers(), 1);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn frames_wait_in_the_ring_until_the_daemon_listens() {
+        let path = path("daemon");
+        // a stale socket from an earlier run
+        drop(UnixListener::bind(&path).unwrap());
+        let mut sink = UnixSink::connect(&path);
+        let mut ring = RingBuffer::new(64);
+        let _ = ring.log(Frame::new(b"queued"));
+        assert!(matches!(
+            ring.flush_all_to(&mut sink),
+            FlushToResult::Err(_)
+        ));
+        assert!(!ring.is_empty());
+        assert_eq!(sink.clients(), 0);
+
+        let mut source = UnixSource::bind(&path).unwrap();
+        assert!(matches!(ring.flush_all_to(&mut sink), FlushToResult::Ok(_)));
+        assert_eq!(sink.clients(), 1);
+        assert_eq!(payloads(&mut source, 1), [b"queued"]);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn a_listening_sink_serves_every_reader() {
+        let path = path("serve");
+        let mut sink = UnixSink::bind(&path).unwrap();
+        let mut first = UnixSource::connect(&path).unwrap();
+        let mut second = UnixSource::connect(&path).unwrap();
+        let _ = sink.flush();
+        assert_eq!(sink.clients(), 2);
+
+        let _ = sink.write_frame(&Frame::new(b"to all"));
+        assert_eq!(payloads(&mut first, 1), [b"to all"]);
+        assert_eq!(payloads(&mut second, 1), [b"to all"]);
+
+        // a reader that went away is dropped on the next write
+        drop(second);
+        let _ = sink.write_frame(&Frame::new(b"left"));
+        assert_eq!(sink.clients(), 1);
+        assert_eq!(payloads(&mut first, 1), [b"left"]);
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,165 @@
+// typed frames: serde structs packed with postcard or bincode, so firmware can push
+// telemetry records through the ring instead of hand-packing bytes. neither format is
+// self-describing, producer and consumer have to agree on the type and the codec
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::frame::{Frame, FrameResult};
+use crate::kind::FrameKind;
+use crate::{PushResult, RingBuffer};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueCodec {
+    // compact varint encoding, works without std
+    #[cfg(feature = "postcard")]
+    Postcard,
+    // bincode's standard config, for host programs that already speak it
+    #[cfg(feature = "bincode")]
+    Bincode,
+}
+
+impl ValueCodec {
+    fn encode<T: Serialize>(self, value: &T) -> Result<Vec<u8>, String> {
+        match self {
+            #[cfg(feature = "postcard")]
+            ValueCodec::Postcard => postcard::to_allocvec(value).map_err(|e| e.to_string()),
+            #[cfg(feature = "bincode")]
+            ValueCodec::Bincode => {
+                bincode::serde::encode_to_vec(value, bincode::config::standard())
+                    .map_err(|e| e.to_string())
+            }
+        }
+    }
+
+    fn decode<T: DeserializeOwned>(self, bytes: &[u8]) -> Result<T, String> {
+        match self {
+            #[cfg(feature = "postcard")]
+            ValueCodec::Postcard => postcard::from_bytes(bytes).map_err(|e| e.to_string()),
+            #[cfg(feature = "bincode")]
+            ValueCodec::Bincode => {
+                bincode::serde::decode_from_slice(bytes, bincode::config::standard())
+                    .map(|(value, _)| value)
+                    .map_err(|e| e.to_string())
+            }
+        }
+    }
+}
+
+pub enum ValueResult<T> {
+    Ok(T),
+    Err(String),
+}
+
+impl RingBuffer {
+    // log `value` as a postcard telemetry frame
+    #[cfg(feature = "postcard")]
+    pub fn log_value<T: Serialize>(&mut self, value: &T) -> PushResult {
+        self.log_value_as(ValueCodec::Postcard, value)
+    }
+
+    // pop the next frame and decode its payload as a postcard `T`
+    #[cfg(feature = "postcard")]
+    pub fn flush_value<T: DeserializeOwned>(&mut self) -> ValueResult<T> {
+        self.flush_value_as(ValueCodec::Postcard)
+    }
+
+    pub fn log_value_as<T: Serialize>(&mut self, codec: ValueCodec, value: &T) -> PushResult {
+        match codec.encode(value) {
+            Ok(payload) => self.log(Frame::new(&payload).with_kind(FrameKind::Telemetry)),
+            Err(e) => PushResult::Err(String::from("Error encoding value: ") + &e),
+        }
+    }
+
+    // the frame is gone either way, even if it didn't decode
+    pub fn flush_value_as<T: DeserializeOwned>(&mut self, codec: ValueCodec) -> ValueResult<T> {
+        let frame = match self.flush_frame() {
+            FrameResult::Ok(frame) => frame,
+            FrameResult::Err(e) => return ValueResult::Err(e),
+        };
+        match codec.decode(&frame.payload) {
+            Ok(value) => ValueResult::Ok(value),
+            Err(e) => ValueResult::Err(String::from("Error decoding value: ") + &e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "postcard")]
+    #[test]
+    fn postcard_values_round_trip_in_order() {
+        let mut ring = RingBuffer::new(128);
+        let _ = ring.log_value(&(1200u32, -3i8, String::from("running")));
+        let _ = ring.log_value(&[1.5f32, 2.5]);
+
+        assert!(matches!(
+            ring.flush_value::<(u32, i8, String)>(),
+            ValueResult::Ok((1200, -3, s)) if s == "running"
+        ));
+        assert!(matches!(
+            ring.flush_value::<[f32; 2]>(),
+            ValueResult::Ok([1.5, 2.5])
+        ));
+        assert!(matches!(ring.flush_value::<u8>(), ValueResult::Err(_)));
+    }
+
+    #[cfg(feature = "postcard")]
+    #[test]
+    fn a_value_that_doesnt_decode_is_still_flushed() {
+        let mut ring = RingBuffer::new(128);
+        let _ = ring.log_value(&1u8);
+        let _ = ring.log_value(&2u8);
+
+        assert!(matches!(
+            ring.flush_value::<(u8, u8)>(),
+            ValueResult::Err(e) if e.starts_with("Error decoding value: ")
+        ));
+        assert!(matches!(ring.flush_value::<u8>(), ValueResult::Ok(2)));
+        assert!(ring.is_empty());
+    }
+
+    #[cfg(feature = "postcard")]
+    #[test]
+    fn values_are_flagged_as_telemetry() {
+        let mut ring = RingBuffer::new(128);
+        let _ = ring.log_value(&7u16);
+        match ring.flush_frame() {
+            FrameResult::Ok(frame) => {
+                assert_eq!(frame.kind, Some(FrameKind::Telemetry));
+                assert_eq!(frame.payload, postcard::to_allocvec(&7u16).unwrap());
+            }
+            FrameResult::Err(e) => panic!("{e}"),
+        }
+    }
+
+    #[cfg(feature = "bincode")]
+    #[test]
+    fn bincode_values_round_trip() {
+        let mut ring = RingBuffer::new(128);
+        let value = (u64::MAX, String::from("host"), vec![1u16, 2, 3]);
+        let _ = ring.log_value_as(ValueCodec::Bincode, &value);
+        assert!(matches!(
+            ring.flush_value_as::<(u64, String, Vec<u16>)>(ValueCodec::Bincode),
+            ValueResult::Ok(decoded) if decoded == value
+        ));
+    }
+
+    #[cfg(feature = "bincode")]
+    #[test]
+    fn the_payload_is_bincodes_standard_encoding() {
+        let mut ring = RingBuffer::new(128);
+        let _ = ring.log_value_as(ValueCodec::Bincode, &(300u32, true));
+        let expected =
+            bincode::serde::encode_to_vec((300u32, true), bincode::config::standard()).unwrap();
+        match ring.flush_frame() {
+            FrameResult::Ok(frame) => assert_eq!(frame.payload, expected),
+            FrameResult::Err(e) => panic!("{e}"),
+        }
+    }
+}
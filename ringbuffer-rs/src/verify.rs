@@ -0,0 +1,106 @@
+// whole-buffer integrity check that doesn't consume anything, e.g. right after a ring
+// was recovered from retained ram or attached to shared memory:
+//
+//     let report = ring.verify();
+//     if !report.is_ok() {
+//         // salvage, or at least say so before flushing
+//     }
+
+use core::sync::atomic::Ordering;
+
+use crate::frame::{self, Frame, FrameResult};
+use crate::RingBuffer;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IntegrityReport {
+    // head and tail inside the storage
+    pub indices_ok: bool,
+    // complete frames that pass their crc
+    pub frames_ok: usize,
+    // complete frames that don't, empty ones included
+    pub frames_corrupt: usize,
+    // bytes after the last terminator: a frame still being written, or garbage
+    pub partial_bytes: usize,
+}
+
+impl IntegrityReport {
+    pub fn is_ok(&self) -> bool {
+        self.indices_ok && self.frames_corrupt == 0 && self.partial_bytes == 0
+    }
+}
+
+impl RingBuffer {
+    pub fn verify(&self) -> IntegrityReport {
+        let mut report = IntegrityReport {
+            indices_ok: self.head < self.size && self.tail < self.size,
+            ..Default::default()
+        };
+        // with broken indices there's no telling which bytes are stored
+        if !report.indices_ok {
+            return report;
+        }
+        self.fence(Ordering::Acquire);
+        for bytes in self.frame_bytes() {
+            match Frame::decode(&bytes) {
+                FrameResult::Ok(_) => report.frames_ok += 1,
+                FrameResult::Err(_) => report.frames_corrupt += 1,
+            }
+        }
+        report.partial_bytes = (0..self.len())
+            .rev()
+            .take_while(|&i| self.stored_byte(i) != frame::TERMINATOR)
+            .count();
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_healthy_ring_checks_out() {
+        let mut ring = RingBuffer::new(64);
+        assert!(ring.verify().is_ok());
+        for payload in [b"one", b"two"] {
+            let _ = ring.log(Frame::new(payload));
+        }
+        let report = ring.verify();
+        assert!(report.is_ok());
+        assert_eq!(report.frames_ok, 2);
+    }
+
+    #[test]
+    fn corrupt_and_partial_frames_are_counted_without_consuming() {
+        let mut ring = RingBuffer::new(64);
+        let _ = ring.log(Frame::new(b"good"));
+        let mut corrupt = Frame::new(b"bad").encode();
+        corrupt[1] ^= 0xff;
+        ring.push_slice(&corrupt);
+        // an empty frame is corrupt too
+        let _ = ring.push(frame::TERMINATOR);
+        ring.push_slice(&Frame::new(b"half").encode()[..3]);
+
+        let stored = ring.len();
+        let report = ring.verify();
+        assert_eq!(
+            report,
+            IntegrityReport {
+                indices_ok: true,
+                frames_ok: 1,
+                frames_corrupt: 2,
+                partial_bytes: 3,
+            }
+        );
+        assert!(!report.is_ok());
+        assert_eq!(ring.len(), stored);
+    }
+
+    #[test]
+    fn broken_indices_stop_the_check() {
+        let mut ring = RingBuffer::new(64);
+        let _ = ring.log(Frame::new(b"good"));
+        ring.head = 64;
+        assert_eq!(ring.verify(), IntegrityReport::default());
+    }
+}
@@ -0,0 +1,222 @@
+// wasm-bindgen bindings for the sans-io decoder, so a web dashboard getting raw frame
+// bytes over websocket or webusb decodes them with the same code as the firmware:
+//
+//     wasm-pack build --target web -- --features wasm
+//
+//     const decoder = new FrameDecoder();
+//     socket.onmessage = (e) => {
+//         for (const frame of decoder.feed(new Uint8Array(e.data))) {
+//             console.log(frame.seq, frame.level, frame.text);
+//         }
+//     };
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use wasm_bindgen::prelude::*;
+
+use crate::decoder;
+use crate::frame::{self, FrameResult};
+use crate::interned::{RehydrateResult, StringTable as Table, TableResult};
+use crate::json;
+
+#[wasm_bindgen]
+pub struct Frame(frame::Frame);
+
+#[wasm_bindgen]
+impl Frame {
+    // parse one frame in wire format, with or without its terminator
+    pub fn decode(bytes: &[u8]) -> Result<Frame, JsError> {
+        let bytes = bytes.strip_suffix(&[frame::TERMINATOR]).unwrap_or(bytes);
+        match frame::Frame::decode(bytes) {
+            FrameResult::Ok(frame) => Ok(Frame(frame)),
+            FrameResult::Err(e) => Err(JsError::new(&e)),
+        }
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn payload(&self) -> Vec<u8> {
+        self.0.payload.clone()
+    }
+
+    // the payload as text, invalid utf-8 replaced
+    #[wasm_bindgen(getter)]
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.0.payload).into_owned()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn timestamp(&self) -> Option<u64> {
+        self.0.timestamp
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn seq(&self) -> Option<u16> {
+        self.0.seq
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn level(&self) -> Option<String> {
+        self.0.level.map(|level| level.as_str().into())
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn tag(&self) -> Option<u16> {
+        self.0.tag
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn channel(&self) -> Option<u8> {
+        self.0.channel
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn kind(&self) -> Option<String> {
+        self.0.kind.map(|kind| json::kind_name(kind).into())
+    }
+
+    // all fields as a json object, key-value pairs included, ready for JSON.parse
+    pub fn json(&self) -> String {
+        let mut out = String::from("{");
+        json::write_frame_fields(&mut out, &self.0);
+        out.push('}');
+        out
+    }
+}
+
+#[wasm_bindgen]
+pub struct FrameDecoder(decoder::FrameDecoder);
+
+impl Default for FrameDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[wasm_bindgen]
+impl FrameDecoder {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> FrameDecoder {
+        FrameDecoder(decoder::FrameDecoder::new())
+    }
+
+    #[wasm_bindgen(js_name = withMaxFrameLen)]
+    pub fn with_max_frame_len(max_frame_len: usize) -> FrameDecoder {
+        FrameDecoder(decoder::FrameDecoder::new().with_max_frame_len(max_frame_len))
+    }
+
+    pub fn feed(&mut self, bytes: &[u8]) -> Vec<Frame> {
+        self.0.feed(bytes).into_iter().map(Frame).collect()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn pending(&self) -> usize {
+        self.0.pending()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn corrupt(&self) -> usize {
+        self.0.corrupt()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn oversized(&self) -> usize {
+        self.0.oversized()
+    }
+
+    pub fn reset(&mut self) {
+        self.0.reset();
+    }
+}
+
+#[wasm_bindgen]
+pub struct StringTable(Table);
+
+// colliding message ids come out as exceptions
+fn table(result: TableResult) -> Result<StringTable, JsError> {
+    match result {
+        TableResult::Ok(table) => Ok(StringTable(table)),
+        TableResult::Err(e) => Err(JsError::new(&e)),
+    }
+}
+
+#[wasm_bindgen]
+impl StringTable {
+    // `<id> <format>` lines
+    pub fn parse(text: &str) -> Result<StringTable, JsError> {
+        table(Table::parse(text))
+    }
+
+    // the raw contents of the firmware's `ring_log` section
+    #[wasm_bindgen(js_name = fromSection)]
+    pub fn from_section(bytes: &[u8]) -> Result<StringTable, JsError> {
+        table(Table::from_section(bytes))
+    }
+
+    pub fn rehydrate(&self, frame: &Frame) -> Result<String, JsError> {
+        match self.0.rehydrate(&frame.0) {
+            RehydrateResult::Ok(text) => Ok(text),
+            RehydrateResult::Err(e) => Err(JsError::new(&e)),
+        }
+    }
+}
+
+// the error paths build a JsError, which only exists on a wasm target
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interned;
+    use crate::kind::FrameKind;
+    use crate::level::Level;
+
+    #[test]
+    fn the_decoder_hands_out_frames_with_their_fields() {
+        let stream = [
+            frame::Frame::new(b"boot").with_seq(1).encode(),
+            frame::Frame::new(b"stalled")
+                .with_level(Level::Warn)
+                .with_tag(3)
+                .encode(),
+        ]
+        .concat();
+        let mut decoder = FrameDecoder::new();
+        let mut frames = decoder.feed(&stream[..7]);
+        frames.extend(decoder.feed(&stream[7..]));
+
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].text(), "boot");
+        assert_eq!(frames[0].seq(), Some(1));
+        assert_eq!(frames[1].level().as_deref(), Some("WARN"));
+        assert_eq!(frames[1].tag(), Some(3));
+        assert_eq!(
+            frames[1].json(),
+            r#"{"level":"WARN","tag":3,"payload":"stalled"}"#
+        );
+        assert_eq!((decoder.pending(), decoder.corrupt()), (0, 0));
+    }
+
+    #[test]
+    fn a_frame_decodes_with_or_without_its_terminator() {
+        let encoded = frame::Frame::default().kv("rpm", 1200).encode();
+        for bytes in [&encoded[..], &encoded[..encoded.len() - 1]] {
+            let Ok(frame) = Frame::decode(bytes) else {
+                panic!("didn't decode");
+            };
+            assert_eq!(frame.kind().as_deref(), Some("kv"));
+        }
+    }
+
+    #[test]
+    fn interned_frames_rehydrate() {
+        let Ok(table) = StringTable::parse("7 motor ready") else {
+            panic!("didn't parse");
+        };
+        let encoded = frame::Frame::new(&interned::encode(7, &[]))
+            .with_kind(FrameKind::Interned)
+            .encode();
+        let Ok(frame) = Frame::decode(&encoded) else {
+            panic!("didn't decode");
+        };
+        assert!(matches!(table.rehydrate(&frame), Ok(text) if text == "motor ready"));
+    }
+}
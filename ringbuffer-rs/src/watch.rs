@@ -0,0 +1,145 @@
+// latest value only: a triple buffer for things like the current sensor reading, next to
+// the ring that keeps the history. the producer overwrites, the consumer always gets the
+// newest complete value, and neither ever waits for the other:
+//
+//     let (mut reading, mut current) = watch(Reading::default());
+//     // sampling interrupt
+//     reading.publish(sample);
+//     // main loop
+//     display(current.latest());
+//
+// three slots: one the producer writes into, one the consumer reads from, and one in
+// the middle holding the last published value. publishing and picking up are a single
+// atomic swap of the middle slot, values the consumer never got to are simply replaced
+
+use alloc::sync::Arc;
+use core::cell::UnsafeCell;
+use core::sync::atomic::{AtomicU8, Ordering};
+
+// low bits of `middle` name the slot, this one says it holds a value not yet picked up
+const FRESH: u8 = 1 << 2;
+const INDEX: u8 = FRESH - 1;
+
+struct Shared<T> {
+    slots: [UnsafeCell<T>; 3],
+    middle: AtomicU8,
+}
+
+// each slot is only ever touched by the side that owns it, ownership moves through the
+// swaps on `middle`
+unsafe impl<T: Send> Sync for Shared<T> {}
+
+pub struct WatchProducer<T> {
+    shared: Arc<Shared<T>>,
+    back: u8,
+}
+
+pub struct WatchConsumer<T> {
+    shared: Arc<Shared<T>>,
+    front: u8,
+}
+
+// split a watch into its producing and consuming ends, `initial` is what the consumer
+// sees until the first publish
+pub fn watch<T: Clone>(initial: T) -> (WatchProducer<T>, WatchConsumer<T>) {
+    let shared = Arc::new(Shared {
+        slots: [
+            UnsafeCell::new(initial.clone()),
+            UnsafeCell::new(initial.clone()),
+            UnsafeCell::new(initial),
+        ],
+        middle: AtomicU8::new(1),
+    });
+    (
+        WatchProducer {
+            shared: shared.clone(),
+            back: 0,
+        },
+        WatchConsumer { shared, front: 2 },
+    )
+}
+
+impl<T> WatchProducer<T> {
+    pub fn publish(&mut self, value: T) {
+        self.publish_with(|slot| *slot = value);
+    }
+
+    // update the value in place, for big ones. the slot holds whatever was published two
+    // values ago, so `f` has to write all of it
+    pub fn publish_with(&mut self, f: impl FnOnce(&mut T)) {
+        // the back slot is ours until it's swapped out below
+        f(unsafe { &mut *self.shared.slots[self.back as usize].get() });
+        let previous = self.shared.middle.swap(self.back | FRESH, Ordering::AcqRel);
+        self.back = previous & INDEX;
+    }
+}
+
+impl<T> WatchConsumer<T> {
+    // whether something was published since the last `latest`
+    pub fn has_update(&self) -> bool {
+        self.shared.middle.load(Ordering::Acquire) & FRESH != 0
+    }
+
+    // the newest published value
+    pub fn latest(&mut self) -> &T {
+        if self.has_update() {
+            let previous = self.shared.middle.swap(self.front, Ordering::AcqRel);
+            self.front = previous & INDEX;
+        }
+        // the front slot is ours until it's swapped out on a later call
+        unsafe { &*self.shared.slots[self.front as usize].get() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_consumer_sees_the_initial_value_then_the_newest() {
+        let (mut producer, mut consumer) = watch(0u32);
+        assert!(!consumer.has_update());
+        assert_eq!(*consumer.latest(), 0);
+
+        producer.publish(1);
+        producer.publish(2);
+        assert!(consumer.has_update());
+        assert_eq!(*consumer.latest(), 2);
+        assert!(!consumer.has_update());
+        // and keeps it until the next publish
+        assert_eq!(*consumer.latest(), 2);
+        producer.publish(3);
+        assert_eq!(*consumer.latest(), 3);
+    }
+
+    #[test]
+    fn publish_with_gets_the_value_from_two_publishes_ago() {
+        let (mut producer, mut consumer) = watch(vec![0u8; 4]);
+        producer.publish(vec![1; 4]);
+        producer.publish(vec![2; 4]);
+        producer.publish_with(|slot| {
+            assert_eq!(*slot, [1; 4]);
+            slot[0] = 3;
+        });
+        assert_eq!(*consumer.latest(), [3, 1, 1, 1]);
+    }
+
+    #[test]
+    fn values_only_move_forward_across_threads() {
+        let (mut producer, mut consumer) = watch((0u64, 0u64));
+        let publisher = std::thread::spawn(move || {
+            for i in 1..=100_000u64 {
+                // both halves written together, a torn read would show them apart
+                producer.publish((i, i * 3));
+            }
+        });
+        let mut last = 0;
+        while last < 100_000 {
+            let &(value, tripled) = consumer.latest();
+            assert_eq!(tripled, value * 3);
+            assert!(value >= last);
+            last = value;
+        }
+        publisher.join().unwrap();
+    }
+}
@@ -0,0 +1,176 @@
+// occupancy watermarks with hysteresis: once the stored bytes reach the high mark the
+// ring says so, once, and only re-arms after dropping to the low mark. flush when told
+// instead of on a fixed schedule:
+//
+//     ring.set_watermarks(3 * 1024, 1024);
+//     ...
+//     if ring.take_high_watermark() {
+//         ring.flush_all_to(&mut sink);
+//     }
+//
+// the crossing is reported three ways, use whichever fits: `Hooks::on_high_watermark`
+// right when it happens, the latched flag behind `take_high_watermark` for a main loop,
+// and `poll_high_watermark` to wake an async task
+
+use core::task::{Context, Poll, Waker};
+
+use crate::RingBuffer;
+
+pub(crate) struct Watermarks {
+    high: usize,
+    low: usize,
+    armed: bool,
+    // crossed and not yet taken
+    pending: bool,
+    waker: Option<Waker>,
+}
+
+impl RingBuffer {
+    pub fn set_watermarks(&mut self, high: usize, low: usize) {
+        assert!(low < high, "Low watermark has to be below the high one");
+        self.watermarks = Some(Watermarks {
+            high,
+            low,
+            armed: true,
+            pending: false,
+            waker: None,
+        });
+        self.check_watermarks();
+    }
+
+    pub fn clear_watermarks(&mut self) {
+        self.watermarks = None;
+    }
+
+    // whether the high watermark was crossed since the last call
+    pub fn take_high_watermark(&mut self) -> bool {
+        match &mut self.watermarks {
+            Some(marks) => core::mem::take(&mut marks.pending),
+            None => false,
+        }
+    }
+
+    // ready once the high watermark was crossed since the last ready. never ready
+    // without watermarks
+    pub fn poll_high_watermark(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        let Some(marks) = &mut self.watermarks else {
+            return Poll::Pending;
+        };
+        if core::mem::take(&mut marks.pending) {
+            return Poll::Ready(());
+        }
+        marks.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+
+    // called whenever occupancy changes
+    pub(crate) fn check_watermarks(&mut self) {
+        let len = self.len();
+        let Some(marks) = &mut self.watermarks else {
+            return;
+        };
+        if !marks.armed {
+            marks.armed = len <= marks.low;
+            return;
+        }
+        if len < marks.high {
+            return;
+        }
+        marks.armed = false;
+        marks.pending = true;
+        if let Some(waker) = marks.waker.take() {
+            waker.wake();
+        }
+        if let Some(hooks) = &mut self.hooks {
+            hooks.on_high_watermark(len);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::task::Wake;
+
+    use super::*;
+    use crate::Hooks;
+
+    struct Flag(AtomicBool);
+
+    impl Wake for Flag {
+        fn wake(self: Arc<Self>) {
+            self.0.store(true, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn the_high_mark_is_reported_once_until_the_low_mark_rearms_it() {
+        let mut ring = RingBuffer::new(32);
+        ring.set_watermarks(20, 8);
+        ring.push_slice(&[1; 19]);
+        assert!(!ring.take_high_watermark());
+
+        ring.push_slice(&[1; 5]);
+        assert!(ring.take_high_watermark());
+        assert!(!ring.take_high_watermark());
+
+        // dipping below high without reaching low doesn't re-arm
+        ring.read_view().consume(10);
+        ring.push_slice(&[1; 10]);
+        assert!(!ring.take_high_watermark());
+
+        let above_low = ring.len() - 8;
+        ring.read_view().consume(above_low);
+        ring.push_slice(&[1; 12]);
+        assert!(ring.take_high_watermark());
+    }
+
+    #[test]
+    fn setting_marks_on_a_full_ring_reports_right_away() {
+        let mut ring = RingBuffer::new(32);
+        ring.push_slice(&[1; 25]);
+        ring.set_watermarks(20, 8);
+        assert!(ring.take_high_watermark());
+
+        ring.clear_watermarks();
+        ring.read_view().consume(25);
+        ring.push_slice(&[1; 25]);
+        assert!(!ring.take_high_watermark());
+    }
+
+    #[test]
+    fn a_polling_task_is_woken_on_the_crossing() {
+        let flag = Arc::new(Flag(AtomicBool::new(false)));
+        let waker = Waker::from(flag.clone());
+        let mut cx = Context::from_waker(&waker);
+
+        let mut ring = RingBuffer::new(32);
+        assert!(ring.poll_high_watermark(&mut cx).is_pending());
+        ring.set_watermarks(20, 8);
+        assert!(ring.poll_high_watermark(&mut cx).is_pending());
+
+        ring.push_slice(&[1; 20]);
+        assert!(flag.0.load(Ordering::SeqCst));
+        assert!(ring.poll_high_watermark(&mut cx).is_ready());
+        assert!(ring.poll_high_watermark(&mut cx).is_pending());
+    }
+
+    #[test]
+    fn the_hook_hears_the_occupancy_at_the_crossing() {
+        struct Occupancy(Arc<AtomicUsize>);
+
+        impl Hooks for Occupancy {
+            fn on_high_watermark(&mut self, occupancy: usize) {
+                self.0.store(occupancy, Ordering::SeqCst);
+            }
+        }
+
+        let occupancy = Arc::new(AtomicUsize::new(0));
+        let mut ring = RingBuffer::new(32);
+        ring.set_hooks(Occupancy(occupancy.clone()));
+        ring.set_watermarks(20, 8);
+        ring.push_slice(&[1; 23]);
+        assert_eq!(occupancy.load(Ordering::SeqCst), 23);
+    }
+}
@@ -0,0 +1,194 @@
+// serves the frame stream to browsers: listens for websocket clients and pushes every
+// flushed frame to each of them as one binary message (wire format, decode with the
+// same frame decoder on the page). with nobody connected frames are simply dropped,
+// this is a live tail and not a delivery guarantee
+
+use std::io;
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+use tungstenite::handshake::server::{NoCallback, ServerHandshake};
+use tungstenite::handshake::{HandshakeError, MidHandshake};
+use tungstenite::{Message, WebSocket};
+
+use crate::frame::Frame;
+use crate::sink::{FlushSink, SinkResult};
+
+type Handshake = MidHandshake<ServerHandshake<TcpStream, NoCallback>>;
+
+pub struct WebSocketSink {
+    listener: TcpListener,
+    clients: Vec<WebSocket<TcpStream>>,
+    // handshakes that haven't finished, and when their client connected
+    pending: Vec<(Handshake, Instant)>,
+    handshake_timeout: Duration,
+}
+
+impl WebSocketSink {
+    pub fn bind(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        Ok(WebSocketSink {
+            listener,
+            clients: Vec::new(),
+            pending: Vec::new(),
+            handshake_timeout: Duration::from_secs(1),
+        })
+    }
+
+    // a client that doesn't finish the handshake in time is dropped, also the most a
+    // write to a client may block
+    pub fn with_handshake_timeout(mut self, timeout: Duration) -> Self {
+        self.handshake_timeout = timeout;
+        self
+    }
+
+    pub fn local_addr(&self) -> io::Result<std::net::SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    pub fn clients(&self) -> usize {
+        self.clients.len()
+    }
+
+    // take in any clients waiting on the listener and move their handshakes along as far
+    // as they go without waiting, handshakes go on at the next write or flush
+    fn accept_clients(&mut self) {
+        let mut started = Vec::new();
+        while let Ok((stream, _)) = self.listener.accept() {
+            if stream.set_nonblocking(true).is_ok() {
+                started.push((tungstenite::accept(stream), Instant::now()));
+            }
+        }
+        let pending = self
+            .pending
+            .drain(..)
+            .map(|(handshake, since)| (handshake.handshake(), since))
+            .chain(started)
+            .collect::<Vec<_>>();
+
+        for (result, since) in pending {
+            match result {
+                Ok(mut client) => {
+                    // once connected, frames go out blocking, up to the timeout
+                    let stream = client.get_mut();
+                    let configured = stream.set_nonblocking(false).is_ok()
+                        && stream
+                            .set_write_timeout(Some(self.handshake_timeout))
+                            .is_ok();
+                    if configured {
+                        self.clients.push(client);
+                    }
+                }
+                Err(HandshakeError::Interrupted(handshake)) => {
+                    if since.elapsed() < self.handshake_timeout {
+                        self.pending.push((handshake, since));
+                    }
+                }
+                Err(HandshakeError::Failure(_)) => {}
+            }
+        }
+    }
+}
+
+impl FlushSink for WebSocketSink {
+    fn write_frame(&mut self, frame: &Frame) -> SinkResult {
+        self.accept_clients();
+        let message = Message::binary(frame.encode());
+        // a client that can't keep up or went away is dropped, the others carry on
+        self.clients
+            .retain_mut(|client| client.write(message.clone()).is_ok());
+        SinkResult::Ok
+    }
+
+    fn flush(&mut self) -> SinkResult {
+        self.accept_clients();
+        self.clients.retain_mut(|client| client.flush().is_ok());
+        SinkResult::Ok
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    #[test]
+    fn a_stalled_handshake_doesnt_block_writes() {
+        let mut sink = WebSocketSink::bind("127.0.0.1:0")
+            .unwrap()
+            .with_handshake_timeout(Duration::from_millis(100));
+        let addr = sink.local_addr().unwrap();
+
+        // connects but never sends its upgrade request
+        let _silent = TcpStream::connect(addr).unwrap();
+        let started = Instant::now();
+        for _ in 0..10 {
+            assert!(matches!(
+                sink.write_frame(&Frame::new(b"x")),
+                SinkResult::Ok
+            ));
+        }
+        assert!(started.elapsed() < Duration::from_millis(100));
+        assert_eq!(sink.pending.len(), 1);
+
+        std::thread::sleep(Duration::from_millis(150));
+        let _ = sink.flush();
+        assert!(sink.pending.is_empty());
+        assert_eq!(sink.clients(), 0);
+    }
+
+    #[test]
+    fn a_handshake_finishes_across_writes() {
+        let mut sink = WebSocketSink::bind("127.0.0.1:0").unwrap();
+        let addr = sink.local_addr().unwrap();
+        let mut browser = TcpStream::connect(addr).unwrap();
+
+        // the request arrives in two pieces, with a flush in between
+        let request = format!(
+            "GET / HTTP/1.1\r\nHost: {}\r\nConnection: Upgrade\r\nUpgrade: websocket\r\n\
+             Sec-WebSocket-Version: 13\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\r\n",
+            addr
+        );
+        let (first, rest) = request.split_at(20);
+        browser.write_all(first.as_bytes()).unwrap();
+        std::thread::sleep(Duration::from_millis(20));
+        let _ = sink.flush();
+        assert_eq!(sink.clients(), 0);
+
+        browser.write_all(rest.as_bytes()).unwrap();
+        for _ in 0..50 {
+            std::thread::sleep(Duration::from_millis(10));
+            let _ = sink.flush();
+            if sink.clients() == 1 {
+                break;
+            }
+        }
+        assert_eq!(sink.clients(), 1);
+    }
+
+    #[test]
+    fn clients_get_every_frame_as_a_binary_message() {
+        let mut sink = WebSocketSink::bind("127.0.0.1:0").unwrap();
+        let addr = sink.local_addr().unwrap();
+        let browser = std::thread::spawn(move || {
+            let stream = TcpStream::connect(addr).unwrap();
+            let (mut socket, _) = tungstenite::client(format!("ws://{}/", addr), stream).unwrap();
+            socket.read().unwrap()
+        });
+
+        for _ in 0..100 {
+            let _ = sink.flush();
+            if sink.clients() == 1 {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(sink.clients(), 1);
+        let frame = Frame::new(b"to the page").with_seq(3);
+        assert!(matches!(sink.write_frame(&frame), SinkResult::Ok));
+        let _ = sink.flush();
+        assert_eq!(browser.join().unwrap(), Message::binary(frame.encode()));
+    }
+}